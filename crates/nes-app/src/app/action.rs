@@ -1,16 +1,97 @@
 use crate::app::app::App;
+use crate::app::bindings::{
+    BINDINGS_STORAGE_KEY, EXPANSION_DEVICE_STORAGE_KEY, KeyBindings, PORT2_DEVICE_STORAGE_KEY,
+    TURBO_RATE_STORAGE_KEY,
+};
 use crate::app::event::AppEventSource;
+use crate::app::screenshot::timestamp;
+use crate::app::settings::{
+    CAPTURE_STORAGE_KEY, CaptureSettings, SCREENSHOTS_STORAGE_KEY, SPRITES_STORAGE_KEY,
+    ScreenshotSettings, SpriteSettings,
+};
+use crate::app::storage;
+use crate::app::ui::nametable_viewer::NametableViewer;
+use crate::app::ui::pattern_viewer::PatternViewer;
+use crate::app::ui::video_options::{
+    DISPLAY_STORAGE_KEY, NTSC_FILTER_STORAGE_KEY, PALETTE_STORAGE_KEY, VideoOptionsWindow,
+};
 use crate::app::ui::views::UiView;
+use crate::app::ui::views::options_view::OptionsView;
 use crate::app::ui::views::rom_select_view::RomSelectView;
 use crate::emu::commands::{AudioChannel, EmuCommand};
+use crate::emu::emu_input::PointerInput;
+use crate::video::display::DisplaySettings;
+use crate::video::ntsc::NtscPreset;
+use crate::video::palette::PaletteSettings;
+use nes_core::prelude::{ConsoleType, ExpansionDevice, PortDevice, RecordFormat, Region, StepKind};
+use std::path::PathBuf;
 
 pub enum Action {
     Start,
     Navigate(UiView),
-    PlayRom(Vec<u8>),
+    /// Load a ROM or an archive of them, with the name of the file it came from
+    PlayRom {
+        name: String,
+        bytes: Vec<u8>,
+    },
     AcknowledgeError,
     TogglePause,
     SetPaused(bool),
+    Step(StepKind),
+    SetRegionOverride(Option<Region>),
+    SetConsoleOverride(Option<ConsoleType>),
+    ToggleStats,
+    SetFastForward(bool),
+    CycleSpeed,
+    SetRewinding(bool),
+    StartRecording {
+        from_power_on: bool,
+    },
+    PlayMovie(Vec<u8>),
+    StopMovie,
+    /// Open the controls screen, returning to the game or the ROM select screen
+    OpenOptions {
+        back_to_game: bool,
+    },
+    SetKeyBindings(Box<KeyBindings>),
+    SetTurboRate(u32),
+    SetPort2Device(PortDevice),
+    /// `None` to use the ROM header's
+    SetExpansionDevice(Option<ExpansionDevice>),
+    /// Latest mouse state over the game, for the Zapper or paddle
+    SetPointer(PointerInput),
+    ToggleMicrophone,
+    /// Open the video settings window, or close and save them
+    ToggleVideoOptions,
+    /// Draw with this palette from now on
+    SetPalette(Box<PaletteSettings>),
+    /// Draw through the NTSC filter, or `None` for plain palette colors
+    SetNtscFilter(Option<NtscPreset>),
+    /// Scaler, scanlines and picture geometry
+    SetDisplay(DisplaySettings),
+    /// Draw every sprite, or only the 8 per line the PPU can
+    SetSprites(SpriteSettings),
+    /// Whether screenshots go through the scaler
+    SetScreenshots(ScreenshotSettings),
+    /// What format F11 records in
+    SetCapture(CaptureSettings),
+    /// Use a dropped `.pal` file
+    LoadPalette {
+        name: String,
+        data: Vec<u8>,
+    },
+    /// Type on the Family BASIC keyboard, or go back to the controllers
+    ToggleKeyboardCapture,
+    /// Save what's on screen to a PNG
+    Screenshot,
+    /// Start recording video and audio to `recordings/`, or stop
+    ToggleCapture,
+    ToggleNametableViewer,
+    TogglePatternViewer,
+    /// Take the debugger views' snapshots at the start of this scanline
+    SetSnapshotScanline(usize),
+    /// Save the pattern viewer's tables as a PNG, like a screenshot
+    SavePatternTables,
 
     ToggleAudioChannel(AudioChannel),
 }
@@ -30,8 +111,8 @@ impl<E: AppEventSource> App<E> {
                 }
                 self.view = v;
             }
-            Action::PlayRom(rom_bytes) => {
-                self.play_rom(rom_bytes);
+            Action::PlayRom { name, bytes } => {
+                self.play_rom(name, bytes);
             }
             Action::AcknowledgeError => {
                 self.view = UiView::RomSelect(RomSelectView::new());
@@ -48,6 +129,223 @@ impl<E: AppEventSource> App<E> {
                 self.paused = p;
                 self.send_command(EmuCommand::Pause(self.paused));
             }
+            Action::Step(kind) => {
+                if self.paused {
+                    self.send_command(EmuCommand::Step(kind));
+                }
+            }
+            Action::SetRegionOverride(region) => {
+                // Applied the next time a ROM is loaded
+                self.region_override = region;
+            }
+            Action::SetConsoleOverride(console) => {
+                // Applied the next time a ROM is loaded
+                self.console_override = console;
+            }
+            Action::ToggleStats => {
+                self.show_stats = !self.show_stats;
+            }
+            Action::SetFastForward(held) => {
+                self.speed.set_fast_forward(held);
+                self.send_command(EmuCommand::SetSpeed(self.speed.speed()));
+            }
+            Action::CycleSpeed => {
+                self.speed.cycle();
+                self.send_command(EmuCommand::SetSpeed(self.speed.speed()));
+            }
+            Action::SetRewinding(held) => {
+                self.rewinding = held;
+                self.send_command(EmuCommand::Rewind(held));
+            }
+            Action::StartRecording { from_power_on } => {
+                if let Some(movie) = self.movie_template.clone() {
+                    self.send_command(EmuCommand::StartRecording {
+                        movie,
+                        from_power_on,
+                    });
+                }
+            }
+            Action::PlayMovie(movie_bytes) => {
+                self.play_movie(movie_bytes);
+            }
+            Action::StopMovie => {
+                self.send_command(EmuCommand::StopMovie);
+            }
+            Action::OpenOptions { back_to_game } => {
+                let view = OptionsView::new(
+                    self.key_bindings.clone(),
+                    self.turbo_rate,
+                    self.port2_device,
+                    self.expansion_device,
+                    back_to_game,
+                );
+                self.view = UiView::Options(Box::new(view));
+            }
+            Action::SetKeyBindings(bindings) => {
+                if let Err(e) = storage::save(BINDINGS_STORAGE_KEY, &bindings.to_config()) {
+                    self.log(format!("Failed to save key bindings: {e:#}"));
+                }
+                self.key_bindings = *bindings;
+            }
+            Action::SetTurboRate(rate) => {
+                if let Err(e) = storage::save(TURBO_RATE_STORAGE_KEY, &rate.to_string()) {
+                    self.log(format!("Failed to save turbo rate: {e:#}"));
+                }
+                self.turbo_rate = rate;
+                self.send_command(EmuCommand::SetTurboRate(rate));
+            }
+            Action::SetPort2Device(device) => {
+                if let Err(e) = storage::save(PORT2_DEVICE_STORAGE_KEY, device.name()) {
+                    self.log(format!("Failed to save port 2 device: {e:#}"));
+                }
+                self.port2_device = device;
+                self.send_command(EmuCommand::SetPort2Device(device));
+            }
+            Action::SetExpansionDevice(device) => {
+                // Applied the next time a ROM is loaded
+                let name = device.map_or("", |device| device.name());
+                if let Err(e) = storage::save(EXPANSION_DEVICE_STORAGE_KEY, name) {
+                    self.log(format!("Failed to save expansion device: {e:#}"));
+                }
+                self.expansion_device = device;
+            }
+            Action::SetPointer(pointer) => {
+                self.set_pointer(pointer);
+            }
+            Action::ToggleMicrophone => {
+                self.microphone = !self.microphone;
+                self.send_command(EmuCommand::SetMicrophone(self.microphone));
+            }
+            Action::ToggleVideoOptions => match self.video_options.take() {
+                Some(_) => {
+                    let config = self.palette_settings.to_config();
+                    if let Err(e) = storage::save(PALETTE_STORAGE_KEY, &config) {
+                        self.log(format!("Failed to save palette: {e:#}"));
+                    }
+                    let name = self.ntsc_preset.map_or("", |preset| preset.name());
+                    if let Err(e) = storage::save(NTSC_FILTER_STORAGE_KEY, name) {
+                        self.log(format!("Failed to save NTSC filter: {e:#}"));
+                    }
+                    let config = self.display.to_config();
+                    if let Err(e) = storage::save(DISPLAY_STORAGE_KEY, &config) {
+                        self.log(format!("Failed to save display settings: {e:#}"));
+                    }
+                    let config = self.sprites.to_config();
+                    if let Err(e) = storage::save(SPRITES_STORAGE_KEY, &config) {
+                        self.log(format!("Failed to save sprite settings: {e:#}"));
+                    }
+                    let config = self.screenshots.to_config();
+                    if let Err(e) = storage::save(SCREENSHOTS_STORAGE_KEY, &config) {
+                        self.log(format!("Failed to save screenshot settings: {e:#}"));
+                    }
+                    let config = self.capture_settings.to_config();
+                    if let Err(e) = storage::save(CAPTURE_STORAGE_KEY, &config) {
+                        self.log(format!("Failed to save recording settings: {e:#}"));
+                    }
+                }
+                None => {
+                    self.video_options = Some(VideoOptionsWindow::new(
+                        self.palette_settings.clone(),
+                        self.ntsc_preset,
+                        self.display,
+                        self.sprites,
+                        self.screenshots,
+                        self.capture_settings,
+                    ));
+                }
+            },
+            Action::SetPalette(settings) => {
+                self.set_palette(*settings);
+            }
+            Action::SetNtscFilter(preset) => {
+                self.set_ntsc_preset(preset);
+            }
+            Action::SetDisplay(display) => {
+                self.display = display;
+            }
+            Action::SetSprites(sprites) => {
+                self.send_command(EmuCommand::SetSpriteLimit(!sprites.unlimited));
+                self.sprites = sprites;
+            }
+            Action::SetScreenshots(screenshots) => {
+                self.screenshots = screenshots;
+            }
+            Action::SetCapture(capture) => {
+                self.capture_settings = capture;
+            }
+            Action::LoadPalette { name, data } => {
+                // Open the window so the result, or what went wrong, shows
+                let settings = self
+                    .video_options
+                    .get_or_insert_with(|| {
+                        VideoOptionsWindow::new(
+                            self.palette_settings.clone(),
+                            self.ntsc_preset,
+                            self.display,
+                            self.sprites,
+                            self.screenshots,
+                            self.capture_settings,
+                        )
+                    })
+                    .load_pal_file(name, &data);
+                if let Some(settings) = settings {
+                    self.set_palette(settings);
+                }
+            }
+            Action::ToggleKeyboardCapture => {
+                self.keyboard_captured = !self.keyboard_captured && self.has_family_keyboard();
+            }
+            Action::Screenshot => {
+                self.send_command(EmuCommand::Screenshot);
+            }
+            Action::ToggleNametableViewer => {
+                self.nametable_viewer = match self.nametable_viewer.take() {
+                    Some(_) => None,
+                    None => {
+                        let mut viewer = NametableViewer::new();
+                        viewer.set_scanline(self.snapshot_scanline().unwrap_or(0));
+                        Some(viewer)
+                    }
+                };
+                self.send_command(EmuCommand::SetSnapshotScanline(self.snapshot_scanline()));
+            }
+            Action::TogglePatternViewer => {
+                self.pattern_viewer = match self.pattern_viewer.take() {
+                    Some(_) => None,
+                    None => Some(PatternViewer::new(self.snapshot_scanline().unwrap_or(0))),
+                };
+                self.send_command(EmuCommand::SetSnapshotScanline(self.snapshot_scanline()));
+            }
+            Action::SetSnapshotScanline(scanline) => {
+                // The views share the runtime's one snapshot
+                if let Some(viewer) = &mut self.nametable_viewer {
+                    viewer.set_scanline(scanline);
+                }
+                if let Some(viewer) = &mut self.pattern_viewer {
+                    viewer.set_scanline(scanline);
+                }
+                self.send_command(EmuCommand::SetSnapshotScanline(Some(scanline)));
+            }
+            Action::SavePatternTables => {
+                self.save_pattern_tables();
+            }
+            Action::ToggleCapture => {
+                if self.capturing {
+                    self.send_command(EmuCommand::StopCapture);
+                } else {
+                    let (format, extension) = match self.capture_settings.png_wav {
+                        true => (RecordFormat::PngWav, ""),
+                        false => (RecordFormat::Avi, ".avi"),
+                    };
+                    let path = PathBuf::from("recordings")
+                        .join(format!("recording-{}{extension}", timestamp()));
+                    self.send_command(EmuCommand::StartCapture {
+                        path,
+                        format,
+                        palette: Box::new(self.palette.clone()),
+                    });
+                }
+            }
 
             Action::ToggleAudioChannel(channel) => {
                 self.send_command(EmuCommand::ToggleAudioChannel(channel));
@@ -1,19 +1,48 @@
 use crate::app::action::Action;
+use crate::app::bindings::{
+    BINDINGS_STORAGE_KEY, EXPANSION_DEVICE_STORAGE_KEY, KeyBindings, PORT2_DEVICE_STORAGE_KEY,
+    PORT2_DEVICES, TURBO_RATE_STORAGE_KEY,
+};
 use crate::app::event::{AppEvent, AppEventSource};
+use crate::app::input;
+use crate::app::input::gamepad::{GamepadBackend, Gamepads, Hotplug};
+use crate::app::settings::{
+    CAPTURE_STORAGE_KEY, CaptureSettings, SCREENSHOTS_STORAGE_KEY, SPRITES_STORAGE_KEY,
+    ScreenshotSettings, SpriteSettings,
+};
+use crate::app::speed::SpeedControl;
+use crate::app::storage;
 pub(crate) use crate::app::ui::app_input;
 use crate::app::ui::error::ErrorInfo;
 use crate::app::ui::file_drop_overlay;
+use crate::app::ui::nametable_viewer::NametableViewer;
+use crate::app::ui::pattern_viewer::PatternViewer;
+use crate::app::ui::video_options::{
+    DISPLAY_STORAGE_KEY, NTSC_FILTER_STORAGE_KEY, PALETTE_STORAGE_KEY, VideoOptionsWindow,
+};
 use crate::app::ui::views::UiView;
 use crate::app::ui::views::error_view::ErrorView;
+use crate::app::ui::views::rom_picker_view::RomPickerView;
 use crate::app::ui::views::rom_select_view::RomSelectView;
 use crate::app::ui::views::waiting_view::WaitingView;
 use crate::emu::commands::EmuCommand;
+use crate::emu::emu_input::PointerInput;
 use crate::emu::event::EmuEvent;
 use crate::emu::host::EmuHost;
+use crate::emu::movie::{self, MovieStatus};
+use crate::emu::netplay::{NetplayConfig, NetplayStatus};
+use crate::emu::runtime::DEFAULT_TURBO_RATE;
+use crate::emu::telemetry::EmuTelemetry;
+use crate::rom::RomSource;
 use crate::shared::frame_buffer::{SharedFrame, SharedFrameHandle};
+use crate::video::display::DisplaySettings;
+use crate::video::ntsc::{NtscFilter, NtscPreset};
+use crate::video::palette::{Palette, PaletteSettings};
 use anyhow::Context;
 use eframe::epaint::TextureHandle;
-use nes_core::prelude::Rom;
+use nes_core::prelude::{
+    ConsoleType, ExpansionDevice, MachinePosition, Movie, PortDevice, Region, Rom,
+};
 use std::sync::Arc;
 
 pub struct UiCtx<'a> {
@@ -22,6 +51,28 @@ pub struct UiCtx<'a> {
     pub actions: &'a mut Vec<Action>,
     pub started: bool,
     pub paused: bool,
+    pub region_override: Option<Region>,
+    pub telemetry: Option<EmuTelemetry>,
+    pub show_stats: bool,
+    pub speed: f32,
+    pub fast_forward: bool,
+    pub rewinding: bool,
+    pub position: Option<MachinePosition>,
+    pub movie_status: MovieStatus,
+    /// Video and audio are being recorded
+    pub capturing: bool,
+    pub netplay_status: NetplayStatus,
+    pub key_bindings: &'a KeyBindings,
+    pub port2_device: PortDevice,
+    pub console_override: Option<ConsoleType>,
+    /// The loaded game has a Family BASIC keyboard attached
+    pub family_keyboard: bool,
+    pub keyboard_captured: bool,
+    pub microphone: bool,
+    pub palette: &'a Palette,
+    /// Draw through this instead of `palette` when set
+    pub ntsc_filter: Option<&'a NtscFilter>,
+    pub display: DisplaySettings,
 }
 
 pub struct App<E: AppEventSource> {
@@ -30,26 +81,145 @@ pub struct App<E: AppEventSource> {
     pub(crate) frame: SharedFrameHandle,
     pub(crate) texture: Option<TextureHandle>,
     log_callback: Option<Box<dyn Fn(String) + 'static>>,
+    /// Offers a file to the user, as the browser can't save anywhere itself
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) download_callback: Option<Box<dyn Fn(&str, &[u8]) + 'static>>,
 
     // UI
     pub(crate) view: UiView,
     pub(crate) started: bool,
     pub(crate) paused: bool,
+    /// Forces console timing instead of using the ROM header
+    pub(crate) region_override: Option<Region>,
+    /// Latest speed report from the runtime
+    pub(crate) telemetry: Option<EmuTelemetry>,
+    pub(crate) show_stats: bool,
+    pub(crate) speed: SpeedControl,
+    pub(crate) rewinding: bool,
+    /// Last reported CPU/PPU position, refreshed while paused
+    pub(crate) position: Option<MachinePosition>,
+    pub(crate) movie_status: MovieStatus,
+    pub(crate) capturing: bool,
+    /// Header for new recordings, filled in from the loaded ROM
+    pub(crate) movie_template: Option<Movie>,
+    /// Session to start once a ROM is loaded
+    pub(crate) netplay_config: Option<NetplayConfig>,
+    pub(crate) netplay_status: NetplayStatus,
+    pub(crate) key_bindings: KeyBindings,
+    /// Frames each turbo press and release lasts
+    pub(crate) turbo_rate: u32,
+    pub(crate) port2_device: PortDevice,
+    /// Forces NES or Famicom input wiring instead of using the ROM header
+    pub(crate) console_override: Option<ConsoleType>,
+    /// Expansion port device to use instead of the ROM header's
+    pub(crate) expansion_device: Option<ExpansionDevice>,
+    /// Input wiring of the loaded game
+    pub(crate) console: ConsoleType,
+    pub(crate) expansion: Option<ExpansionDevice>,
+    pub(crate) microphone: bool,
+    /// Host keys go to the Family BASIC keyboard instead of the controllers
+    /// and hotkeys
+    pub(crate) keyboard_captured: bool,
+    pub(crate) palette_settings: PaletteSettings,
+    /// Built from `palette_settings`
+    pub(crate) palette: Palette,
+    /// Composite video look, if any
+    pub(crate) ntsc_preset: Option<NtscPreset>,
+    /// Built from `ntsc_preset` and `palette_settings`
+    pub(crate) ntsc_filter: Option<NtscFilter>,
+    pub(crate) display: DisplaySettings,
+    pub(crate) sprites: SpriteSettings,
+    pub(crate) screenshots: ScreenshotSettings,
+    pub(crate) capture_settings: CaptureSettings,
+    /// Open while the video settings window is showing
+    pub(crate) video_options: Option<VideoOptionsWindow>,
+    pub(crate) nametable_viewer: Option<NametableViewer>,
+    pub(crate) pattern_viewer: Option<PatternViewer>,
+    pub(crate) gamepads: Gamepads,
+    /// Created with the emulator; `None` without gamepad support
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
 }
 
 impl<E: AppEventSource> App<E> {
     pub fn new(events: E) -> Self {
-        Self {
+        let palette_settings = storage::load(PALETTE_STORAGE_KEY)
+            .map(|config| PaletteSettings::from_config(&config))
+            .unwrap_or_default();
+        let ntsc_preset = storage::load(NTSC_FILTER_STORAGE_KEY).and_then(|name| {
+            NtscPreset::ALL
+                .into_iter()
+                .find(|preset| preset.name() == name.trim())
+        });
+        let mut app = Self {
             events,
             emu_host: None,
             frame: Arc::new(SharedFrame::new()),
             texture: None,
 
             log_callback: None,
+            #[cfg(target_arch = "wasm32")]
+            download_callback: None,
             view: UiView::Waiting(WaitingView::new()),
             started: false,
             paused: false,
-        }
+            region_override: None,
+            telemetry: None,
+            show_stats: false,
+            speed: SpeedControl::new(),
+            rewinding: false,
+            position: None,
+            movie_status: MovieStatus::Idle,
+            capturing: false,
+            movie_template: None,
+            netplay_config: None,
+            netplay_status: NetplayStatus::Off,
+            key_bindings: storage::load(BINDINGS_STORAGE_KEY)
+                .map(|config| KeyBindings::from_config(&config))
+                .unwrap_or_default(),
+            turbo_rate: storage::load(TURBO_RATE_STORAGE_KEY)
+                .and_then(|rate| rate.trim().parse().ok())
+                .unwrap_or(DEFAULT_TURBO_RATE),
+            port2_device: storage::load(PORT2_DEVICE_STORAGE_KEY)
+                .and_then(|name| {
+                    PORT2_DEVICES
+                        .into_iter()
+                        .find(|device| device.name() == name.trim())
+                })
+                .unwrap_or_default(),
+            console_override: None,
+            expansion_device: storage::load(EXPANSION_DEVICE_STORAGE_KEY).and_then(|name| {
+                ExpansionDevice::ALL
+                    .into_iter()
+                    .find(|device| device.name() == name.trim())
+            }),
+            console: ConsoleType::default(),
+            expansion: None,
+            microphone: false,
+            keyboard_captured: false,
+            palette: palette_settings.palette(),
+            palette_settings,
+            ntsc_preset: None,
+            ntsc_filter: None,
+            display: storage::load(DISPLAY_STORAGE_KEY)
+                .map(|config| DisplaySettings::from_config(&config))
+                .unwrap_or_default(),
+            sprites: storage::load(SPRITES_STORAGE_KEY)
+                .map(|config| SpriteSettings::from_config(&config))
+                .unwrap_or_default(),
+            screenshots: storage::load(SCREENSHOTS_STORAGE_KEY)
+                .map(|config| ScreenshotSettings::from_config(&config))
+                .unwrap_or_default(),
+            capture_settings: storage::load(CAPTURE_STORAGE_KEY)
+                .map(|config| CaptureSettings::from_config(&config))
+                .unwrap_or_default(),
+            video_options: None,
+            nametable_viewer: None,
+            pattern_viewer: None,
+            gamepads: Gamepads::default(),
+            gamepad_backend: None,
+        };
+        app.set_ntsc_preset(ntsc_preset);
+        app
     }
 
     /// When building to WASM, this must be called in a user-interaction context
@@ -68,6 +238,13 @@ impl<E: AppEventSource> App<E> {
             Ok(emu) => {
                 self.log("EmuHost::start() => Ok()");
                 self.emu_host = Some(emu);
+                self.send_command(EmuCommand::SetTurboRate(self.turbo_rate));
+                self.send_command(EmuCommand::SetPort2Device(self.port2_device));
+                self.send_command(EmuCommand::SetSpriteLimit(!self.sprites.unlimited));
+                match input::platform_gamepad_backend() {
+                    Ok(backend) => self.gamepad_backend = Some(backend),
+                    Err(e) => self.log(format!("{e:#}")),
+                }
                 self.apply_action(Action::Navigate(UiView::RomSelect(RomSelectView::new())))
             }
             Err(e) => {
@@ -95,16 +272,53 @@ impl<E: AppEventSource> App<E> {
         self
     }
 
+    /// How to hand a file, like a screenshot, to the user in the browser.
+    /// Called with the file name and contents
+    #[cfg(target_arch = "wasm32")]
+    pub fn with_download_callback<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &[u8]) + 'static,
+    {
+        self.download_callback = Some(Box::new(f));
+        self
+    }
+
     /// Handle events from the Emulator Runtime
     fn handle_emu_events(&mut self) {
-        let Some(emu) = self.emu_host.as_ref() else {
-            return;
-        };
-        while let Some(event) = emu.try_recv() {
+        while let Some(event) = self.emu_host.as_ref().and_then(|emu| emu.try_recv()) {
             match event {
                 EmuEvent::Log(msg) => {
                     self.log(msg);
                 }
+                EmuEvent::Telemetry(telemetry) => {
+                    self.telemetry = Some(telemetry);
+                }
+                EmuEvent::Position(position) => {
+                    self.position = Some(position);
+                }
+                EmuEvent::MovieStatus(status) => {
+                    self.movie_status = status;
+                }
+                EmuEvent::MovieRecorded(movie) => {
+                    self.save_movie(movie);
+                }
+                EmuEvent::NetplayStatus(status) => {
+                    self.netplay_status = status;
+                }
+                EmuEvent::ScreenshotReady => {
+                    self.save_screenshot();
+                }
+                EmuEvent::CaptureStatus(capturing) => {
+                    self.capturing = capturing;
+                }
+                EmuEvent::PpuSnapshot(snapshot) => {
+                    if let Some(viewer) = &mut self.nametable_viewer {
+                        viewer.set_snapshot(snapshot.clone());
+                    }
+                    if let Some(viewer) = &mut self.pattern_viewer {
+                        viewer.set_snapshot(snapshot);
+                    }
+                }
             }
         }
     }
@@ -116,6 +330,52 @@ impl<E: AppEventSource> App<E> {
         }
     }
 
+    /// Whether the loaded game has the Family BASIC keyboard to type on
+    pub(crate) fn has_family_keyboard(&self) -> bool {
+        self.console == ConsoleType::Famicom
+            && self.expansion == Some(ExpansionDevice::FamilyBasicKeyboard)
+    }
+
+    /// Scanline the open debugger views want snapshots at, if any are open
+    pub(crate) fn snapshot_scanline(&self) -> Option<usize> {
+        let nametables = self.nametable_viewer.as_ref().map(|v| v.scanline());
+        nametables.or_else(|| self.pattern_viewer.as_ref().map(|v| v.scanline()))
+    }
+
+    pub(crate) fn set_palette(&mut self, settings: PaletteSettings) {
+        self.palette = settings.palette();
+        self.palette_settings = settings;
+        self.set_ntsc_preset(self.ntsc_preset);
+    }
+
+    pub(crate) fn set_ntsc_preset(&mut self, preset: Option<NtscPreset>) {
+        self.ntsc_preset = preset;
+        self.ntsc_filter = preset
+            .map(|preset| NtscFilter::new(preset, &self.palette_settings.ntsc, &self.palette));
+    }
+
+    pub(crate) fn set_pointer(&self, pointer: PointerInput) {
+        if let Some(emu) = &self.emu_host {
+            emu.set_pointer(pointer);
+        }
+    }
+
+    fn poll_gamepads(&mut self) {
+        let Some(backend) = self.gamepad_backend.as_mut() else {
+            return;
+        };
+        for hotplug in self.gamepads.update(backend.as_mut()) {
+            let message = match hotplug {
+                Hotplug::Connected { name, player } => match player {
+                    Some(player) => format!("Gamepad connected: {name} (P{})", player + 1),
+                    None => format!("Gamepad connected: {name} (no free player)"),
+                },
+                Hotplug::Disconnected { name, .. } => format!("Gamepad disconnected: {name}"),
+            };
+            self.log(message);
+        }
+    }
+
     pub fn log(&self, message: impl Into<String>) {
         if let Some(callback) = self.log_callback.as_ref() {
             callback(message.into());
@@ -128,16 +388,86 @@ impl<E: AppEventSource> App<E> {
         self.view = UiView::Error(ErrorView::new(info));
     }
 
-    fn load_rom_and_start(&mut self, rom_bytes: Vec<u8>) -> anyhow::Result<()> {
+    fn load_rom_and_start(&mut self, name: &str, rom_bytes: Vec<u8>) -> anyhow::Result<()> {
         let rom = Rom::parse(&rom_bytes).context("Rom parsing failed")?;
+        let region = self.region_override.or(rom.region).unwrap_or_default();
+        let mut movie_template = Movie::new(movie::rom_filename(name), Movie::rom_checksum(&rom));
+        movie_template.pal = region == Region::Pal;
+        self.movie_template = Some(movie_template);
+        self.console = self.console_override.or(rom.console).unwrap_or_default();
+        self.expansion = self.expansion_device.or(rom.expansion);
         let cartridge = rom.into_cartridge().context("Cartridge parsing failed")?;
         self.log("Cartridge parsed!");
-        self.send_command(EmuCommand::InsertCartridge(cartridge));
+        self.telemetry = None;
+        self.microphone = false;
+        self.keyboard_captured = false;
+        self.send_command(EmuCommand::SetConsole(self.console, self.expansion));
+        self.send_command(EmuCommand::SetMicrophone(false));
+        self.send_command(EmuCommand::InsertCartridge(cartridge, region));
+
+        if let Some(mut config) = self.netplay_config.clone() {
+            config.game_id = crc32fast::hash(&rom_bytes);
+            self.send_command(EmuCommand::StartNetplay(config));
+        }
         Ok(())
     }
 
-    pub(crate) fn play_rom(&mut self, rom_bytes: Vec<u8>) {
-        match self.load_rom_and_start(rom_bytes) {
+    pub(crate) fn play_movie(&mut self, movie_bytes: Vec<u8>) {
+        let movie = String::from_utf8(movie_bytes)
+            .context("Movie isn't valid text")
+            .and_then(|text| Movie::parse(&text).context("Movie parsing failed"));
+        let movie = movie.and_then(|movie| match &self.movie_template {
+            Some(template) => movie::check_rom(&movie, template).map(|()| movie),
+            None => Ok(movie),
+        });
+        match movie {
+            Ok(movie) => self.send_command(EmuCommand::PlayMovie(movie)),
+            Err(e) => self.set_error(e),
+        }
+    }
+
+    fn save_movie(&mut self, movie: Movie) {
+        let frames = movie.frames.len();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("FM2 movie", &["fm2"])
+                .set_file_name("movie.fm2")
+                .save_file()
+            else {
+                self.log("Movie discarded");
+                return;
+            };
+            match std::fs::write(&path, movie.to_fm2()) {
+                Ok(()) => self.log(format!("Saved {frames} frame movie to {}", path.display())),
+                Err(e) => self.set_error(anyhow::Error::new(e).context("Failed to save movie")),
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.log(format!(
+                "Recorded a {frames} frame movie, but saving isn't supported in the browser yet"
+            ));
+        }
+    }
+
+    pub(crate) fn play_rom(&mut self, name: String, bytes: Vec<u8>) {
+        // Archives may hold several ROMs, in which case the user picks one
+        let rom = match crate::rom::unpack(name, bytes).context("Failed to read archive") {
+            Ok(RomSource::Single(rom)) => rom,
+            Ok(RomSource::Choice(roms)) => {
+                self.view = UiView::RomPicker(RomPickerView::new(roms));
+                return;
+            }
+            Err(e) => {
+                self.set_error(e);
+                return;
+            }
+        };
+
+        match self.load_rom_and_start(&rom.name, rom.bytes) {
             Ok(()) => self.view = UiView::playing(),
             Err(e) => self.set_error(e),
         }
@@ -153,15 +483,23 @@ impl<E: AppEventSource> eframe::App for App<E> {
             return;
         }
         self.handle_emu_events();
+        self.poll_gamepads();
 
         // Update controller states while playing
         if matches!(self.view, UiView::Playing { .. })
             && let Some(emu_host) = self.emu_host.as_ref()
         {
-            app_input::update_controller_state(ctx, &emu_host);
+            app_input::update_controller_state(
+                ctx,
+                emu_host,
+                &self.key_bindings,
+                &self.gamepads,
+                self.keyboard_captured,
+            );
         }
 
         let mut actions = Vec::<Action>::new();
+        let family_keyboard = self.has_family_keyboard();
         {
             // Build context
             let mut ui_ctx = UiCtx {
@@ -170,6 +508,25 @@ impl<E: AppEventSource> eframe::App for App<E> {
                 actions: &mut actions,
                 started: self.started,
                 paused: self.paused,
+                region_override: self.region_override,
+                telemetry: self.telemetry,
+                show_stats: self.show_stats,
+                speed: self.speed.speed(),
+                fast_forward: self.speed.fast_forward(),
+                rewinding: self.rewinding,
+                position: self.position,
+                movie_status: self.movie_status,
+                capturing: self.capturing,
+                netplay_status: self.netplay_status,
+                key_bindings: &self.key_bindings,
+                port2_device: self.port2_device,
+                console_override: self.console_override,
+                family_keyboard,
+                keyboard_captured: self.keyboard_captured,
+                microphone: self.microphone,
+                palette: &self.palette,
+                ntsc_filter: self.ntsc_filter.as_ref(),
+                display: self.display,
             };
 
             // Handle Hotkeys
@@ -178,11 +535,21 @@ impl<E: AppEventSource> eframe::App for App<E> {
             // Render
             match &mut self.view {
                 UiView::RomSelect(v) => v.ui(ctx, &mut ui_ctx),
-                UiView::Options => {}
+                UiView::RomPicker(v) => v.ui(ctx, &mut ui_ctx),
+                UiView::Options(v) => v.ui(ctx, &mut ui_ctx),
                 UiView::Playing(v) => v.ui(ctx, &mut ui_ctx),
                 UiView::Error(v) => v.ui(ctx, &mut ui_ctx),
                 UiView::Waiting(v) => v.ui(ctx, &mut ui_ctx),
             }
+            if let Some(window) = &mut self.video_options {
+                window.ui(ctx, &mut ui_ctx);
+            }
+            if let Some(window) = &mut self.nametable_viewer {
+                window.ui(ctx, &mut ui_ctx);
+            }
+            if let Some(window) = &mut self.pattern_viewer {
+                window.ui(ctx, &mut ui_ctx);
+            }
 
             // Allow file-drop only if emulator has already started
             if self.started {
@@ -191,7 +558,13 @@ impl<E: AppEventSource> eframe::App for App<E> {
         };
 
         // Commit actions
+        let window_size = self.display.window_size();
         self.apply_actions(actions);
+        // Native windows follow the visible picture's size
+        if self.display.window_size() != window_size {
+            let (width, height) = self.display.window_size();
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(width, height)));
+        }
 
         ctx.request_repaint();
     }
@@ -0,0 +1,398 @@
+use crate::emu::emu_input::{ControllerInput, PLAYERS};
+use egui::Key;
+use nes_core::prelude::{JoypadButton, PortDevice};
+use std::fmt::Write;
+
+/// Storage key the bindings are persisted under
+pub const BINDINGS_STORAGE_KEY: &str = "key_bindings";
+/// Storage key for the turbo rate, in frames
+pub const TURBO_RATE_STORAGE_KEY: &str = "turbo_rate";
+/// Storage key for what's plugged into port 2, by `PortDevice::name()`
+pub const PORT2_DEVICE_STORAGE_KEY: &str = "port2_device";
+
+/// Storage key for the Famicom expansion port device, by
+/// `ExpansionDevice::name()`. Absent to use the ROM header's
+pub const EXPANSION_DEVICE_STORAGE_KEY: &str = "expansion_device";
+
+/// Port 2 devices the mouse, keyboard and gamepads can drive. The 4-player
+/// adapters take port 1 as well
+pub const PORT2_DEVICES: [PortDevice; 7] = [
+    PortDevice::Joypad,
+    PortDevice::Zapper,
+    PortDevice::Vaus,
+    PortDevice::FourScore,
+    PortDevice::FamicomFourPlayer,
+    PortDevice::PowerPad,
+    PortDevice::FamilyTrainer,
+];
+
+/// Buttons on the Power Pad and Family Trainer mats
+pub const MAT_BUTTONS: usize = 12;
+
+/// Something a key can be bound to: a controller button, or its turbo version
+#[derive(Debug, Copy, Clone)]
+pub struct BindableButton {
+    pub button: JoypadButton,
+    pub turbo: bool,
+    /// Shown in the UI and used in the config file
+    pub name: &'static str,
+}
+
+const fn bindable(button: JoypadButton, turbo: bool, name: &'static str) -> BindableButton {
+    BindableButton {
+        button,
+        turbo,
+        name,
+    }
+}
+
+/// Bindable buttons in the order they're listed
+pub const BUTTONS: [BindableButton; 10] = [
+    bindable(JoypadButton::UP, false, "Up"),
+    bindable(JoypadButton::DOWN, false, "Down"),
+    bindable(JoypadButton::LEFT, false, "Left"),
+    bindable(JoypadButton::RIGHT, false, "Right"),
+    bindable(JoypadButton::BUTTON_B, false, "B"),
+    bindable(JoypadButton::BUTTON_A, false, "A"),
+    bindable(JoypadButton::SELECT, false, "Select"),
+    bindable(JoypadButton::START, false, "Start"),
+    bindable(JoypadButton::BUTTON_B, true, "Turbo B"),
+    bindable(JoypadButton::BUTTON_A, true, "Turbo A"),
+];
+
+/// Keys taken by `app_input::handle_hotkeys` while playing
+pub const RESERVED_KEYS: &[Key] = &[
+    Key::P,
+    Key::Tab,
+    Key::Backtick,
+    Key::Backspace,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+];
+
+/// Where a key is bound
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BindingSlot {
+    Controller {
+        player: usize,
+        /// Index into `BUTTONS`
+        button: usize,
+    },
+    /// Mat button, numbered from 0
+    Mat(usize),
+}
+
+impl BindingSlot {
+    /// Shown in the UI and used in the config file, like `p1.Up` or `mat.1`
+    pub fn name(&self) -> String {
+        match self {
+            BindingSlot::Controller { player, button } => {
+                format!("p{}.{}", player + 1, BUTTONS[*button].name)
+            }
+            BindingSlot::Mat(button) => format!("mat.{}", button + 1),
+        }
+    }
+}
+
+/// A key that does more than one thing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyConflict {
+    pub key: Key,
+    pub slots: Vec<BindingSlot>,
+    /// Also a hotkey
+    pub reserved: bool,
+}
+
+/// Keys for each controller button, per player, and for the mat. A button can
+/// have several keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    /// `[player][button]`, buttons in `BUTTONS` order
+    keys: [[Vec<Key>; BUTTONS.len()]; PLAYERS],
+    mat: [Vec<Key>; MAT_BUTTONS],
+}
+
+impl Default for KeyBindings {
+    /// P1 on WASD, P2 on the arrow keys, so both fit on one keyboard. P3 and
+    /// P4 are left to gamepads. The mat's three rows are on 6-9, E-Y and X-B
+    fn default() -> Self {
+        let p1 = [
+            vec![Key::W],
+            vec![Key::S],
+            vec![Key::A],
+            vec![Key::D],
+            vec![Key::J],
+            vec![Key::K],
+            vec![Key::Space],
+            vec![Key::Enter],
+            vec![Key::U],
+            vec![Key::I],
+        ];
+        let p2 = [
+            vec![Key::ArrowUp],
+            vec![Key::ArrowDown],
+            vec![Key::ArrowLeft],
+            vec![Key::ArrowRight],
+            vec![Key::Comma],
+            vec![Key::Period],
+            vec![Key::M],
+            vec![Key::Slash],
+            vec![Key::L],
+            vec![Key::Semicolon],
+        ];
+        let mat = [
+            Key::Num6,
+            Key::Num7,
+            Key::Num8,
+            Key::Num9,
+            Key::E,
+            Key::R,
+            Key::T,
+            Key::Y,
+            Key::X,
+            Key::C,
+            Key::V,
+            Key::B,
+        ]
+        .map(|key| vec![key]);
+        Self {
+            keys: [p1, p2, Default::default(), Default::default()],
+            mat,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, slot: BindingSlot) -> &[Key] {
+        match slot {
+            BindingSlot::Controller { player, button } => &self.keys[player][button],
+            BindingSlot::Mat(button) => &self.mat[button],
+        }
+    }
+
+    fn keys_mut(&mut self, slot: BindingSlot) -> &mut Vec<Key> {
+        match slot {
+            BindingSlot::Controller { player, button } => &mut self.keys[player][button],
+            BindingSlot::Mat(button) => &mut self.mat[button],
+        }
+    }
+
+    pub fn add(&mut self, slot: BindingSlot, key: Key) {
+        let keys = self.keys_mut(slot);
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    pub fn remove(&mut self, slot: BindingSlot, key: Key) {
+        self.keys_mut(slot).retain(|k| *k != key);
+    }
+
+    /// Controller state for `player` given which keys are held
+    pub fn input(&self, player: usize, key_down: impl Fn(Key) -> bool) -> ControllerInput {
+        let mut input = ControllerInput::default();
+        let held = BUTTONS
+            .iter()
+            .zip(&self.keys[player])
+            .filter(|(_, keys)| keys.iter().any(|&key| key_down(key)));
+        for (bindable, _) in held {
+            match bindable.turbo {
+                true => input.turbo |= bindable.button.bits(),
+                false => input.held |= bindable.button.bits(),
+            }
+        }
+        input
+    }
+
+    /// Mat buttons held given which keys are held, bit 0 being button 1
+    pub fn mat_input(&self, key_down: impl Fn(Key) -> bool) -> u16 {
+        self.mat
+            .iter()
+            .enumerate()
+            .filter(|(_, keys)| keys.iter().any(|&key| key_down(key)))
+            .fold(0, |pressed, (button, _)| pressed | 1 << button)
+    }
+
+    /// Keys bound to more than one button, or that are also hotkeys
+    pub fn conflicts(&self) -> Vec<KeyConflict> {
+        let mut conflicts: Vec<KeyConflict> = Vec::new();
+        for slot in self.slots() {
+            for &key in self.keys(slot) {
+                match conflicts.iter_mut().find(|c| c.key == key) {
+                    Some(conflict) => conflict.slots.push(slot),
+                    None => conflicts.push(KeyConflict {
+                        key,
+                        slots: vec![slot],
+                        reserved: RESERVED_KEYS.contains(&key),
+                    }),
+                }
+            }
+        }
+        conflicts.retain(|c| c.slots.len() > 1 || c.reserved);
+        conflicts
+    }
+
+    /// One `p<player>.<button> = <key>, <key>` line per button, then
+    /// `mat.<number> = <key>` for the mat
+    pub fn to_config(&self) -> String {
+        let mut out = String::new();
+        for slot in self.slots() {
+            let keys: Vec<&str> = self.keys(slot).iter().map(|key| key.name()).collect();
+            let _ = writeln!(out, "{} = {}", slot.name(), keys.join(", "));
+        }
+        out
+    }
+
+    /// Read bindings written by `to_config()`. Buttons missing from `text`
+    /// keep their defaults, and unknown lines or keys are skipped
+    pub fn from_config(text: &str) -> Self {
+        let mut bindings = Self::default();
+        for line in text.lines() {
+            let Some((name, keys)) = line.split_once('=') else {
+                continue;
+            };
+            let Some(slot) = parse_slot(name.trim()) else {
+                continue;
+            };
+            *bindings.keys_mut(slot) = keys
+                .split(',')
+                .filter_map(|key| Key::from_name(key.trim()))
+                .collect();
+        }
+        bindings
+    }
+
+    fn slots(&self) -> impl Iterator<Item = BindingSlot> {
+        let controllers = (0..PLAYERS).flat_map(|player| {
+            (0..BUTTONS.len()).map(move |button| BindingSlot::Controller { player, button })
+        });
+        controllers.chain((0..MAT_BUTTONS).map(BindingSlot::Mat))
+    }
+}
+
+fn parse_slot(name: &str) -> Option<BindingSlot> {
+    let (device, button) = name.split_once('.')?;
+    if device.eq_ignore_ascii_case("mat") {
+        let button = button
+            .parse::<usize>()
+            .ok()?
+            .checked_sub(1)
+            .filter(|b| *b < MAT_BUTTONS)?;
+        return Some(BindingSlot::Mat(button));
+    }
+    let player = device
+        .strip_prefix('p')?
+        .parse::<usize>()
+        .ok()?
+        .checked_sub(1)
+        .filter(|p| *p < PLAYERS)?;
+    let button = BUTTONS
+        .iter()
+        .position(|b| b.name.eq_ignore_ascii_case(button))?;
+    Some(BindingSlot::Controller { player, button })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_have_no_conflicts() {
+        let bindings = KeyBindings::default();
+        assert!(bindings.conflicts().is_empty());
+
+        let p1 = bindings.input(0, |key| key == Key::K || key == Key::W);
+        assert_eq!(p1.held, (JoypadButton::BUTTON_A | JoypadButton::UP).bits());
+        assert_eq!(p1.turbo, 0);
+        let p2 = bindings.input(1, |key| key == Key::Period || key == Key::L);
+        assert_eq!(p2.held, JoypadButton::BUTTON_A.bits());
+        assert_eq!(p2.turbo, JoypadButton::BUTTON_B.bits());
+
+        assert_eq!(bindings.input(2, |_| true), ControllerInput::default());
+        assert_eq!(
+            bindings.mat_input(|key| key == Key::Num6 || key == Key::B),
+            0x801
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut bindings = KeyBindings::default();
+        let p2_up = BindingSlot::Controller {
+            player: 1,
+            button: 0,
+        };
+        bindings.add(p2_up, Key::W);
+        bindings.add(p2_up, Key::P);
+
+        let conflicts = bindings.conflicts();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].key, Key::W);
+        assert_eq!(
+            conflicts[0].slots,
+            vec![
+                BindingSlot::Controller {
+                    player: 0,
+                    button: 0
+                },
+                p2_up
+            ]
+        );
+        assert!(!conflicts[0].reserved);
+        assert_eq!(conflicts[1].key, Key::P);
+        assert!(conflicts[1].reserved);
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut bindings = KeyBindings::default();
+        let p1_a = BindingSlot::Controller {
+            player: 0,
+            button: 5,
+        };
+        bindings.add(p1_a, Key::L);
+        bindings.remove(
+            BindingSlot::Controller {
+                player: 1,
+                button: 7,
+            },
+            Key::Slash,
+        );
+
+        let config = bindings.to_config();
+        assert!(config.contains("p1.A = K, L\n"));
+        assert!(config.contains("p2.Start = \n"));
+        assert!(config.contains("p4.A = \n"));
+        assert!(config.contains("mat.12 = B\n"));
+        assert_eq!(KeyBindings::from_config(&config), bindings);
+
+        // Missing and garbled entries fall back to the defaults
+        let partial =
+            KeyBindings::from_config("p1.a = L\np5.a = K\nmat.5 = Q\nmat.13 = K\nnonsense\n");
+        assert_eq!(partial.keys(p1_a), &[Key::L]);
+        assert_eq!(
+            partial.keys(BindingSlot::Controller {
+                player: 1,
+                button: 0
+            }),
+            &[Key::ArrowUp]
+        );
+        assert_eq!(partial.keys(BindingSlot::Mat(4)), &[Key::Q]);
+        assert_eq!(partial.keys(BindingSlot::Mat(11)), &[Key::B]);
+    }
+}
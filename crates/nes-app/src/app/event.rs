@@ -1,5 +1,6 @@
 use crate::app::action::Action;
 use crate::app::app::App;
+use crate::emu::netplay::NetplayConfig;
 
 pub trait AppEventSource {
     fn poll_event(&mut self) -> Option<AppEvent>;
@@ -8,10 +9,16 @@ pub trait AppEventSource {
 #[derive(Debug)]
 pub enum AppEvent {
     Start,
-    LoadRom(Vec<u8>),
+    /// A ROM file's name and contents
+    LoadRom {
+        name: String,
+        bytes: Vec<u8>,
+    },
     Run,
     Pause,
     Reset,
+    /// Play against a peer with every ROM loaded from now on
+    Netplay(NetplayConfig),
 }
 
 impl<E: AppEventSource> App<E> {
@@ -26,9 +33,9 @@ impl<E: AppEventSource> App<E> {
     pub(crate) fn handle_external_event(&mut self, event: AppEvent) -> anyhow::Result<()> {
         match event {
             AppEvent::Start => self.start_emulator(),
-            AppEvent::LoadRom(rom) => {
+            AppEvent::LoadRom { name, bytes } => {
                 self.log("AppEvent::LoadRom");
-                self.apply_action(Action::PlayRom(rom))
+                self.apply_action(Action::PlayRom { name, bytes })
             }
             AppEvent::Run => {
                 self.log("AppEvent::Run");
@@ -40,6 +47,10 @@ impl<E: AppEventSource> App<E> {
             AppEvent::Reset => {
                 self.log("AppEvent::Reset");
             }
+            AppEvent::Netplay(config) => {
+                self.log("AppEvent::Netplay");
+                self.netplay_config = Some(config);
+            }
         }
         Ok(())
     }
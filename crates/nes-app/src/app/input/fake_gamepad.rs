@@ -0,0 +1,53 @@
+use crate::app::input::gamepad::{GamepadBackend, GamepadEvent, GamepadId, PadAxis, PadButton};
+use std::collections::VecDeque;
+
+/// In-memory backend for exercising gamepad handling without hardware.
+/// Queue events with its methods; `Gamepads::update` reads them in order
+#[derive(Default)]
+pub struct FakeGamepadBackend {
+    events: VecDeque<GamepadEvent>,
+}
+
+impl FakeGamepadBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connect(&mut self, id: GamepadId, name: &str) {
+        self.events.push_back(GamepadEvent::Connected {
+            id,
+            name: name.to_string(),
+        });
+    }
+
+    pub fn disconnect(&mut self, id: GamepadId) {
+        self.events.push_back(GamepadEvent::Disconnected { id });
+    }
+
+    pub fn press(&mut self, id: GamepadId, button: PadButton) {
+        self.events.push_back(GamepadEvent::Button {
+            id,
+            button,
+            pressed: true,
+        });
+    }
+
+    pub fn release(&mut self, id: GamepadId, button: PadButton) {
+        self.events.push_back(GamepadEvent::Button {
+            id,
+            button,
+            pressed: false,
+        });
+    }
+
+    pub fn axis(&mut self, id: GamepadId, axis: PadAxis, value: f32) {
+        self.events
+            .push_back(GamepadEvent::Axis { id, axis, value });
+    }
+}
+
+impl GamepadBackend for FakeGamepadBackend {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        self.events.pop_front()
+    }
+}
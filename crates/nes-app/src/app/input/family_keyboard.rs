@@ -0,0 +1,109 @@
+use egui::Key;
+use nes_core::prelude::FamilyKey;
+
+/// Host keys for the Family BASIC keyboard, by position where the layouts
+/// differ. Keys with no host equivalent go somewhere nearby: ¥ on
+/// backslash, カナ on backtick, STOP on End, @ on Delete and _ on Page Down
+const KEY_MAP: [(Key, FamilyKey); 68] = [
+    (Key::F1, FamilyKey::F1),
+    (Key::F2, FamilyKey::F2),
+    (Key::F3, FamilyKey::F3),
+    (Key::F4, FamilyKey::F4),
+    (Key::F5, FamilyKey::F5),
+    (Key::F6, FamilyKey::F6),
+    (Key::F7, FamilyKey::F7),
+    (Key::F8, FamilyKey::F8),
+    (Key::Num0, FamilyKey::Num0),
+    (Key::Num1, FamilyKey::Num1),
+    (Key::Num2, FamilyKey::Num2),
+    (Key::Num3, FamilyKey::Num3),
+    (Key::Num4, FamilyKey::Num4),
+    (Key::Num5, FamilyKey::Num5),
+    (Key::Num6, FamilyKey::Num6),
+    (Key::Num7, FamilyKey::Num7),
+    (Key::Num8, FamilyKey::Num8),
+    (Key::Num9, FamilyKey::Num9),
+    (Key::A, FamilyKey::A),
+    (Key::B, FamilyKey::B),
+    (Key::C, FamilyKey::C),
+    (Key::D, FamilyKey::D),
+    (Key::E, FamilyKey::E),
+    (Key::F, FamilyKey::F),
+    (Key::G, FamilyKey::G),
+    (Key::H, FamilyKey::H),
+    (Key::I, FamilyKey::I),
+    (Key::J, FamilyKey::J),
+    (Key::K, FamilyKey::K),
+    (Key::L, FamilyKey::L),
+    (Key::M, FamilyKey::M),
+    (Key::N, FamilyKey::N),
+    (Key::O, FamilyKey::O),
+    (Key::P, FamilyKey::P),
+    (Key::Q, FamilyKey::Q),
+    (Key::R, FamilyKey::R),
+    (Key::S, FamilyKey::S),
+    (Key::T, FamilyKey::T),
+    (Key::U, FamilyKey::U),
+    (Key::V, FamilyKey::V),
+    (Key::W, FamilyKey::W),
+    (Key::X, FamilyKey::X),
+    (Key::Y, FamilyKey::Y),
+    (Key::Z, FamilyKey::Z),
+    (Key::Minus, FamilyKey::Minus),
+    (Key::Equals, FamilyKey::Caret),
+    (Key::Backslash, FamilyKey::Yen),
+    (Key::End, FamilyKey::Stop),
+    (Key::Escape, FamilyKey::Escape),
+    (Key::OpenBracket, FamilyKey::LeftBracket),
+    (Key::CloseBracket, FamilyKey::RightBracket),
+    (Key::Enter, FamilyKey::Return),
+    (Key::Semicolon, FamilyKey::Semicolon),
+    (Key::Quote, FamilyKey::Colon),
+    (Key::Backtick, FamilyKey::Kana),
+    (Key::Comma, FamilyKey::Comma),
+    (Key::Period, FamilyKey::Period),
+    (Key::Slash, FamilyKey::Slash),
+    (Key::Space, FamilyKey::Space),
+    (Key::Home, FamilyKey::ClrHome),
+    (Key::Insert, FamilyKey::Insert),
+    (Key::Backspace, FamilyKey::Delete),
+    (Key::Delete, FamilyKey::At),
+    (Key::PageDown, FamilyKey::Underscore),
+    (Key::ArrowUp, FamilyKey::Up),
+    (Key::ArrowDown, FamilyKey::Down),
+    (Key::ArrowLeft, FamilyKey::Left),
+    (Key::ArrowRight, FamilyKey::Right),
+];
+
+/// Family BASIC keys held this UI frame. Shift, Ctrl and GRPH follow the
+/// modifiers
+pub fn family_keys(input: &egui::InputState) -> Vec<FamilyKey> {
+    let modifiers = [
+        (input.modifiers.shift, FamilyKey::LeftShift),
+        (input.modifiers.ctrl, FamilyKey::Ctrl),
+        (input.modifiers.alt, FamilyKey::Graph),
+    ];
+    KEY_MAP
+        .iter()
+        .map(|&(key, family_key)| (input.key_down(key), family_key))
+        .chain(modifiers)
+        .filter_map(|(held, key)| held.then_some(key))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_keys_cover_the_keyboard() {
+        let mut mapped: Vec<FamilyKey> = KEY_MAP.iter().map(|&(_, key)| key).collect();
+        mapped.extend([FamilyKey::LeftShift, FamilyKey::Ctrl, FamilyKey::Graph]);
+        // Both shift keys act the same in Family BASIC, so one will do
+        let unmapped: Vec<FamilyKey> = FamilyKey::ALL
+            .into_iter()
+            .filter(|key| !mapped.contains(key))
+            .collect();
+        assert_eq!(unmapped, [FamilyKey::RightShift]);
+    }
+}
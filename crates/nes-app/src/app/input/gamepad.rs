@@ -0,0 +1,347 @@
+use crate::app::bindings::{BUTTONS, BindableButton};
+use crate::app::input::InputSource;
+use crate::emu::emu_input::{ControllerInput, PLAYERS};
+use nes_core::prelude::JoypadButton;
+
+/// Backend-specific gamepad handle, stable while the pad stays connected
+pub type GamepadId = usize;
+
+/// Buttons in the standard gamepad layout, named by position
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PadButton {
+    South,
+    East,
+    West,
+    North,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Positive X is right, positive Y is up
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PadAxis {
+    LeftStickX,
+    LeftStickY,
+}
+
+/// What backends report, already translated to the standard layout
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    Connected {
+        id: GamepadId,
+        name: String,
+    },
+    Disconnected {
+        id: GamepadId,
+    },
+    Button {
+        id: GamepadId,
+        button: PadButton,
+        pressed: bool,
+    },
+    Axis {
+        id: GamepadId,
+        axis: PadAxis,
+        value: f32,
+    },
+}
+
+pub trait GamepadBackend {
+    /// Next pending event, or `None` once they've all been read
+    fn next_event(&mut self) -> Option<GamepadEvent>;
+}
+
+/// Reported when gamepads come and go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hotplug {
+    /// `player` is `None` when all four controllers already have a gamepad
+    Connected {
+        name: String,
+        player: Option<usize>,
+    },
+    Disconnected {
+        name: String,
+        player: Option<usize>,
+    },
+}
+
+/// How gamepad buttons and the left stick map onto controller buttons
+#[derive(Debug, Clone)]
+pub struct GamepadMapping {
+    pub buttons: Vec<(PadButton, BindableButton)>,
+    /// How far the stick has to be pushed (0.0 to 1.0) to press a direction
+    pub stick_threshold: f32,
+}
+
+impl Default for GamepadMapping {
+    /// NES B and A on the bottom and right face buttons, their turbo versions
+    /// on the left and top ones
+    fn default() -> Self {
+        let find = |name| *BUTTONS.iter().find(|b| b.name == name).unwrap();
+        Self {
+            buttons: vec![
+                (PadButton::DPadUp, find("Up")),
+                (PadButton::DPadDown, find("Down")),
+                (PadButton::DPadLeft, find("Left")),
+                (PadButton::DPadRight, find("Right")),
+                (PadButton::South, find("B")),
+                (PadButton::East, find("A")),
+                (PadButton::Select, find("Select")),
+                (PadButton::Start, find("Start")),
+                (PadButton::West, find("Turbo B")),
+                (PadButton::North, find("Turbo A")),
+            ],
+            stick_threshold: 0.5,
+        }
+    }
+}
+
+struct Pad {
+    id: GamepadId,
+    name: String,
+    player: Option<usize>,
+    pressed: Vec<PadButton>,
+    stick: [f32; 2],
+}
+
+/// Connected gamepads and their state. Each new gamepad takes the first free
+/// player, and gives it up again when disconnected
+pub struct Gamepads {
+    pads: Vec<Pad>,
+    mapping: GamepadMapping,
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new(GamepadMapping::default())
+    }
+}
+
+impl Gamepads {
+    pub fn new(mapping: GamepadMapping) -> Self {
+        Self {
+            pads: Vec::new(),
+            mapping,
+        }
+    }
+
+    /// Read all pending events from `backend`
+    pub fn update(&mut self, backend: &mut dyn GamepadBackend) -> Vec<Hotplug> {
+        let mut hotplug = Vec::new();
+        while let Some(event) = backend.next_event() {
+            match event {
+                GamepadEvent::Connected { id, name } => {
+                    self.pads.retain(|pad| pad.id != id);
+                    let player =
+                        (0..PLAYERS).find(|&p| self.pads.iter().all(|pad| pad.player != Some(p)));
+                    hotplug.push(Hotplug::Connected {
+                        name: name.clone(),
+                        player,
+                    });
+                    self.pads.push(Pad {
+                        id,
+                        name,
+                        player,
+                        pressed: Vec::new(),
+                        stick: [0.0; 2],
+                    });
+                }
+                GamepadEvent::Disconnected { id } => {
+                    if let Some(index) = self.pads.iter().position(|pad| pad.id == id) {
+                        let pad = self.pads.remove(index);
+                        hotplug.push(Hotplug::Disconnected {
+                            name: pad.name,
+                            player: pad.player,
+                        });
+                    }
+                }
+                GamepadEvent::Button {
+                    id,
+                    button,
+                    pressed,
+                } => {
+                    if let Some(pad) = self.pad_mut(id) {
+                        pad.pressed.retain(|b| *b != button);
+                        if pressed {
+                            pad.pressed.push(button);
+                        }
+                    }
+                }
+                GamepadEvent::Axis { id, axis, value } => {
+                    if let Some(pad) = self.pad_mut(id) {
+                        pad.stick[axis as usize] = value;
+                    }
+                }
+            }
+        }
+        hotplug
+    }
+
+    /// Number of gamepads connected, including any without a player
+    pub fn connected(&self) -> usize {
+        self.pads.len()
+    }
+
+    fn pad_mut(&mut self, id: GamepadId) -> Option<&mut Pad> {
+        self.pads.iter_mut().find(|pad| pad.id == id)
+    }
+}
+
+impl InputSource for Gamepads {
+    fn controller_input(&self, player: usize) -> ControllerInput {
+        let mut input = ControllerInput::default();
+        let Some(pad) = self.pads.iter().find(|pad| pad.player == Some(player)) else {
+            return input;
+        };
+
+        for (button, bindable) in &self.mapping.buttons {
+            if pad.pressed.contains(button) {
+                match bindable.turbo {
+                    true => input.turbo |= bindable.button.bits(),
+                    false => input.held |= bindable.button.bits(),
+                }
+            }
+        }
+
+        let threshold = self.mapping.stick_threshold;
+        let [x, y] = pad.stick;
+        let directions = [
+            (x > threshold, JoypadButton::RIGHT),
+            (x < -threshold, JoypadButton::LEFT),
+            (y > threshold, JoypadButton::UP),
+            (y < -threshold, JoypadButton::DOWN),
+        ];
+        for (pushed, direction) in directions {
+            if pushed {
+                input.held |= direction.bits();
+            }
+        }
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::input::fake_gamepad::FakeGamepadBackend;
+    use crate::app::input::merge;
+
+    #[test]
+    fn test_hotplug_assigns_ports() {
+        let mut backend = FakeGamepadBackend::new();
+        let mut gamepads = Gamepads::default();
+
+        backend.connect(7, "Pad A");
+        backend.connect(9, "Pad B");
+        backend.connect(11, "Pad C");
+        backend.connect(13, "Pad D");
+        backend.connect(15, "Pad E");
+        let hotplug = gamepads.update(&mut backend);
+        assert_eq!(
+            hotplug,
+            [
+                Hotplug::Connected {
+                    name: "Pad A".into(),
+                    player: Some(0)
+                },
+                Hotplug::Connected {
+                    name: "Pad B".into(),
+                    player: Some(1)
+                },
+                Hotplug::Connected {
+                    name: "Pad C".into(),
+                    player: Some(2)
+                },
+                Hotplug::Connected {
+                    name: "Pad D".into(),
+                    player: Some(3)
+                },
+                Hotplug::Connected {
+                    name: "Pad E".into(),
+                    player: None
+                },
+            ]
+        );
+
+        // Unplugging P1 frees its player for the next gamepad
+        backend.disconnect(7);
+        backend.press(15, PadButton::Start);
+        backend.connect(12, "Pad F");
+        backend.press(12, PadButton::East);
+        let hotplug = gamepads.update(&mut backend);
+        assert_eq!(hotplug.len(), 2);
+        assert_eq!(
+            hotplug[1],
+            Hotplug::Connected {
+                name: "Pad F".into(),
+                player: Some(0)
+            }
+        );
+        assert_eq!(gamepads.connected(), 5);
+        assert_eq!(
+            gamepads.controller_input(0).held,
+            JoypadButton::BUTTON_A.bits()
+        );
+        assert_eq!(gamepads.controller_input(1).held, 0);
+    }
+
+    #[test]
+    fn test_mapping_and_stick_threshold() {
+        let mut backend = FakeGamepadBackend::new();
+        let mut gamepads = Gamepads::default();
+        backend.connect(0, "Pad");
+        backend.press(0, PadButton::South);
+        backend.press(0, PadButton::North);
+        backend.axis(0, PadAxis::LeftStickX, 0.4);
+        backend.axis(0, PadAxis::LeftStickY, -0.9);
+        gamepads.update(&mut backend);
+
+        let input = gamepads.controller_input(0);
+        assert_eq!(
+            input.held,
+            (JoypadButton::BUTTON_B | JoypadButton::DOWN).bits()
+        );
+        assert_eq!(input.turbo, JoypadButton::BUTTON_A.bits());
+
+        backend.release(0, PadButton::South);
+        backend.axis(0, PadAxis::LeftStickX, 0.6);
+        gamepads.update(&mut backend);
+        assert_eq!(
+            gamepads.controller_input(0).held,
+            (JoypadButton::RIGHT | JoypadButton::DOWN).bits()
+        );
+    }
+
+    #[test]
+    fn test_merge_sources() {
+        let mut backend = FakeGamepadBackend::new();
+        let mut gamepads = Gamepads::default();
+        backend.connect(0, "Pad");
+        backend.press(0, PadButton::DPadLeft);
+        gamepads.update(&mut backend);
+
+        let keyboard = FixedInput(ControllerInput {
+            held: JoypadButton::START.bits(),
+            turbo: JoypadButton::BUTTON_B.bits(),
+        });
+        let sources: [&dyn InputSource; 2] = [&keyboard, &gamepads];
+        let merged = merge(&sources, 0);
+        assert_eq!(
+            merged.held,
+            (JoypadButton::START | JoypadButton::LEFT).bits()
+        );
+        assert_eq!(merged.turbo, JoypadButton::BUTTON_B.bits());
+    }
+
+    struct FixedInput(ControllerInput);
+
+    impl InputSource for FixedInput {
+        fn controller_input(&self, _player: usize) -> ControllerInput {
+            self.0
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::app::input::gamepad::{GamepadBackend, GamepadEvent, PadAxis, PadButton};
+use anyhow::Context;
+use gilrs::{Axis, Button, EventType, Gilrs};
+use std::collections::VecDeque;
+
+/// Native gamepads through gilrs
+pub struct GilrsBackend {
+    gilrs: Gilrs,
+    /// Gamepads already connected at startup, which gilrs doesn't announce
+    startup: VecDeque<GamepadEvent>,
+}
+
+impl GilrsBackend {
+    pub fn new() -> anyhow::Result<Self> {
+        let gilrs = Gilrs::new()
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .context("Gamepad support unavailable")?;
+        let startup = gilrs
+            .gamepads()
+            .map(|(id, gamepad)| GamepadEvent::Connected {
+                id: id.into(),
+                name: gamepad.name().to_string(),
+            })
+            .collect();
+        Ok(Self { gilrs, startup })
+    }
+}
+
+impl GamepadBackend for GilrsBackend {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        if let Some(event) = self.startup.pop_front() {
+            return Some(event);
+        }
+
+        // Skip events that don't map to anything
+        while let Some(gilrs::Event {
+            id: gilrs_id,
+            event,
+            ..
+        }) = self.gilrs.next_event()
+        {
+            let id = gilrs_id.into();
+            let event = match event {
+                EventType::Connected => GamepadEvent::Connected {
+                    id,
+                    name: self.gilrs.gamepad(gilrs_id).name().to_string(),
+                },
+                EventType::Disconnected => GamepadEvent::Disconnected { id },
+                EventType::ButtonPressed(button, _) | EventType::ButtonReleased(button, _) => {
+                    let Some(button) = pad_button(button) else {
+                        continue;
+                    };
+                    let pressed = matches!(event, EventType::ButtonPressed(..));
+                    GamepadEvent::Button {
+                        id,
+                        button,
+                        pressed,
+                    }
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    let axis = match axis {
+                        Axis::LeftStickX => PadAxis::LeftStickX,
+                        Axis::LeftStickY => PadAxis::LeftStickY,
+                        _ => continue,
+                    };
+                    GamepadEvent::Axis { id, axis, value }
+                }
+                _ => continue,
+            };
+            return Some(event);
+        }
+        None
+    }
+}
+
+fn pad_button(button: Button) -> Option<PadButton> {
+    Some(match button {
+        Button::South => PadButton::South,
+        Button::East => PadButton::East,
+        Button::West => PadButton::West,
+        Button::North => PadButton::North,
+        Button::Select => PadButton::Select,
+        Button::Start => PadButton::Start,
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadRight => PadButton::DPadRight,
+        _ => return None,
+    })
+}
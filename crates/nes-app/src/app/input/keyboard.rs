@@ -0,0 +1,21 @@
+use crate::app::bindings::KeyBindings;
+use crate::app::input::InputSource;
+use crate::emu::emu_input::ControllerInput;
+
+/// Keys held this UI frame, read through the user's key bindings
+pub struct Keyboard<'a> {
+    bindings: &'a KeyBindings,
+    input: &'a egui::InputState,
+}
+
+impl<'a> Keyboard<'a> {
+    pub fn new(bindings: &'a KeyBindings, input: &'a egui::InputState) -> Self {
+        Self { bindings, input }
+    }
+}
+
+impl InputSource for Keyboard<'_> {
+    fn controller_input(&self, player: usize) -> ControllerInput {
+        self.bindings.input(player, |key| self.input.key_down(key))
+    }
+}
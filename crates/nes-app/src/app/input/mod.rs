@@ -0,0 +1,48 @@
+//! Everything that can press buttons on an emulated controller.
+//!
+//! Each device type is an `InputSource` reporting what it holds for a player;
+//! `merge` combines them, so a keyboard and a gamepad can drive the same
+//! controller at once
+
+use crate::emu::emu_input::ControllerInput;
+
+pub mod fake_gamepad;
+pub mod family_keyboard;
+pub mod gamepad;
+#[cfg(not(target_arch = "wasm32"))]
+mod gilrs_gamepad;
+pub mod keyboard;
+#[cfg(target_arch = "wasm32")]
+mod web_gamepad;
+
+pub trait InputSource {
+    /// Buttons this source holds on `player`'s controller
+    fn controller_input(&self, player: usize) -> ControllerInput;
+}
+
+/// Every source's buttons for `player`, combined
+pub fn merge(sources: &[&dyn InputSource], player: usize) -> ControllerInput {
+    sources
+        .iter()
+        .fold(ControllerInput::default(), |merged, source| {
+            let input = source.controller_input(player);
+            ControllerInput {
+                held: merged.held | input.held,
+                turbo: merged.turbo | input.turbo,
+            }
+        })
+}
+
+/// The platform's gamepad backend: gilrs on native, the Gamepad API in the
+/// browser. Fails if the platform has no gamepad support
+pub fn platform_gamepad_backend() -> anyhow::Result<Box<dyn gamepad::GamepadBackend>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Ok(Box::new(gilrs_gamepad::GilrsBackend::new()?))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Ok(Box::new(web_gamepad::WebGamepadBackend::new()))
+    }
+}
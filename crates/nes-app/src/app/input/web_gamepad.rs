@@ -0,0 +1,131 @@
+use crate::app::input::gamepad::{GamepadBackend, GamepadEvent, GamepadId, PadAxis, PadButton};
+use std::collections::VecDeque;
+use wasm_bindgen::JsCast;
+
+/// Button indices in the Gamepad API's "standard" mapping
+const STANDARD_BUTTONS: [(u32, PadButton); 10] = [
+    (0, PadButton::South),
+    (1, PadButton::East),
+    (2, PadButton::West),
+    (3, PadButton::North),
+    (8, PadButton::Select),
+    (9, PadButton::Start),
+    (12, PadButton::DPadUp),
+    (13, PadButton::DPadDown),
+    (14, PadButton::DPadLeft),
+    (15, PadButton::DPadRight),
+];
+
+struct PadSnapshot {
+    id: GamepadId,
+    pressed: [bool; STANDARD_BUTTONS.len()],
+    stick: [f32; 2],
+}
+
+/// Browser gamepads through the Gamepad API. The browser only offers polling,
+/// so events come from diffing each poll against the last one
+pub struct WebGamepadBackend {
+    pads: Vec<PadSnapshot>,
+    events: VecDeque<GamepadEvent>,
+}
+
+impl WebGamepadBackend {
+    pub fn new() -> Self {
+        Self {
+            pads: Vec::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(gamepads) = web_sys::window().and_then(|w| w.navigator().get_gamepads().ok())
+        else {
+            return;
+        };
+
+        let current: Vec<web_sys::Gamepad> = gamepads
+            .iter()
+            .filter_map(|pad| pad.dyn_into::<web_sys::Gamepad>().ok())
+            .filter(|pad| pad.connected())
+            .collect();
+
+        let events = &mut self.events;
+        self.pads.retain(|old| {
+            let still_there = current.iter().any(|pad| pad.index() as GamepadId == old.id);
+            if !still_there {
+                events.push_back(GamepadEvent::Disconnected { id: old.id });
+            }
+            still_there
+        });
+
+        for pad in current {
+            let id = pad.index() as GamepadId;
+            let snapshot = snapshot(&pad);
+            let old = match self.pads.iter().position(|old| old.id == id) {
+                Some(index) => &mut self.pads[index],
+                None => {
+                    self.events
+                        .push_back(GamepadEvent::Connected { id, name: pad.id() });
+                    self.pads.push(PadSnapshot {
+                        id,
+                        pressed: [false; STANDARD_BUTTONS.len()],
+                        stick: [0.0; 2],
+                    });
+                    self.pads.last_mut().unwrap()
+                }
+            };
+
+            for (i, (_, button)) in STANDARD_BUTTONS.iter().enumerate() {
+                if old.pressed[i] != snapshot.pressed[i] {
+                    self.events.push_back(GamepadEvent::Button {
+                        id,
+                        button: *button,
+                        pressed: snapshot.pressed[i],
+                    });
+                }
+            }
+            for (i, axis) in [PadAxis::LeftStickX, PadAxis::LeftStickY]
+                .into_iter()
+                .enumerate()
+            {
+                if old.stick[i] != snapshot.stick[i] {
+                    self.events.push_back(GamepadEvent::Axis {
+                        id,
+                        axis,
+                        value: snapshot.stick[i],
+                    });
+                }
+            }
+            *old = snapshot;
+        }
+    }
+}
+
+/// Current state of `pad`. The API's Y axis points down, so it's flipped
+fn snapshot(pad: &web_sys::Gamepad) -> PadSnapshot {
+    let buttons = pad.buttons();
+    let mut pressed = [false; STANDARD_BUTTONS.len()];
+    for (i, (index, _)) in STANDARD_BUTTONS.iter().enumerate() {
+        pressed[i] = buttons
+            .get(*index)
+            .dyn_into::<web_sys::GamepadButton>()
+            .is_ok_and(|button| button.pressed());
+    }
+
+    let axes = pad.axes();
+    let axis = |index| axes.get(index).as_f64().unwrap_or(0.0) as f32;
+    PadSnapshot {
+        id: pad.index() as GamepadId,
+        pressed,
+        stick: [axis(0), -axis(1)],
+    }
+}
+
+impl GamepadBackend for WebGamepadBackend {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        if self.events.is_empty() {
+            self.poll();
+        }
+        self.events.pop_front()
+    }
+}
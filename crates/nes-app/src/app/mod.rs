@@ -1,4 +1,10 @@
 mod action;
 pub mod app;
+mod bindings;
 pub mod event;
+pub mod input;
+mod screenshot;
+mod settings;
+mod speed;
+mod storage;
 pub mod ui;
@@ -0,0 +1,126 @@
+use crate::app::app::App;
+use crate::app::event::AppEventSource;
+use crate::video::Image;
+
+impl<E: AppEventSource> App<E> {
+    /// Save the shared frame in the current palette, scaled if the screenshot
+    /// settings ask for it. Native builds write to `screenshots/`, browsers
+    /// get a download
+    pub(crate) fn save_screenshot(&mut self) {
+        let frame = self.frame.read();
+        let mut image = Image::new(256, 240, self.palette.to_rgba(&frame.pixels));
+        if self.screenshots.scaled {
+            image = self.display.scaler.scale(image);
+        }
+        self.save_png(&image, "screenshot");
+    }
+
+    /// Save the pattern viewer's tables in its palette, the same way as
+    /// screenshots
+    pub(crate) fn save_pattern_tables(&mut self) {
+        let image = self
+            .pattern_viewer
+            .as_ref()
+            .and_then(|viewer| viewer.image(&self.palette));
+        match image {
+            Some(image) => self.save_png(&image, "patterns"),
+            None => self.log("No pattern tables to save yet"),
+        }
+    }
+
+    /// Write `image` to `screenshots/<prefix>-<time>.png`, or download it in
+    /// a browser
+    fn save_png(&mut self, image: &Image, prefix: &str) {
+        let png = match image.to_png() {
+            Ok(png) => png,
+            Err(e) => {
+                self.log(format!("Failed to encode {prefix}: {e:#}"));
+                return;
+            }
+        };
+        let name = format!("{prefix}-{}", timestamp());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            use anyhow::Context;
+
+            let dir = std::path::Path::new("screenshots");
+            let result = std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))
+                .and_then(|()| {
+                    // Several screenshots in a second get numbered
+                    let path = (1..)
+                        .map(|n| match n {
+                            1 => dir.join(format!("{name}.png")),
+                            n => dir.join(format!("{name}-{n}.png")),
+                        })
+                        .find(|path| !path.exists())
+                        .unwrap_or_default();
+                    std::fs::write(&path, &png)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    Ok(path)
+                });
+            match result {
+                Ok(path) => self.log(format!("Saved {prefix} to {}", path.display())),
+                Err(e) => self.log(format!("{e:#}")),
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        match &self.download_callback {
+            Some(download) => download(&format!("{name}.png"), &png),
+            None => self.log("Saving images needs a download callback in the browser"),
+        }
+    }
+}
+
+/// The current UTC time as `YYYYMMDD-HHMMSS`, for file names
+pub(crate) fn timestamp() -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    #[cfg(target_arch = "wasm32")]
+    let seconds = (web_sys::js_sys::Date::now() / 1000.0) as u64;
+    format_timestamp(seconds)
+}
+
+/// `seconds` since the Unix epoch as `YYYYMMDD-HHMMSS`
+fn format_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // Days to a calendar date.
+    // See: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day comes last
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "19700101-000000");
+        assert_eq!(format_timestamp(1_700_000_000), "20231114-221320");
+        // Leap day
+        assert_eq!(format_timestamp(951_782_400 + 59), "20000229-000059");
+        assert_eq!(format_timestamp(4_102_444_799), "20991231-235959");
+    }
+}
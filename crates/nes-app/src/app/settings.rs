@@ -0,0 +1,125 @@
+//! Settings edited alongside the picture that aren't about the picture
+//! itself, each stored under its own key as `name = value` lines
+
+/// Storage key for the sprite settings, in `SpriteSettings::to_config()` format
+pub const SPRITES_STORAGE_KEY: &str = "sprites";
+
+/// How the emulated PPU draws sprites
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct SpriteSettings {
+    /// Draw sprites past the 8 per line the PPU can
+    pub unlimited: bool,
+}
+
+impl SpriteSettings {
+    pub fn to_config(self) -> String {
+        format!("unlimited = {}\n", self.unlimited)
+    }
+
+    /// Read settings written by `to_config()`. Anything missing or invalid
+    /// keeps its default
+    pub fn from_config(text: &str) -> Self {
+        let mut settings = Self::default();
+        for (name, value) in config_lines(text) {
+            if name == "unlimited"
+                && let Ok(value) = value.parse()
+            {
+                settings.unlimited = value;
+            }
+        }
+        settings
+    }
+}
+
+/// Storage key for the screenshot settings, in
+/// `ScreenshotSettings::to_config()` format
+pub const SCREENSHOTS_STORAGE_KEY: &str = "screenshots";
+
+/// How F1 screenshots are saved
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ScreenshotSettings {
+    /// Run screenshots through the display's scaler
+    pub scaled: bool,
+}
+
+impl ScreenshotSettings {
+    pub fn to_config(self) -> String {
+        format!("scaled = {}\n", self.scaled)
+    }
+
+    /// Read settings written by `to_config()`. Anything missing or invalid
+    /// keeps its default
+    pub fn from_config(text: &str) -> Self {
+        let mut settings = Self::default();
+        for (name, value) in config_lines(text) {
+            if name == "scaled"
+                && let Ok(value) = value.parse()
+            {
+                settings.scaled = value;
+            }
+        }
+        settings
+    }
+}
+
+/// Storage key for the recording settings, in
+/// `CaptureSettings::to_config()` format
+pub const CAPTURE_STORAGE_KEY: &str = "capture";
+
+/// How F11 records video and audio
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CaptureSettings {
+    /// Numbered PNGs and a WAV instead of an AVI
+    pub png_wav: bool,
+}
+
+impl CaptureSettings {
+    pub fn to_config(self) -> String {
+        format!("png_wav = {}\n", self.png_wav)
+    }
+
+    /// Read settings written by `to_config()`. Anything missing or invalid
+    /// keeps its default
+    pub fn from_config(text: &str) -> Self {
+        let mut settings = Self::default();
+        for (name, value) in config_lines(text) {
+            if name == "png_wav"
+                && let Ok(value) = value.parse()
+            {
+                settings.png_wav = value;
+            }
+        }
+        settings
+    }
+}
+
+/// Trimmed `name = value` pairs, skipping lines without an `=`
+fn config_lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip() {
+        let sprites = SpriteSettings { unlimited: true };
+        assert_eq!(SpriteSettings::from_config(&sprites.to_config()), sprites);
+        assert_eq!(
+            SpriteSettings::from_config("unlimited = maybe\nnonsense"),
+            SpriteSettings::default()
+        );
+
+        let screenshots = ScreenshotSettings { scaled: true };
+        assert_eq!(
+            ScreenshotSettings::from_config(&screenshots.to_config()),
+            screenshots
+        );
+
+        let capture = CaptureSettings { png_wav: true };
+        assert_eq!(CaptureSettings::from_config(&capture.to_config()), capture);
+    }
+}
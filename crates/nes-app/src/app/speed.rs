@@ -0,0 +1,46 @@
+/// Speeds cycled through by the speed toggle hotkey
+const SPEED_PRESETS: [f32; 4] = [1.0, 2.0, 0.5, 0.25];
+
+/// Speed while the fast-forward key is held
+const FAST_FORWARD_SPEED: f32 = 3.0;
+
+/// Tracks the user's chosen speed and whether fast-forward is held
+pub struct SpeedControl {
+    preset: usize,
+    fast_forward: bool,
+}
+
+impl Default for SpeedControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpeedControl {
+    pub fn new() -> Self {
+        Self {
+            preset: 0,
+            fast_forward: false,
+        }
+    }
+
+    /// Speed the emulator should currently run at
+    pub fn speed(&self) -> f32 {
+        match self.fast_forward {
+            true => FAST_FORWARD_SPEED,
+            false => SPEED_PRESETS[self.preset],
+        }
+    }
+
+    pub fn fast_forward(&self) -> bool {
+        self.fast_forward
+    }
+
+    pub fn set_fast_forward(&mut self, held: bool) {
+        self.fast_forward = held;
+    }
+
+    pub fn cycle(&mut self) {
+        self.preset = (self.preset + 1) % SPEED_PRESETS.len();
+    }
+}
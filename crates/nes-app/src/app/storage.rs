@@ -0,0 +1,58 @@
+//! Small persistent settings: a file per key in the user's config directory
+//! on native, and `localStorage` in the browser
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use anyhow::Context;
+    use std::path::PathBuf;
+
+    fn config_dir() -> Option<PathBuf> {
+        let dir = if cfg!(windows) {
+            PathBuf::from(std::env::var_os("APPDATA")?)
+        } else if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+            PathBuf::from(dir)
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from(std::env::var_os("HOME")?).join("Library/Application Support")
+        } else {
+            PathBuf::from(std::env::var_os("HOME")?).join(".config")
+        };
+        Some(dir.join("nes-emulator"))
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        std::fs::read_to_string(config_dir()?.join(format!("{key}.cfg"))).ok()
+    }
+
+    pub fn save(key: &str, value: &str) -> anyhow::Result<()> {
+        let dir = config_dir().context("No config directory")?;
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(format!("{key}.cfg"));
+        std::fs::write(&path, value).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    const PREFIX: &str = "nes-emulator.";
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        local_storage()?.get_item(&format!("{PREFIX}{key}")).ok()?
+    }
+
+    pub fn save(key: &str, value: &str) -> anyhow::Result<()> {
+        let storage = local_storage().ok_or_else(|| anyhow::anyhow!("localStorage unavailable"))?;
+        storage
+            .set_item(&format!("{PREFIX}{key}"), value)
+            .map_err(|_| anyhow::anyhow!("Failed to write localStorage"))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::{load, save};
+#[cfg(target_arch = "wasm32")]
+pub use web::{load, save};
@@ -1,35 +1,44 @@
 use crate::app::action::Action;
 use crate::app::app::UiCtx;
+use crate::app::bindings::KeyBindings;
+use crate::app::input;
+use crate::app::input::InputSource;
+use crate::app::input::family_keyboard;
+use crate::app::input::gamepad::Gamepads;
+use crate::app::input::keyboard::Keyboard;
 use crate::app::ui::views::UiView;
 use crate::emu::commands::AudioChannel;
 use crate::emu::host::EmuHost;
-use nes_core::prelude::JoypadButton;
+use crate::emu::movie::MovieStatus;
+use nes_core::prelude::StepKind;
 use nes_core::trace_dump;
 use std::process;
 
-pub fn update_controller_state(ctx: &egui::Context, emu: &EmuHost) {
-    let p1_key_map: &[(egui::Key, JoypadButton)] = &[
-        (egui::Key::K, JoypadButton::BUTTON_A),
-        (egui::Key::J, JoypadButton::BUTTON_B),
-        (egui::Key::Enter, JoypadButton::START),
-        (egui::Key::Space, JoypadButton::SELECT),
-        (egui::Key::W, JoypadButton::UP),
-        (egui::Key::S, JoypadButton::DOWN),
-        (egui::Key::A, JoypadButton::LEFT),
-        (egui::Key::D, JoypadButton::RIGHT),
-    ];
-
-    let mut p1 = 0u8;
-    for (key, button) in p1_key_map.iter() {
-        if ctx.input(|i| i.key_down(*key)) {
-            p1 |= button.bits();
-        }
-    }
-
-    let p2 = 0u8;
-    // TODO Maybe later: p2 input
-
-    emu.set_input(p1, p2);
+/// While `keyboard_captured`, host keys type on the Family BASIC keyboard
+/// and only gamepads drive the controllers. The mat is keyboard-only
+pub fn update_controller_state(
+    ctx: &egui::Context,
+    emu: &EmuHost,
+    bindings: &KeyBindings,
+    gamepads: &Gamepads,
+    keyboard_captured: bool,
+) {
+    let (players, mat, family_keys) = ctx.input(|i| {
+        let keyboard = Keyboard::new(bindings, i);
+        let sources: Vec<&dyn InputSource> = match keyboard_captured {
+            true => vec![gamepads],
+            false => vec![&keyboard, gamepads],
+        };
+        let (mat, family_keys) = match keyboard_captured {
+            true => (0, family_keyboard::family_keys(i)),
+            false => (bindings.mat_input(|key| i.key_down(key)), Vec::new()),
+        };
+        let players = std::array::from_fn(|player| input::merge(&sources, player));
+        (players, mat, family_keys)
+    });
+    emu.set_input(players);
+    emu.set_mat(mat);
+    emu.set_family_keys(&family_keys);
 }
 
 pub fn handle_hotkeys(egui_ctx: &egui::Context, ui_ctx: &mut UiCtx, view: &UiView) {
@@ -45,10 +54,83 @@ pub fn handle_hotkeys(egui_ctx: &egui::Context, ui_ctx: &mut UiCtx, view: &UiVie
 
     // Play screen hotkeys
     if matches!(view, UiView::Playing(..)) {
+        // F12 is the only way out of typing on the Family BASIC keyboard
+        if ui_ctx.family_keyboard && input.key_pressed(egui::Key::F12) {
+            ui_ctx.actions.push(Action::ToggleKeyboardCapture);
+        }
+        if ui_ctx.keyboard_captured {
+            return;
+        }
+
         if input.key_pressed(egui::Key::P) {
             ui_ctx.actions.push(Action::TogglePause);
         }
 
+        // Single-step while paused
+        if ui_ctx.paused {
+            let step_keys = [
+                (egui::Key::F6, StepKind::Frame),
+                (egui::Key::F7, StepKind::Scanline),
+                (egui::Key::F8, StepKind::Instruction),
+                (egui::Key::F9, StepKind::Dot),
+            ];
+            for (key, kind) in step_keys {
+                if input.key_pressed(key) {
+                    ui_ctx.actions.push(Action::Step(kind));
+                }
+            }
+        }
+
+        // Hold to fast-forward
+        let fast_forward = input.key_down(egui::Key::Tab);
+        if fast_forward != ui_ctx.fast_forward {
+            ui_ctx.actions.push(Action::SetFastForward(fast_forward));
+        }
+        if input.key_pressed(egui::Key::Backtick) {
+            ui_ctx.actions.push(Action::CycleSpeed);
+        }
+
+        // Hold to rewind
+        let rewinding = input.key_down(egui::Key::Backspace);
+        if rewinding != ui_ctx.rewinding {
+            ui_ctx.actions.push(Action::SetRewinding(rewinding));
+        }
+
+        // F10 records from power-on, Shift+F10 from the current state; F10 again stops
+        if input.key_pressed(egui::Key::F10) {
+            let action = match ui_ctx.movie_status {
+                MovieStatus::Idle => Action::StartRecording {
+                    from_power_on: !input.modifiers.shift,
+                },
+                _ => Action::StopMovie,
+            };
+            ui_ctx.actions.push(action);
+        }
+
+        if input.key_pressed(egui::Key::F3) {
+            ui_ctx.actions.push(Action::ToggleStats);
+        }
+
+        if input.key_pressed(egui::Key::F2) {
+            ui_ctx.actions.push(Action::ToggleVideoOptions);
+        }
+
+        if input.key_pressed(egui::Key::F4) {
+            ui_ctx.actions.push(Action::ToggleMicrophone);
+        }
+
+        if input.key_pressed(egui::Key::F1) {
+            ui_ctx.actions.push(Action::Screenshot);
+        }
+
+        if input.key_pressed(egui::Key::F5) {
+            ui_ctx.actions.push(Action::ToggleNametableViewer);
+        }
+
+        if input.key_pressed(egui::Key::F11) {
+            ui_ctx.actions.push(Action::ToggleCapture);
+        }
+
         if input.key_pressed(egui::Key::Num1) {
             ui_ctx
                 .actions
@@ -14,7 +14,7 @@ pub fn handle_file_drop(ctx: &egui::Context, ui_ctx: &mut UiCtx) {
         painter.text(
             content_rect.center(),
             Align2::CENTER_CENTER,
-            "Drop ROM file here",
+            "Drop ROM, .zip, .fm2 or .pal file here",
             FontId::proportional(40.0),
             Color32::WHITE,
         );
@@ -30,7 +30,10 @@ pub fn handle_file_drop(ctx: &egui::Context, ui_ctx: &mut UiCtx) {
                 if let Some(path) = &file.path
                     && let Ok(rom_data) = std::fs::read(path)
                 {
-                    ui_ctx.actions.push(Action::PlayRom(rom_data));
+                    let name = path
+                        .file_name()
+                        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                    ui_ctx.actions.push(open_file_action(name, rom_data));
                 }
             }
 
@@ -38,9 +41,23 @@ pub fn handle_file_drop(ctx: &egui::Context, ui_ctx: &mut UiCtx) {
             {
                 if let Some(bytes) = &file.bytes {
                     let rom_data = bytes.to_vec();
-                    ui_ctx.actions.push(Action::PlayRom(rom_data));
+                    ui_ctx
+                        .actions
+                        .push(open_file_action(file.name.clone(), rom_data));
                 }
             }
         }
     });
 }
+
+/// FM2 movies are text starting with their version line and palettes are
+/// named `.pal`; anything else is treated as a ROM
+fn open_file_action(name: String, data: Vec<u8>) -> Action {
+    if name.to_ascii_lowercase().ends_with(".pal") {
+        return Action::LoadPalette { name, data };
+    }
+    match data.starts_with(b"version ") {
+        true => Action::PlayMovie(data),
+        false => Action::PlayRom { name, bytes: data },
+    }
+}
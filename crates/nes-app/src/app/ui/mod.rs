@@ -1,4 +1,7 @@
 pub mod app_input;
 pub mod error;
 pub mod file_drop_overlay;
+pub mod nametable_viewer;
+pub mod pattern_viewer;
+pub mod video_options;
 pub mod views;
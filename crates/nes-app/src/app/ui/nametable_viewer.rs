@@ -0,0 +1,166 @@
+use crate::app::action::Action;
+use crate::app::app::UiCtx;
+use eframe::epaint::ColorImage;
+use eframe::epaint::textures::TextureOptions;
+use nes_core::nes::ppu::viewer::{NAMETABLES_HEIGHT, NAMETABLES_WIDTH};
+use nes_core::prelude::PpuSnapshot;
+use std::sync::Arc;
+
+const SCROLL_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 60, 60);
+const GRID_COLOR: egui::Color32 = egui::Color32::from_rgba_premultiplied(90, 90, 90, 90);
+
+/// Debug window with the four nametables as the PPU sees them at the start
+/// of a chosen scanline, the scroll position and the attribute grid over them
+pub struct NametableViewer {
+    scanline: usize,
+    show_scroll: bool,
+    show_attribute_grid: bool,
+    snapshot: Option<Arc<PpuSnapshot>>,
+    texture: Option<egui::TextureHandle>,
+    /// `texture` doesn't show `snapshot` yet
+    stale: bool,
+}
+
+impl Default for NametableViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NametableViewer {
+    pub fn new() -> Self {
+        Self {
+            scanline: 0,
+            show_scroll: true,
+            show_attribute_grid: false,
+            snapshot: None,
+            texture: None,
+            stale: false,
+        }
+    }
+
+    pub fn scanline(&self) -> usize {
+        self.scanline
+    }
+
+    pub fn set_scanline(&mut self, scanline: usize) {
+        self.scanline = scanline;
+    }
+
+    pub fn set_snapshot(&mut self, snapshot: Arc<PpuSnapshot>) {
+        self.snapshot = Some(snapshot);
+        self.stale = true;
+    }
+
+    pub fn ui(&mut self, egui_ctx: &egui::Context, ui_ctx: &mut UiCtx) {
+        if self.stale
+            && let Some(snapshot) = &self.snapshot
+        {
+            let rgba = ui_ctx.palette.to_rgba(&snapshot.render_nametables());
+            let image =
+                ColorImage::from_rgba_unmultiplied([NAMETABLES_WIDTH, NAMETABLES_HEIGHT], &rgba);
+            match &mut self.texture {
+                Some(texture) => texture.set(image, TextureOptions::NEAREST),
+                None => {
+                    self.texture =
+                        Some(egui_ctx.load_texture("nametables", image, TextureOptions::NEAREST))
+                }
+            }
+            self.stale = false;
+        }
+
+        let scanlines = ui_ctx.telemetry.map_or(262, |t| t.region.scanlines());
+        let scanline_before = self.scanline;
+        let mut open = true;
+        egui::Window::new("Nametables")
+            .open(&mut open)
+            .resizable(false)
+            .show(egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Refresh at scanline");
+                    ui.add(egui::DragValue::new(&mut self.scanline).range(0..=scanlines - 1));
+                    ui.checkbox(&mut self.show_scroll, "Scroll");
+                    ui.checkbox(&mut self.show_attribute_grid, "Attribute grid");
+                });
+                self.nametables_ui(ui);
+            });
+
+        if self.scanline != scanline_before {
+            ui_ctx
+                .actions
+                .push(Action::SetSnapshotScanline(self.scanline));
+        }
+        if !open {
+            ui_ctx.actions.push(Action::ToggleNametableViewer);
+        }
+    }
+
+    fn nametables_ui(&self, ui: &mut egui::Ui) {
+        let (Some(snapshot), Some(texture)) = (&self.snapshot, &self.texture) else {
+            ui.label("Waiting for the emulator to reach the scanline…");
+            return;
+        };
+
+        let size = egui::vec2(NAMETABLES_WIDTH as f32, NAMETABLES_HEIGHT as f32);
+        let response = ui.add(egui::Image::new((texture.id(), size)).sense(egui::Sense::hover()));
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+
+        if self.show_attribute_grid {
+            // One attribute byte covers 32x32 pixels
+            for x in (32..NAMETABLES_WIDTH).step_by(32) {
+                let x = rect.left() + x as f32;
+                painter.vline(x, rect.y_range(), egui::Stroke::new(1.0, GRID_COLOR));
+            }
+            // Nametables are 240 lines, so the grid starts over in the bottom two
+            for top in [0, 240] {
+                for y in (top..top + 240).step_by(32).filter(|&y| y > 0) {
+                    let y = rect.top() + y as f32;
+                    painter.hline(rect.x_range(), y, egui::Stroke::new(1.0, GRID_COLOR));
+                }
+            }
+        }
+
+        if self.show_scroll {
+            // The screen wraps around the edges of the layout
+            let (x, y) = snapshot.scroll_origin();
+            let screen = egui::Rect::from_min_size(
+                rect.min + egui::vec2(x as f32, y as f32),
+                egui::vec2(256.0, 240.0),
+            );
+            for dx in [0.0, -size.x] {
+                for dy in [0.0, -size.y] {
+                    painter.rect_stroke(
+                        screen.translate(egui::vec2(dx, dy)),
+                        0.0,
+                        egui::Stroke::new(2.0, SCROLL_COLOR),
+                        egui::StrokeKind::Inside,
+                    );
+                }
+            }
+        }
+
+        ui.label(format!(
+            "Scanline {}  Mirroring {:?}  v ${:04X}  t ${:04X}  Fine X {}",
+            snapshot.scanline, snapshot.mirroring, snapshot.v, snapshot.t, snapshot.fine_x
+        ));
+        let hovered = response
+            .hover_pos()
+            .map(|pos| pos - rect.min)
+            .and_then(|offset| snapshot.tile_at(offset.x as usize, offset.y as usize));
+        match hovered {
+            Some(tile) => ui.monospace(format!(
+                "Tile ({:2}, {:2}) at ${:04X} = ${:02X}  Pattern ${:04X}\nAttribute at ${:04X} = ${:02X}  Palette {}",
+                tile.column,
+                tile.row,
+                tile.address,
+                tile.tile,
+                tile.pattern_address,
+                tile.attribute_address,
+                tile.attribute,
+                tile.palette
+            )),
+            None => ui.monospace("Hover over a tile for details\n"),
+        };
+    }
+}
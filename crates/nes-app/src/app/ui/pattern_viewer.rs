@@ -0,0 +1,156 @@
+use crate::app::action::Action;
+use crate::app::app::UiCtx;
+use crate::video::Image;
+use crate::video::palette::Palette;
+use eframe::epaint::ColorImage;
+use eframe::epaint::textures::TextureOptions;
+use nes_core::nes::ppu::viewer::{PATTERNS_HEIGHT, PATTERNS_WIDTH};
+use nes_core::prelude::PpuSnapshot;
+use std::sync::Arc;
+
+/// How much bigger than the real 8x8 tiles to show them
+const ZOOM: f32 = 2.0;
+
+/// Debug window with both pattern tables as the CHR banks had them at the
+/// start of a chosen scanline, drawn in any of the eight palettes
+pub struct PatternViewer {
+    scanline: usize,
+    /// 0-3 are the background palettes, 4-7 the sprite palettes
+    palette: u8,
+    snapshot: Option<Arc<PpuSnapshot>>,
+    texture: Option<egui::TextureHandle>,
+    /// `texture` doesn't show `snapshot` in `palette` yet
+    stale: bool,
+}
+
+impl Default for PatternViewer {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl PatternViewer {
+    pub fn new(scanline: usize) -> Self {
+        Self {
+            scanline,
+            palette: 0,
+            snapshot: None,
+            texture: None,
+            stale: false,
+        }
+    }
+
+    pub fn scanline(&self) -> usize {
+        self.scanline
+    }
+
+    pub fn set_scanline(&mut self, scanline: usize) {
+        self.scanline = scanline;
+    }
+
+    pub fn set_snapshot(&mut self, snapshot: Arc<PpuSnapshot>) {
+        self.snapshot = Some(snapshot);
+        self.stale = true;
+    }
+
+    /// The pattern tables as shown, for saving
+    pub fn image(&self, palette: &Palette) -> Option<Image> {
+        let snapshot = self.snapshot.as_ref()?;
+        let rgba = palette.to_rgba(&snapshot.render_patterns(self.palette));
+        Some(Image::new(PATTERNS_WIDTH, PATTERNS_HEIGHT, rgba))
+    }
+
+    pub fn ui(&mut self, egui_ctx: &egui::Context, ui_ctx: &mut UiCtx) {
+        if self.stale
+            && let Some(image) = self.image(ui_ctx.palette)
+        {
+            let image =
+                ColorImage::from_rgba_unmultiplied([image.width, image.height], &image.rgba);
+            match &mut self.texture {
+                Some(texture) => texture.set(image, TextureOptions::NEAREST),
+                None => {
+                    self.texture =
+                        Some(egui_ctx.load_texture("patterns", image, TextureOptions::NEAREST))
+                }
+            }
+            self.stale = false;
+        }
+
+        let scanlines = ui_ctx.telemetry.map_or(262, |t| t.region.scanlines());
+        let scanline_before = self.scanline;
+        let palette_before = self.palette;
+        let mut open = true;
+        egui::Window::new("Pattern tables")
+            .open(&mut open)
+            .resizable(false)
+            .show(egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Refresh at scanline");
+                    ui.add(egui::DragValue::new(&mut self.scanline).range(0..=scanlines - 1));
+                    egui::ComboBox::from_label("Palette")
+                        .selected_text(palette_name(self.palette))
+                        .show_ui(ui, |ui| {
+                            for palette in 0..8 {
+                                ui.selectable_value(
+                                    &mut self.palette,
+                                    palette,
+                                    palette_name(palette),
+                                );
+                            }
+                        });
+                    let export =
+                        ui.add_enabled(self.snapshot.is_some(), egui::Button::new("Save PNG"));
+                    if export.clicked() {
+                        ui_ctx.actions.push(Action::SavePatternTables);
+                    }
+                });
+                self.patterns_ui(ui);
+            });
+
+        if self.palette != palette_before {
+            self.stale = true;
+            egui_ctx.request_repaint();
+        }
+        if self.scanline != scanline_before {
+            ui_ctx
+                .actions
+                .push(Action::SetSnapshotScanline(self.scanline));
+        }
+        if !open {
+            ui_ctx.actions.push(Action::TogglePatternViewer);
+        }
+    }
+
+    fn patterns_ui(&self, ui: &mut egui::Ui) {
+        let (Some(snapshot), Some(texture)) = (&self.snapshot, &self.texture) else {
+            ui.label("Waiting for the emulator to reach the scanline…");
+            return;
+        };
+
+        let size = egui::vec2(PATTERNS_WIDTH as f32, PATTERNS_HEIGHT as f32) * ZOOM;
+        let response = ui.add(egui::Image::new((texture.id(), size)).sense(egui::Sense::hover()));
+
+        let hovered = response
+            .hover_pos()
+            .map(|pos| (pos - response.rect.min) / ZOOM)
+            .and_then(|offset| snapshot.pattern_at(offset.x as usize, offset.y as usize));
+        match hovered {
+            Some(address) => ui.monospace(format!(
+                "Tile ${:02X} at ${address:04X}  Scanline {}",
+                address / 16 % 256,
+                snapshot.scanline
+            )),
+            None => ui.monospace(format!(
+                "Hover over a tile for details  Scanline {}",
+                snapshot.scanline
+            )),
+        };
+    }
+}
+
+fn palette_name(palette: u8) -> String {
+    match palette {
+        0..4 => format!("Background {palette}"),
+        _ => format!("Sprite {}", palette - 4),
+    }
+}
@@ -0,0 +1,298 @@
+use crate::app::action::Action;
+use crate::app::app::UiCtx;
+use crate::app::settings::{CaptureSettings, ScreenshotSettings, SpriteSettings};
+use crate::app::storage;
+use crate::video::display::{DisplaySettings, Overscan, PixelAspect};
+use crate::video::ntsc::NtscPreset;
+use crate::video::palette::{
+    NtscPaletteParams, Palette, PaletteFile, PaletteSettings, PaletteSource,
+};
+use crate::video::scale::Scaler;
+
+/// Storage key for the palette settings, in `PaletteSettings::to_config()` format
+pub const PALETTE_STORAGE_KEY: &str = "palette";
+/// Storage key for the NTSC filter preset's name, empty when it's off
+pub const NTSC_FILTER_STORAGE_KEY: &str = "ntsc_filter";
+/// Storage key for the display settings, in `DisplaySettings::to_config()` format
+pub const DISPLAY_STORAGE_KEY: &str = "display";
+
+/// Size to open the native window at, from the saved display settings
+pub fn saved_window_size() -> [f32; 2] {
+    let display = storage::load(DISPLAY_STORAGE_KEY)
+        .map(|config| DisplaySettings::from_config(&config))
+        .unwrap_or_default();
+    let (width, height) = display.window_size();
+    [width, height]
+}
+
+/// Floating window for picture settings. Changes apply as they're made, and
+/// are saved when the window closes
+pub struct VideoOptionsWindow {
+    palette: PaletteSettings,
+    ntsc: Option<NtscPreset>,
+    display: DisplaySettings,
+    sprites: SpriteSettings,
+    screenshots: ScreenshotSettings,
+    capture: CaptureSettings,
+    /// Why the last `.pal` file couldn't be used
+    load_error: Option<String>,
+}
+
+impl VideoOptionsWindow {
+    pub fn new(
+        palette: PaletteSettings,
+        ntsc: Option<NtscPreset>,
+        display: DisplaySettings,
+        sprites: SpriteSettings,
+        screenshots: ScreenshotSettings,
+        capture: CaptureSettings,
+    ) -> Self {
+        Self {
+            palette,
+            ntsc,
+            display,
+            sprites,
+            screenshots,
+            capture,
+            load_error: None,
+        }
+    }
+
+    pub fn ui(&mut self, egui_ctx: &egui::Context, ui_ctx: &mut UiCtx) {
+        let before = self.palette.clone();
+        let ntsc_before = self.ntsc;
+        let display_before = self.display;
+        let sprites_before = self.sprites;
+        let screenshots_before = self.screenshots;
+        let capture_before = self.capture;
+        let mut open = true;
+        egui::Window::new("Video")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(egui_ctx, |ui| {
+                self.palette_ui(ui);
+                ui.add_space(8.0);
+                palette_preview(ui, &self.palette.palette());
+                ui.add_space(8.0);
+                self.filter_ui(ui);
+                ui.add_space(8.0);
+                self.display_ui(ui);
+            });
+
+        if self.palette != before {
+            ui_ctx
+                .actions
+                .push(Action::SetPalette(Box::new(self.palette.clone())));
+        }
+        if self.ntsc != ntsc_before {
+            ui_ctx.actions.push(Action::SetNtscFilter(self.ntsc));
+        }
+        if self.display != display_before {
+            ui_ctx.actions.push(Action::SetDisplay(self.display));
+        }
+        if self.sprites != sprites_before {
+            ui_ctx.actions.push(Action::SetSprites(self.sprites));
+        }
+        if self.screenshots != screenshots_before {
+            ui_ctx
+                .actions
+                .push(Action::SetScreenshots(self.screenshots));
+        }
+        if self.capture != capture_before {
+            ui_ctx.actions.push(Action::SetCapture(self.capture));
+        }
+        if !open {
+            ui_ctx.actions.push(Action::ToggleVideoOptions);
+        }
+    }
+
+    /// Use a `.pal` file dropped on the window or picked from disk
+    pub fn load_pal_file(&mut self, name: String, bytes: &[u8]) -> Option<PaletteSettings> {
+        match Palette::from_pal(bytes) {
+            Ok(palette) => {
+                self.palette.source = PaletteSource::File;
+                self.palette.file = Some(PaletteFile { name, palette });
+                self.load_error = None;
+                Some(self.palette.clone())
+            }
+            Err(e) => {
+                self.load_error = Some(format!("{name}: {e:#}"));
+                None
+            }
+        }
+    }
+
+    fn palette_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Palette:");
+            for source in PaletteSource::ALL {
+                ui.radio_value(&mut self.palette.source, source, source.name());
+            }
+        });
+
+        match self.palette.source {
+            PaletteSource::System => {}
+            PaletteSource::Ntsc => {
+                let ntsc = &mut self.palette.ntsc;
+                egui::Grid::new("ntsc_palette")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Hue");
+                        ui.add(egui::Slider::new(&mut ntsc.hue, -180.0..=180.0).suffix("°"));
+                        ui.end_row();
+                        ui.label("Saturation");
+                        ui.add(egui::Slider::new(&mut ntsc.saturation, 0.0..=2.0));
+                        ui.end_row();
+                        ui.label("Contrast");
+                        ui.add(egui::Slider::new(&mut ntsc.contrast, 0.5..=1.5));
+                        ui.end_row();
+                        ui.label("Brightness");
+                        ui.add(egui::Slider::new(&mut ntsc.brightness, -0.5..=0.5));
+                        ui.end_row();
+                        ui.label("Gamma");
+                        ui.add(egui::Slider::new(&mut ntsc.gamma, 1.0..=3.0));
+                        ui.end_row();
+                    });
+                if ui.button("Reset").clicked() {
+                    *ntsc = NtscPaletteParams::default();
+                }
+            }
+            PaletteSource::File => {
+                let name = match &self.palette.file {
+                    Some(file) => file.name.as_str(),
+                    None => "No file loaded, using the built-in palette",
+                };
+                ui.label(name);
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if ui.button("Load .pal…").clicked()
+                    && let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Palette", &["pal"])
+                        .pick_file()
+                {
+                    let name = path
+                        .file_name()
+                        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                    match std::fs::read(&path) {
+                        Ok(bytes) => {
+                            self.load_pal_file(name, &bytes);
+                        }
+                        Err(e) => self.load_error = Some(format!("{name}: {e}")),
+                    }
+                }
+
+                ui.label(
+                    egui::RichText::new("Or drop a .pal file on the window")
+                        .color(ui.visuals().weak_text_color()),
+                );
+                if let Some(error) = &self.load_error {
+                    ui.label(egui::RichText::new(error).color(ui.visuals().error_fg_color));
+                }
+            }
+        }
+    }
+
+    fn filter_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("NTSC filter:");
+            egui::ComboBox::from_id_salt("ntsc_filter")
+                .selected_text(self.ntsc.map_or("Off", |preset| preset.name()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.ntsc, None, "Off");
+                    for preset in NtscPreset::ALL {
+                        ui.selectable_value(&mut self.ntsc, Some(preset), preset.name());
+                    }
+                });
+        });
+        if matches!(self.ntsc, Some(preset) if preset != NtscPreset::Rgb) {
+            ui.label(
+                egui::RichText::new("Colors come from the NTSC generator's settings")
+                    .color(ui.visuals().weak_text_color()),
+            );
+        }
+    }
+
+    fn display_ui(&mut self, ui: &mut egui::Ui) {
+        let display = &mut self.display;
+        egui::Grid::new("display").num_columns(2).show(ui, |ui| {
+            ui.label("Scaler");
+            ui.add_enabled_ui(self.ntsc.is_none(), |ui| {
+                egui::ComboBox::from_id_salt("scaler")
+                    .selected_text(display.scaler.name())
+                    .show_ui(ui, |ui| {
+                        for scaler in Scaler::ALL {
+                            ui.selectable_value(&mut display.scaler, scaler, scaler.name());
+                        }
+                    })
+                    .response
+                    .on_disabled_hover_text("Not used with the NTSC filter");
+            });
+            ui.end_row();
+            ui.label("Pixel aspect");
+            ui.horizontal(|ui| {
+                for aspect in PixelAspect::ALL {
+                    ui.radio_value(&mut display.aspect, aspect, aspect.name());
+                }
+            });
+            ui.end_row();
+            ui.label("Scanlines");
+            ui.add(egui::Slider::new(&mut display.scanlines, 0.0..=1.0));
+            ui.end_row();
+            ui.label("Overscan");
+            ui.horizontal(|ui| {
+                let overscan = &mut display.overscan;
+                for (edge, name) in [
+                    (&mut overscan.top, "Top"),
+                    (&mut overscan.bottom, "Bottom"),
+                    (&mut overscan.left, "Left"),
+                    (&mut overscan.right, "Right"),
+                ] {
+                    ui.label(name);
+                    ui.add(egui::DragValue::new(edge).range(0..=Overscan::MAX));
+                }
+            });
+            ui.end_row();
+            ui.label("Window size");
+            ui.add(
+                egui::Slider::new(&mut display.window_scale, DisplaySettings::WINDOW_SCALES)
+                    .suffix("×"),
+            );
+            ui.end_row();
+        });
+        ui.checkbox(&mut display.hide_left_column, "Hide leftmost 8 pixels");
+        ui.checkbox(&mut self.sprites.unlimited, "No sprite limit")
+            .on_hover_text("Draw every sprite on a line instead of the first 8, so they flicker less. Games still see the limit");
+        ui.checkbox(&mut display.integer_scaling, "Integer scaling");
+        ui.add_enabled_ui(display.scaler != Scaler::Nearest, |ui| {
+            ui.checkbox(&mut self.screenshots.scaled, "Scale screenshots (F1)");
+        });
+        ui.checkbox(
+            &mut self.capture.png_wav,
+            "Record PNG frames and a WAV (F11)",
+        )
+        .on_hover_text("Instead of an uncompressed AVI");
+    }
+}
+
+/// The 64 colors without emphasis, a row per brightness level
+fn palette_preview(ui: &mut egui::Ui, palette: &Palette) {
+    let swatch = egui::vec2(14.0, 14.0);
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(swatch.x * 16.0, swatch.y * 4.0),
+        egui::Sense::hover(),
+    );
+    for index in 0..64u16 {
+        let (r, g, b) = palette.rgb(index);
+        let min = rect.min
+            + egui::vec2(
+                (index % 16) as f32 * swatch.x,
+                (index / 16) as f32 * swatch.y,
+            );
+        ui.painter().rect_filled(
+            egui::Rect::from_min_size(min, swatch),
+            0.0,
+            egui::Color32::from_rgb(r, g, b),
+        );
+    }
+}
@@ -1,17 +1,22 @@
 use crate::app::ui::views::error_view::ErrorView;
+use crate::app::ui::views::options_view::OptionsView;
 use crate::app::ui::views::playing_view::PlayingView;
+use crate::app::ui::views::rom_picker_view::RomPickerView;
 use crate::app::ui::views::rom_select_view::RomSelectView;
 use crate::app::ui::views::waiting_view::WaitingView;
 
 pub mod error_view;
+pub mod options_view;
 pub mod playing_view;
+pub mod rom_picker_view;
 pub mod rom_select_view;
 pub mod waiting_view;
 
 pub enum UiView {
     Waiting(WaitingView),
     RomSelect(RomSelectView),
-    Options,
+    RomPicker(RomPickerView),
+    Options(Box<OptionsView>),
     Playing(PlayingView),
     Error(ErrorView),
 }
@@ -0,0 +1,326 @@
+use crate::app::action::Action;
+use crate::app::app::UiCtx;
+use crate::app::bindings::{
+    BUTTONS, BindingSlot, KeyBindings, KeyConflict, MAT_BUTTONS, PORT2_DEVICES,
+};
+use crate::app::ui::views::UiView;
+use crate::app::ui::views::rom_select_view::RomSelectView;
+use crate::emu::runtime::DEFAULT_TURBO_RATE;
+use nes_core::prelude::{ExpansionDevice, PortDevice};
+
+/// Which bindings the grid shows
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BindingsPage {
+    /// Players 1 and 2, or 3 and 4 from `first_player`
+    Players {
+        first_player: usize,
+    },
+    Mat,
+}
+
+impl BindingsPage {
+    const ALL: [BindingsPage; 3] = [
+        BindingsPage::Players { first_player: 0 },
+        BindingsPage::Players { first_player: 2 },
+        BindingsPage::Mat,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            BindingsPage::Players { first_player: 0 } => "Players 1 & 2",
+            BindingsPage::Players { .. } => "Players 3 & 4",
+            BindingsPage::Mat => "Power Pad",
+        }
+    }
+}
+
+/// Key binding editor. Edits a copy of the bindings, applied on "Save"
+pub struct OptionsView {
+    bindings: KeyBindings,
+    page: BindingsPage,
+    turbo_rate: u32,
+    port2_device: PortDevice,
+    /// `None` to use the ROM header's
+    expansion_device: Option<ExpansionDevice>,
+    /// Slot waiting for a key press
+    capturing: Option<BindingSlot>,
+    /// Return to the running game rather than the ROM select screen
+    back_to_game: bool,
+}
+
+impl OptionsView {
+    pub fn new(
+        bindings: KeyBindings,
+        turbo_rate: u32,
+        port2_device: PortDevice,
+        expansion_device: Option<ExpansionDevice>,
+        back_to_game: bool,
+    ) -> Self {
+        Self {
+            bindings,
+            page: BindingsPage::ALL[0],
+            turbo_rate,
+            port2_device,
+            expansion_device,
+            capturing: None,
+            back_to_game,
+        }
+    }
+
+    pub fn ui(&mut self, egui_ctx: &egui::Context, ui_ctx: &mut UiCtx) {
+        if let Some(slot) = self.capturing {
+            self.capture_key(egui_ctx, slot);
+        }
+
+        let conflicts = self.bindings.conflicts();
+
+        egui::CentralPanel::default().show(egui_ctx, |_ui| {
+            egui::Area::new("options_panel".into())
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(egui_ctx, |ui| {
+                    ui.set_min_size(egui::vec2(420.0, 240.0));
+
+                    egui::Frame::group(ui.style())
+                        .inner_margin(egui::Margin::symmetric(32, 32))
+                        .corner_radius(egui::CornerRadius::same(12))
+                        .show(ui, |ui| {
+                            ui.vertical_centered(|ui| {
+                                ui.heading("Controls");
+                                ui.add_space(12.0);
+
+                                ui.label(
+                                    egui::RichText::new(
+                                        "Click + and press a key to bind it, or a key to remove it",
+                                    )
+                                    .color(ui.visuals().weak_text_color()),
+                                );
+                                ui.add_space(16.0);
+
+                                ui.horizontal(|ui| {
+                                    for page in BindingsPage::ALL {
+                                        ui.selectable_value(&mut self.page, page, page.name());
+                                    }
+                                });
+                                ui.add_space(8.0);
+                                match self.page {
+                                    BindingsPage::Players { first_player } => {
+                                        self.bindings_grid(ui, first_player, &conflicts)
+                                    }
+                                    BindingsPage::Mat => self.mat_grid(ui, &conflicts),
+                                }
+
+                                ui.add_space(12.0);
+                                ui.horizontal(|ui| {
+                                    ui.label("Turbo rate:");
+                                    ui.add(
+                                        egui::Slider::new(&mut self.turbo_rate, 1..=8)
+                                            .suffix(" frames"),
+                                    )
+                                    .on_hover_text("How long each turbo press and release lasts");
+                                });
+
+                                ui.add_space(8.0);
+                                self.port2_picker(ui);
+
+                                ui.add_space(8.0);
+                                self.expansion_picker(ui);
+
+                                ui.add_space(12.0);
+                                for conflict in &conflicts {
+                                    ui.label(
+                                        egui::RichText::new(describe_conflict(conflict))
+                                            .color(ui.visuals().error_fg_color),
+                                    );
+                                }
+
+                                ui.add_space(16.0);
+                                ui.horizontal(|ui| {
+                                    if ui.button("Reset to defaults").clicked() {
+                                        self.bindings = KeyBindings::default();
+                                        self.turbo_rate = DEFAULT_TURBO_RATE;
+                                        self.port2_device = PortDevice::default();
+                                        self.expansion_device = None;
+                                        self.capturing = None;
+                                    }
+                                    if ui.button("Cancel").clicked() {
+                                        ui_ctx.actions.push(self.close());
+                                    }
+                                    if ui.button("Save").clicked() {
+                                        ui_ctx.actions.push(Action::SetKeyBindings(Box::new(
+                                            self.bindings.clone(),
+                                        )));
+                                        ui_ctx.actions.push(Action::SetTurboRate(self.turbo_rate));
+                                        ui_ctx
+                                            .actions
+                                            .push(Action::SetPort2Device(self.port2_device));
+                                        ui_ctx.actions.push(Action::SetExpansionDevice(
+                                            self.expansion_device,
+                                        ));
+                                        ui_ctx.actions.push(self.close());
+                                    }
+                                });
+                            });
+                        });
+                });
+        });
+    }
+
+    fn port2_picker(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Port 2:");
+            egui::ComboBox::from_id_salt("port2_device")
+                .selected_text(self.port2_device.name())
+                .show_ui(ui, |ui| {
+                    for device in PORT2_DEVICES {
+                        ui.selectable_value(&mut self.port2_device, device, device.name());
+                    }
+                });
+        })
+        .response
+        .on_hover_text(
+            "The Zapper and paddle follow the mouse, and a click fires. The 4-player adapters take port 1 too, for players 3 and 4",
+        );
+    }
+
+    fn expansion_picker(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Expansion port:");
+            egui::ComboBox::from_id_salt("expansion_device")
+                .selected_text(expansion_label(self.expansion_device))
+                .show_ui(ui, |ui| {
+                    let choices = std::iter::once(None).chain(ExpansionDevice::ALL.map(Some));
+                    for choice in choices {
+                        ui.selectable_value(
+                            &mut self.expansion_device,
+                            choice,
+                            expansion_label(choice),
+                        );
+                    }
+                });
+        })
+        .response
+        .on_hover_text(
+            "Famicom only, applied when a ROM is loaded. The 4-player adapters take players 3 and 4, F12 types on the keyboard, F4 toggles the microphone",
+        );
+    }
+
+    /// Two players' bindings, from `first_player`
+    fn bindings_grid(&mut self, ui: &mut egui::Ui, first_player: usize, conflicts: &[KeyConflict]) {
+        let players = first_player..first_player + 2;
+        egui::Grid::new("bindings_grid")
+            .num_columns(3)
+            .spacing(egui::vec2(24.0, 8.0))
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("");
+                for player in players.clone() {
+                    ui.strong(format!("Player {}", player + 1));
+                }
+                ui.end_row();
+
+                for (button, bindable) in BUTTONS.iter().enumerate() {
+                    ui.label(bindable.name);
+                    for player in players.clone() {
+                        let slot = BindingSlot::Controller { player, button };
+                        ui.horizontal(|ui| self.slot_ui(ui, slot, conflicts));
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// Power Pad and Family Trainer buttons, numbered as on side B
+    fn mat_grid(&mut self, ui: &mut egui::Ui, conflicts: &[KeyConflict]) {
+        egui::Grid::new("mat_grid")
+            .num_columns(2)
+            .spacing(egui::vec2(24.0, 8.0))
+            .striped(true)
+            .show(ui, |ui| {
+                for button in 0..MAT_BUTTONS {
+                    ui.label(format!("Button {}", button + 1));
+                    let slot = BindingSlot::Mat(button);
+                    ui.horizontal(|ui| self.slot_ui(ui, slot, conflicts));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn slot_ui(&mut self, ui: &mut egui::Ui, slot: BindingSlot, conflicts: &[KeyConflict]) {
+        for &key in self.bindings.keys(slot).to_vec().iter() {
+            let mut text = egui::RichText::new(key.name());
+            if conflicts.iter().any(|c| c.key == key) {
+                text = text.color(ui.visuals().error_fg_color);
+            }
+            if ui.small_button(text).on_hover_text("Remove").clicked() {
+                self.bindings.remove(slot, key);
+            }
+        }
+
+        let label = match self.capturing == Some(slot) {
+            true => "Press a key…",
+            false => "+",
+        };
+        if ui.small_button(label).clicked() {
+            self.capturing = Some(slot);
+        }
+    }
+
+    /// Take the first key press for `slot`, hiding it from the widgets.
+    /// Escape cancels
+    fn capture_key(&mut self, egui_ctx: &egui::Context, slot: BindingSlot) {
+        let key = egui_ctx.input_mut(|i| {
+            let key = i.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    repeat: false,
+                    ..
+                } => Some(*key),
+                _ => None,
+            });
+            if key.is_some() {
+                i.events
+                    .retain(|event| !matches!(event, egui::Event::Key { .. }));
+            }
+            key
+        });
+
+        match key {
+            Some(egui::Key::Escape) => self.capturing = None,
+            Some(key) => {
+                self.bindings.add(slot, key);
+                self.capturing = None;
+            }
+            None => {}
+        }
+    }
+
+    fn close(&self) -> Action {
+        match self.back_to_game {
+            true => Action::Navigate(UiView::playing()),
+            false => Action::Navigate(UiView::RomSelect(RomSelectView::new())),
+        }
+    }
+}
+
+fn expansion_label(device: Option<ExpansionDevice>) -> &'static str {
+    match device {
+        Some(device) => device.name(),
+        None => "Auto (ROM header)",
+    }
+}
+
+fn describe_conflict(conflict: &KeyConflict) -> String {
+    let uses: Vec<String> = conflict
+        .slots
+        .iter()
+        .map(|slot| match *slot {
+            BindingSlot::Controller { player, button } => {
+                format!("P{} {}", player + 1, BUTTONS[button].name)
+            }
+            BindingSlot::Mat(button) => format!("mat button {}", button + 1),
+        })
+        .chain(conflict.reserved.then(|| "a hotkey".to_string()))
+        .collect();
+    format!("{} is bound to {}", conflict.key.name(), uses.join(" and "))
+}
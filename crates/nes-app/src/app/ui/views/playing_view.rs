@@ -1,7 +1,15 @@
+use crate::app::action::Action;
 use crate::app::app::UiCtx;
+use crate::emu::emu_input::PointerInput;
+use crate::emu::movie::MovieStatus;
+use crate::emu::netplay::NetplayStatus;
+use crate::video::Image;
+use crate::video::display::VisibleArea;
+use crate::video::ntsc::NTSC_WIDTH;
+use crate::video::scale::Scaler;
 use eframe::epaint::ColorImage;
 use eframe::epaint::textures::TextureOptions;
-use nes_core::prelude::NES_SYSTEM_PALETTE;
+use nes_core::prelude::PortDevice;
 
 pub struct PlayingView {}
 
@@ -18,31 +26,143 @@ impl PlayingView {
 
     pub fn ui(&mut self, egui_ctx: &egui::Context, ui_ctx: &mut UiCtx) {
         egui::CentralPanel::default().show(egui_ctx, |ui| {
-            let pixels = ui_ctx.frame.read();
-            let mut rgba = Vec::with_capacity(256 * 240 * 4);
-            for &palette_idx in pixels.iter() {
-                let color = NES_SYSTEM_PALETTE[palette_idx as usize];
-                rgba.extend_from_slice(&[color.0, color.1, color.2, 255]);
-            }
+            let frame = ui_ctx.frame.read();
+            let image = match ui_ctx.ntsc_filter {
+                Some(filter) => {
+                    Image::new(NTSC_WIDTH, 240, filter.render(&frame.pixels, &frame.phases))
+                }
+                None => Image::new(256, 240, ui_ctx.palette.to_rgba(&frame.pixels)),
+            };
+            let display = ui_ctx.display;
+            let image = display.process(image, ui_ctx.ntsc_filter.is_some());
+            // Unscaled pixel art stays crisp, anything else is smoothed
+            let options = match ui_ctx.ntsc_filter.is_none() && display.scaler == Scaler::Nearest {
+                true => TextureOptions::NEAREST,
+                false => TextureOptions::LINEAR,
+            };
 
-            let color_image = ColorImage::from_rgba_unmultiplied([256, 240], &rgba);
+            let color_image =
+                ColorImage::from_rgba_unmultiplied([image.width, image.height], &image.rgba);
             let tex = ui_ctx.texture.get_or_insert_with(|| {
                 ui.ctx()
-                    .load_texture("nes_frame", color_image.clone(), TextureOptions::LINEAR)
+                    .load_texture("nes_frame", color_image.clone(), options)
             });
-            tex.set(color_image, TextureOptions::LINEAR);
-
-            // Scale while maintaining aspect
-            let avail = ui.available_size();
-            let aspect = 256.0 / 240.0;
-            let (w, h) = if avail.x / avail.y > aspect {
-                (avail.y * aspect, avail.y)
-            } else {
-                (avail.x, avail.x / aspect)
-            };
+            tex.set(color_image, options);
+
+            // Fit in physical pixels, so integer scaling lines up with the screen's
+            let pixels_per_point = ui.ctx().pixels_per_point();
+            let avail = ui.available_size() * pixels_per_point;
+            let (w, h) = display.fit((avail.x, avail.y));
+
+            let response = ui.image((tex.id(), egui::vec2(w, h) / pixels_per_point));
+            if matches!(ui_ctx.port2_device, PortDevice::Zapper | PortDevice::Vaus) {
+                let response = response.on_hover_cursor(egui::CursorIcon::Crosshair);
+                let pointer = ui.input(|i| PointerInput {
+                    aim: i
+                        .pointer
+                        .hover_pos()
+                        .and_then(|pos| screen_pixel(response.rect, pos, display.visible_area())),
+                    trigger: i.pointer.primary_down(),
+                });
+                ui_ctx.actions.push(Action::SetPointer(pointer));
+            }
+        });
+
+        let badge = if ui_ctx.rewinding {
+            Some("Rewind".to_string())
+        } else if ui_ctx.speed > 1.0 {
+            Some(format!("Fast-forward {}×", ui_ctx.speed))
+        } else if ui_ctx.speed < 1.0 {
+            Some(format!("Slow-motion {}×", ui_ctx.speed))
+        } else {
+            None
+        };
+        if let Some(badge) = badge {
+            egui::Area::new("speed_badge".into())
+                .anchor(egui::Align2::LEFT_TOP, egui::vec2(8.0, 8.0))
+                .show(egui_ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(egui::RichText::new(badge).strong());
+                    });
+                });
+        }
 
-            ui.image((tex.id(), egui::vec2(w, h)));
+        let movie_badge = match ui_ctx.movie_status {
+            MovieStatus::Idle => None,
+            MovieStatus::Recording => Some(
+                egui::RichText::new("● REC")
+                    .strong()
+                    .color(egui::Color32::from_rgb(230, 60, 60)),
+            ),
+            MovieStatus::Playing => Some(egui::RichText::new("▶ Movie").strong()),
+        };
+        let capture_badge = ui_ctx.capturing.then(|| {
+            egui::RichText::new("● Video")
+                .strong()
+                .color(egui::Color32::from_rgb(230, 60, 60))
         });
+        if movie_badge.is_some() || capture_badge.is_some() {
+            egui::Area::new("movie_badge".into())
+                .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 8.0))
+                .show(egui_ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            for badge in [movie_badge, capture_badge].into_iter().flatten() {
+                                ui.label(badge);
+                            }
+                        });
+                    });
+                });
+        }
+
+        let netplay_badge = match ui_ctx.netplay_status {
+            NetplayStatus::Off => None,
+            NetplayStatus::Connecting => Some(egui::RichText::new("Netplay: waiting for peer…")),
+            NetplayStatus::Running => Some(egui::RichText::new("Netplay")),
+            NetplayStatus::Desynced { frame } => Some(
+                egui::RichText::new(format!("Netplay desynced at frame {frame}"))
+                    .strong()
+                    .color(egui::Color32::from_rgb(230, 60, 60)),
+            ),
+        };
+        if let Some(netplay_badge) = netplay_badge {
+            egui::Area::new("netplay_badge".into())
+                .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+                .show(egui_ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(netplay_badge);
+                    });
+                });
+        }
+
+        // Famicom input state, shown while it changes what the game sees
+        let mut input_badges = Vec::new();
+        if ui_ctx.microphone {
+            input_badges.push("Mic");
+        }
+        if ui_ctx.keyboard_captured {
+            input_badges.push("Keyboard · F12 to release");
+        }
+        if ui_ctx.show_stats
+            && let Some(telemetry) = ui_ctx.telemetry
+        {
+            egui::Area::new("stats_overlay".into())
+                .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+                .show(egui_ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "{}  {:.1}/{:.1} fps  {} Hz",
+                                telemetry.region.name(),
+                                telemetry.fps,
+                                telemetry.target_fps,
+                                telemetry.sample_rate
+                            ))
+                            .monospace(),
+                        );
+                    });
+                });
+        }
 
         if ui_ctx.paused {
             // Dim the background
@@ -69,9 +189,128 @@ impl PlayingView {
                                 ui.label(egui::RichText::new("Paused").strong().size(22.0));
                                 ui.add_space(6.0);
                                 ui.label(egui::RichText::new("Press P to resume"));
+                                ui.label(
+                                    egui::RichText::new(
+                                        "F6 frame · F7 scanline · F8 instruction · F9 dot",
+                                    )
+                                    .small(),
+                                );
+                                ui.add_space(8.0);
+                                ui.horizontal(|ui| {
+                                    if ui.button("Controls…").clicked() {
+                                        ui_ctx
+                                            .actions
+                                            .push(Action::OpenOptions { back_to_game: true });
+                                    }
+                                    if ui.button("Video…").clicked() {
+                                        ui_ctx.actions.push(Action::ToggleVideoOptions);
+                                    }
+                                    if ui.button("Nametables…").clicked() {
+                                        ui_ctx.actions.push(Action::ToggleNametableViewer);
+                                    }
+                                    if ui.button("Patterns…").clicked() {
+                                        ui_ctx.actions.push(Action::TogglePatternViewer);
+                                    }
+                                });
                             });
                         });
                 });
         }
+
+        // The machine position while paused, stacked on the input state
+        let position = ui_ctx.position.filter(|_| ui_ctx.paused);
+        if position.is_some() || !input_badges.is_empty() {
+            egui::Area::new("bottom_status".into())
+                .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(8.0, -8.0))
+                .show(egui_ctx, |ui| {
+                    if let Some(position) = position {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "Frame {}  Scanline {}  Dot {}  CPU cycle {}  PC ${:04X}",
+                                    position.frame,
+                                    position.scanline,
+                                    position.dot,
+                                    position.cpu_cycles,
+                                    position.pc
+                                ))
+                                .monospace(),
+                            );
+                        });
+                    }
+                    if !input_badges.is_empty() {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.label(egui::RichText::new(input_badges.join(" · ")).strong());
+                        });
+                    }
+                });
+        }
+    }
+}
+
+/// Emulated screen pixel under `pos`, given where the `visible` part of the
+/// frame is drawn
+fn screen_pixel(image: egui::Rect, pos: egui::Pos2, visible: VisibleArea) -> Option<(u8, u8)> {
+    if !image.contains(pos) {
+        return None;
+    }
+    let x = (pos.x - image.min.x) / image.width() * visible.width as f32;
+    let y = (pos.y - image.min.y) / image.height() * visible.height as f32;
+    let x = visible.x + (x as usize).min(visible.width - 1);
+    let y = visible.y + (y as usize).min(visible.height - 1);
+    Some((x as u8, y as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL: VisibleArea = VisibleArea {
+        x: 0,
+        y: 0,
+        width: 256,
+        height: 240,
+    };
+
+    #[test]
+    fn test_screen_pixel_scales_to_frame() {
+        let image = egui::Rect::from_min_size(egui::pos2(40.0, 10.0), egui::vec2(512.0, 480.0));
+        assert_eq!(
+            screen_pixel(image, egui::pos2(40.0, 10.0), FULL),
+            Some((0, 0))
+        );
+        assert_eq!(
+            screen_pixel(image, egui::pos2(41.9, 11.9), FULL),
+            Some((0, 0))
+        );
+        assert_eq!(
+            screen_pixel(image, egui::pos2(296.0, 250.0), FULL),
+            Some((128, 120))
+        );
+        assert_eq!(
+            screen_pixel(image, egui::pos2(552.0, 490.0), FULL),
+            Some((255, 239))
+        );
+        assert_eq!(screen_pixel(image, egui::pos2(39.0, 100.0), FULL), None);
+        assert_eq!(screen_pixel(image, egui::pos2(100.0, 491.0), FULL), None);
+    }
+
+    #[test]
+    fn test_screen_pixel_skips_overscan() {
+        let image = egui::Rect::from_min_size(egui::pos2(0.0, 0.0), egui::vec2(480.0, 448.0));
+        let visible = VisibleArea {
+            x: 8,
+            y: 8,
+            width: 240,
+            height: 224,
+        };
+        assert_eq!(
+            screen_pixel(image, egui::pos2(0.0, 0.0), visible),
+            Some((8, 8))
+        );
+        assert_eq!(
+            screen_pixel(image, egui::pos2(480.0, 448.0), visible),
+            Some((247, 231))
+        );
     }
 }
@@ -0,0 +1,73 @@
+use crate::app::action::Action;
+use crate::app::app::UiCtx;
+use crate::app::ui::views::UiView;
+use crate::app::ui::views::rom_select_view::RomSelectView;
+use crate::rom::RomFile;
+
+/// Lists the ROMs found in an archive so the user can pick one
+pub struct RomPickerView {
+    roms: Vec<RomFile>,
+}
+
+impl RomPickerView {
+    pub fn new(roms: Vec<RomFile>) -> Self {
+        RomPickerView { roms }
+    }
+
+    pub fn ui(&mut self, egui_ctx: &egui::Context, ui_ctx: &mut UiCtx) {
+        egui::CentralPanel::default().show(egui_ctx, |_ui| {
+            egui::Area::new("rom_picker_panel".into())
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(egui_ctx, |ui| {
+                    ui.set_min_size(egui::vec2(420.0, 240.0));
+
+                    egui::Frame::group(ui.style())
+                        .inner_margin(egui::Margin::symmetric(32, 32))
+                        .corner_radius(egui::CornerRadius::same(12))
+                        .show(ui, |ui| {
+                            ui.vertical_centered(|ui| {
+                                ui.heading("Choose a ROM");
+                                ui.add_space(12.0);
+
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "This archive contains {} ROMs",
+                                        self.roms.len()
+                                    ))
+                                    .color(ui.visuals().weak_text_color()),
+                                );
+
+                                ui.add_space(16.0);
+
+                                egui::ScrollArea::vertical()
+                                    .max_height(320.0)
+                                    .show(ui, |ui| {
+                                        for rom in &self.roms {
+                                            if ui
+                                                .add_sized(
+                                                    [360.0, 28.0],
+                                                    egui::Button::new(&rom.name),
+                                                )
+                                                .clicked()
+                                            {
+                                                ui_ctx.actions.push(Action::PlayRom {
+                                                    name: rom.name.clone(),
+                                                    bytes: rom.bytes.clone(),
+                                                });
+                                            }
+                                        }
+                                    });
+
+                                ui.add_space(16.0);
+
+                                if ui.button("Cancel").clicked() {
+                                    ui_ctx.actions.push(Action::Navigate(UiView::RomSelect(
+                                        RomSelectView::new(),
+                                    )));
+                                }
+                            });
+                        });
+                });
+        });
+    }
+}
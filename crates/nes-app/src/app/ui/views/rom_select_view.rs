@@ -1,6 +1,6 @@
-#[cfg(not(target_arch = "wasm32"))]
 use crate::app::action::Action;
 use crate::app::app::UiCtx;
+use nes_core::prelude::{ConsoleType, Region};
 
 pub struct RomSelectView {
     pub rom_bytes: Option<Vec<u8>>,
@@ -49,22 +49,96 @@ impl RomSelectView {
                                         )
                                         .clicked()
                                         && let Some(path) = rfd::FileDialog::new()
-                                            .add_filter("NES ROM", &["nes"])
+                                            .add_filter("NES ROM", &["nes", "zip"])
                                             .pick_file()
-                                        && let Ok(rom_bytes) = std::fs::read(path)
+                                        && let Ok(bytes) = std::fs::read(&path)
                                     {
-                                        ui_ctx.actions.push(Action::PlayRom(rom_bytes));
+                                        let name = path.to_string_lossy().into_owned();
+                                        ui_ctx.actions.push(Action::PlayRom { name, bytes });
                                     }
 
                                     ui.add_space(16.0);
                                 }
 
                                 ui.label(
-                                    egui::RichText::new("Or drag & drop a .nes file").size(16.0),
+                                    egui::RichText::new("Or drag & drop a .nes or .zip file")
+                                        .size(16.0),
                                 );
+
+                                ui.add_space(16.0);
+                                region_picker(ui, ui_ctx);
+                                console_picker(ui, ui_ctx);
+
+                                ui.add_space(8.0);
+                                ui.horizontal(|ui| {
+                                    if ui.button("Controls…").clicked() {
+                                        ui_ctx.actions.push(Action::OpenOptions {
+                                            back_to_game: false,
+                                        });
+                                    }
+                                    if ui.button("Video…").clicked() {
+                                        ui_ctx.actions.push(Action::ToggleVideoOptions);
+                                    }
+                                });
                             });
                         });
                 });
         });
     }
 }
+
+fn region_label(region: Option<Region>) -> &'static str {
+    match region {
+        Some(region) => region.name(),
+        None => "Auto (ROM header)",
+    }
+}
+
+fn region_picker(ui: &mut egui::Ui, ui_ctx: &mut UiCtx) {
+    ui.horizontal(|ui| {
+        ui.label("Region:");
+        let current = ui_ctx.region_override;
+        egui::ComboBox::from_id_salt("region_override")
+            .selected_text(region_label(current))
+            .show_ui(ui, |ui| {
+                let choices = std::iter::once(None).chain(Region::ALL.map(Some));
+                for choice in choices {
+                    if ui
+                        .selectable_label(current == choice, region_label(choice))
+                        .clicked()
+                    {
+                        ui_ctx.actions.push(Action::SetRegionOverride(choice));
+                    }
+                }
+            });
+    });
+}
+
+fn console_label(console: Option<ConsoleType>) -> &'static str {
+    match console {
+        Some(console) => console.name(),
+        None => "Auto (ROM header)",
+    }
+}
+
+fn console_picker(ui: &mut egui::Ui, ui_ctx: &mut UiCtx) {
+    ui.horizontal(|ui| {
+        ui.label("Console:");
+        let current = ui_ctx.console_override;
+        egui::ComboBox::from_id_salt("console_override")
+            .selected_text(console_label(current))
+            .show_ui(ui, |ui| {
+                let choices = std::iter::once(None).chain(ConsoleType::ALL.map(Some));
+                for choice in choices {
+                    if ui
+                        .selectable_label(current == choice, console_label(choice))
+                        .clicked()
+                    {
+                        ui_ctx.actions.push(Action::SetConsoleOverride(choice));
+                    }
+                }
+            });
+    })
+    .response
+    .on_hover_text("The Famicom adds the expansion port and a microphone on controller 2");
+}
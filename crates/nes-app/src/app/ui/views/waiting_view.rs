@@ -52,7 +52,7 @@ impl WaitingView {
 
                                 ui.label(
                                     egui::RichText::new(
-                                        "After starting, you can drag & drop a .nes file.",
+                                        "After starting, you can drag & drop a .nes or .zip file.",
                                     )
                                         .color(ui.visuals().weak_text_color()),
                                 );
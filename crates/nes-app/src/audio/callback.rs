@@ -19,7 +19,7 @@ impl AudioCallback {
         channels: usize,
         sample_rate: SampleRate,
     ) {
-        self.runtime.process_commands();
+        self.runtime.process_commands(&self.frame);
         self.runtime
             .tick_audio(data, channels, sample_rate, &self.frame);
     }
@@ -0,0 +1,102 @@
+use crate::emu::event::EmuEvent;
+use crate::video::palette::Palette;
+use crossbeam_channel::Sender;
+use nes_core::prelude::{RecordFormat, Region};
+use std::path::PathBuf;
+
+enum CaptureData {
+    /// 9-bit pixels of a finished frame
+    Frame(Vec<u16>),
+    /// Raw APU samples and the rate they were made at
+    Samples(Vec<f32>, u32),
+}
+
+/// Video and audio recording for the runtime. Frames and samples go to a
+/// writer thread with the `AvRecorder`, so encoding never holds up the audio
+/// callback. Dropping it finishes the files
+pub struct Capture {
+    tx: Sender<CaptureData>,
+    /// Samples that were already buffered at the start, which belong to
+    /// frames from before it
+    skip_samples: usize,
+}
+
+impl Capture {
+    /// Start recording to `path` at `sample_rate`, skipping the first
+    /// `skip_samples` samples. The writer thread logs through `event_tx`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start(
+        path: PathBuf,
+        format: RecordFormat,
+        palette: Palette,
+        region: Region,
+        sample_rate: u32,
+        skip_samples: usize,
+        event_tx: Sender<EmuEvent>,
+    ) -> anyhow::Result<Self> {
+        use anyhow::Context;
+        use nes_core::prelude::AvRecorder;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut recorder = AvRecorder::create(&path, format, region, sample_rate)
+            .with_context(|| format!("Failed to start recording {}", path.display()))?;
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        std::thread::Builder::new()
+            .name("capture".into())
+            .spawn(move || {
+                let mut result = Ok(());
+                for data in rx {
+                    result = match data {
+                        CaptureData::Frame(pixels) => recorder.add_frame(&palette.to_rgba(&pixels)),
+                        CaptureData::Samples(samples, rate) => {
+                            recorder.add_samples(&samples, rate);
+                            Ok(())
+                        }
+                    };
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                let frames = recorder.frames();
+                let message = match result.and_then(|()| recorder.finish()) {
+                    Ok(()) => format!("Saved {frames} frames to {}", path.display()),
+                    Err(e) => format!("Recording {} failed: {e}", path.display()),
+                };
+                event_tx.send(EmuEvent::Log(message.into())).ok();
+            })
+            .context("Failed to start the capture thread")?;
+
+        Ok(Self { tx, skip_samples })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn start(
+        _path: PathBuf,
+        _format: RecordFormat,
+        _palette: Palette,
+        _region: Region,
+        _sample_rate: u32,
+        _skip_samples: usize,
+        _event_tx: Sender<EmuEvent>,
+    ) -> anyhow::Result<Self> {
+        anyhow::bail!("Recording video needs the native build")
+    }
+
+    pub fn on_frame(&self, pixels: &[u16]) {
+        self.tx.send(CaptureData::Frame(pixels.to_vec())).ok();
+    }
+
+    pub fn on_samples(&mut self, samples: &[f32], sample_rate: u32) {
+        let skip = self.skip_samples.min(samples.len());
+        self.skip_samples -= skip;
+        if skip < samples.len() {
+            self.tx
+                .send(CaptureData::Samples(samples[skip..].to_vec(), sample_rate))
+                .ok();
+        }
+    }
+}
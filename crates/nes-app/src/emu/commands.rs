@@ -1,4 +1,14 @@
+use crate::emu::netplay::NetplayConfig;
+use crate::emu::rewind::RewindConfig;
+use crate::video::palette::Palette;
 use nes_core::nes::cartridge;
+use nes_core::nes::controller::expansion::ExpansionDevice;
+use nes_core::nes::controller::{ConsoleType, PortDevice};
+use nes_core::nes::movie::Movie;
+use nes_core::nes::recorder::RecordFormat;
+use nes_core::nes::region::Region;
+use nes_core::nes::stepping::StepKind;
+use std::path::PathBuf;
 
 pub enum AudioChannel {
     Pulse1,
@@ -8,9 +18,53 @@ pub enum AudioChannel {
     DMC,
 }
 pub enum EmuCommand {
-    InsertCartridge(Box<dyn cartridge::Cartridge>),
+    InsertCartridge(Box<dyn cartridge::Cartridge>, Region),
     Reset,
     Pause(bool),
+    /// Advance by one frame/scanline/instruction/dot; ignored unless paused
+    Step(StepKind),
+    /// Emulation speed relative to real time (1.0 = normal)
+    SetSpeed(f32),
+    /// Play backwards through recent snapshots while `true`
+    Rewind(bool),
+    ConfigureRewind(RewindConfig),
+    /// Record input into `movie`, from power-on or from the current state
+    StartRecording {
+        movie: Movie,
+        from_power_on: bool,
+    },
+    PlayMovie(Movie),
+    /// Stop recording (the movie is sent back as `EmuEvent::MovieRecorded`) or playback
+    StopMovie,
+    /// Power on and play against a peer, who drives the other controller port
+    StartNetplay(NetplayConfig),
+    StopNetplay,
+    /// Frames each turbo press and release lasts
+    SetTurboRate(u32),
+    /// Plug a device into port 2. Kept across ROM changes
+    SetPort2Device(PortDevice),
+    /// NES or Famicom input wiring, and what's in the Famicom expansion port.
+    /// Kept across resets
+    SetConsole(ConsoleType, Option<ExpansionDevice>),
+    /// Hold controller 2's microphone on or off. Only a Famicom has one
+    SetMicrophone(bool),
+    /// Draw only 8 sprites per line like the hardware, or all of them. Kept
+    /// across ROM changes
+    SetSpriteLimit(bool),
+    /// Publish the frame as it is now, even part-drawn, then answer with
+    /// `EmuEvent::ScreenshotReady` so it can be saved
+    Screenshot,
+    /// Record every frame, colored with `palette`, and the audio to `path`
+    /// until `StopCapture`. Answered with `EmuEvent::CaptureStatus`
+    StartCapture {
+        path: PathBuf,
+        format: RecordFormat,
+        palette: Box<Palette>,
+    },
+    StopCapture,
+    /// Send an `EmuEvent::PpuSnapshot` taken at the start of this scanline
+    /// every frame, or stop with `None`
+    SetSnapshotScanline(Option<usize>),
 
     ToggleAudioChannel(AudioChannel),
 }
@@ -1,34 +1,210 @@
+use nes_core::prelude::FamilyKey;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, Ordering};
+
+/// Controllers the input state carries. Players 3 and 4 only reach the game
+/// through a 4-player adapter
+pub const PLAYERS: usize = 4;
+
+/// Buttons held on one controller, in `JoypadButton` bit order
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ControllerInput {
+    pub held: u8,
+    /// Buttons held through a turbo binding
+    pub turbo: u8,
+}
+
+impl ControllerInput {
+    /// Buttons the console sees on emulated frame `frame`. Turbo buttons are
+    /// pressed for `rate` frames, then released for `rate` frames, so they
+    /// follow emulated time and replay the same way every run
+    pub fn resolve(self, frame: u64, rate: u32) -> u8 {
+        let turbo_pressed = (frame / rate.max(1) as u64).is_multiple_of(2);
+        match turbo_pressed {
+            true => self.held | self.turbo,
+            false => self.held,
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct ControllerState {
-    buttons: AtomicU8,
+    /// `held` in the low byte, `turbo` in the high byte
+    buttons: AtomicU16,
 }
 
 impl ControllerState {
     #[inline]
-    pub fn load(&self) -> u8 {
-        self.buttons.load(Ordering::Relaxed)
+    pub fn load(&self) -> ControllerInput {
+        let bits = self.buttons.load(Ordering::Relaxed);
+        ControllerInput {
+            held: bits as u8,
+            turbo: (bits >> 8) as u8,
+        }
     }
 
     #[inline]
-    pub fn set(&self, mask: u8) {
-        self.buttons.store(mask, Ordering::Relaxed);
+    pub fn set(&self, input: ControllerInput) {
+        let bits = (input.turbo as u16) << 8 | input.held as u16;
+        self.buttons.store(bits, Ordering::Relaxed);
+    }
+}
+
+/// Mouse state for pointing devices: where it is over the emulated screen,
+/// in pixels, and whether it's clicked. Drives the Zapper and the paddle
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct PointerInput {
+    /// `None` when aimed off-screen
+    pub aim: Option<(u8, u8)>,
+    pub trigger: bool,
+}
+
+#[derive(Default)]
+pub struct PointerState {
+    /// `x`, `y`, trigger and aim-on-screen, one per byte from the low end
+    bits: AtomicU32,
+}
+
+impl PointerState {
+    #[inline]
+    pub fn load(&self) -> PointerInput {
+        let bits = self.bits.load(Ordering::Relaxed);
+        PointerInput {
+            aim: (bits >> 24 != 0).then_some((bits as u8, (bits >> 8) as u8)),
+            trigger: (bits >> 16) as u8 != 0,
+        }
+    }
+
+    #[inline]
+    pub fn set(&self, input: PointerInput) {
+        let (x, y) = input.aim.unwrap_or_default();
+        let bits = (input.aim.is_some() as u32) << 24
+            | (input.trigger as u32) << 16
+            | (y as u32) << 8
+            | x as u32;
+        self.bits.store(bits, Ordering::Relaxed);
+    }
+}
+
+/// Power Pad or Family Trainer buttons held, bit 0 being button 1
+#[derive(Default)]
+pub struct MatState {
+    pressed: AtomicU16,
+}
+
+impl MatState {
+    #[inline]
+    pub fn load(&self) -> u16 {
+        self.pressed.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn set(&self, pressed: u16) {
+        self.pressed.store(pressed, Ordering::Relaxed);
+    }
+}
+
+/// Keys held on the Family BASIC keyboard, one bit per `FamilyKey`
+#[derive(Default)]
+pub struct FamilyKeyboardState {
+    /// Keys 0-63, then 64 and up
+    bits: [AtomicU64; 2],
+}
+
+impl FamilyKeyboardState {
+    pub fn load(&self) -> Vec<FamilyKey> {
+        let [low, high] = self
+            .bits
+            .each_ref()
+            .map(|bits| bits.load(Ordering::Relaxed));
+        let mask = (high as u128) << 64 | low as u128;
+        FamilyKey::ALL
+            .into_iter()
+            .filter(|&key| mask & 1 << key as u32 != 0)
+            .collect()
+    }
+
+    pub fn set(&self, keys: &[FamilyKey]) {
+        let mask = keys.iter().fold(0u128, |mask, &key| mask | 1 << key as u32);
+        self.bits[0].store(mask as u64, Ordering::Relaxed);
+        self.bits[1].store((mask >> 64) as u64, Ordering::Relaxed);
     }
 }
 
 #[derive(Clone)]
 pub struct InputState {
-    pub p1: Arc<ControllerState>,
-    pub p2: Arc<ControllerState>,
+    pub players: [Arc<ControllerState>; PLAYERS],
+    pub pointer: Arc<PointerState>,
+    pub mat: Arc<MatState>,
+    pub family_keyboard: Arc<FamilyKeyboardState>,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InputState {
     pub fn new() -> Self {
         Self {
-            p1: Arc::new(ControllerState::default()),
-            p2: Arc::new(ControllerState::default()),
+            players: std::array::from_fn(|_| Arc::new(ControllerState::default())),
+            pointer: Arc::new(PointerState::default()),
+            mat: Arc::new(MatState::default()),
+            family_keyboard: Arc::new(FamilyKeyboardState::default()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turbo_follows_frames() {
+        let input = ControllerInput {
+            held: 0b0100_0000,
+            turbo: 0b0000_0001,
+        };
+        let pressed: Vec<u8> = (0..8).map(|frame| input.resolve(frame, 2)).collect();
+        assert_eq!(pressed, [0x41, 0x41, 0x40, 0x40, 0x41, 0x41, 0x40, 0x40]);
+        assert_eq!(input.resolve(1, 1), 0x40);
+        assert_eq!(input.resolve(1, 0), 0x40);
+    }
+
+    #[test]
+    fn test_pointer_state_round_trip() {
+        let state = PointerState::default();
+        assert_eq!(state.load(), PointerInput::default());
+
+        for input in [
+            PointerInput {
+                aim: Some((255, 239)),
+                trigger: false,
+            },
+            PointerInput {
+                aim: Some((0, 0)),
+                trigger: true,
+            },
+            PointerInput {
+                aim: None,
+                trigger: true,
+            },
+        ] {
+            state.set(input);
+            assert_eq!(state.load(), input);
+        }
+    }
+
+    #[test]
+    fn test_family_keyboard_state_round_trip() {
+        let state = FamilyKeyboardState::default();
+        assert!(state.load().is_empty());
+
+        let keys = [FamilyKey::F1, FamilyKey::Return, FamilyKey::Right];
+        state.set(&keys);
+        assert_eq!(state.load(), keys);
+        state.set(&[]);
+        assert!(state.load().is_empty());
+    }
+}
@@ -1,6 +1,25 @@
+use crate::emu::movie::MovieStatus;
+use crate::emu::netplay::NetplayStatus;
+use crate::emu::telemetry::EmuTelemetry;
+use nes_core::nes::movie::Movie;
+use nes_core::nes::ppu::viewer::PpuSnapshot;
+use nes_core::nes::stepping::MachinePosition;
 use std::borrow::Cow;
+use std::sync::Arc;
 
 /// EmuEvents are sent Audio -> UI
 pub enum EmuEvent {
     Log(Cow<'static, str>),
+    Telemetry(EmuTelemetry),
+    /// Sent when pausing and after every step
+    Position(MachinePosition),
+    MovieStatus(MovieStatus),
+    MovieRecorded(Movie),
+    NetplayStatus(NetplayStatus),
+    /// The shared frame holds what to save for a screenshot
+    ScreenshotReady,
+    /// Whether video and audio are being recorded
+    CaptureStatus(bool),
+    /// PPU memory for the debugger views
+    PpuSnapshot(Arc<PpuSnapshot>),
 }
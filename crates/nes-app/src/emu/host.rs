@@ -1,10 +1,11 @@
 use crate::audio::callback::AudioCallback;
 use crate::audio::driver::AudioDriver;
 use crate::emu::commands::EmuCommand;
-use crate::emu::emu_input::InputState;
+use crate::emu::emu_input::{ControllerInput, InputState, PLAYERS, PointerInput};
 use crate::emu::event::EmuEvent;
 use crate::emu::runtime::EmuRuntime;
 use crate::shared::frame_buffer::SharedFrameHandle;
+use nes_core::prelude::FamilyKey;
 
 /// EmuHost links the UI to the Audio/emulation thread
 pub struct EmuHost {
@@ -59,9 +60,23 @@ impl EmuHost {
         self.event_rx.try_recv().ok()
     }
 
-    pub fn set_input(&self, p1: u8, p2: u8) {
-        // write to AtomicU8 for sharing input states with runtime
-        self.input_state.p1.set(p1);
-        self.input_state.p2.set(p2);
+    pub fn set_input(&self, players: [ControllerInput; PLAYERS]) {
+        // write to atomics for sharing input states with runtime
+        for (state, input) in self.input_state.players.iter().zip(players) {
+            state.set(input);
+        }
+    }
+
+    /// Power Pad or Family Trainer buttons, bit 0 being button 1
+    pub fn set_mat(&self, pressed: u16) {
+        self.input_state.mat.set(pressed);
+    }
+
+    pub fn set_pointer(&self, pointer: PointerInput) {
+        self.input_state.pointer.set(pointer);
+    }
+
+    pub fn set_family_keys(&self, keys: &[FamilyKey]) {
+        self.input_state.family_keyboard.set(keys);
     }
 }
@@ -1,5 +1,10 @@
+pub mod capture;
 pub mod commands;
-mod emu_input;
+pub mod emu_input;
 pub mod event;
 pub mod host;
+pub mod movie;
+pub mod netplay;
+pub mod rewind;
 pub mod runtime;
+pub mod telemetry;
@@ -0,0 +1,201 @@
+use anyhow::bail;
+use nes_core::prelude::{Movie, MovieFrame, NES, SaveStateError};
+
+/// What the movie session is doing, as shown in the UI
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MovieStatus {
+    #[default]
+    Idle,
+    Recording,
+    Playing,
+}
+
+enum Mode {
+    Idle,
+    Recording(Movie),
+    Playing { movie: Movie, next_frame: usize },
+}
+
+/// Records or plays back per-frame controller input.
+///
+/// Hands out the input for each frame; the runtime runs every frame through
+/// `MovieFrame::run`, so a recording replays exactly
+pub struct MovieSession {
+    mode: Mode,
+}
+
+impl Default for MovieSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MovieSession {
+    pub fn new() -> Self {
+        Self { mode: Mode::Idle }
+    }
+
+    pub fn status(&self) -> MovieStatus {
+        match self.mode {
+            Mode::Idle => MovieStatus::Idle,
+            Mode::Recording(_) => MovieStatus::Recording,
+            Mode::Playing { .. } => MovieStatus::Playing,
+        }
+    }
+
+    /// Start recording into `movie` (its header is kept, its frames replaced).
+    /// From power-on resets the console; otherwise the current state is embedded
+    pub fn start_recording(&mut self, nes: &mut NES, mut movie: Movie, from_power_on: bool) {
+        movie.frames.clear();
+        movie.start_state = match from_power_on {
+            true => {
+                nes.reset();
+                None
+            }
+            false => Some(nes.save_state()),
+        };
+        self.mode = Mode::Recording(movie);
+    }
+
+    pub fn start_playback(&mut self, nes: &mut NES, movie: Movie) -> Result<(), SaveStateError> {
+        match &movie.start_state {
+            Some(state) => nes.load_state(state)?,
+            None => nes.reset(),
+        }
+        self.mode = Mode::Playing {
+            movie,
+            next_frame: 0,
+        };
+        Ok(())
+    }
+
+    /// Stop recording or playback. Returns the movie if one was being recorded
+    pub fn stop(&mut self) -> Option<Movie> {
+        match std::mem::replace(&mut self.mode, Mode::Idle) {
+            Mode::Recording(movie) => Some(movie),
+            _ => None,
+        }
+    }
+
+    /// Input for the frame about to run: the movie's during playback,
+    /// otherwise `live` (which is recorded if a recording is active).
+    ///
+    /// # Returns
+    ///
+    /// A `(MovieFrame, bool)` tuple containing the input and whether playback
+    /// just ran out of frames
+    pub fn next_input(&mut self, live: [u8; 2]) -> (MovieFrame, bool) {
+        let live_frame = MovieFrame {
+            commands: 0,
+            joypads: live,
+        };
+
+        match &mut self.mode {
+            Mode::Idle => (live_frame, false),
+            Mode::Recording(movie) => {
+                movie.frames.push(live_frame);
+                (live_frame, false)
+            }
+            Mode::Playing { movie, next_frame } => match movie.frames.get(*next_frame) {
+                Some(&frame) => {
+                    *next_frame += 1;
+                    (frame, false)
+                }
+                None => {
+                    self.mode = Mode::Idle;
+                    (live_frame, true)
+                }
+            },
+        }
+    }
+}
+
+/// `romFilename` for a ROM loaded from `file_name`: FCEUX writes the name
+/// without its folder or extension
+pub fn rom_filename(file_name: &str) -> String {
+    let name = file_name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    match stem.is_empty() {
+        true => "unknown".to_string(),
+        false => stem.to_string(),
+    }
+}
+
+/// Refuse to play `movie` unless it was recorded with the ROM `loaded` was
+/// made for. Movies without a checksum are let through
+pub fn check_rom(movie: &Movie, loaded: &Movie) -> anyhow::Result<()> {
+    if movie.rom_checksum.is_empty() || movie.rom_checksum == loaded.rom_checksum {
+        return Ok(());
+    }
+    bail!(
+        "Movie was recorded with a different ROM ('{}', {}); the loaded one is '{}', {}",
+        movie.rom_filename,
+        movie.rom_checksum,
+        loaded.rom_filename,
+        loaded.rom_checksum
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nes_core::nes::test_utils::nes_with_program;
+
+    fn test_nes() -> NES {
+        // Polls the controller into RAM every iteration:
+        // LDA #1; STA $4016; LDA #0; STA $4016; LDA $4016; ADC $00; STA $00; JMP $8000
+        let program = [
+            0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40, 0xAD, 0x16, 0x40, 0x65,
+            0x00, 0x85, 0x00, 0x4C, 0x00, 0x80,
+        ];
+        nes_with_program(&program)
+    }
+
+    #[test]
+    fn test_rom_identity() {
+        assert_eq!(
+            rom_filename("roms/Super Mario Bros. (W).nes"),
+            "Super Mario Bros. (W)"
+        );
+        assert_eq!(rom_filename("C:\\roms\\Zelda.nes"), "Zelda");
+        assert_eq!(rom_filename(""), "unknown");
+
+        let loaded = Movie::new("Zelda", "base64:AAAA");
+        assert!(check_rom(&Movie::new("zelda", "base64:AAAA"), &loaded).is_ok());
+        assert!(check_rom(&Movie::new("Zelda", ""), &loaded).is_ok());
+        assert!(check_rom(&Movie::new("Zelda", "base64:BBBB"), &loaded).is_err());
+    }
+
+    #[test]
+    fn test_recording_replays_exactly() {
+        let mut nes = test_nes();
+        for _ in 0..3 {
+            nes.run_frame([0, 0]);
+        }
+
+        let mut session = MovieSession::new();
+        session.start_recording(&mut nes, Movie::new("test.nes", ""), false);
+        for i in 0..10u8 {
+            session.next_input([i % 3, 0]).0.run(&mut nes);
+        }
+        let expected = nes.save_state();
+        let movie = session.stop().unwrap();
+        assert_eq!(movie.frames.len(), 10);
+        assert!(movie.start_state.is_some());
+
+        // Round-trip through FM2 text as well
+        let movie = Movie::parse(&movie.to_fm2()).unwrap();
+        session.start_playback(&mut nes, movie).unwrap();
+        assert_eq!(session.status(), MovieStatus::Playing);
+        for _ in 0..10 {
+            let (input, finished) = session.next_input([0xFF, 0xFF]);
+            assert!(!finished);
+            input.run(&mut nes);
+        }
+        assert_eq!(nes.save_state(), expected);
+
+        // Out of frames
+        assert!(session.next_input([0, 0]).1);
+        assert_eq!(session.status(), MovieStatus::Idle);
+    }
+}
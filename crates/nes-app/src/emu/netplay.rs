@@ -0,0 +1,690 @@
+use anyhow::{Context, bail};
+use nes_core::prelude::NES;
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+
+const MAGIC: &[u8; 2] = b"NP";
+const PROTOCOL_VERSION: u8 = 1;
+
+const MSG_HELLO: u8 = 0;
+const MSG_INPUT: u8 = 1;
+
+/// Most inputs resent in one packet; the rest follow once these are acked
+const MAX_INPUTS_PER_PACKET: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetplayConfig {
+    /// Local address to listen on, e.g. `0.0.0.0:7000`
+    pub bind: SocketAddr,
+    /// Address of the other instance. `None` waits for it to connect first
+    pub peer: Option<SocketAddr>,
+    /// Controller port driven by this instance; the peer drives the other one
+    pub local_port: usize,
+    /// Frames between pressing a button and it taking effect. Hides latency
+    /// up to this many frames without any rollback
+    pub input_delay: u32,
+    /// How many frames to run ahead of the peer's last known input before stalling
+    pub max_prediction: u32,
+    /// Compare save state checksums with the peer every N frames
+    pub checksum_interval: u32,
+    /// Identifies the loaded game, so peers running different ROMs refuse to connect
+    pub game_id: u32,
+}
+
+impl NetplayConfig {
+    pub fn new(bind: SocketAddr, peer: Option<SocketAddr>, local_port: usize) -> Self {
+        Self {
+            bind,
+            peer,
+            local_port,
+            input_delay: 2,
+            max_prediction: 8,
+            checksum_interval: 60,
+            game_id: 0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NetplayStatus {
+    #[default]
+    Off,
+    /// Waiting to hear from the peer
+    Connecting,
+    Running,
+    /// Checksums stopped matching at `frame`; the games have diverged
+    Desynced {
+        frame: u32,
+    },
+}
+
+enum Message {
+    Hello {
+        game_id: u32,
+    },
+    Input {
+        /// Number of our inputs the peer has received
+        ack: u32,
+        /// Frame of the first input in `inputs`
+        start: u32,
+        inputs: Vec<u8>,
+        /// Latest `(frame, checksum)` the sender has confirmed
+        checksum: Option<(u32, u32)>,
+    },
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(16);
+        out.extend_from_slice(MAGIC);
+        out.push(PROTOCOL_VERSION);
+        match self {
+            Message::Hello { game_id } => {
+                out.push(MSG_HELLO);
+                out.extend_from_slice(&game_id.to_le_bytes());
+            }
+            Message::Input {
+                ack,
+                start,
+                inputs,
+                checksum,
+            } => {
+                out.push(MSG_INPUT);
+                out.extend_from_slice(&ack.to_le_bytes());
+                out.extend_from_slice(&start.to_le_bytes());
+                out.extend_from_slice(&(inputs.len() as u16).to_le_bytes());
+                out.extend_from_slice(inputs);
+                let (frame, crc) = checksum.unwrap_or_default();
+                out.push(checksum.is_some() as u8);
+                out.extend_from_slice(&frame.to_le_bytes());
+                out.extend_from_slice(&crc.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// `None` for anything malformed or from another protocol version
+    fn decode(bytes: &[u8]) -> Option<Message> {
+        let mut reader = PacketReader { bytes };
+        if reader.take(2)? != MAGIC || reader.u8()? != PROTOCOL_VERSION {
+            return None;
+        }
+        match reader.u8()? {
+            MSG_HELLO => Some(Message::Hello {
+                game_id: reader.u32()?,
+            }),
+            MSG_INPUT => {
+                let ack = reader.u32()?;
+                let start = reader.u32()?;
+                let count = u16::from_le_bytes(reader.take(2)?.try_into().ok()?) as usize;
+                let inputs = reader.take(count)?.to_vec();
+                let has_checksum = reader.u8()? != 0;
+                let checksum = (reader.u32()?, reader.u32()?);
+                Some(Message::Input {
+                    ack,
+                    start,
+                    inputs,
+                    checksum: has_checksum.then_some(checksum),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+struct PacketReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PacketReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+/// One byte of input per frame, counted from when the session connected.
+/// Frames that can't be rolled back or resent any more are trimmed off the
+/// front, so the log stays short however long the session runs
+#[derive(Default)]
+struct InputLog {
+    /// Frame of `inputs[0]`
+    first: u32,
+    inputs: VecDeque<u8>,
+}
+
+impl InputLog {
+    /// One past the last frame logged
+    fn end(&self) -> u32 {
+        self.first + self.inputs.len() as u32
+    }
+
+    fn get(&self, frame: u32) -> Option<u8> {
+        let index = frame.checked_sub(self.first)?;
+        self.inputs.get(index as usize).copied()
+    }
+
+    fn last(&self) -> Option<u8> {
+        self.inputs.back().copied()
+    }
+
+    fn push(&mut self, input: u8) {
+        self.inputs.push_back(input);
+    }
+
+    /// Drop `frame` and everything after it
+    fn truncate(&mut self, frame: u32) {
+        let len = frame.saturating_sub(self.first) as usize;
+        self.inputs.truncate(len);
+    }
+
+    /// Drop everything before `frame`
+    fn trim_before(&mut self, frame: u32) {
+        while self.first < frame && !self.inputs.is_empty() {
+            self.inputs.pop_front();
+            self.first += 1;
+        }
+    }
+
+    /// Inputs for frames `start..end`, clamped to what's logged
+    fn range(&self, start: u32, end: u32) -> Vec<u8> {
+        let start = start.saturating_sub(self.first) as usize;
+        let end = (end.saturating_sub(self.first) as usize).min(self.inputs.len());
+        self.inputs.range(start.min(end)..end).copied().collect()
+    }
+}
+
+/// GGPO-style rollback session between two instances.
+///
+/// Every frame runs immediately with the local input (delayed by
+/// `input_delay`) and a prediction of the peer's: their last known input.
+/// When the real input arrives and differs, the machine is loaded from the
+/// snapshot taken before the first mispredicted frame and resimulated up to the
+/// present. Both sides power on the console when they connect, and
+/// `NES::run_frame` is deterministic, so identical inputs give identical games.
+/// Periodic checksums of confirmed frames' save states catch it if they don't
+pub struct NetplaySession {
+    config: NetplayConfig,
+    socket: UdpSocket,
+    peer: Option<SocketAddr>,
+    connected: bool,
+    status: NetplayStatus,
+
+    /// Next frame to run, counted from when the session connected
+    frame: u32,
+    /// Local input per frame, `input_delay` frames ahead of `frame`
+    local_inputs: InputLog,
+    /// The peer's input per frame, as far as it's been received
+    remote_inputs: InputLog,
+    /// Peer input each frame was last simulated with, to spot mispredictions
+    remote_used: InputLog,
+    /// How many of `local_inputs` the peer has acknowledged
+    remote_ack: u32,
+    /// Earliest frame that needs resimulating because of a misprediction
+    rollback_to: Option<u32>,
+    /// Machine state at the start of each frame that may still be rolled back
+    snapshots: VecDeque<(u32, Vec<u8>)>,
+
+    local_checksums: HashMap<u32, u32>,
+    remote_checksums: HashMap<u32, u32>,
+    latest_checksum: Option<(u32, u32)>,
+
+    rollbacks: u32,
+    checksums_matched: u32,
+    scratch: Vec<f32>,
+}
+
+impl NetplaySession {
+    pub fn bind(config: NetplayConfig) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(config.bind)
+            .with_context(|| format!("Failed to bind {}", config.bind))?;
+        socket
+            .set_nonblocking(true)
+            .context("Failed to make the socket non-blocking")?;
+
+        Ok(Self {
+            peer: config.peer,
+            config,
+            socket,
+            connected: false,
+            status: NetplayStatus::Connecting,
+            frame: 0,
+            local_inputs: InputLog::default(),
+            remote_inputs: InputLog::default(),
+            remote_used: InputLog::default(),
+            remote_ack: 0,
+            rollback_to: None,
+            snapshots: VecDeque::new(),
+            local_checksums: HashMap::new(),
+            remote_checksums: HashMap::new(),
+            latest_checksum: None,
+            rollbacks: 0,
+            checksums_matched: 0,
+            scratch: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub fn status(&self) -> NetplayStatus {
+        self.status
+    }
+
+    /// Next frame to run, counted from when the session connected
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    pub fn checksums_matched(&self) -> u32 {
+        self.checksums_matched
+    }
+
+    /// Exchange packets with the peer and run the next frame, with `input` on
+    /// the local controller port. Mispredicted frames are resimulated first.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a frame ran; `false` while connecting, or while stalled
+    /// waiting for the peer to catch up
+    pub fn advance(&mut self, nes: &mut NES, input: u8) -> anyhow::Result<bool> {
+        self.receive(nes)?;
+        if !self.connected {
+            self.send(&Message::Hello {
+                game_id: self.config.game_id,
+            });
+            return Ok(false);
+        }
+
+        if let Some(frame) = self.rollback_to.take() {
+            self.rollback(nes, frame)?;
+        }
+        self.check_desync();
+
+        let confirmed = self.remote_inputs.end();
+        if self.frame >= confirmed + self.config.max_prediction {
+            self.send_inputs();
+            return Ok(false);
+        }
+
+        // Input pressed now applies `input_delay` frames from now
+        while self.local_inputs.end() <= self.frame + self.config.input_delay {
+            self.local_inputs.push(input);
+        }
+        self.send_inputs();
+
+        self.run_frame(nes);
+        self.prune_snapshots();
+        Ok(true)
+    }
+
+    fn receive(&mut self, nes: &mut NES) -> anyhow::Result<()> {
+        let mut buf = [0u8; 1024];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                // ICMP "port unreachable" from a peer that isn't up yet
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e).context("Netplay socket failed"),
+            };
+            if self.peer.is_some_and(|peer| peer != from) {
+                continue;
+            }
+            let Some(message) = Message::decode(&buf[..len]) else {
+                continue;
+            };
+
+            if !self.connected {
+                if let Message::Hello { game_id } = message
+                    && game_id != self.config.game_id
+                {
+                    bail!("Peer at {from} is playing a different game");
+                }
+                self.peer = Some(from);
+                self.connect(nes);
+            }
+
+            match message {
+                // The peer hasn't heard from us yet
+                Message::Hello { .. } => self.send(&Message::Hello {
+                    game_id: self.config.game_id,
+                }),
+                Message::Input {
+                    ack,
+                    start,
+                    inputs,
+                    checksum,
+                } => {
+                    self.remote_ack = self.remote_ack.max(ack);
+                    self.receive_inputs(start, &inputs);
+                    if let Some((frame, crc)) = checksum {
+                        self.remote_checksums.insert(frame, crc);
+                    }
+                }
+            }
+        }
+    }
+
+    fn connect(&mut self, nes: &mut NES) {
+        self.connected = true;
+        self.status = NetplayStatus::Running;
+        nes.reset();
+    }
+
+    fn receive_inputs(&mut self, start: u32, inputs: &[u8]) {
+        // Inputs are resent from the last ack, so anything past a gap will come again
+        let known = self.remote_inputs.end();
+        if start > known {
+            return;
+        }
+        for (frame, &input) in (start..).zip(inputs).skip((known - start) as usize) {
+            self.remote_inputs.push(input);
+            let mispredicted = self
+                .remote_used
+                .get(frame)
+                .is_some_and(|used| used != input);
+            if mispredicted && self.rollback_to.is_none_or(|f| frame < f) {
+                self.rollback_to = Some(frame);
+            }
+        }
+    }
+
+    /// Reload the state before `frame` and run forward to the present again
+    fn rollback(&mut self, nes: &mut NES, frame: u32) -> anyhow::Result<()> {
+        let Some(index) = self.snapshots.iter().position(|(f, _)| *f == frame) else {
+            bail!("Missing netplay snapshot for frame {frame}");
+        };
+        // The audio of those frames is already queued, mispredicted or not.
+        // It's kept in order and the rerun's copy of it is dropped, so
+        // nothing plays twice. Loading a state would discard it otherwise
+        let mut queued = vec![0.0; nes.bus.apu.samples_available()];
+        let queued_len = nes.bus.apu.read_samples_f32(&mut queued);
+        queued.truncate(queued_len);
+
+        let (_, snapshot) = &self.snapshots[index];
+        nes.load_state(snapshot)
+            .context("Failed to load netplay snapshot")?;
+        self.snapshots.truncate(index);
+        self.rollbacks += 1;
+
+        let present = self.frame;
+        self.frame = frame;
+        while self.frame < present {
+            self.run_frame(nes);
+        }
+        self.scratch.resize(nes.bus.apu.samples_available(), 0.0);
+        nes.bus.apu.read_samples_f32(&mut self.scratch);
+        nes.bus.apu.restore_samples(&queued);
+        Ok(())
+    }
+
+    fn run_frame(&mut self, nes: &mut NES) {
+        let frame = self.frame;
+        let remote = self
+            .remote_inputs
+            .get(frame)
+            .or_else(|| self.remote_inputs.last())
+            .unwrap_or(0);
+        let mut input = [remote; 2];
+        input[self.config.local_port] = self.local_inputs.get(frame).unwrap_or(0);
+
+        self.snapshots.push_back((self.frame, nes.save_state()));
+        self.remote_used.truncate(frame);
+        self.remote_used.push(remote);
+        nes.run_frame(input);
+        self.frame += 1;
+    }
+
+    fn check_desync(&mut self) {
+        let mut compared = Vec::new();
+        for (&frame, &remote) in &self.remote_checksums {
+            if let Some(&local) = self.local_checksums.get(&frame) {
+                compared.push(frame);
+                if local == remote {
+                    self.checksums_matched += 1;
+                } else if !matches!(self.status, NetplayStatus::Desynced { .. }) {
+                    self.status = NetplayStatus::Desynced { frame };
+                }
+            }
+        }
+        // Older checksums the other side never sent won't be compared any more
+        if let Some(newest) = compared.into_iter().max() {
+            self.local_checksums.retain(|&frame, _| frame > newest);
+            self.remote_checksums.retain(|&frame, _| frame > newest);
+        }
+    }
+
+    /// Confirmed frames can't be rolled back to any more. Snapshots of those
+    /// are final, so they're what gets checksummed. Inputs are trimmed along
+    /// with them, keeping local ones until the peer acknowledges them
+    fn prune_snapshots(&mut self) {
+        let confirmed = self.remote_inputs.end();
+        let interval = self.config.checksum_interval.max(1);
+        for (frame, snapshot) in &self.snapshots {
+            if *frame > confirmed {
+                break;
+            }
+            if *frame > 0 && frame.is_multiple_of(interval) {
+                let crc = crc32fast::hash(snapshot);
+                self.local_checksums.insert(*frame, crc);
+                self.latest_checksum = Some((*frame, crc));
+            }
+        }
+
+        while self.snapshots.front().is_some_and(|(f, _)| *f < confirmed) {
+            self.snapshots.pop_front();
+        }
+
+        let oldest = self.snapshots.front().map_or(self.frame, |(f, _)| *f);
+        self.remote_used.trim_before(oldest.min(confirmed));
+        // The newest confirmed input is still the prediction for what follows
+        self.remote_inputs
+            .trim_before(oldest.min(confirmed.saturating_sub(1)));
+        self.local_inputs.trim_before(oldest.min(self.remote_ack));
+    }
+
+    fn send_inputs(&mut self) {
+        let start = self.remote_ack.min(self.local_inputs.end());
+        let end = start + MAX_INPUTS_PER_PACKET as u32;
+        self.send(&Message::Input {
+            ack: self.remote_inputs.end(),
+            start,
+            inputs: self.local_inputs.range(start, end),
+            checksum: self.latest_checksum,
+        });
+    }
+
+    fn send(&self, message: &Message) {
+        if let Some(peer) = self.peer {
+            // Lost packets are covered by resending every frame
+            self.socket.send_to(&message.encode(), peer).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nes_core::nes::test_utils::nes_with_program;
+    use nes_core::prelude::Region;
+
+    fn test_nes() -> NES {
+        // Sums both controllers' A button into RAM every iteration:
+        // LDA #1; STA $4016; LDA #0; STA $4016; LDA $4016; ADC $00; STA $00;
+        // LDA $4017; ADC $01; STA $01; JMP $8000
+        let program = [
+            0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40, 0xAD, 0x16, 0x40, 0x65,
+            0x00, 0x85, 0x00, 0xAD, 0x17, 0x40, 0x65, 0x01, 0x85, 0x01, 0x4C, 0x00, 0x80,
+        ];
+        nes_with_program(&program)
+    }
+
+    /// `test_nes` with a square wave playing, so its audio isn't silence
+    fn tone_nes() -> NES {
+        // LDA #$01; STA $4015; LDA #$BF; STA $4000; LDA #$FD; STA $4002;
+        // LDA #$00; STA $4003, then `test_nes`'s loop from $8014
+        let mut program = vec![
+            0xA9, 0x01, 0x8D, 0x15, 0x40, 0xA9, 0xBF, 0x8D, 0x00, 0x40, 0xA9, 0xFD, 0x8D, 0x02,
+            0x40, 0xA9, 0x00, 0x8D, 0x03, 0x40,
+        ];
+        program.extend_from_slice(&[
+            0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40, 0xAD, 0x16, 0x40, 0x65,
+            0x00, 0x85, 0x00, 0xAD, 0x17, 0x40, 0x65, 0x01, 0x85, 0x01, 0x4C, 0x14, 0x80,
+        ]);
+        nes_with_program(&program)
+    }
+
+    fn loopback_pair() -> (NetplaySession, NetplaySession) {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let mut config = NetplayConfig::new(localhost, None, 0);
+        config.checksum_interval = 10;
+        let host = NetplaySession::bind(config.clone()).unwrap();
+
+        config.peer = Some(host.local_addr().unwrap());
+        config.local_port = 1;
+        let guest = NetplaySession::bind(config).unwrap();
+        (host, guest)
+    }
+
+    /// Advance and throw the audio away, as the test never plays it
+    fn advance(session: &mut NetplaySession, nes: &mut NES, input: u8) {
+        session.advance(nes, input).unwrap();
+        nes.bus.apu.discard_samples();
+    }
+
+    /// Changes every few frames so predictions keep missing
+    fn input_for(frame: u32, player: u32) -> u8 {
+        ((frame / (3 + player)) % 2) as u8
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let message = Message::Input {
+            ack: 7,
+            start: 3,
+            inputs: vec![1, 2, 3],
+            checksum: Some((60, 0xDEADBEEF)),
+        };
+        let Some(Message::Input {
+            ack,
+            start,
+            inputs,
+            checksum,
+        }) = Message::decode(&message.encode())
+        else {
+            panic!("expected an input message");
+        };
+        assert_eq!((ack, start, inputs), (7, 3, vec![1, 2, 3]));
+        assert_eq!(checksum, Some((60, 0xDEADBEEF)));
+
+        assert!(Message::decode(b"NP").is_none());
+        assert!(
+            Message::decode(&[b'N', b'P', PROTOCOL_VERSION + 1, MSG_HELLO, 0, 0, 0, 0]).is_none()
+        );
+    }
+
+    #[test]
+    fn test_loopback_rollback_stays_in_sync() {
+        let (mut host, mut guest) = loopback_pair();
+        let mut host_nes = test_nes();
+        let mut guest_nes = test_nes();
+
+        // Uneven pacing: the host runs ahead in bursts and predicts the guest's input
+        for step in 0..60u32 {
+            for _ in 0..1 + step % 3 {
+                let frame = host.frame();
+                advance(&mut host, &mut host_nes, input_for(frame, 0));
+            }
+            let frame = guest.frame();
+            advance(&mut guest, &mut guest_nes, input_for(frame, 1));
+        }
+
+        assert_eq!(host.status(), NetplayStatus::Running);
+        assert_eq!(guest.status(), NetplayStatus::Running);
+        assert!(host.rollbacks() > 0);
+        assert!(host.checksums_matched() > 0);
+        assert!(guest.checksums_matched() > 0);
+
+        // Only checksums that may still be compared and inputs for frames
+        // that may still be rolled back or resent are kept
+        for session in [&host, &guest] {
+            assert!(session.frame() > 50);
+            assert!(session.local_inputs.inputs.len() <= 16);
+            assert!(session.remote_inputs.inputs.len() <= 16);
+            assert!(session.remote_used.inputs.len() <= 16);
+            assert!(session.local_checksums.len() <= 2);
+            assert!(session.remote_checksums.len() <= 2);
+        }
+    }
+
+    #[test]
+    fn test_rollback_keeps_queued_audio_in_order() {
+        let (mut host, mut guest) = loopback_pair();
+        let mut host_nes = tone_nes();
+        let mut guest_nes = tone_nes();
+        for _ in 0..10 {
+            advance(&mut host, &mut host_nes, 0);
+            advance(&mut guest, &mut guest_nes, 0);
+        }
+
+        // The host runs ahead predicting the guest keeps its input, and
+        // nobody plays the audio yet
+        for _ in 0..4 {
+            host.advance(&mut host_nes, 0).unwrap();
+        }
+        for _ in 0..4 {
+            advance(&mut guest, &mut guest_nes, 1);
+        }
+        let mut queued = vec![0.0; host_nes.bus.apu.samples_available()];
+        host_nes.bus.apu.read_samples_f32(&mut queued);
+        host_nes.bus.apu.restore_samples(&queued);
+        assert!(queued.iter().any(|&s| s != queued[0]));
+
+        let rollbacks = host.rollbacks();
+        assert!(host.advance(&mut host_nes, 0).unwrap());
+        assert_eq!(host.rollbacks(), rollbacks + 1);
+
+        // The queued audio comes first, untouched, then one new frame's
+        let mut after = vec![0.0; host_nes.bus.apu.samples_available()];
+        host_nes.bus.apu.read_samples_f32(&mut after);
+        assert_eq!(after[..queued.len()], queued[..]);
+        let frame_samples = 44_100.0 / Region::Ntsc.frame_rate();
+        let new = (after.len() - queued.len()) as f64;
+        assert!((new - frame_samples).abs() < 2.0, "{new} new samples");
+    }
+
+    #[test]
+    fn test_loopback_detects_desync() {
+        let (mut host, mut guest) = loopback_pair();
+        let mut host_nes = test_nes();
+        let mut guest_nes = test_nes();
+
+        for step in 0..40u32 {
+            if step == 10 {
+                guest_nes.bus.cpu_ram[0x100] ^= 0xFF;
+            }
+            advance(&mut host, &mut host_nes, 0);
+            advance(&mut guest, &mut guest_nes, 0);
+        }
+        assert!(matches!(host.status(), NetplayStatus::Desynced { .. }));
+    }
+}
@@ -0,0 +1,310 @@
+use nes_core::prelude::{NES, SaveStateError};
+use std::collections::VecDeque;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RewindConfig {
+    /// Take a snapshot every N frames
+    pub interval_frames: u32,
+    /// Upper bound on memory held by snapshots; the oldest are dropped first
+    pub budget_bytes: usize,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            interval_frames: 2,
+            budget_bytes: 32 * 1024 * 1024,
+        }
+    }
+}
+
+/// Ring buffer of save states for playing the game backwards.
+///
+/// The newest snapshot is kept whole. Every older one is stored as a delta
+/// against the snapshot taken after it, so consecutive states (which mostly
+/// differ in a few bytes of RAM) cost very little. Popping the newest snapshot
+/// lets the next one be decoded against it, so the chain unwinds in order
+pub struct RewindBuffer {
+    config: RewindConfig,
+    latest: Option<Vec<u8>>,
+    /// Oldest at the front
+    deltas: VecDeque<Vec<u8>>,
+    used_bytes: usize,
+    frames_since_capture: u32,
+}
+
+impl RewindBuffer {
+    pub fn new(config: RewindConfig) -> Self {
+        Self {
+            config,
+            latest: None,
+            deltas: VecDeque::new(),
+            used_bytes: 0,
+            frames_since_capture: 0,
+        }
+    }
+
+    pub fn config(&self) -> RewindConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: RewindConfig) {
+        self.config = config;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.used_bytes = 0;
+        self.frames_since_capture = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Call once per emulated frame; captures a snapshot every `interval_frames`
+    pub fn on_frame(&mut self, nes: &NES) {
+        self.frames_since_capture += 1;
+        if self.frames_since_capture >= self.config.interval_frames.max(1) {
+            self.frames_since_capture = 0;
+            self.push(snapshot(nes));
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.latest.take() {
+            let delta = encode_delta(&previous, &state);
+            self.used_bytes += delta.len();
+            self.used_bytes -= previous.len();
+            self.deltas.push_back(delta);
+        }
+        self.used_bytes += state.len();
+        self.latest = Some(state);
+        self.evict();
+    }
+
+    /// Remove and return the newest snapshot
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let latest = self.latest.take()?;
+        self.used_bytes -= latest.len();
+
+        if let Some(delta) = self.deltas.pop_back() {
+            let previous = decode_delta(&delta, &latest);
+            self.used_bytes -= delta.len();
+            self.used_bytes += previous.len();
+            self.latest = Some(previous);
+        }
+        self.frames_since_capture = 0;
+        Some(latest)
+    }
+
+    fn evict(&mut self) {
+        while self.used_bytes > self.config.budget_bytes {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used_bytes -= delta.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Save states don't hold the picture, so each snapshot carries the frame
+/// buffer and scanline phases after the state. Rewinding can then show the
+/// restored frame without running another one
+fn snapshot(nes: &NES) -> Vec<u8> {
+    let mut snapshot = nes.save_state();
+    for pixel in nes.get_frame_buffer() {
+        snapshot.extend_from_slice(&pixel.to_le_bytes());
+    }
+    snapshot.extend_from_slice(nes.get_scanline_phases());
+    snapshot
+}
+
+/// Load a snapshot taken by `RewindBuffer::on_frame()`, picture included
+pub fn restore(nes: &mut NES, snapshot: &[u8]) -> Result<(), SaveStateError> {
+    let ppu = &nes.bus.ppu;
+    let phases_len = ppu.scanline_phases.len();
+    let picture_len = ppu.frame_buffer.len() * 2 + phases_len;
+    let Some(state_len) = snapshot.len().checked_sub(picture_len) else {
+        return Err(SaveStateError::Corrupt("rewind snapshot too short"));
+    };
+    let (state, picture) = snapshot.split_at(state_len);
+    nes.load_state(state)?;
+
+    let (pixels, phases) = picture.split_at(picture.len() - phases_len);
+    let ppu = &mut nes.bus.ppu;
+    for (pixel, bytes) in ppu.frame_buffer.iter_mut().zip(pixels.chunks_exact(2)) {
+        *pixel = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+    ppu.scanline_phases.copy_from_slice(phases);
+    Ok(())
+}
+
+// Delta encoding
+//
+// The target is XORed with the base, which turns unchanged bytes into zeros,
+// then stored as alternating runs: (zero count, literal count, literal bytes).
+// Counts are LEB128 varints. States of different lengths (e.g. after a region
+// change) are stored whole instead.
+
+const DELTA_FULL: u8 = 0;
+const DELTA_XOR: u8 = 1;
+
+fn encode_delta(target: &[u8], base: &[u8]) -> Vec<u8> {
+    if target.len() != base.len() {
+        let mut out = Vec::with_capacity(target.len() + 1);
+        out.push(DELTA_FULL);
+        out.extend_from_slice(target);
+        return out;
+    }
+
+    let mut out = vec![DELTA_XOR];
+    let mut i = 0;
+    while i < target.len() {
+        let zeros_start = i;
+        while i < target.len() && target[i] == base[i] {
+            i += 1;
+        }
+        let literals_start = i;
+        while i < target.len() && target[i] != base[i] {
+            i += 1;
+        }
+
+        write_varint(&mut out, literals_start - zeros_start);
+        write_varint(&mut out, i - literals_start);
+        out.extend((literals_start..i).map(|j| target[j] ^ base[j]));
+    }
+    out
+}
+
+fn decode_delta(delta: &[u8], base: &[u8]) -> Vec<u8> {
+    match delta.split_first() {
+        Some((&DELTA_XOR, mut rest)) => {
+            let mut out = base.to_vec();
+            let mut i = 0;
+            while !rest.is_empty() {
+                i += read_varint(&mut rest);
+                let literals = read_varint(&mut rest);
+                for (dst, x) in out[i..i + literals].iter_mut().zip(&rest[..literals]) {
+                    *dst ^= x;
+                }
+                rest = &rest[literals..];
+                i += literals;
+            }
+            out
+        }
+        Some((_, rest)) => rest.to_vec(),
+        None => Vec::new(),
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = data.split_first() {
+        *data = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nes_core::nes::test_utils::nes_with_program;
+
+    fn state(seed: u8) -> Vec<u8> {
+        let mut state = vec![0u8; 4096];
+        state[10] = seed;
+        state[2000..2004].copy_from_slice(&[seed; 4]);
+        state[4095] = seed.wrapping_mul(3);
+        state
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let base = state(1);
+        let target = state(2);
+        let delta = encode_delta(&target, &base);
+        assert!(delta.len() < 32);
+        assert_eq!(decode_delta(&delta, &base), target);
+
+        // Mismatched lengths fall back to a full copy
+        let short = vec![7u8; 100];
+        assert_eq!(decode_delta(&encode_delta(&short, &base), &base), short);
+    }
+
+    #[test]
+    fn test_pops_newest_first() {
+        let mut buffer = RewindBuffer::new(RewindConfig::default());
+        for seed in 0..5 {
+            buffer.push(state(seed));
+        }
+        for seed in (0..5).rev() {
+            assert_eq!(buffer.pop(), Some(state(seed)));
+        }
+        assert_eq!(buffer.pop(), None);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_restore_brings_back_state_and_picture() {
+        // INC $00; JMP $8000
+        let mut nes = nes_with_program(&[0xE6, 0x00, 0x4C, 0x00, 0x80]);
+        let mut buffer = RewindBuffer::new(RewindConfig {
+            interval_frames: 1,
+            ..RewindConfig::default()
+        });
+        nes.run_frame([0, 0]);
+        nes.bus.ppu.frame_buffer[1234] = 0x2A;
+        nes.bus.ppu.scanline_phases[7] = 2;
+        buffer.on_frame(&nes);
+        let state = nes.save_state();
+
+        nes.run_frame([0, 0]);
+        nes.bus.ppu.frame_buffer.fill(0x0F);
+        nes.bus.ppu.scanline_phases.fill(0);
+        assert_ne!(nes.save_state(), state);
+
+        restore(&mut nes, &buffer.pop().unwrap()).unwrap();
+        assert_eq!(nes.save_state(), state);
+        assert_eq!(nes.get_frame_buffer()[1234], 0x2A);
+        assert_eq!(nes.get_frame_buffer()[0], 0);
+        assert_eq!(nes.get_scanline_phases()[7], 2);
+        assert!(restore(&mut nes, &[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_budget_drops_oldest() {
+        let mut buffer = RewindBuffer::new(RewindConfig {
+            interval_frames: 1,
+            budget_bytes: 4096 + 40,
+        });
+        for seed in 0..10 {
+            buffer.push(state(seed));
+        }
+        assert!(buffer.used_bytes <= 4096 + 40);
+
+        let mut popped = Vec::new();
+        while let Some(s) = buffer.pop() {
+            popped.push(s);
+        }
+        assert!(popped.len() < 10);
+        assert_eq!(popped[0], state(9));
+        assert_eq!(buffer.used_bytes, 0);
+    }
+}
@@ -1,22 +1,57 @@
+use crate::emu::capture::Capture;
 use crate::emu::commands::{AudioChannel, EmuCommand};
 use crate::emu::emu_input::InputState;
 use crate::emu::event::EmuEvent;
+use crate::emu::movie::{MovieSession, MovieStatus};
+use crate::emu::netplay::{NetplaySession, NetplayStatus};
+use crate::emu::rewind::{self, RewindBuffer, RewindConfig};
+use crate::emu::telemetry::{EmuTelemetry, TelemetryCounter};
 use crate::shared::frame_buffer::SharedFrameHandle;
 use cpal::{FromSample, Sample, SampleRate, SizedSample};
 use crossbeam_channel::{Receiver, Sender};
+use nes_core::nes::controller::expansion::FourPlayers;
+use nes_core::nes::controller::family_keyboard::FamilyKeyboard;
+use nes_core::nes::controller::four_score::FourScore;
+use nes_core::nes::controller::power_pad::{FamilyTrainer, PowerPad};
+use nes_core::nes::controller::vaus::{self, Vaus};
+use nes_core::nes::controller::zapper::Zapper;
 use nes_core::prelude::*;
 
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+
+pub const DEFAULT_TURBO_RATE: u32 = 2;
+
 pub struct EmuRuntime {
     nes: NES,
     input_state: InputState,
     command_rx: Receiver<EmuCommand>,
     event_tx: Sender<EmuEvent>,
     paused: bool,
+    speed: f32,
+
+    rewind: RewindBuffer,
+    rewinding: bool,
+    /// Emulated frames' worth of audio played since the last rewind step
+    rewind_frames: f64,
+
+    movie: MovieSession,
+    netplay: Option<NetplaySession>,
+    /// Frames each turbo press and release lasts
+    turbo_rate: u32,
+
+    capture: Option<Capture>,
 
     scratch_buf: Vec<f32>,
     last_sample_rate: Option<u32>,
+    /// The audio device's rate, once known
+    output_sample_rate: Option<u32>,
+    telemetry: TelemetryCounter,
 }
 
+/// Recording rate before the audio device has asked for samples
+const DEFAULT_CAPTURE_RATE: u32 = 48_000;
+
 impl EmuRuntime {
     pub fn new(
         command_rx: Receiver<EmuCommand>,
@@ -29,28 +64,230 @@ impl EmuRuntime {
             command_rx,
             event_tx,
             paused: false,
+            speed: 1.0,
+            rewind: RewindBuffer::new(RewindConfig::default()),
+            rewinding: false,
+            rewind_frames: 0.0,
+            movie: MovieSession::new(),
+            netplay: None,
+            turbo_rate: DEFAULT_TURBO_RATE,
+            capture: None,
             scratch_buf: Vec::new(),
             last_sample_rate: None,
+            output_sample_rate: None,
+            telemetry: TelemetryCounter::new(),
         }
     }
 
     /// Handle EmuCommands received from the App UI thread
-    pub fn process_commands(&mut self) {
+    pub fn process_commands(&mut self, frame_buffer: &SharedFrameHandle) {
         while let Ok(command) = self.command_rx.try_recv() {
             match command {
-                EmuCommand::InsertCartridge(cartridge) => {
+                EmuCommand::InsertCartridge(cartridge, region) => {
                     self.event_tx
-                        .send(EmuEvent::Log("[Audio thread] InsertCartridge!".into()))
+                        .send(EmuEvent::Log(
+                            format!("[Audio thread] InsertCartridge! ({})", region.name()).into(),
+                        ))
                         .ok();
+                    // A recording is for one game and region
+                    self.stop_capture();
+                    self.nes.set_region(region);
                     self.nes.insert_cartridge(cartridge);
+                    self.telemetry.reset();
+                    self.rewind.clear();
+                    self.stop_movie();
+                    self.stop_netplay();
                     self.paused = false;
                 }
                 EmuCommand::Reset => {
                     self.nes.bus.reset_components();
+                    self.rewind.clear();
+                    self.stop_movie();
+                    self.stop_netplay();
                     self.paused = true;
                 }
                 EmuCommand::Pause(p) => {
                     self.paused = p;
+                    if p {
+                        self.send_position();
+                    }
+                }
+                EmuCommand::Step(kind) => {
+                    if self.paused {
+                        // Whole frames go through run_frame() so input is latched (and
+                        // recorded) like when running normally. Movies and netplay only
+                        // know whole frames, so they can't be stepped through any finer
+                        let sessions =
+                            self.movie.status() != MovieStatus::Idle || self.netplay.is_some();
+                        match kind {
+                            StepKind::Frame => {
+                                self.run_frame(frame_buffer);
+                            }
+                            _ if sessions => {
+                                let message =
+                                    "Only whole frames can be stepped during a movie or netplay";
+                                self.event_tx.send(EmuEvent::Log(message.into())).ok();
+                                continue;
+                            }
+                            _ => {
+                                if self.nes.step(kind) {
+                                    self.finish_stepped_frame();
+                                }
+                            }
+                        }
+
+                        // Show the partially drawn frame too, so scanline and
+                        // dot steps are visible
+                        frame_buffer
+                            .write(self.nes.get_frame_buffer(), self.nes.get_scanline_phases());
+                        self.discard_samples();
+                        self.send_snapshot();
+                        self.send_position();
+                    }
+                }
+                EmuCommand::SetSpeed(speed) => {
+                    self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+                }
+                EmuCommand::Rewind(rewinding) => {
+                    // Jumping back would desync a movie or the peer
+                    self.rewinding = rewinding
+                        && self.movie.status() == MovieStatus::Idle
+                        && self.netplay.is_none();
+                    self.rewind_frames = 0.0;
+                }
+                EmuCommand::ConfigureRewind(config) => {
+                    self.rewind.set_config(config);
+                }
+                EmuCommand::StartRecording {
+                    movie,
+                    from_power_on,
+                } => {
+                    self.stop_movie();
+                    self.stop_netplay();
+                    if let Some(device) = self.non_joypad_device() {
+                        let message =
+                            format!("Movies only hold controller buttons; unplug the {device}");
+                        self.event_tx.send(EmuEvent::Log(message.into())).ok();
+                        continue;
+                    }
+                    self.movie
+                        .start_recording(&mut self.nes, movie, from_power_on);
+                    self.rewind.clear();
+                    self.send_movie_status();
+                }
+                EmuCommand::PlayMovie(movie) => {
+                    self.stop_movie();
+                    self.stop_netplay();
+                    if let Err(e) = self.movie.start_playback(&mut self.nes, movie) {
+                        self.event_tx
+                            .send(EmuEvent::Log(format!("Movie playback failed: {e}").into()))
+                            .ok();
+                        self.nes.reset();
+                    }
+                    self.rewind.clear();
+                    self.send_movie_status();
+                }
+                EmuCommand::StopMovie => {
+                    self.stop_movie();
+                }
+                EmuCommand::StartNetplay(config) => {
+                    self.stop_movie();
+                    self.stop_netplay();
+                    if let Some(device) = self.non_joypad_device() {
+                        let message =
+                            format!("Netplay only sends controller buttons; unplug the {device}");
+                        self.event_tx.send(EmuEvent::Log(message.into())).ok();
+                        self.send_netplay_status();
+                        continue;
+                    }
+                    self.nes.set_microphone(false);
+                    match NetplaySession::bind(config) {
+                        Ok(session) => {
+                            self.netplay = Some(session);
+                            self.rewind.clear();
+                            self.paused = false;
+                        }
+                        Err(e) => {
+                            self.event_tx
+                                .send(EmuEvent::Log(format!("Netplay failed: {e:#}").into()))
+                                .ok();
+                        }
+                    }
+                    self.send_netplay_status();
+                }
+                EmuCommand::StopNetplay => {
+                    self.stop_netplay();
+                }
+                EmuCommand::SetTurboRate(rate) => {
+                    self.turbo_rate = rate.max(1);
+                }
+                EmuCommand::SetPort2Device(device) => {
+                    self.nes.set_port_device(1, device);
+                    // The 4-player adapters take both ports
+                    let port1 = match device {
+                        PortDevice::FourScore | PortDevice::FamicomFourPlayer => device,
+                        _ => PortDevice::Joypad,
+                    };
+                    if self.nes.port_device(0) != port1 {
+                        self.nes.set_port_device(0, port1);
+                    }
+                    self.stop_sessions_for_non_joypad_devices();
+                }
+                EmuCommand::SetConsole(console, expansion) => {
+                    self.nes.set_console(console);
+                    self.nes.set_expansion_device(expansion);
+                    self.stop_sessions_for_non_joypad_devices();
+                }
+                EmuCommand::SetMicrophone(active) => {
+                    // Neither the peer nor a movie hears it
+                    if self.netplay.is_none() && self.movie.status() != MovieStatus::Recording {
+                        self.nes.set_microphone(active);
+                    }
+                }
+                EmuCommand::SetSpriteLimit(enabled) => {
+                    self.nes.set_sprite_limit(enabled);
+                }
+                EmuCommand::Screenshot => {
+                    frame_buffer.write(self.nes.get_frame_buffer(), self.nes.get_scanline_phases());
+                    self.event_tx.send(EmuEvent::ScreenshotReady).ok();
+                }
+                EmuCommand::StartCapture {
+                    path,
+                    format,
+                    palette,
+                } => {
+                    self.stop_capture();
+                    let sample_rate = self.output_sample_rate.unwrap_or(DEFAULT_CAPTURE_RATE);
+                    match Capture::start(
+                        path.clone(),
+                        format,
+                        *palette,
+                        self.nes.region(),
+                        sample_rate,
+                        self.nes.bus.apu.samples_available(),
+                        self.event_tx.clone(),
+                    ) {
+                        Ok(capture) => {
+                            self.capture = Some(capture);
+                            self.event_tx
+                                .send(EmuEvent::Log(
+                                    format!("Recording to {}", path.display()).into(),
+                                ))
+                                .ok();
+                        }
+                        Err(e) => {
+                            self.event_tx
+                                .send(EmuEvent::Log(format!("{e:#}").into()))
+                                .ok();
+                        }
+                    }
+                    self.send_capture_status();
+                }
+                EmuCommand::StopCapture => {
+                    self.stop_capture();
+                }
+                EmuCommand::SetSnapshotScanline(scanline) => {
+                    self.nes.set_snapshot_scanline(scanline);
                 }
                 EmuCommand::ToggleAudioChannel(audio_channel) => match audio_channel {
                     AudioChannel::Pulse1 => self.nes.bus.apu.mute_pulse1 ^= true,
@@ -63,23 +300,55 @@ impl EmuRuntime {
         }
     }
 
-    fn run_cpu_cycles(&mut self, cpu_cycles: u32, frame_buffer: &SharedFrameHandle) {
-        let mut ran = 0;
-        while ran < cpu_cycles {
-            // Run the PPU until we hit a CPU tick
-            loop {
-                let (cpu_tick, frame_ready) = self.nes.tick();
-                if frame_ready {
-                    frame_buffer.write(self.nes.get_frame_buffer());
-                }
+    /// Run one frame with input latched at its start
+    ///
+    /// # Returns
+    ///
+    /// `false` if no frame ran because netplay is waiting on the peer
+    fn run_frame(&mut self, frame_buffer: &SharedFrameHandle) -> bool {
+        let live = self.live_input();
+        self.update_devices();
 
-                if cpu_tick {
-                    break;
+        if let Some(netplay) = &mut self.netplay {
+            // The local player uses the P1 controls whichever port they're on
+            let status = netplay.status();
+            let result = netplay.advance(&mut self.nes, live[0]);
+            if netplay.status() != status {
+                self.send_netplay_status();
+            }
+            match result {
+                Ok(true) => {}
+                Ok(false) => return false,
+                Err(e) => {
+                    self.event_tx
+                        .send(EmuEvent::Log(format!("Netplay stopped: {e:#}").into()))
+                        .ok();
+                    self.stop_netplay();
+                    return false;
                 }
             }
-            ran += 1;
+            frame_buffer.write(self.nes.get_frame_buffer(), self.nes.get_scanline_phases());
+            self.capture_frame();
+            self.send_snapshot();
+            self.telemetry.on_frame();
+            return true;
         }
-        self.nes.bus.apu.end_frame();
+
+        let (input, finished) = self.movie.next_input(live);
+        if finished {
+            self.event_tx
+                .send(EmuEvent::Log("Movie playback finished".into()))
+                .ok();
+            self.send_movie_status();
+        }
+
+        input.run(&mut self.nes);
+        frame_buffer.write(self.nes.get_frame_buffer(), self.nes.get_scanline_phases());
+        self.capture_frame();
+        self.send_snapshot();
+        self.telemetry.on_frame();
+        self.rewind.on_frame(&self.nes);
+        true
     }
 
     pub fn tick_audio<T>(
@@ -91,6 +360,7 @@ impl EmuRuntime {
     ) where
         T: Sample + SizedSample + FromSample<f32>,
     {
+        self.output_sample_rate = Some(sample_rate);
         if self.paused {
             for out in data.iter_mut() {
                 *out = T::from_sample(0.0);
@@ -99,39 +369,43 @@ impl EmuRuntime {
         }
 
         let frames = data.len() / channels;
+
+        if self.rewinding {
+            self.tick_rewind(frames, sample_rate, frame_buffer);
+            for out in data.iter_mut() {
+                *out = T::from_sample(0.0);
+            }
+            return;
+        }
+
+        // Speed is applied by resampling the APU to `sample_rate / speed`: each
+        // output sample then covers `speed` times as many CPU cycles. Emulation
+        // stays paced by audio demand, so it can't underrun, and the audio is
+        // pitch-shifted along with the game
+        let apu_sample_rate = (sample_rate as f32 / self.speed).round() as u32;
+
         // Only update APU SR when it changes
-        if self.last_sample_rate != Some(sample_rate) {
+        if self.last_sample_rate != Some(apu_sample_rate) {
             // let msg = format!("update sample rate {} -> {}", self.last_sample_rate.unwrap_or(0), sample_rate) ;
             // self.event_tx.send(EmuEvent::Log(msg.into())).ok();
-            self.nes.bus.apu.set_sample_rate(sample_rate as f64);
-            self.last_sample_rate = Some(sample_rate);
+            self.nes.bus.apu.set_sample_rate(apu_sample_rate as f64);
+            self.last_sample_rate = Some(apu_sample_rate);
         }
 
         if self.scratch_buf.len() < frames {
             self.scratch_buf.resize(frames, 0.0);
         }
 
-        // update user input
-        self.nes.bus.joypads[0].set_buttons(self.input_state.p1.load());
-        self.nes.bus.joypads[1].set_buttons(self.input_state.p2.load());
-
         // Let the emulator run ahead a little bit to provide a
         // buffer in case the audio thread gets bogged down
         let target_available = frames * 3;
 
-        // run cpu until blip buffer has enough frames
+        // Run whole frames until the blip buffer has enough samples. Input is
+        // only sampled between frames, never mid-frame
         while self.nes.bus.apu.samples_available() < target_available {
-            let samples_available = self.nes.bus.apu.samples_available() as u32;
-            let need_samples = target_available as u32 - samples_available;
-
-            let mut cpu_cycles = self.nes.bus.apu.clocks_needed(need_samples);
-
-            // avoid stalling if clocks_needed() is zero
-            if cpu_cycles == 0 {
-                cpu_cycles = 1;
+            if !self.run_frame(frame_buffer) {
+                break;
             }
-
-            self.run_cpu_cycles(cpu_cycles, frame_buffer);
         }
 
         let got = self
@@ -140,6 +414,11 @@ impl EmuRuntime {
             .apu
             .read_samples_f32(&mut self.scratch_buf[..frames]);
 
+        // Recordings get the samples as the APU made them, unfiltered
+        if let Some(capture) = &mut self.capture {
+            capture.on_samples(&self.scratch_buf[..got], apu_sample_rate);
+        }
+
         // If for some reason we get less than needed, fill remaining with silence
         if got < frames {
             for s in &mut self.scratch_buf[got..frames] {
@@ -159,5 +438,237 @@ impl EmuRuntime {
                 *out = s;
             }
         }
+
+        if let Some(fps) = self.telemetry.on_samples(frames, sample_rate) {
+            self.send_telemetry(fps, sample_rate);
+        }
+    }
+
+    /// Step back one snapshot for every `interval_frames` frames of audio
+    /// played, so rewinding runs at roughly normal speed. Audio is muted
+    fn tick_rewind(&mut self, frames: usize, sample_rate: u32, frame_buffer: &SharedFrameHandle) {
+        let interval = self.rewind.config().interval_frames.max(1) as f64;
+        self.rewind_frames += frames as f64 * self.nes.region().frame_rate() / sample_rate as f64;
+
+        let mut snapshot = None;
+        while self.rewind_frames >= interval {
+            self.rewind_frames -= interval;
+            match self.rewind.pop() {
+                Some(state) => snapshot = Some(state),
+                None => break,
+            }
+            // Hold on to the oldest snapshot so rewinding stops there
+            if self.rewind.is_empty() {
+                if let Some(state) = &snapshot {
+                    self.rewind.push(state.clone());
+                }
+                break;
+            }
+        }
+        let Some(snapshot) = snapshot else {
+            return;
+        };
+
+        if let Err(e) = rewind::restore(&mut self.nes, &snapshot) {
+            self.event_tx
+                .send(EmuEvent::Log(format!("Rewind failed: {e}").into()))
+                .ok();
+            self.rewind.clear();
+            return;
+        }
+
+        frame_buffer.write(self.nes.get_frame_buffer(), self.nes.get_scanline_phases());
+        self.nes.bus.apu.discard_samples();
+    }
+
+    /// A step inside a frame crossed into the next one. Do what running
+    /// that frame would have: latch input for the new frame and keep the
+    /// recording and rewind buffer up to date
+    fn finish_stepped_frame(&mut self) {
+        let live = self.live_input();
+        self.nes.set_buttons(live);
+        self.update_devices();
+        self.capture_frame();
+        self.telemetry.on_frame();
+        self.rewind.on_frame(&self.nes);
+    }
+
+    /// Input beyond controller buttons, for the frame about to run
+    fn update_devices(&mut self) {
+        self.update_pointer_devices();
+        self.update_extra_controllers();
+        if let Some(keyboard) = self.nes.expansion_mut::<FamilyKeyboard>() {
+            keyboard.set_pressed(&self.input_state.family_keyboard.load());
+        }
+    }
+
+    /// Controller state for the frame about to run, with turbo applied
+    fn live_input(&self) -> [u8; 2] {
+        let frame = self.nes.frame_count();
+        [0, 1].map(|player| {
+            self.input_state.players[player]
+                .load()
+                .resolve(frame, self.turbo_rate)
+        })
+    }
+
+    /// Feed the mouse to a Zapper or paddle in port 2
+    fn update_pointer_devices(&mut self) {
+        let pointer = self.input_state.pointer.load();
+        if let Some(zapper) = self.nes.port_mut::<Zapper>(1) {
+            zapper.set_aim(pointer.aim);
+            zapper.set_trigger(pointer.trigger);
+        }
+        if let Some(vaus) = self.nes.port_mut::<Vaus>(1) {
+            // Across the screen turns the knob through its whole range
+            if let Some((x, _)) = pointer.aim {
+                let range = (vaus::KNOB_MAX - vaus::KNOB_MIN) as u32;
+                vaus.set_position(vaus::KNOB_MIN + (x as u32 * range / 255) as u8);
+            }
+            vaus.set_button(pointer.trigger);
+        }
+    }
+
+    /// Feed players 3 and 4 to a 4-player adapter, on the controller ports or
+    /// the expansion port, and the mat buttons to a Power Pad or Family
+    /// Trainer. Port 1 carries player 3, port 2 player 4
+    fn update_extra_controllers(&mut self) {
+        let frame = self.nes.frame_count();
+        for port in 0..2 {
+            let buttons = self.input_state.players[port + 2]
+                .load()
+                .resolve(frame, self.turbo_rate);
+            if let Some(adapter) = self.nes.port_mut::<FourScore>(port) {
+                adapter.set_controller(1, buttons);
+            }
+            if let Some(adapter) = self.nes.expansion_mut::<FourPlayers>() {
+                adapter.set_controller(port, buttons);
+            }
+        }
+
+        let mat = self.input_state.mat.load();
+        if let Some(pad) = self.nes.port_mut::<PowerPad>(1) {
+            pad.set_pressed(mat);
+        }
+        if let Some(trainer) = self.nes.port_mut::<FamilyTrainer>(1) {
+            trainer.set_pressed(mat);
+        }
+    }
+
+    fn stop_movie(&mut self) {
+        let was_active = self.movie.status() != MovieStatus::Idle;
+        if let Some(movie) = self.movie.stop() {
+            self.event_tx.send(EmuEvent::MovieRecorded(movie)).ok();
+        }
+        if was_active {
+            self.send_movie_status();
+        }
+    }
+
+    fn capture_frame(&self) {
+        if let Some(capture) = &self.capture {
+            capture.on_frame(self.nes.get_frame_buffer());
+        }
+    }
+
+    /// Drop the buffered audio. A recording still gets it, so it stays in
+    /// step with the frames
+    fn discard_samples(&mut self) {
+        if let Some(capture) = &mut self.capture {
+            let mut samples = vec![0.0; self.nes.bus.apu.samples_available()];
+            let got = self.nes.bus.apu.read_samples_f32(&mut samples);
+            capture.on_samples(&samples[..got], self.last_sample_rate.unwrap_or(0));
+        }
+        self.nes.bus.apu.discard_samples();
+    }
+
+    fn stop_capture(&mut self) {
+        // The writer thread finishes the files once the channel closes
+        if self.capture.take().is_some() {
+            self.send_capture_status();
+        }
+    }
+
+    fn send_snapshot(&mut self) {
+        if let Some(snapshot) = self.nes.take_snapshot() {
+            self.event_tx
+                .send(EmuEvent::PpuSnapshot(snapshot.into()))
+                .ok();
+        }
+    }
+
+    fn send_capture_status(&self) {
+        self.event_tx
+            .send(EmuEvent::CaptureStatus(self.capture.is_some()))
+            .ok();
+    }
+
+    /// A plugged-in device with input beyond controller buttons. Netplay and
+    /// movies carry nothing else, so the peer's game or a replay would never
+    /// see it
+    fn non_joypad_device(&self) -> Option<String> {
+        for port in 0..2 {
+            let device = self.nes.port_device(port);
+            if device != PortDevice::Joypad {
+                return Some(format!("{device:?}"));
+            }
+        }
+        self.nes
+            .expansion_device()
+            .map(|device| format!("{device:?}"))
+    }
+
+    fn stop_sessions_for_non_joypad_devices(&mut self) {
+        let Some(device) = self.non_joypad_device() else {
+            return;
+        };
+        if self.netplay.is_some() {
+            let message = format!("Netplay stopped: the {device} can't be played over it");
+            self.event_tx.send(EmuEvent::Log(message.into())).ok();
+            self.stop_netplay();
+        }
+        if self.movie.status() == MovieStatus::Recording {
+            let message = format!("Recording stopped: movies can't hold the {device}'s input");
+            self.event_tx.send(EmuEvent::Log(message.into())).ok();
+            self.stop_movie();
+        }
+    }
+
+    fn stop_netplay(&mut self) {
+        if self.netplay.take().is_some() {
+            self.send_netplay_status();
+        }
+    }
+
+    fn send_netplay_status(&self) {
+        let status = self
+            .netplay
+            .as_ref()
+            .map_or(NetplayStatus::Off, |netplay| netplay.status());
+        self.event_tx.send(EmuEvent::NetplayStatus(status)).ok();
+    }
+
+    fn send_movie_status(&self) {
+        self.event_tx
+            .send(EmuEvent::MovieStatus(self.movie.status()))
+            .ok();
+    }
+
+    fn send_position(&self) {
+        self.event_tx
+            .send(EmuEvent::Position(self.nes.position()))
+            .ok();
+    }
+
+    fn send_telemetry(&self, fps: f64, sample_rate: u32) {
+        let region = self.nes.region();
+        let telemetry = EmuTelemetry {
+            region,
+            fps,
+            target_fps: region.frame_rate(),
+            sample_rate,
+            cpu_hz: region.cpu_hz(),
+        };
+        self.event_tx.send(EmuEvent::Telemetry(telemetry)).ok();
     }
 }
@@ -0,0 +1,80 @@
+use nes_core::prelude::Region;
+
+/// Snapshot of emulation speed, sent Audio -> UI roughly once per second
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EmuTelemetry {
+    pub region: Region,
+    /// Frames actually produced per second of played audio
+    pub fps: f64,
+    /// The region's nominal frame rate
+    pub target_fps: f64,
+    /// Output device sample rate
+    pub sample_rate: u32,
+    /// Emulated CPU clock the APU output is resampled from
+    pub cpu_hz: f64,
+}
+
+/// Measures frame rate against the audio clock.
+///
+/// Emulation is paced by audio demand, so samples played are the runtime's
+/// notion of wall time. This also works on WASM, where `Instant` isn't available
+pub struct TelemetryCounter {
+    frames: u32,
+    samples: u64,
+}
+
+impl Default for TelemetryCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryCounter {
+    pub fn new() -> Self {
+        Self {
+            frames: 0,
+            samples: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.samples = 0;
+    }
+
+    pub fn on_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Record `samples` played at `sample_rate`; returns the measured FPS once a second's worth has elapsed
+    pub fn on_samples(&mut self, samples: usize, sample_rate: u32) -> Option<f64> {
+        self.samples += samples as u64;
+        if sample_rate == 0 || self.samples < sample_rate as u64 {
+            return None;
+        }
+
+        let seconds = self.samples as f64 / sample_rate as f64;
+        let fps = self.frames as f64 / seconds;
+        self.reset();
+        Some(fps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_once_per_second_of_audio() {
+        let mut counter = TelemetryCounter::new();
+        for _ in 0..50 {
+            counter.on_frame();
+        }
+        assert_eq!(counter.on_samples(24_000, 48_000), None);
+        assert_eq!(counter.on_samples(24_000, 48_000), Some(50.0));
+
+        // Counter restarts after reporting
+        counter.on_frame();
+        assert_eq!(counter.on_samples(96_000, 48_000), Some(0.5));
+    }
+}
@@ -1,4 +1,6 @@
 pub mod app;
 pub mod audio;
 pub mod emu;
+pub mod rom;
 pub mod shared;
+pub mod video;
@@ -0,0 +1,97 @@
+use anyhow::bail;
+
+pub mod zip_archive;
+
+const ROM_EXTENSION: &str = ".nes";
+
+/// A ROM file found inside an archive
+pub struct RomFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Result of unpacking user-supplied ROM bytes
+pub enum RomSource {
+    /// A single iNES image, ready to be parsed
+    Single(RomFile),
+    /// An archive holding several ROMs; the user has to pick one
+    Choice(Vec<RomFile>),
+}
+
+/// Unpack `bytes` (read from a file called `name`) if they're an archive,
+/// otherwise pass them through untouched
+pub fn unpack(name: String, bytes: Vec<u8>) -> anyhow::Result<RomSource> {
+    if !zip_archive::is_zip(&bytes) {
+        return Ok(RomSource::Single(RomFile { name, bytes }));
+    }
+
+    let mut roms: Vec<RomFile> = zip_archive::read_entries(&bytes, is_rom_name)?
+        .into_iter()
+        .map(|entry| RomFile {
+            name: entry.name,
+            bytes: entry.bytes,
+        })
+        .collect();
+
+    match roms.len() {
+        0 => bail!("Archive doesn't contain any {ROM_EXTENSION} files"),
+        1 => Ok(RomSource::Single(roms.remove(0))),
+        _ => {
+            roms.sort_by_key(|rom| rom.name.to_lowercase());
+            Ok(RomSource::Choice(roms))
+        }
+    }
+}
+
+fn is_rom_name(name: &str) -> bool {
+    // Skip resource-fork junk added by macOS' archiver
+    if name.starts_with("__MACOSX/") {
+        return false;
+    }
+    name.to_lowercase().ends_with(ROM_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::zip_archive::tests::build_zip;
+
+    #[test]
+    fn test_plain_rom_passes_through() {
+        let Ok(RomSource::Single(rom)) = unpack("game.nes".into(), b"NES\x1Adata".to_vec()) else {
+            panic!("expected a single ROM");
+        };
+        assert_eq!(rom.name, "game.nes");
+        assert_eq!(rom.bytes, b"NES\x1Adata");
+    }
+
+    #[test]
+    fn test_single_rom_archive() {
+        let zip = build_zip(&[
+            ("info.txt", b"hi", false),
+            ("Game (U).NES", b"NES\x1Aone", true),
+            ("__MACOSX/._Game (U).NES", b"junk", false),
+        ]);
+        let Ok(RomSource::Single(rom)) = unpack("set.zip".into(), zip) else {
+            panic!("expected a single ROM");
+        };
+        assert_eq!(rom.name, "Game (U).NES");
+        assert_eq!(rom.bytes, b"NES\x1Aone");
+    }
+
+    #[test]
+    fn test_multi_rom_archive_is_sorted() {
+        let zip = build_zip(&[("b.nes", b"NES\x1Ab", false), ("A.nes", b"NES\x1Aa", false)]);
+        let Ok(RomSource::Choice(roms)) = unpack("set.zip".into(), zip) else {
+            panic!("expected a choice of ROMs");
+        };
+        let names: Vec<_> = roms.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["A.nes", "b.nes"]);
+    }
+
+    #[test]
+    fn test_archive_without_roms() {
+        let zip = build_zip(&[("readme.txt", b"hi", false)]);
+        assert!(unpack("set.zip".into(), zip).is_err());
+    }
+}
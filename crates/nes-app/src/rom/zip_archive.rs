@@ -0,0 +1,254 @@
+// See: https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+//
+// Only what's needed to pull ROMs out of typical ROM-set zips is supported:
+// single-disk archives with stored (0) or deflated (8) entries. Zip64 archives
+// and encrypted ROM entries are rejected.
+
+use anyhow::{Context, bail};
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4B50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4B50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4B50;
+
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const END_OF_CENTRAL_DIR_LEN: usize = 22;
+const MAX_COMMENT_LEN: usize = 0xFFFF;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+const FLAG_ENCRYPTED: u16 = 0b0000_0001;
+
+/// Refuse to inflate anything bigger than this (no NES ROM comes close)
+const MAX_ENTRY_SIZE: usize = 16 * 1024 * 1024;
+
+/// A file stored in a zip archive
+pub struct ZipEntry {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Returns `true` if `bytes` starts with a zip local file header
+pub fn is_zip(bytes: &[u8]) -> bool {
+    read_u32(bytes, 0) == Some(LOCAL_HEADER_SIG)
+}
+
+/// Reads and decompresses the file entries whose names pass `wanted`
+/// (directories are skipped). Entries that aren't wanted are never inflated,
+/// so they can't fail the read even if they're encrypted, huge or use an
+/// unsupported compression method
+pub fn read_entries(bytes: &[u8], wanted: impl Fn(&str) -> bool) -> anyhow::Result<Vec<ZipEntry>> {
+    let eocd = find_end_of_central_dir(bytes).context("Zip end of central directory not found")?;
+
+    let entry_count = read_u16(bytes, eocd + 10).context("Truncated zip directory")? as usize;
+    let dir_offset = read_u32(bytes, eocd + 16).context("Truncated zip directory")?;
+    if dir_offset == u32::MAX {
+        bail!("Zip64 archives are not supported");
+    }
+
+    let mut entries = Vec::with_capacity(entry_count);
+    let mut pos = dir_offset as usize;
+    for _ in 0..entry_count {
+        if read_u32(bytes, pos) != Some(CENTRAL_HEADER_SIG) {
+            bail!("Corrupt zip central directory at offset {pos}");
+        }
+        let header = bytes
+            .get(pos..pos + CENTRAL_HEADER_LEN)
+            .context("Truncated zip central directory")?;
+
+        let flags = u16::from_le_bytes([header[8], header[9]]);
+        let method = u16::from_le_bytes([header[10], header[11]]);
+        let crc = u32::from_le_bytes([header[16], header[17], header[18], header[19]]);
+        let compressed_size =
+            u32::from_le_bytes([header[20], header[21], header[22], header[23]]) as usize;
+        let size = u32::from_le_bytes([header[24], header[25], header[26], header[27]]) as usize;
+        let name_len = u16::from_le_bytes([header[28], header[29]]) as usize;
+        let extra_len = u16::from_le_bytes([header[30], header[31]]) as usize;
+        let comment_len = u16::from_le_bytes([header[32], header[33]]) as usize;
+        let local_offset =
+            u32::from_le_bytes([header[42], header[43], header[44], header[45]]) as usize;
+
+        let name_start = pos + CENTRAL_HEADER_LEN;
+        let name_bytes = bytes
+            .get(name_start..name_start + name_len)
+            .context("Truncated zip entry name")?;
+        let name = String::from_utf8_lossy(name_bytes).into_owned();
+        pos = name_start + name_len + extra_len + comment_len;
+
+        // Directory entries carry no data
+        if name.ends_with('/') || !wanted(&name) {
+            continue;
+        }
+        if flags & FLAG_ENCRYPTED != 0 {
+            bail!("'{name}' is encrypted");
+        }
+        if size > MAX_ENTRY_SIZE {
+            bail!("'{name}' is too large ({size} bytes)");
+        }
+
+        let data = local_entry_data(bytes, local_offset, compressed_size)
+            .with_context(|| format!("Failed to locate '{name}' in archive"))?;
+
+        let contents = match method {
+            METHOD_STORED => data.to_vec(),
+            METHOD_DEFLATE => miniz_oxide::inflate::decompress_to_vec_with_limit(data, size)
+                .map_err(|e| anyhow::anyhow!("Failed to inflate '{name}': {e:?}"))?,
+            other => bail!("'{name}' uses unsupported compression method {other}"),
+        };
+
+        if contents.len() != size || crc32fast::hash(&contents) != crc {
+            bail!("'{name}' failed its CRC check");
+        }
+
+        entries.push(ZipEntry {
+            name,
+            bytes: contents,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Locate the compressed data for an entry via its local file header
+fn local_entry_data(bytes: &[u8], offset: usize, compressed_size: usize) -> Option<&[u8]> {
+    if read_u32(bytes, offset)? != LOCAL_HEADER_SIG {
+        return None;
+    }
+    // Note: Sizes in the local header may be zeroed when a data descriptor is used,
+    //       so only the name/extra lengths are taken from it
+    let name_len = read_u16(bytes, offset + 26)? as usize;
+    let extra_len = read_u16(bytes, offset + 28)? as usize;
+    let start = offset + LOCAL_HEADER_LEN + name_len + extra_len;
+    bytes.get(start..start + compressed_size)
+}
+
+/// Scan backwards for the end-of-central-directory record (it may be followed by a comment)
+fn find_end_of_central_dir(bytes: &[u8]) -> Option<usize> {
+    let last = bytes.len().checked_sub(END_OF_CENTRAL_DIR_LEN)?;
+    let first = last.saturating_sub(MAX_COMMENT_LEN);
+    (first..=last)
+        .rev()
+        .find(|&pos| read_u32(bytes, pos) == Some(END_OF_CENTRAL_DIR_SIG))
+}
+
+fn read_u16(bytes: &[u8], pos: usize) -> Option<u16> {
+    let b = bytes.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> Option<u32> {
+    let b = bytes.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Build a minimal zip archive in memory
+    pub(crate) fn build_zip(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+
+        for (name, contents, deflate) in files {
+            let (method, data) = if *deflate {
+                (
+                    METHOD_DEFLATE,
+                    miniz_oxide::deflate::compress_to_vec(contents, 6),
+                )
+            } else {
+                (METHOD_STORED, contents.to_vec())
+            };
+            let crc = crc32fast::hash(contents);
+            let offset = out.len() as u32;
+
+            out.extend_from_slice(&LOCAL_HEADER_SIG.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0]); // version, flags
+            out.extend_from_slice(&method.to_le_bytes());
+            out.extend_from_slice(&[0; 4]); // mod time/date
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&data);
+
+            central.extend_from_slice(&CENTRAL_HEADER_SIG.to_le_bytes());
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0]); // versions, flags
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 4]); // mod time/date
+            central.extend_from_slice(&crc.to_le_bytes());
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]); // extra, comment, disk, attrs
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+
+        let dir_offset = out.len() as u32;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(&END_OF_CENTRAL_DIR_SIG.to_le_bytes());
+        out.extend_from_slice(&[0; 4]); // disk numbers
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&dir_offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn test_reads_stored_and_deflated_entries() {
+        let rom: Vec<u8> = (0..4096u32).map(|i| (i % 7) as u8).collect();
+        let zip = build_zip(&[("readme.txt", b"hello", false), ("game.nes", &rom, true)]);
+
+        assert!(is_zip(&zip));
+        let entries = read_entries(&zip, |_| true).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "readme.txt");
+        assert_eq!(entries[0].bytes, b"hello");
+        assert_eq!(entries[1].name, "game.nes");
+        assert_eq!(entries[1].bytes, rom);
+    }
+
+    #[test]
+    fn test_skips_directories() {
+        let zip = build_zip(&[("roms/", b"", false), ("roms/a.nes", b"NES\x1A", false)]);
+        let entries = read_entries(&zip, |_| true).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "roms/a.nes");
+    }
+
+    #[test]
+    fn test_rejects_corrupt_data() {
+        let mut zip = build_zip(&[("game.nes", b"NES\x1A-data", false)]);
+        zip[LOCAL_HEADER_LEN + "game.nes".len() + 5] ^= 0xFF;
+        assert!(read_entries(&zip, |_| true).is_err());
+    }
+
+    #[test]
+    fn test_unwanted_entries_are_not_read() {
+        let mut zip = build_zip(&[
+            ("readme.txt", b"hello", false),
+            ("game.nes", b"NES\x1A", false),
+        ]);
+        // Corrupt the readme and mark it with an unsupported method
+        zip[LOCAL_HEADER_LEN + "readme.txt".len()] ^= 0xFF;
+        let dir = zip.len() - END_OF_CENTRAL_DIR_LEN - 2 * CENTRAL_HEADER_LEN - 18;
+        zip[dir + 10] = 99;
+        assert!(read_entries(&zip, |_| true).is_err());
+
+        let entries = read_entries(&zip, |name| name.ends_with(".nes")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "game.nes");
+    }
+
+    #[test]
+    fn test_not_a_zip() {
+        assert!(!is_zip(b"NES\x1A"));
+        assert!(read_entries(b"NES\x1A", |_| true).is_err());
+    }
+}
@@ -4,7 +4,20 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 
 const W: usize = 256;
 const H: usize = 240;
-type Frame = [u8; W * H];
+/// A finished frame, as the PPU outputs it
+pub struct Frame {
+    /// 9-bit pixels
+    pub pixels: [u16; W * H],
+    /// Color subcarrier phase at the start of each line
+    pub phases: [u8; H],
+}
+
+impl Frame {
+    const EMPTY: Frame = Frame {
+        pixels: [0; W * H],
+        phases: [0; H],
+    };
+}
 
 pub type SharedFrameHandle = Arc<SharedFrame>;
 pub struct SharedFrame {
@@ -21,7 +34,7 @@ impl SharedFrame {
     pub fn new() -> Self {
         Self {
             active: AtomicUsize::new(0),
-            buffers: [UnsafeCell::new([0u8; W * H]), UnsafeCell::new([0u8; W * H])],
+            buffers: [UnsafeCell::new(Frame::EMPTY), UnsafeCell::new(Frame::EMPTY)],
         }
     }
 
@@ -34,12 +47,16 @@ impl SharedFrame {
     }
 
     #[inline]
-    pub fn write(&self, frame: &Frame) {
+    pub fn write(&self, pixels: &[u16; W * H], phases: &[u8; H]) {
         let index = self.active.load(Ordering::Relaxed);
         let other = index ^ 1;
 
         // SAFETY: write() only writes to non-active buffer
-        unsafe { (*self.buffers[other].get()).copy_from_slice(frame) };
+        unsafe {
+            let frame = &mut *self.buffers[other].get();
+            frame.pixels.copy_from_slice(pixels);
+            frame.phases.copy_from_slice(phases);
+        }
 
         self.active.store(other, Ordering::Release);
     }
@@ -0,0 +1,418 @@
+use crate::video::Image;
+use crate::video::scale::Scaler;
+use std::fmt::Write;
+
+/// Width the NES image is drawn at relative to its height. The PPU's pixels
+/// aren't square on a TV
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PixelAspect {
+    #[default]
+    Square,
+    /// 8:7, how NTSC TVs stretch the picture
+    Ntsc,
+}
+
+impl PixelAspect {
+    pub const ALL: [PixelAspect; 2] = [PixelAspect::Square, PixelAspect::Ntsc];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PixelAspect::Square => "Square",
+            PixelAspect::Ntsc => "8:7",
+        }
+    }
+
+    /// Pixel width over pixel height
+    pub fn ratio(&self) -> f32 {
+        match self {
+            PixelAspect::Square => 1.0,
+            PixelAspect::Ntsc => 8.0 / 7.0,
+        }
+    }
+}
+
+/// NES pixels cut off each edge of the picture, the way a TV's bezel hides
+/// the garbage many games leave there
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Overscan {
+    pub top: u8,
+    pub bottom: u8,
+    pub left: u8,
+    pub right: u8,
+}
+
+impl Overscan {
+    /// Most that can be cut off a single edge
+    pub const MAX: u8 = 64;
+}
+
+/// The part of the 256x240 frame that's shown, in NES pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VisibleArea {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// How the picture gets from the palette (or NTSC filter) to the screen
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DisplaySettings {
+    pub scaler: Scaler,
+    /// Only draw at whole multiples of the NES resolution
+    pub integer_scaling: bool,
+    /// How much the gaps between lines are darkened, 0.0 (off) to 1.0
+    pub scanlines: f32,
+    pub aspect: PixelAspect,
+    pub overscan: Overscan,
+    /// Black out the leftmost 8 columns, where games that scroll sideways
+    /// draw tiles they're about to scroll in
+    pub hide_left_column: bool,
+    /// Native window size, as a multiple of the visible picture
+    pub window_scale: u8,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            scaler: Scaler::Nearest,
+            integer_scaling: false,
+            scanlines: 0.0,
+            aspect: PixelAspect::Square,
+            overscan: Overscan::default(),
+            hide_left_column: false,
+            window_scale: 3,
+        }
+    }
+}
+
+impl DisplaySettings {
+    pub const WINDOW_SCALES: std::ops::RangeInclusive<u8> = 1..=6;
+
+    pub fn visible_area(&self) -> VisibleArea {
+        let Overscan {
+            top,
+            bottom,
+            left,
+            right,
+        } = self.overscan;
+        VisibleArea {
+            x: left as usize,
+            y: top as usize,
+            width: 256 - left as usize - right as usize,
+            height: 240 - top as usize - bottom as usize,
+        }
+    }
+
+    /// Crop, scale and darken a 240-line frame for upload. `filtered` frames
+    /// came through the NTSC filter and aren't pixel art anymore, so they skip
+    /// the scaler
+    pub fn process(&self, image: Image, filtered: bool) -> Image {
+        let mut image = image;
+        if self.hide_left_column {
+            blank_columns(&mut image, 8);
+        }
+        let visible = self.visible_area();
+        let image = crop(image, visible);
+        let image = match filtered {
+            true => image,
+            false => self.scaler.scale(image),
+        };
+        match self.scanlines > 0.0 {
+            true => scanlines(image, self.scanlines, visible.height),
+            false => image,
+        }
+    }
+
+    /// Size to draw the visible picture at, in physical pixels, to fit
+    /// `available`
+    pub fn fit(&self, available: (f32, f32)) -> (f32, f32) {
+        let visible = self.visible_area();
+        let width = visible.width as f32 * self.aspect.ratio();
+        let height = visible.height as f32;
+        let mut scale = (available.0 / width).min(available.1 / height);
+        if self.integer_scaling && scale >= 1.0 {
+            scale = scale.floor();
+        }
+        (width * scale, height * scale)
+    }
+
+    /// Size of the native window, in points: the visible picture at
+    /// `window_scale`
+    pub fn window_size(&self) -> (f32, f32) {
+        let visible = self.visible_area();
+        let scale = self.window_scale as f32;
+        (
+            (visible.width as f32 * self.aspect.ratio() * scale).round(),
+            visible.height as f32 * scale,
+        )
+    }
+
+    /// One `name = value` line per setting
+    pub fn to_config(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "scaler = {}", self.scaler.name());
+        let _ = writeln!(out, "integer_scaling = {}", self.integer_scaling);
+        let _ = writeln!(out, "scanlines = {}", self.scanlines);
+        let _ = writeln!(out, "aspect = {}", self.aspect.name());
+        let _ = writeln!(out, "overscan_top = {}", self.overscan.top);
+        let _ = writeln!(out, "overscan_bottom = {}", self.overscan.bottom);
+        let _ = writeln!(out, "overscan_left = {}", self.overscan.left);
+        let _ = writeln!(out, "overscan_right = {}", self.overscan.right);
+        let _ = writeln!(out, "hide_left_column = {}", self.hide_left_column);
+        let _ = writeln!(out, "window_scale = {}", self.window_scale);
+        out
+    }
+
+    /// Read settings written by `to_config()`. Anything missing or invalid
+    /// keeps its default
+    pub fn from_config(text: &str) -> Self {
+        let mut settings = Self::default();
+        for line in text.lines() {
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match name.trim() {
+                "scaler" => {
+                    if let Some(scaler) = Scaler::from_name(value) {
+                        settings.scaler = scaler;
+                    }
+                }
+                "integer_scaling" => {
+                    if let Ok(value) = value.parse() {
+                        settings.integer_scaling = value;
+                    }
+                }
+                "scanlines" => {
+                    if let Ok(value) = value.parse::<f32>()
+                        && (0.0..=1.0).contains(&value)
+                    {
+                        settings.scanlines = value;
+                    }
+                }
+                "aspect" => {
+                    if let Some(aspect) = PixelAspect::ALL.into_iter().find(|a| a.name() == value) {
+                        settings.aspect = aspect;
+                    }
+                }
+                "overscan_top" | "overscan_bottom" | "overscan_left" | "overscan_right" => {
+                    if let Ok(value) = value.parse()
+                        && value <= Overscan::MAX
+                    {
+                        let edge = match name.trim() {
+                            "overscan_top" => &mut settings.overscan.top,
+                            "overscan_bottom" => &mut settings.overscan.bottom,
+                            "overscan_left" => &mut settings.overscan.left,
+                            _ => &mut settings.overscan.right,
+                        };
+                        *edge = value;
+                    }
+                }
+                "hide_left_column" => {
+                    if let Ok(value) = value.parse() {
+                        settings.hide_left_column = value;
+                    }
+                }
+                "window_scale" => {
+                    if let Ok(value) = value.parse()
+                        && Self::WINDOW_SCALES.contains(&value)
+                    {
+                        settings.window_scale = value;
+                    }
+                }
+                _ => {}
+            }
+        }
+        settings
+    }
+}
+
+/// Turn the leftmost `columns` NES pixels black, however wide the image's
+/// pixels are
+fn blank_columns(image: &mut Image, columns: usize) {
+    let blank = (columns * image.width).div_ceil(256);
+    for line in image.rgba.chunks_exact_mut(image.width * 4) {
+        for pixel in line[..blank * 4].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[0, 0, 0, 255]);
+        }
+    }
+}
+
+/// Cut a 240-line image down to `visible`, which is in NES pixels even if the
+/// image's are narrower
+fn crop(image: Image, visible: VisibleArea) -> Image {
+    if visible.width == 256 && visible.height == 240 {
+        return image;
+    }
+    let left = visible.x * image.width / 256;
+    let right = (visible.x + visible.width) * image.width / 256;
+    let width = right - left;
+    let row = image.width * 4;
+    let rgba = image
+        .rgba
+        .chunks_exact(row)
+        .skip(visible.y)
+        .take(visible.height)
+        .flat_map(|line| &line[left * 4..right * 4])
+        .copied()
+        .collect();
+    Image::new(width, visible.height, rgba)
+}
+
+/// Darken the bottom row of every NES line, doubling the rows first if a
+/// line is only one row tall
+fn scanlines(image: Image, intensity: f32, lines: usize) -> Image {
+    let mut image = match image.height / lines {
+        0 | 1 => {
+            let row = image.width * 4;
+            let rgba = image
+                .rgba
+                .chunks_exact(row)
+                .flat_map(|line| [line, line])
+                .flatten()
+                .copied()
+                .collect();
+            Image::new(image.width, image.height * 2, rgba)
+        }
+        _ => image,
+    };
+
+    let rows_per_line = image.height / lines;
+    let row = image.width * 4;
+    let keep = 1.0 - intensity.clamp(0.0, 1.0);
+    for line in 0..lines {
+        let start = ((line + 1) * rows_per_line - 1) * row;
+        for pixel in image.rgba[start..start + row].chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as f32 * keep).round() as u8;
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanlines() {
+        let image = Image::new(2, 240, vec![200; 2 * 240 * 4]);
+        let settings = DisplaySettings {
+            scanlines: 0.5,
+            ..Default::default()
+        };
+        let out = settings.process(image.clone(), false);
+        assert_eq!((out.width, out.height), (2, 480));
+        assert_eq!(&out.rgba[..4], &[200, 200, 200, 200]);
+        assert_eq!(&out.rgba[8..12], &[100, 100, 100, 200]);
+
+        let settings = DisplaySettings {
+            scaler: Scaler::Scale3x,
+            ..settings
+        };
+        let out = settings.process(image, false);
+        assert_eq!((out.width, out.height), (6, 720));
+        let row = 6 * 4;
+        assert_eq!(out.rgba[row], 200);
+        assert_eq!(out.rgba[2 * row], 100);
+    }
+
+    #[test]
+    fn test_fit() {
+        let settings = DisplaySettings::default();
+        let (width, height) = settings.fit((1000.0, 500.0));
+        assert!((height - 500.0).abs() < 0.01);
+        assert!((width - 256.0 * 500.0 / 240.0).abs() < 0.01);
+
+        let settings = DisplaySettings {
+            integer_scaling: true,
+            aspect: PixelAspect::Ntsc,
+            ..Default::default()
+        };
+        let (width, height) = settings.fit((2000.0, 500.0));
+        assert_eq!(height, 480.0);
+        assert!((width - 2.0 * 256.0 * 8.0 / 7.0).abs() < 0.01);
+
+        // Cropped lines make room for a bigger picture
+        let settings = DisplaySettings {
+            overscan: Overscan {
+                top: 8,
+                bottom: 8,
+                ..Default::default()
+            },
+            ..settings
+        };
+        let (_, height) = settings.fit((2000.0, 700.0));
+        assert_eq!(height, 672.0);
+    }
+
+    #[test]
+    fn test_overscan_and_left_column() {
+        let pixels: Vec<u32> = (0..256 * 240).map(|i| 0xFF00_0000 | i).collect();
+        let image = Image::from_pixels(256, 240, &pixels);
+        let settings = DisplaySettings {
+            overscan: Overscan {
+                top: 8,
+                bottom: 16,
+                left: 4,
+                right: 12,
+            },
+            hide_left_column: true,
+            ..Default::default()
+        };
+        let out = settings.process(image, false);
+        assert_eq!((out.width, out.height), (240, 216));
+        let out = out.pixels();
+        // Columns 4 to 7 are blanked, then the picture carries on
+        assert_eq!(out[3], 0xFF00_0000);
+        assert_eq!(out[4], 0xFF00_0000 | (8 * 256 + 8));
+        assert_eq!(out[216 * 240 - 1], 0xFF00_0000 | (223 * 256 + 243));
+
+        // The NTSC filter's wider pixels are cropped in proportion
+        let image = Image::new(512, 240, vec![255; 512 * 240 * 4]);
+        let out = settings.process(image, true);
+        assert_eq!((out.width, out.height), (480, 216));
+        assert_eq!(&out.rgba[4 * 7..4 * 9], &[0, 0, 0, 255, 255, 255, 255, 255]);
+
+        let window = DisplaySettings {
+            window_scale: 2,
+            ..settings
+        };
+        assert_eq!(window.window_size(), (480.0, 432.0));
+        assert_eq!(DisplaySettings::default().window_size(), (768.0, 720.0));
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let settings = DisplaySettings {
+            scaler: Scaler::Xbrz3x,
+            integer_scaling: true,
+            scanlines: 0.25,
+            aspect: PixelAspect::Ntsc,
+            overscan: Overscan {
+                top: 8,
+                bottom: 8,
+                left: 0,
+                right: 2,
+            },
+            hide_left_column: true,
+            window_scale: 4,
+        };
+        assert_eq!(
+            DisplaySettings::from_config(&settings.to_config()),
+            settings
+        );
+        assert_eq!(
+            DisplaySettings::from_config(
+                "scanlines = 3\nscaler = nope\noverscan_top = 200\nwindow_scale = 0"
+            ),
+            DisplaySettings::default()
+        );
+        assert_eq!(
+            DisplaySettings::from_config("scaler = HQ2x").scaler,
+            Scaler::Hq2x
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Turning the PPU's 9-bit pixels into something to show.
+//!
+//! The core only produces palette indices with emphasis bits; which colors
+//! those become is up to the frontend
+
+pub mod display;
+pub mod ntsc;
+pub mod palette;
+pub mod scale;
+mod xbrz;
+
+/// An RGBA picture on its way to the screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, rgba: Vec<u8>) -> Self {
+        debug_assert_eq!(rgba.len(), width * height * 4);
+        Self {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// One `u32` per pixel, so pixels can be compared and copied whole
+    fn pixels(&self) -> Vec<u32> {
+        self.rgba
+            .chunks_exact(4)
+            .map(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+            .collect()
+    }
+
+    fn from_pixels(width: usize, height: usize, pixels: &[u32]) -> Self {
+        let rgba = pixels.iter().flat_map(|p| p.to_le_bytes()).collect();
+        Self::new(width, height, rgba)
+    }
+
+    /// Encode as a PNG file
+    pub fn to_png(&self) -> anyhow::Result<Vec<u8>> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        writer.finish()?;
+        Ok(png)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_round_trip() {
+        let rgba: Vec<u8> = (0..3 * 2 * 4).map(|i| i as u8 * 10).collect();
+        let png = Image::new(3, 2, rgba.clone()).to_png().unwrap();
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(png))
+            .read_info()
+            .unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (3, 2));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(decoded, rgba);
+    }
+}
@@ -0,0 +1,445 @@
+// See: https://www.nesdev.org/wiki/NTSC_video
+//      http://blargg.8bitalley.com/libs/ntsc.html
+
+use crate::video::palette::{NtscPaletteParams, Palette};
+
+/// Width of a filtered line, the same as nes_ntsc gives for 256 pixels
+pub const NTSC_WIDTH: usize = 602;
+const WIDTH: usize = 256;
+const HEIGHT: usize = 240;
+
+/// The PPU puts out 8 signal samples per pixel, 12 to a color cycle
+const SAMPLES_PER_PIXEL: usize = 8;
+const LINE_SAMPLES: usize = WIDTH * SAMPLES_PER_PIXEL;
+/// Black on either side of a line, so no filter window runs off the end
+const PADDING: usize = 24;
+
+/// Signal voltages for luma 0-3: the low level of the square wave, then the
+/// high level. Colors $xD-$xF are the low level throughout
+const LOW_LEVELS: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const HIGH_LEVELS: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK: f32 = LOW_LEVELS[1];
+const WHITE: f32 = HIGH_LEVELS[3];
+/// Emphasis pulls the signal down over a third of the color cycle
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+
+/// What the PPU outputs for `pixel` at subcarrier `phase` (0-11), scaled so
+/// black is 0.0 and white is 1.0
+pub(crate) fn signal_level(pixel: u16, phase: usize) -> f32 {
+    let color = (pixel & 0x0F) as usize;
+    let luma = match color {
+        0x0E | 0x0F => 1,
+        _ => ((pixel >> 4) & 3) as usize,
+    };
+    let in_phase = |hue: usize| (hue + phase) % 12 < 6;
+
+    let mut signal = match color {
+        0x00 => HIGH_LEVELS[luma],
+        0x0D..=0x0F => LOW_LEVELS[luma],
+        _ if in_phase(color) => HIGH_LEVELS[luma],
+        _ => LOW_LEVELS[luma],
+    };
+    // Red, green and blue emphasis line up with hues 0, 4 and 8
+    let emphasis = pixel >> 6;
+    if (0..3).any(|bit| emphasis & (1 << bit) != 0 && in_phase(bit * 4)) {
+        signal *= EMPHASIS_ATTENUATION;
+    }
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+/// The kinds of video cable a TV could be connected with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NtscPreset {
+    /// Luma and chroma share a wire, so each bleeds into the other
+    #[default]
+    Composite,
+    /// Separate luma and chroma: sharp, no artifacts, some color blur
+    SVideo,
+    /// Straight palette colors, only stretched to the filter's width
+    Rgb,
+    /// Composite into a black and white set
+    Monochrome,
+}
+
+impl NtscPreset {
+    pub const ALL: [NtscPreset; 4] = [
+        NtscPreset::Composite,
+        NtscPreset::SVideo,
+        NtscPreset::Rgb,
+        NtscPreset::Monochrome,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NtscPreset::Composite => "Composite",
+            NtscPreset::SVideo => "S-Video",
+            NtscPreset::Rgb => "RGB",
+            NtscPreset::Monochrome => "Monochrome",
+        }
+    }
+
+    /// How the TV decodes the signal; `None` when there's nothing to decode
+    fn decoder(&self) -> Option<Decoder> {
+        match self {
+            NtscPreset::Composite => Some(Decoder {
+                luma_width: 10,
+                chroma_width: 24,
+                separate: false,
+                color: true,
+            }),
+            NtscPreset::SVideo => Some(Decoder {
+                luma_width: 4,
+                chroma_width: 12,
+                separate: true,
+                color: true,
+            }),
+            NtscPreset::Rgb => None,
+            NtscPreset::Monochrome => Some(Decoder {
+                luma_width: 10,
+                chroma_width: 12,
+                separate: false,
+                color: false,
+            }),
+        }
+    }
+}
+
+/// Box filter widths, in samples, that the TV averages the signal over.
+/// Luma narrower than a color cycle lets chroma through as artifacts and dot
+/// crawl; wider chroma blurs colors into their neighbors
+#[derive(Debug, Copy, Clone)]
+struct Decoder {
+    luma_width: usize,
+    chroma_width: usize,
+    /// Luma and chroma arrive on their own wires
+    separate: bool,
+    color: bool,
+}
+
+/// Steps in the gamma lookup table
+const GAMMA_STEPS: usize = 1024;
+
+/// Turns 9-bit PPU pixels into the picture an NTSC TV would show, with the
+/// same picture controls as `Palette::generate()`
+pub struct NtscFilter {
+    preset: NtscPreset,
+    params: NtscPaletteParams,
+    /// Signal level of every pixel value at every phase
+    levels: Box<[[f32; 12]; 512]>,
+    /// Each pixel value's average level over a cycle, what an S-Video luma
+    /// wire carries
+    luma: Box<[f32; 512]>,
+    /// Demodulation carrier for each phase, hue knob included
+    carrier: [(f32, f32); 12],
+    gamma: Box<[u8; GAMMA_STEPS]>,
+    /// Colors for the RGB preset
+    palette: Palette,
+}
+
+impl NtscFilter {
+    pub fn new(preset: NtscPreset, params: &NtscPaletteParams, palette: &Palette) -> Self {
+        let mut levels = Box::new([[0.0; 12]; 512]);
+        let mut luma = Box::new([0.0; 512]);
+        for (pixel, (levels, luma)) in levels.iter_mut().zip(luma.iter_mut()).enumerate() {
+            for (phase, level) in levels.iter_mut().enumerate() {
+                *level = signal_level(pixel as u16, phase);
+            }
+            *luma = levels.iter().sum::<f32>() / 12.0;
+        }
+
+        let mut carrier = [(0.0, 0.0); 12];
+        for (phase, carrier) in carrier.iter_mut().enumerate() {
+            let angle = params.demod_angle(phase);
+            *carrier = (angle.cos(), angle.sin());
+        }
+
+        let mut gamma = Box::new([0; GAMMA_STEPS]);
+        for (step, value) in gamma.iter_mut().enumerate() {
+            *value = params.channel(step as f32 / (GAMMA_STEPS - 1) as f32);
+        }
+
+        Self {
+            preset,
+            params: *params,
+            levels,
+            luma,
+            carrier,
+            gamma,
+            palette: palette.clone(),
+        }
+    }
+
+    pub fn preset(&self) -> NtscPreset {
+        self.preset
+    }
+
+    /// Filter a 256x240 frame into `NTSC_WIDTH`x240 RGBA. `phases` is the
+    /// subcarrier phase at the start of each line, which moves from line to
+    /// line and frame to frame
+    pub fn render(&self, pixels: &[u16], phases: &[u8]) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(NTSC_WIDTH * HEIGHT * 4);
+        let lines = pixels.chunks_exact(WIDTH).zip(phases).take(HEIGHT);
+        match self.preset.decoder() {
+            Some(decoder) => {
+                let mut sums = vec![[0.0; 3]; LINE_SAMPLES + 2 * PADDING + 1];
+                let windows = Windows::new(&decoder);
+                for (line, &phase) in lines {
+                    self.integrate(line, phase as usize, &decoder, &mut sums);
+                    self.decode_line(&sums, &windows, &mut rgba);
+                }
+            }
+            None => {
+                for (line, _) in lines {
+                    for x in 0..NTSC_WIDTH {
+                        let (r, g, b) = self.palette.rgb(line[x * WIDTH / NTSC_WIDTH]);
+                        rgba.extend_from_slice(&[r, g, b, 255]);
+                    }
+                }
+            }
+        }
+        rgba
+    }
+
+    /// Running sums of the line's luma, and its chroma against both carriers,
+    /// so any window's average is a subtraction
+    fn integrate(&self, line: &[u16], phase: usize, decoder: &Decoder, sums: &mut [[f32; 3]]) {
+        sums.fill([0.0; 3]);
+        let mut total = [0.0; 3];
+        for (x, &pixel) in line.iter().enumerate() {
+            let pixel = (pixel & 0x1FF) as usize;
+            let luma = self.luma[pixel];
+            for sample in 0..SAMPLES_PER_PIXEL {
+                let index = x * SAMPLES_PER_PIXEL + sample;
+                let phase = (phase + index) % 12;
+                let level = self.levels[pixel][phase];
+                let (y, c) = match decoder.separate {
+                    true => (luma, level - luma),
+                    false => (level, level),
+                };
+                let (cos, sin) = self.carrier[phase];
+                total[0] += y;
+                total[1] += c * cos;
+                total[2] += c * sin;
+                sums[PADDING + index + 1] = total;
+            }
+        }
+        // Black padding adds nothing
+        for sum in &mut sums[PADDING + LINE_SAMPLES + 1..] {
+            *sum = total;
+        }
+    }
+
+    fn decode_line(&self, sums: &[[f32; 3]], windows: &Windows, rgba: &mut Vec<u8>) {
+        let average = |start: usize, width: usize, channel: usize| {
+            (sums[start + width][channel] - sums[start][channel]) / width as f32
+        };
+        for x in 0..NTSC_WIDTH {
+            let y = average(windows.luma[x], windows.luma_width, 0);
+            let (i, q) = match windows.color {
+                true => {
+                    let start = windows.chroma[x];
+                    (
+                        average(start, windows.chroma_width, 1),
+                        average(start, windows.chroma_width, 2),
+                    )
+                }
+                false => (0.0, 0.0),
+            };
+            let [r, g, b] = self.params.yiq_to_rgb(y, i, q).map(|value| {
+                let step = (value.clamp(0.0, 1.0) * (GAMMA_STEPS - 1) as f32).round();
+                self.gamma[step as usize]
+            });
+            rgba.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+}
+
+/// Where each output pixel's filter windows start in a line's running sums
+struct Windows {
+    luma: Vec<usize>,
+    chroma: Vec<usize>,
+    luma_width: usize,
+    chroma_width: usize,
+    color: bool,
+}
+
+impl Windows {
+    fn new(decoder: &Decoder) -> Self {
+        let start = |x: usize, width: usize| {
+            let center =
+                PADDING as f32 + (x as f32 + 0.5) * LINE_SAMPLES as f32 / NTSC_WIDTH as f32;
+            (center - width as f32 / 2.0).round() as usize
+        };
+        Self {
+            luma: (0..NTSC_WIDTH)
+                .map(|x| start(x, decoder.luma_width))
+                .collect(),
+            chroma: (0..NTSC_WIDTH)
+                .map(|x| start(x, decoder.chroma_width))
+                .collect(),
+            luma_width: decoder.luma_width,
+            chroma_width: decoder.chroma_width,
+            color: decoder.color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Color bars over the top half, then vertical stripes that composite
+    /// turns into artifact colors, then a grey ramp
+    fn test_frame() -> ([u16; WIDTH * HEIGHT], [u8; HEIGHT]) {
+        let mut pixels = [0x0F; WIDTH * HEIGHT];
+        for (y, line) in pixels.chunks_exact_mut(WIDTH).enumerate() {
+            for (x, pixel) in line.iter_mut().enumerate() {
+                *pixel = match y {
+                    0..120 => 0x20 | (x / 16) as u16,
+                    120..180 if x % 2 == 0 => 0x30,
+                    120..180 => 0x0F,
+                    _ => [0x0F, 0x00, 0x10, 0x30][x / 64] | ((y as u16 / 20 % 8) << 6),
+                };
+            }
+        }
+        let mut phases = [0; HEIGHT];
+        for (line, phase) in phases.iter_mut().enumerate() {
+            *phase = (line * 4 % 12) as u8;
+        }
+        (pixels, phases)
+    }
+
+    fn render(preset: NtscPreset) -> Vec<u8> {
+        let params = NtscPaletteParams::default();
+        let filter = NtscFilter::new(preset, &params, &Palette::generate(&params));
+        let (pixels, phases) = test_frame();
+        filter.render(&pixels, &phases)
+    }
+
+    fn rgb(rgba: &[u8], x: usize, y: usize) -> (u8, u8, u8) {
+        let i = (y * NTSC_WIDTH + x) * 4;
+        (rgba[i], rgba[i + 1], rgba[i + 2])
+    }
+
+    /// Reference pixels from each preset's output: the color bars, the
+    /// artifact stripes, an emphasized shade and the grey ramp. Float math
+    /// rounds a little differently across platforms, so each channel gets
+    /// some slack
+    #[test]
+    fn test_golden_images() {
+        const POINTS: [(usize, usize); 8] = [
+            (40, 10),
+            (300, 60),
+            (520, 110),
+            (150, 130),
+            (451, 170),
+            (200, 190),
+            (300, 215),
+            (525, 235),
+        ];
+        let golden = [
+            (
+                NtscPreset::Composite,
+                [
+                    (116, 148, 245),
+                    (192, 173, 65),
+                    (79, 79, 79),
+                    (63, 152, 172),
+                    (182, 85, 206),
+                    (89, 66, 61),
+                    (70, 106, 61),
+                    (187, 189, 156),
+                ],
+            ),
+            (
+                NtscPreset::SVideo,
+                [
+                    (126, 170, 255),
+                    (192, 173, 65),
+                    (79, 79, 79),
+                    (128, 128, 128),
+                    (128, 128, 128),
+                    (94, 71, 66),
+                    (62, 89, 55),
+                    (184, 186, 153),
+                ],
+            ),
+            (
+                NtscPreset::Rgb,
+                [
+                    (126, 170, 255),
+                    (207, 163, 77),
+                    (78, 78, 78),
+                    (0, 0, 0),
+                    (0, 0, 0),
+                    (94, 71, 66),
+                    (63, 88, 57),
+                    (184, 186, 153),
+                ],
+            ),
+            (
+                NtscPreset::Monochrome,
+                [
+                    (149, 149, 149),
+                    (167, 167, 167),
+                    (79, 79, 79),
+                    (128, 128, 128),
+                    (128, 128, 128),
+                    (72, 72, 72),
+                    (90, 90, 90),
+                    (185, 185, 185),
+                ],
+            ),
+        ];
+        for (preset, expected) in golden {
+            let rgba = render(preset);
+            assert_eq!(rgba.len(), NTSC_WIDTH * HEIGHT * 4);
+            for (&(x, y), &want) in POINTS.iter().zip(&expected) {
+                let got = rgb(&rgba, x, y);
+                let close = got.0.abs_diff(want.0) <= 2
+                    && got.1.abs_diff(want.1) <= 2
+                    && got.2.abs_diff(want.2) <= 2;
+                assert!(
+                    close,
+                    "{} output changed at ({x}, {y}): {got:?}, expected {want:?}",
+                    preset.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_flat_colors_match_the_palette() {
+        let palette = Palette::generate(&NtscPaletteParams::default());
+        let close = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+            a.0.abs_diff(b.0) <= 3 && a.1.abs_diff(b.1) <= 3 && a.2.abs_diff(b.2) <= 3
+        };
+        for preset in [NtscPreset::SVideo, NtscPreset::Rgb] {
+            let rgba = render(preset);
+            // Middle of the $26 bar, away from its edges
+            let x = (6 * 16 + 8) * NTSC_WIDTH / WIDTH;
+            assert!(close(rgb(&rgba, x, 10), palette.rgb(0x26)), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn test_artifacts_and_dot_crawl() {
+        // Composite turns the stripes into color, in a pattern that follows
+        // the line's phase
+        let composite = render(NtscPreset::Composite);
+        let (r, g, b) = rgb(&composite, 300, 150);
+        assert!(r != g || g != b, "stripes should pick up color");
+        assert_ne!(rgb(&composite, 300, 150), rgb(&composite, 300, 151));
+
+        // S-Video keeps them grey
+        let svideo = render(NtscPreset::SVideo);
+        let (r, g, b) = rgb(&svideo, 300, 150);
+        assert!(r.abs_diff(g) <= 2 && g.abs_diff(b) <= 2);
+
+        let mono = render(NtscPreset::Monochrome);
+        for x in 0..NTSC_WIDTH {
+            let (r, g, b) = rgb(&mono, x, 10);
+            assert!(r == g && g == b);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+// See: https://www.nesdev.org/wiki/NTSC_video
+//      https://www.nesdev.org/wiki/.pal
+
+use crate::video::ntsc::signal_level;
+use anyhow::bail;
+use nes_core::prelude::{NES_SYSTEM_PALETTE, emphasised_palette};
+use std::f32::consts::PI;
+use std::fmt::Write;
+
+/// Colors for all 512 pixel values: 64 palette indices under each of the 8
+/// emphasis combinations
+#[derive(Clone, PartialEq)]
+pub struct Palette {
+    colors: Box<[(u8, u8, u8); 512]>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+impl Palette {
+    /// The built-in palette from the core
+    pub fn system() -> Self {
+        Self {
+            colors: Box::new(NES_SYSTEM_PALETTE),
+        }
+    }
+
+    /// Read a `.pal` file: 64 RGB triplets, or 512 with the emphasised
+    /// colors too. 64-color files get emphasis added the core's way
+    pub fn from_pal(bytes: &[u8]) -> anyhow::Result<Self> {
+        let rgb = |chunk: &[u8]| (chunk[0], chunk[1], chunk[2]);
+        let colors = match bytes.len() {
+            192 => {
+                let mut base = [(0, 0, 0); 64];
+                for (color, chunk) in base.iter_mut().zip(bytes.chunks_exact(3)) {
+                    *color = rgb(chunk);
+                }
+                emphasised_palette(&base)
+            }
+            1536 => {
+                let mut colors = [(0, 0, 0); 512];
+                for (color, chunk) in colors.iter_mut().zip(bytes.chunks_exact(3)) {
+                    *color = rgb(chunk);
+                }
+                colors
+            }
+            len => {
+                bail!("Palette files hold 64 or 512 colors (192 or 1536 bytes), not {len} bytes")
+            }
+        };
+        Ok(Self {
+            colors: Box::new(colors),
+        })
+    }
+
+    /// All 512 colors as a `.pal` file
+    pub fn to_pal(&self) -> Vec<u8> {
+        self.colors
+            .iter()
+            .flat_map(|&(r, g, b)| [r, g, b])
+            .collect()
+    }
+
+    /// Decode what an NTSC PPU puts on the wire, the way a TV would
+    pub fn generate(params: &NtscPaletteParams) -> Self {
+        let mut colors = [(0, 0, 0); 512];
+        for (pixel, color) in colors.iter_mut().enumerate() {
+            *color = params.decode(pixel as u16);
+        }
+        Self {
+            colors: Box::new(colors),
+        }
+    }
+
+    #[inline]
+    pub fn rgb(&self, pixel: u16) -> (u8, u8, u8) {
+        self.colors[(pixel & 0x1FF) as usize]
+    }
+
+    /// `frame` as RGBA bytes, row by row
+    pub fn to_rgba(&self, frame: &[u16]) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(frame.len() * 4);
+        for &pixel in frame {
+            let (r, g, b) = self.rgb(pixel);
+            rgba.extend_from_slice(&[r, g, b, 255]);
+        }
+        rgba
+    }
+}
+
+/// Where hue 0 lands on the decoder's color wheel, in 30° phase steps
+const BURST_PHASE: f32 = 3.9;
+
+/// Knobs for `Palette::generate()`, like the ones on a TV
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NtscPaletteParams {
+    /// Hue rotation in degrees
+    pub hue: f32,
+    /// 1.0 is the signal as-is, 0.0 is greyscale
+    pub saturation: f32,
+    pub contrast: f32,
+    /// Added to the brightness of every color, -1.0 to 1.0
+    pub brightness: f32,
+    /// Display gamma the colors are corrected for. 2.2 leaves them as they
+    /// come out of the decoder
+    pub gamma: f32,
+}
+
+impl Default for NtscPaletteParams {
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+            gamma: 2.2,
+        }
+    }
+}
+
+impl NtscPaletteParams {
+    /// Sample one color cycle of `pixel`'s signal at 12 phases and turn it
+    /// into YIQ, then RGB
+    fn decode(&self, pixel: u16) -> (u8, u8, u8) {
+        let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+        for phase in 0..12 {
+            let level = signal_level(pixel, phase);
+            let angle = self.demod_angle(phase);
+            y += level;
+            i += level * angle.cos();
+            q += level * angle.sin();
+        }
+        let [r, g, b] = self
+            .yiq_to_rgb(y / 12.0, i / 12.0, q / 12.0)
+            .map(|v| self.channel(v));
+        (r, g, b)
+    }
+
+    /// Where the decoder's color wheel puts a signal sample at `phase`
+    pub(crate) fn demod_angle(&self, phase: usize) -> f32 {
+        PI * (phase as f32 + BURST_PHASE) / 6.0 + self.hue.to_radians()
+    }
+
+    /// Apply the picture controls to what the decoder got, giving linear RGB
+    /// that `channel()` still has to clamp
+    pub(crate) fn yiq_to_rgb(&self, y: f32, i: f32, q: f32) -> [f32; 3] {
+        let y = y * self.contrast + self.brightness;
+        let i = i * self.saturation * self.contrast;
+        let q = q * self.saturation * self.contrast;
+        [
+            y + 0.946882 * i + 0.623557 * q,
+            y - 0.274788 * i - 0.635691 * q,
+            y - 1.108545 * i + 1.709007 * q,
+        ]
+    }
+
+    /// One gamma-corrected 8-bit channel
+    pub(crate) fn channel(&self, value: f32) -> u8 {
+        let corrected = value.clamp(0.0, 1.0).powf(2.2 / self.gamma);
+        (corrected * 255.0).round() as u8
+    }
+}
+
+/// Where the frontend gets its colors from
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PaletteSource {
+    #[default]
+    System,
+    Ntsc,
+    File,
+}
+
+impl PaletteSource {
+    pub const ALL: [PaletteSource; 3] = [
+        PaletteSource::System,
+        PaletteSource::Ntsc,
+        PaletteSource::File,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteSource::System => "Built-in",
+            PaletteSource::Ntsc => "NTSC generator",
+            PaletteSource::File => "File",
+        }
+    }
+}
+
+/// A `.pal` file the user loaded
+#[derive(Clone, PartialEq)]
+pub struct PaletteFile {
+    pub name: String,
+    pub palette: Palette,
+}
+
+/// The palette the user picked, keeping the generator settings and loaded
+/// file around while another source is in use
+#[derive(Clone, PartialEq, Default)]
+pub struct PaletteSettings {
+    pub source: PaletteSource,
+    pub ntsc: NtscPaletteParams,
+    pub file: Option<PaletteFile>,
+}
+
+impl PaletteSettings {
+    /// The palette to draw with. Falls back to the built-in one if the file
+    /// source is picked without a file
+    pub fn palette(&self) -> Palette {
+        match (self.source, &self.file) {
+            (PaletteSource::System, _) | (PaletteSource::File, None) => Palette::system(),
+            (PaletteSource::Ntsc, _) => Palette::generate(&self.ntsc),
+            (PaletteSource::File, Some(file)) => file.palette.clone(),
+        }
+    }
+
+    /// One `name = value` line per setting, with a loaded file's colors in hex
+    pub fn to_config(&self) -> String {
+        let ntsc = &self.ntsc;
+        let mut out = String::new();
+        let _ = writeln!(out, "source = {}", self.source.name());
+        let _ = writeln!(out, "hue = {}", ntsc.hue);
+        let _ = writeln!(out, "saturation = {}", ntsc.saturation);
+        let _ = writeln!(out, "contrast = {}", ntsc.contrast);
+        let _ = writeln!(out, "brightness = {}", ntsc.brightness);
+        let _ = writeln!(out, "gamma = {}", ntsc.gamma);
+        if let Some(file) = &self.file {
+            let hex: String = file
+                .palette
+                .to_pal()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect();
+            let _ = writeln!(out, "file = {}", file.name);
+            let _ = writeln!(out, "colors = {hex}");
+        }
+        out
+    }
+
+    /// Read settings written by `to_config()`. Anything missing or invalid
+    /// keeps its default
+    pub fn from_config(text: &str) -> Self {
+        let mut settings = Self::default();
+        let mut file_name = None;
+        let mut colors = None;
+        for line in text.lines() {
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let ntsc = &mut settings.ntsc;
+            let number = value.parse::<f32>().ok().filter(|v| v.is_finite());
+            match (name.trim(), number) {
+                ("source", _) => {
+                    if let Some(source) = PaletteSource::ALL.into_iter().find(|s| s.name() == value)
+                    {
+                        settings.source = source;
+                    }
+                }
+                ("hue", Some(v)) => ntsc.hue = v,
+                ("saturation", Some(v)) => ntsc.saturation = v,
+                ("contrast", Some(v)) => ntsc.contrast = v,
+                ("brightness", Some(v)) => ntsc.brightness = v,
+                ("gamma", Some(v)) if v > 0.0 => ntsc.gamma = v,
+                ("file", _) => file_name = Some(value.to_string()),
+                ("colors", _) => colors = parse_hex(value),
+                _ => {}
+            }
+        }
+        if let (Some(name), Some(colors)) = (file_name, colors)
+            && let Ok(palette) = Palette::from_pal(&colors)
+        {
+            settings.file = Some(PaletteFile { name, palette });
+        }
+        settings
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pal_files() {
+        let mut bytes = vec![0u8; 192];
+        bytes[0x16 * 3..0x16 * 3 + 3].copy_from_slice(&[200, 10, 20]);
+        let palette = Palette::from_pal(&bytes).unwrap();
+        assert_eq!(palette.rgb(0x16), (200, 10, 20));
+        // Red emphasis dims green and blue
+        let (r, g, b) = palette.rgb(0b001 << 6 | 0x16);
+        assert!(r == 200 && g < 10 && b < 20);
+
+        let full = Palette::from_pal(&palette.to_pal()).unwrap();
+        assert!(full == palette);
+        assert!(Palette::from_pal(&[0; 100]).is_err());
+    }
+
+    #[test]
+    fn test_ntsc_generator() {
+        let palette = Palette::generate(&NtscPaletteParams::default());
+        let (r, g, b) = palette.rgb(0x16);
+        assert!(r > g && r > b, "$16 should be red");
+        let (r, g, b) = palette.rgb(0x12);
+        assert!(b > r && b > g, "$12 should be blue");
+        let (r, g, b) = palette.rgb(0x1A);
+        assert!(g > r && g > b, "$1A should be green");
+        assert_eq!(palette.rgb(0x0F), (0, 0, 0));
+        assert_eq!(palette.rgb(0x30), (255, 255, 255));
+
+        // Blue emphasis darkens white except where blue is
+        let (r, g, b) = palette.rgb(0b100 << 6 | 0x30);
+        assert!(r < 255 && g < 255 && b > r);
+
+        let grey = Palette::generate(&NtscPaletteParams {
+            saturation: 0.0,
+            ..Default::default()
+        });
+        let (r, g, b) = grey.rgb(0x16);
+        assert!(r == g && g == b);
+    }
+
+    #[test]
+    fn test_settings_config_round_trip() {
+        let mut bytes = vec![0u8; 1536];
+        bytes[3..6].copy_from_slice(&[1, 2, 3]);
+        let settings = PaletteSettings {
+            source: PaletteSource::File,
+            ntsc: NtscPaletteParams {
+                hue: -15.0,
+                gamma: 1.8,
+                ..Default::default()
+            },
+            file: Some(PaletteFile {
+                name: "custom.pal".into(),
+                palette: Palette::from_pal(&bytes).unwrap(),
+            }),
+        };
+        let restored = PaletteSettings::from_config(&settings.to_config());
+        assert!(restored == settings);
+        assert_eq!(restored.palette().rgb(1), (1, 2, 3));
+
+        let restored = PaletteSettings::from_config("source = NTSC generator\ngamma = -1\nhue = x");
+        assert_eq!(restored.source, PaletteSource::Ntsc);
+        assert_eq!(restored.ntsc, NtscPaletteParams::default());
+    }
+}
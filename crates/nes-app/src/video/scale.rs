@@ -0,0 +1,455 @@
+// See: https://www.scale2x.it/algorithm
+//      https://en.wikipedia.org/wiki/Pixel-art_scaling_algorithms
+//      https://en.wikipedia.org/wiki/Hqx
+
+use crate::video::{Image, xbrz};
+
+/// Pixel-art upscalers, run on the CPU so every platform looks the same
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Scaler {
+    /// Pixels as they are, left to the display's nearest-neighbor stretch
+    #[default]
+    Nearest,
+    Scale2x,
+    Scale3x,
+    Hq2x,
+    Xbrz2x,
+    Xbrz3x,
+}
+
+impl Scaler {
+    pub const ALL: [Scaler; 6] = [
+        Scaler::Nearest,
+        Scaler::Scale2x,
+        Scaler::Scale3x,
+        Scaler::Hq2x,
+        Scaler::Xbrz2x,
+        Scaler::Xbrz3x,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scaler::Nearest => "Nearest",
+            Scaler::Scale2x => "Scale2x",
+            Scaler::Scale3x => "Scale3x",
+            Scaler::Hq2x => "HQ2x",
+            Scaler::Xbrz2x => "xBRZ 2x",
+            Scaler::Xbrz3x => "xBRZ 3x",
+        }
+    }
+
+    /// The scaler called `name`
+    pub fn from_name(name: &str) -> Option<Scaler> {
+        Scaler::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    pub fn factor(&self) -> usize {
+        match self {
+            Scaler::Nearest => 1,
+            Scaler::Scale2x | Scaler::Hq2x | Scaler::Xbrz2x => 2,
+            Scaler::Scale3x | Scaler::Xbrz3x => 3,
+        }
+    }
+
+    pub fn scale(&self, image: Image) -> Image {
+        let (width, height) = (image.width, image.height);
+        let src = image.pixels();
+        let scaled = match self {
+            Scaler::Nearest => return image,
+            Scaler::Scale2x => scale2x(width, height, &src),
+            Scaler::Scale3x => scale3x(width, height, &src),
+            Scaler::Hq2x => hq2x(width, height, &src),
+            Scaler::Xbrz2x => xbrz::scale(2, width, height, &src),
+            Scaler::Xbrz3x => xbrz::scale(3, width, height, &src),
+        };
+        let factor = self.factor();
+        Image::from_pixels(width * factor, height * factor, &scaled)
+    }
+}
+
+/// The 3x3 neighborhood of a pixel, repeating the edges outwards:
+///
+/// ```text
+/// a b c
+/// d e f
+/// g h i
+/// ```
+#[derive(Copy, Clone)]
+pub(crate) struct Kernel {
+    pub a: u32,
+    pub b: u32,
+    pub c: u32,
+    pub d: u32,
+    pub e: u32,
+    pub f: u32,
+    pub g: u32,
+    pub h: u32,
+    pub i: u32,
+}
+
+impl Kernel {
+    pub(crate) fn at(src: &[u32], width: usize, height: usize, x: usize, y: usize) -> Self {
+        let at = |dx: isize, dy: isize| {
+            let x = x.saturating_add_signed(dx).min(width - 1);
+            let y = y.saturating_add_signed(dy).min(height - 1);
+            src[y * width + x]
+        };
+        Self {
+            a: at(-1, -1),
+            b: at(0, -1),
+            c: at(1, -1),
+            d: at(-1, 0),
+            e: at(0, 0),
+            f: at(1, 0),
+            g: at(-1, 1),
+            h: at(0, 1),
+            i: at(1, 1),
+        }
+    }
+}
+
+/// Run `block` for every source pixel, and copy the `factor`x`factor` block of
+/// pixels it returns (row by row) into place
+fn scale_blocks<const N: usize>(
+    factor: usize,
+    width: usize,
+    height: usize,
+    src: &[u32],
+    block: impl Fn(Kernel) -> [u32; N],
+) -> Vec<u32> {
+    let out_width = width * factor;
+    let mut out = vec![0; out_width * height * factor];
+    for y in 0..height {
+        for x in 0..width {
+            let pixels = block(Kernel::at(src, width, height, x, y));
+            for (n, pixel) in pixels.into_iter().enumerate() {
+                let (row, col) = (n / factor, n % factor);
+                out[(y * factor + row) * out_width + x * factor + col] = pixel;
+            }
+        }
+    }
+    out
+}
+
+/// AdvanceMAME's Scale2x: each corner takes a neighbor's color where two
+/// neighbors meet there and the lines through the pixel differ
+fn scale2x(width: usize, height: usize, src: &[u32]) -> Vec<u32> {
+    scale_blocks(2, width, height, src, |k| {
+        if k.b == k.h || k.d == k.f {
+            return [k.e; 4];
+        }
+        [
+            if k.d == k.b { k.d } else { k.e },
+            if k.b == k.f { k.f } else { k.e },
+            if k.d == k.h { k.d } else { k.e },
+            if k.h == k.f { k.f } else { k.e },
+        ]
+    })
+}
+
+/// Scale2x's rules carried over to 3x3 blocks
+fn scale3x(width: usize, height: usize, src: &[u32]) -> Vec<u32> {
+    scale_blocks(3, width, height, src, |k| {
+        if k.b == k.h || k.d == k.f {
+            return [k.e; 9];
+        }
+        let pick = |take: bool, color: u32| if take { color } else { k.e };
+        [
+            pick(k.d == k.b, k.d),
+            pick(
+                (k.d == k.b && k.e != k.c) || (k.b == k.f && k.e != k.a),
+                k.b,
+            ),
+            pick(k.b == k.f, k.f),
+            pick(
+                (k.d == k.b && k.e != k.g) || (k.d == k.h && k.e != k.a),
+                k.d,
+            ),
+            k.e,
+            pick(
+                (k.b == k.f && k.e != k.i) || (k.h == k.f && k.e != k.c),
+                k.f,
+            ),
+            pick(k.d == k.h, k.d),
+            pick(
+                (k.d == k.h && k.e != k.i) || (k.h == k.f && k.e != k.g),
+                k.h,
+            ),
+            pick(k.h == k.f, k.f),
+        ]
+    })
+}
+
+/// Maxim Stepin's HQ2x. Neighbors that differ from the center past YUV
+/// thresholds make an 8-bit pattern, which picks how each of the four output
+/// pixels blends the center with the neighbors next to it
+fn hq2x(width: usize, height: usize, src: &[u32]) -> Vec<u32> {
+    scale_blocks(2, width, height, src, |k| {
+        // The original's 256 cases are symmetric, so each corner is worked
+        // out as the top-left one of the neighborhood mirrored to put it there
+        let mirror = |k: Kernel, horizontal: bool, vertical: bool| {
+            let k = match horizontal {
+                true => Kernel {
+                    a: k.c,
+                    c: k.a,
+                    d: k.f,
+                    f: k.d,
+                    g: k.i,
+                    i: k.g,
+                    ..k
+                },
+                false => k,
+            };
+            match vertical {
+                true => Kernel {
+                    a: k.g,
+                    b: k.h,
+                    c: k.i,
+                    g: k.a,
+                    h: k.b,
+                    i: k.c,
+                    ..k
+                },
+                false => k,
+            }
+        };
+        [
+            hq2x_top_left(k),
+            hq2x_top_left(mirror(k, true, false)),
+            hq2x_top_left(mirror(k, false, true)),
+            hq2x_top_left(mirror(k, true, true)),
+        ]
+    })
+}
+
+/// One of HQ2x's interpolations for the top-left output pixel, named after
+/// the original's `Interp` functions
+#[derive(Copy, Clone)]
+enum HqBlend {
+    /// The center pixel as it is
+    Center,
+    /// 3:1 with the corner (`Interp1`)
+    Corner,
+    /// 3:1 with the left neighbor (`Interp1`)
+    Left,
+    /// 3:1 with the top neighbor (`Interp1`)
+    Up,
+    /// 2:1:1 with the left and top neighbors (`Interp2`)
+    Sides,
+    /// 2:1:1 with the corner and top neighbor (`Interp2`)
+    CornerUp,
+    /// 2:1:1 with the corner and left neighbor (`Interp2`)
+    CornerLeft,
+    /// 5:2:1 with the top and left neighbors (`Interp6`)
+    MostlyUp,
+    /// 5:2:1 with the left and top neighbors (`Interp6`)
+    MostlyLeft,
+    /// 6:1:1 with the left and top neighbors (`Interp7`)
+    SidesLight,
+    /// 2:3:3 with the left and top neighbors (`Interp9`)
+    SidesHeavy,
+    /// 14:1:1 with the left and top neighbors (`Interp10`)
+    SidesFaint,
+}
+
+/// A pair of neighbors HQ2x compares before picking a blend
+#[derive(Copy, Clone)]
+enum HqEdge {
+    /// Left and top: whether an edge runs through the corner
+    LeftUp,
+    /// Top and right
+    UpRight,
+    /// Bottom and left
+    DownLeft,
+}
+
+/// How to blend the top-left output pixel: always the same way, or one way
+/// if the edge's neighbors differ and another if they don't
+#[derive(Copy, Clone)]
+enum HqRule {
+    Always(HqBlend),
+    IfDiffer(HqEdge, HqBlend, HqBlend),
+}
+
+fn hq2x_top_left(k: Kernel) -> u32 {
+    let pattern = [k.a, k.b, k.c, k.d, k.f, k.g, k.h, k.i]
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, pixel)| yuv_differ(k.e, pixel))
+        .fold(0u8, |pattern, (bit, _)| pattern | 1 << bit);
+
+    let blend = match hq2x_rule(pattern) {
+        HqRule::Always(blend) => blend,
+        HqRule::IfDiffer(edge, differ, same) => {
+            let (p, q) = match edge {
+                HqEdge::LeftUp => (k.d, k.b),
+                HqEdge::UpRight => (k.b, k.f),
+                HqEdge::DownLeft => (k.h, k.d),
+            };
+            match yuv_differ(p, q) {
+                true => differ,
+                false => same,
+            }
+        }
+    };
+
+    match blend {
+        HqBlend::Center => k.e,
+        HqBlend::Corner => interpolate(&[(k.e, 3), (k.a, 1)]),
+        HqBlend::Left => interpolate(&[(k.e, 3), (k.d, 1)]),
+        HqBlend::Up => interpolate(&[(k.e, 3), (k.b, 1)]),
+        HqBlend::Sides => interpolate(&[(k.e, 2), (k.d, 1), (k.b, 1)]),
+        HqBlend::CornerUp => interpolate(&[(k.e, 2), (k.a, 1), (k.b, 1)]),
+        HqBlend::CornerLeft => interpolate(&[(k.e, 2), (k.a, 1), (k.d, 1)]),
+        HqBlend::MostlyUp => interpolate(&[(k.e, 5), (k.b, 2), (k.d, 1)]),
+        HqBlend::MostlyLeft => interpolate(&[(k.e, 5), (k.d, 2), (k.b, 1)]),
+        HqBlend::SidesLight => interpolate(&[(k.e, 6), (k.d, 1), (k.b, 1)]),
+        HqBlend::SidesHeavy => interpolate(&[(k.e, 2), (k.d, 3), (k.b, 3)]),
+        HqBlend::SidesFaint => interpolate(&[(k.e, 14), (k.d, 1), (k.b, 1)]),
+    }
+}
+
+/// The top-left pixel's column of Stepin's case table. Pattern bits are set
+/// for neighbors that differ from the center, in `a b c d f g h i` order
+fn hq2x_rule(pattern: u8) -> HqRule {
+    use HqBlend::*;
+    use HqEdge::*;
+    use HqRule::*;
+
+    const CORNER: u8 = 1 << 0;
+    const UP: u8 = 1 << 1;
+    const LEFT: u8 = 1 << 3;
+
+    match (
+        pattern & UP != 0,
+        pattern & LEFT != 0,
+        pattern & CORNER != 0,
+    ) {
+        (false, false, _) => Always(Sides),
+        (true, false, false) => Always(CornerLeft),
+        (true, false, true) => match pattern {
+            19 | 23 | 51 | 55 | 119 => IfDiffer(UpRight, Left, MostlyUp),
+            _ => Always(Left),
+        },
+        (false, true, false) => Always(CornerUp),
+        (false, true, true) => match pattern {
+            73 | 77 | 105 | 109 | 125 => IfDiffer(DownLeft, Up, MostlyLeft),
+            _ => Always(Up),
+        },
+        // An edge may run through the corner
+        (true, true, _) => match pattern {
+            10 | 138 => IfDiffer(LeftUp, Corner, Sides),
+            11 | 26 | 27 | 31 | 59 | 74 | 75 | 79 | 91 | 95 | 107 | 123 | 139 | 155 | 159 | 203
+            | 219 | 223 | 235 | 251 => IfDiffer(LeftUp, Center, Sides),
+            14 | 42 | 142 | 170 => IfDiffer(LeftUp, Corner, SidesHeavy),
+            15 | 43 | 143 | 171 | 187 | 207 => IfDiffer(LeftUp, Center, SidesHeavy),
+            30 | 62 | 106 | 110 | 126 | 190 | 222 | 238 | 250 | 254 => Always(Corner),
+            46 | 58 | 78 | 90 | 94 | 122 | 154 | 158 | 174 | 186 | 202 | 206 | 218 | 234 => {
+                IfDiffer(LeftUp, Corner, SidesLight)
+            }
+            _ => IfDiffer(LeftUp, Center, SidesFaint),
+        },
+    }
+}
+
+/// Past these thresholds in Y, U or V, two colors count as different
+fn yuv_differ(a: u32, b: u32) -> bool {
+    if a == b {
+        return false;
+    }
+    let yuv = |p: u32| {
+        let [r, g, b, _] = p.to_le_bytes().map(f32::from);
+        (
+            (0.299 * r + 0.587 * g + 0.114 * b) as i32,
+            (-0.169 * r - 0.331 * g + 0.5 * b) as i32,
+            (0.5 * r - 0.419 * g - 0.081 * b) as i32,
+        )
+    };
+    let (y1, u1, v1) = yuv(a);
+    let (y2, u2, v2) = yuv(b);
+    (y1 - y2).abs() > 48 || (u1 - u2).abs() > 7 || (v1 - v2).abs() > 6
+}
+
+/// Weighted average of colors, per channel, rounding down like HQ2x does
+fn interpolate(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|&(_, weight)| weight).sum();
+    let mut channels = [0u32; 4];
+    for &(color, weight) in colors {
+        for (channel, byte) in channels.iter_mut().zip(color.to_le_bytes()) {
+            *channel += byte as u32 * weight;
+        }
+    }
+    u32::from_le_bytes(channels.map(|c| (c / total) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: u32 = 0xFF00_0000;
+    const WHITE: u32 = 0xFFFF_FFFF;
+
+    /// A white triangle under the diagonal of a black square
+    fn staircase(size: usize) -> Image {
+        let pixels: Vec<u32> = (0..size * size)
+            .map(|i| if i % size <= i / size { WHITE } else { BLACK })
+            .collect();
+        Image::from_pixels(size, size, &pixels)
+    }
+
+    #[test]
+    fn test_sizes_and_flat_images() {
+        let flat = Image::from_pixels(5, 4, &[0xFF12_3456; 20]);
+        for scaler in Scaler::ALL {
+            let scaled = scaler.scale(flat.clone());
+            let factor = scaler.factor();
+            assert_eq!((scaled.width, scaled.height), (5 * factor, 4 * factor));
+            assert!(
+                scaled.pixels().iter().all(|&p| p == 0xFF12_3456),
+                "{} changed a flat image",
+                scaler.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_scale2x_smooths_diagonals() {
+        let scaled = Scaler::Scale2x.scale(staircase(4));
+        let pixels = scaled.pixels();
+        let at = |x: usize, y: usize| pixels[y * 8 + x];
+        // The black pixel right of the diagonal gets its lower-left corner
+        // filled in white
+        assert_eq!(at(2, 0), BLACK);
+        assert_eq!(at(2, 1), WHITE);
+        assert_eq!(at(3, 1), BLACK);
+
+        let pixels = Scaler::Scale3x.scale(staircase(4)).pixels();
+        assert_eq!(pixels[3], BLACK);
+        assert_eq!(pixels[2 * 12 + 3], WHITE);
+    }
+
+    #[test]
+    fn test_hq2x_known_blocks() {
+        // A lone dot differs from all its neighbors (case 255), which
+        // darkens it 14:1:1 towards them
+        let mut dot = vec![BLACK; 9];
+        dot[4] = WHITE;
+        let pixels = Scaler::Hq2x.scale(Image::from_pixels(3, 3, &dot)).pixels();
+        let at = |x: usize, y: usize| pixels[y * 6 + x];
+        assert_eq!([at(2, 2), at(3, 2), at(2, 3), at(3, 3)], [0xFFDF_DFDF; 4]);
+        assert_eq!(at(1, 2), BLACK);
+
+        let pixels = Scaler::Hq2x.scale(staircase(4)).pixels();
+        let block = |x: usize, y: usize| {
+            let at = |dx: usize, dy: usize| pixels[(y * 2 + dy) * 8 + x * 2 + dx];
+            [at(0, 0), at(1, 0), at(0, 1), at(1, 1)]
+        };
+        // The black pixel right of the top of the diagonal (case 105):
+        // Interp6 and Interp9 on its left, untouched on its right
+        assert_eq!(block(1, 0), [0xFF3F_3F3F, BLACK, 0xFFBF_BFBF, BLACK]);
+        // Further down the diagonal (case 104): Interp2 at its lower left
+        assert_eq!(block(2, 1), [BLACK, BLACK, 0xFF7F_7F7F, BLACK]);
+        // White on the diagonal (case 22) has its top-right corner rounded off
+        assert_eq!(block(1, 1), [WHITE, 0xFF7F_7F7F, WHITE, WHITE]);
+    }
+}
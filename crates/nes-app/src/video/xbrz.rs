@@ -0,0 +1,307 @@
+// Zenju's xBRZ, for 2x and 3x.
+// See: https://sourceforge.net/projects/xbrz/
+
+use crate::video::scale::Kernel;
+
+const LUMINANCE_WEIGHT: f64 = 1.0;
+const EQUAL_COLOR_TOLERANCE: f64 = 30.0;
+const CENTER_DIRECTION_BIAS: f64 = 4.0;
+const DOMINANT_DIRECTION_THRESHOLD: f64 = 3.6;
+const STEEP_DIRECTION_THRESHOLD: f64 = 2.2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+enum Blend {
+    #[default]
+    None,
+    Normal,
+    Dominant,
+}
+
+/// How each corner of a pixel blends: top-left, top-right, bottom-right,
+/// bottom-left
+type Corners = [Blend; 4];
+const TOP_RIGHT: usize = 1;
+const BOTTOM_RIGHT: usize = 2;
+const BOTTOM_LEFT: usize = 3;
+
+pub(crate) fn scale(factor: usize, width: usize, height: usize, src: &[u32]) -> Vec<u32> {
+    let corners = corner_blends(width, height, src);
+
+    let out_width = width * factor;
+    let mut out = vec![0; out_width * height * factor];
+    let mut block = vec![0; factor * factor];
+    for y in 0..height {
+        for x in 0..width {
+            let kernel = Kernel::at(src, width, height, x, y);
+            block.fill(kernel.e);
+            let mut kernel = kernel;
+            let mut blend = corners[y * width + x];
+            // Blend each corner as if it were the bottom-right one
+            for rotation in 0..4 {
+                blend_corner(factor, rotation, &kernel, &blend, &mut block);
+                kernel = rotate_kernel(&kernel);
+                blend.rotate_right(1);
+            }
+            for row in 0..factor {
+                let start = (y * factor + row) * out_width + x * factor;
+                out[start..start + factor].copy_from_slice(&block[row * factor..][..factor]);
+            }
+        }
+    }
+    out
+}
+
+/// Decide, for every 2x2 block of pixels, which of them get the corner
+/// where all four meet blended
+///
+/// ```text
+/// - b c -
+/// e f g h     f, g, j and k are the block
+/// i j k l
+/// - n o -
+/// ```
+fn corner_blends(width: usize, height: usize, src: &[u32]) -> Vec<Corners> {
+    let mut corners = vec![Corners::default(); width * height];
+    let at = |x: usize, y: usize| src[y.min(height - 1) * width + x.min(width - 1)];
+    for y in 0..height {
+        for x in 0..width {
+            let (left, up) = (x.saturating_sub(1), y.saturating_sub(1));
+            let (b, c) = (at(x, up), at(x + 1, up));
+            let (e, f, g, h) = (at(left, y), at(x, y), at(x + 1, y), at(x + 2, y));
+            let (i, j, k, l) = (
+                at(left, y + 1),
+                at(x, y + 1),
+                at(x + 1, y + 1),
+                at(x + 2, y + 1),
+            );
+            let (n, o) = (at(x, y + 2), at(x + 1, y + 2));
+
+            if (eq(f, g) && eq(j, k)) || (eq(f, j) && eq(g, k)) {
+                continue;
+            }
+            let jg = dist(i, f)
+                + dist(f, c)
+                + dist(n, k)
+                + dist(k, h)
+                + CENTER_DIRECTION_BIAS * dist(j, g);
+            let fk = dist(e, j)
+                + dist(j, o)
+                + dist(b, g)
+                + dist(g, l)
+                + CENTER_DIRECTION_BIAS * dist(f, k);
+
+            let strength = |dominant: bool| match dominant {
+                true => Blend::Dominant,
+                false => Blend::Normal,
+            };
+            let right = (x + 1 < width).then_some(x + 1);
+            let below = (y + 1 < height).then_some(y + 1);
+            if jg < fk {
+                let blend = strength(DOMINANT_DIRECTION_THRESHOLD * jg < fk);
+                if f != g && f != j {
+                    corners[y * width + x][BOTTOM_RIGHT] = blend;
+                }
+                if k != j
+                    && k != g
+                    && let (Some(x), Some(y)) = (right, below)
+                {
+                    corners[y * width + x][0] = blend;
+                }
+            } else if fk < jg {
+                let blend = strength(DOMINANT_DIRECTION_THRESHOLD * fk < jg);
+                if j != f
+                    && j != k
+                    && let Some(y) = below
+                {
+                    corners[y * width + x][TOP_RIGHT] = blend;
+                }
+                if g != f
+                    && g != k
+                    && let Some(x) = right
+                {
+                    corners[y * width + x][BOTTOM_LEFT] = blend;
+                }
+            }
+        }
+    }
+    corners
+}
+
+/// Blend the bottom-right corner of `block`, seen turned `rotation` quarter
+/// turns clockwise
+fn blend_corner(factor: usize, rotation: usize, k: &Kernel, blend: &Corners, block: &mut [u32]) {
+    if blend[BOTTOM_RIGHT] == Blend::None {
+        return;
+    }
+
+    let line_blend = blend[BOTTOM_RIGHT] == Blend::Dominant
+        || !(
+            // Don't blend a line through two corners next to each other
+            // unless it's a 90° corner
+            (blend[TOP_RIGHT] != Blend::None && !eq(k.e, k.g))
+                || (blend[BOTTOM_LEFT] != Blend::None && !eq(k.e, k.c))
+                // Only round off the corner of L shapes
+                || (!eq(k.e, k.i)
+                    && eq(k.g, k.h)
+                    && eq(k.h, k.i)
+                    && eq(k.i, k.f)
+                    && eq(k.f, k.c))
+        );
+
+    let color = match dist(k.e, k.f) <= dist(k.e, k.h) {
+        true => k.f,
+        false => k.h,
+    };
+    let n = factor - 1;
+    let mut out = |row: usize, col: usize, num: u32, den: u32| {
+        let (mut row, mut col) = (row, col);
+        for _ in 0..rotation {
+            (row, col) = (n - col, row);
+        }
+        let pixel = &mut block[row * factor + col];
+        *pixel = alpha_blend(*pixel, color, num, den);
+    };
+
+    if !line_blend {
+        match factor {
+            2 => out(1, 1, 21, 100),
+            _ => out(2, 2, 45, 100),
+        }
+        return;
+    }
+
+    let fg = dist(k.f, k.g);
+    let hc = dist(k.h, k.c);
+    let shallow = STEEP_DIRECTION_THRESHOLD * fg <= hc && k.e != k.g && k.d != k.g;
+    let steep = STEEP_DIRECTION_THRESHOLD * hc <= fg && k.e != k.c && k.b != k.c;
+    match (factor, shallow, steep) {
+        (2, true, true) => {
+            out(1, 0, 1, 4);
+            out(0, 1, 1, 4);
+            out(1, 1, 5, 6);
+        }
+        (2, true, false) => {
+            out(1, 0, 1, 4);
+            out(1, 1, 3, 4);
+        }
+        (2, false, true) => {
+            out(0, 1, 1, 4);
+            out(1, 1, 3, 4);
+        }
+        (2, false, false) => out(1, 1, 1, 2),
+        (_, true, true) => {
+            out(2, 0, 1, 4);
+            out(0, 2, 1, 4);
+            out(2, 1, 3, 4);
+            out(1, 2, 3, 4);
+            out(2, 2, 1, 1);
+        }
+        (_, true, false) => {
+            out(2, 0, 1, 4);
+            out(1, 2, 1, 4);
+            out(2, 1, 3, 4);
+            out(2, 2, 1, 1);
+        }
+        (_, false, true) => {
+            out(0, 2, 1, 4);
+            out(2, 1, 1, 4);
+            out(1, 2, 3, 4);
+            out(2, 2, 1, 1);
+        }
+        (_, false, false) => {
+            out(1, 2, 1, 8);
+            out(2, 1, 1, 8);
+            out(2, 2, 7, 8);
+        }
+    }
+}
+
+/// A quarter turn clockwise: the top-right neighbor ends up bottom-right
+fn rotate_kernel(k: &Kernel) -> Kernel {
+    Kernel {
+        a: k.g,
+        b: k.d,
+        c: k.a,
+        d: k.h,
+        e: k.e,
+        f: k.b,
+        g: k.i,
+        h: k.f,
+        i: k.c,
+    }
+}
+
+fn eq(a: u32, b: u32) -> bool {
+    dist(a, b) < EQUAL_COLOR_TOLERANCE
+}
+
+/// Distance between colors in YCbCr, with BT.2020 weights
+fn dist(a: u32, b: u32) -> f64 {
+    if a == b {
+        return 0.0;
+    }
+    let [r1, g1, b1, _] = a.to_le_bytes();
+    let [r2, g2, b2, _] = b.to_le_bytes();
+    let r = r1 as f64 - r2 as f64;
+    let g = g1 as f64 - g2 as f64;
+    let b = b1 as f64 - b2 as f64;
+
+    const K_B: f64 = 0.0593;
+    const K_R: f64 = 0.2627;
+    const K_G: f64 = 1.0 - K_B - K_R;
+    let y = K_R * r + K_G * g + K_B * b;
+    let c_b = 0.5 / (1.0 - K_B) * (b - y);
+    let c_r = 0.5 / (1.0 - K_R) * (r - y);
+    ((LUMINANCE_WEIGHT * y).powi(2) + c_b.powi(2) + c_r.powi(2)).sqrt()
+}
+
+/// `num / den` of `color` over `pixel`
+fn alpha_blend(pixel: u32, color: u32, num: u32, den: u32) -> u32 {
+    let old = pixel.to_le_bytes();
+    let new = color.to_le_bytes();
+    let mut out = [0u8; 4];
+    for channel in 0..4 {
+        let mixed = new[channel] as u32 * num + old[channel] as u32 * (den - num);
+        out[channel] = (mixed / den) as u8;
+    }
+    u32::from_le_bytes(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: u32 = 0xFF00_0000;
+    const WHITE: u32 = 0xFFFF_FFFF;
+
+    #[test]
+    fn test_diagonal_edges_are_smoothed() {
+        // A white triangle under the diagonal of a black square
+        let size = 6;
+        let src: Vec<u32> = (0..size * size)
+            .map(|i| if i % size <= i / size { WHITE } else { BLACK })
+            .collect();
+        for factor in [2, 3] {
+            let out = scale(factor, size, size, &src);
+            let width = size * factor;
+            // The black pixel above the diagonal, away from the borders
+            let (x, y) = (3 * factor, 2 * factor);
+            let bottom_left = out[(y + factor - 1) * width + x];
+            assert_ne!(bottom_left, BLACK, "{factor}x");
+            assert_eq!(out[y * width + x + factor - 1], BLACK, "{factor}x");
+            // Inside the triangle stays white
+            assert_eq!(out[(size * factor - 1) * width], WHITE);
+        }
+    }
+
+    #[test]
+    fn test_single_pixels_only_get_corners_rounded() {
+        // A lone white pixel in black
+        let mut src = vec![BLACK; 25];
+        src[12] = WHITE;
+        let out = scale(2, 5, 5, &src);
+        let center = out[5 * 10 + 5];
+        assert_ne!(center, WHITE);
+        assert_ne!(center, BLACK);
+    }
+}
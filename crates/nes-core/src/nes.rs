@@ -4,12 +4,17 @@ pub mod bus;
 pub mod cartridge;
 pub mod controller;
 pub mod cpu;
+pub mod movie;
 pub mod ppu;
+pub mod recorder;
+pub mod region;
+pub mod save_state;
+pub mod stepping;
 pub mod tracer;
 
 pub mod dmc_dma;
 mod oam_dma;
-#[cfg(feature = "testing-utils")]
+#[cfg(any(test, feature = "testing-utils"))]
 pub mod test_utils;
 
 use crate::nes::apu::ApuBusInterface;
@@ -19,6 +24,12 @@ use crate::trace;
 use bus::nes_bus::NesBus;
 use cartridge::Cartridge;
 use cartridge::rom::{Rom, RomError};
+use controller::expansion::ExpansionDevice;
+use controller::{ConsoleType, PortDevice};
+use ppu::consts::NES_SYSTEM_PALETTE;
+use ppu::viewer::PpuSnapshot;
+use region::Region;
+use save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 pub const PPU_HZ: u64 = 5_369_318;
 pub const CPU_HZ_NTSC: f64 = PPU_HZ as f64 / 3.0;
@@ -31,6 +42,15 @@ enum DmaMode {
     None,
 }
 
+/// Outcome of `NES::run_frame()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameResult {
+    /// Number of the frame that just completed (see `NES::frame_count()`)
+    pub frame: u64,
+    /// CPU cycles clocked while running it
+    pub cpu_cycles: u64,
+}
+
 pub enum RunState {
     Running,
     Paused,
@@ -46,8 +66,11 @@ unsafe impl Send for NES {}
 pub struct NES {
     pub run_state: RunState,
     pub bus: &'static mut NesBus,
+    region: Region,
     master_clock: u64,
+    next_cpu_clock: u64,
     cpu_cycle_parity: bool,
+    frame_count: u64,
 
     dmc_dma: DmcDma,
     oam_dma: OamDma,
@@ -55,6 +78,10 @@ pub struct NES {
 
     pub ppu_remainder: u64,
     pub last_apu_sample_raw: f32,
+
+    /// Scanline to take a `PpuSnapshot` at the start of, every frame
+    snapshot_scanline: Option<usize>,
+    snapshot: Option<Box<PpuSnapshot>>,
 }
 
 impl Default for NES {
@@ -69,15 +96,21 @@ impl NES {
         Self {
             run_state: RunState::Running,
             bus,
+            region: Region::Ntsc,
             master_clock: 0,
+            next_cpu_clock: 0,
 
             cpu_cycle_parity: false,
+            frame_count: 0,
             dmc_dma: DmcDma::new(),
             oam_dma: OamDma::new(),
             oam_byte: 0,
 
             ppu_remainder: 0,
             last_apu_sample_raw: 0.0,
+
+            snapshot_scanline: None,
+            snapshot: None,
         }
     }
 
@@ -91,7 +124,9 @@ impl NES {
     pub fn reset(&mut self) {
         self.run_state = RunState::Running;
         self.master_clock = 0;
+        self.next_cpu_clock = 0;
         self.cpu_cycle_parity = false;
+        self.frame_count = 0;
         self.oam_dma = OamDma::new();
         self.dmc_dma = DmcDma::new();
         self.oam_byte = 0;
@@ -101,6 +136,27 @@ impl NES {
         self.bus.reset_components();
     }
 
+    /// Press the reset button. `reset` is a power cycle; this keeps RAM,
+    /// VRAM, the frame count and the machine's timing, like the real button
+    pub fn soft_reset(&mut self) {
+        self.run_state = RunState::Running;
+        self.oam_dma = OamDma::new();
+        self.dmc_dma = DmcDma::new();
+        self.bus.soft_reset_components();
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Switch console timing. Takes effect immediately, so it's best called
+    /// before `insert_cartridge()` (which resets the console)
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.bus.ppu.set_region(region);
+        self.bus.apu.set_region(region);
+    }
+
     pub fn insert_cartridge(&mut self, cartridge: Box<dyn Cartridge>) {
         self.bus.insert_cartridge(cartridge);
 
@@ -124,9 +180,12 @@ impl NES {
     /// - First value is `true` if this tick triggered a CPU tick, and `false` otherwise
     /// - Second value is `true` if a new frame is ready to be rendered, and `false` otherwise
     pub fn tick(&mut self) -> (bool, bool) {
-        // CPU runs at 1/3 PPU speed
+        // CPU runs at 1/3 PPU speed on NTSC/Dendy and 1/3.2 on PAL, so it's
+        // clocked whenever the master clock reaches its next divider edge
         let mut cpu_ticked = false;
-        if self.master_clock.is_multiple_of(3) {
+        if self.master_clock >= self.next_cpu_clock {
+            self.next_cpu_clock += self.region.cpu_divider() as u64;
+
             // Handle OAM request
             if let Some(page) = self.bus.oam_dma_request.take() {
                 self.oam_dma.start(page, self.cpu_cycle_parity);
@@ -184,12 +243,184 @@ impl NES {
 
         // Tick PPU
         let frame_ready = self.bus.ppu.tick();
+        if frame_ready {
+            self.frame_count += 1;
+        }
+        if let Some(scanline) = self.snapshot_scanline
+            && self.bus.ppu.cycles == 0
+            && self.bus.ppu.scanline == scanline
+        {
+            self.snapshot = Some(Box::new(self.bus.ppu.snapshot()));
+        }
 
-        self.master_clock = (self.master_clock + 1) % 3_000_000;
+        self.master_clock += self.region.ppu_divider() as u64;
         (cpu_ticked, frame_ready)
     }
 
-    pub fn get_frame_buffer(&self) -> &[u8; 256 * 240] {
+    /// Snapshot the whole machine. The cartridge's ROM isn't included, so the
+    /// state can only be restored with the same game inserted
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        let region_index = Region::ALL.iter().position(|r| *r == self.region);
+        state.write_u8(region_index.unwrap_or(0) as u8);
+        state.write_u64(self.master_clock);
+        state.write_u64(self.next_cpu_clock);
+        state.write_bool(self.cpu_cycle_parity);
+        state.write_u64(self.frame_count);
+        self.dmc_dma.save_state(&mut state);
+        self.oam_dma.save_state(&mut state);
+        state.write_u8(self.oam_byte);
+        state.write_u64(self.ppu_remainder);
+        state.write_f32(self.last_apu_sample_raw);
+        self.bus.save_state(&mut state);
+        state.into_bytes()
+    }
+
+    /// Restore a snapshot taken by `save_state()`. On error the machine may be
+    /// partially overwritten and should be reset
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), SaveStateError> {
+        let mut state = StateReader::new(bytes)?;
+        let region = *Region::ALL
+            .get(state.read_u8()? as usize)
+            .ok_or(SaveStateError::Corrupt("invalid region"))?;
+        if region != self.region {
+            self.set_region(region);
+        }
+        self.master_clock = state.read_u64()?;
+        self.next_cpu_clock = state.read_u64()?;
+        self.cpu_cycle_parity = state.read_bool()?;
+        self.frame_count = state.read_u64()?;
+        self.dmc_dma.load_state(&mut state)?;
+        self.oam_dma.load_state(&mut state)?;
+        self.oam_byte = state.read_u8()?;
+        self.ppu_remainder = state.read_u64()?;
+        self.last_apu_sample_raw = state.read_f32()?;
+        self.bus.load_state(&mut state)?;
+        state.finish()
+    }
+
+    /// Latch controller input, then run until the PPU completes a frame
+    ///
+    /// Input only changes here, at the frame boundary, so the same inputs
+    /// always produce the same frames. This is what replays, netplay and tests
+    /// should drive the emulator with. Audio generated during the frame is
+    /// made available to `apu.read_samples_f32()` before returning
+    pub fn run_frame(&mut self, input: [u8; 2]) -> FrameResult {
+        self.set_buttons(input);
+
+        let start_cycles = self.cpu_cycles();
+        while !self.tick().1 {}
+        self.bus.apu.end_frame();
+
+        FrameResult {
+            frame: self.frame_count,
+            cpu_cycles: self.cpu_cycles() - start_cycles,
+        }
+    }
+
+    /// Frames completed since power-on or reset
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// CPU cycles clocked since power-on or reset, including DMA stalls
+    pub fn cpu_cycles(&self) -> u64 {
+        self.next_cpu_clock / self.region.cpu_divider() as u64
+    }
+
+    /// Plug a new `device` into `port` (0 or 1)
+    /// Standard controller buttons for both ports, in `JoypadButton` order.
+    /// `run_frame()` does this itself; this is for stepping part of a frame
+    pub fn set_buttons(&mut self, input: [u8; 2]) {
+        self.bus.ports[0].set_buttons(input[0]);
+        self.bus.ports[1].set_buttons(input[1]);
+    }
+
+    pub fn set_port_device(&mut self, port: usize, device: PortDevice) {
+        self.bus.ports[port] = device.create(port);
+    }
+
+    pub fn port_device(&self, port: usize) -> PortDevice {
+        self.bus.ports[port].kind()
+    }
+
+    /// The device in `port`, if it's a `T`. For input beyond standard
+    /// controller buttons, like pointing the Zapper
+    pub fn port_mut<T: 'static>(&mut self, port: usize) -> Option<&mut T> {
+        self.bus.ports[port].as_any_mut().downcast_mut()
+    }
+
+    pub fn console(&self) -> ConsoleType {
+        self.bus.console
+    }
+
+    /// Switch between NES and Famicom input wiring. The expansion device and
+    /// microphone stay put, but are only read on a Famicom
+    pub fn set_console(&mut self, console: ConsoleType) {
+        self.bus.console = console;
+    }
+
+    /// Plug `device` into the Famicom expansion port, or unplug it with `None`
+    pub fn set_expansion_device(&mut self, device: Option<ExpansionDevice>) {
+        self.bus.expansion = device.map(|device| device.create());
+    }
+
+    pub fn expansion_device(&self) -> Option<ExpansionDevice> {
+        self.bus.expansion.as_ref().map(|device| device.kind())
+    }
+
+    /// The expansion port device, if it's a `T`. For its input, like keys
+    /// held on the Family BASIC keyboard
+    pub fn expansion_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.bus.expansion.as_mut()?.as_any_mut().downcast_mut()
+    }
+
+    /// Whether someone's blowing into controller 2's microphone
+    pub fn set_microphone(&mut self, active: bool) {
+        self.bus.microphone = active;
+    }
+
+    /// Turn off the 8 sprites per line limit to draw every sprite, without
+    /// games noticing. See `PPU::set_sprite_limit()`
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.bus.ppu.set_sprite_limit(enabled);
+    }
+
+    /// 9-bit pixels, indexing `NES_SYSTEM_PALETTE`
+    pub fn get_frame_buffer(&self) -> &[u16; 256 * 240] {
         &self.bus.ppu.frame_buffer
     }
+
+    /// Take a `PpuSnapshot` for debugger views at the start of `scanline`
+    /// each frame, or stop with `None`
+    pub fn set_snapshot_scanline(&mut self, scanline: Option<usize>) {
+        self.snapshot_scanline = scanline;
+        self.snapshot = None;
+    }
+
+    /// The latest snapshot asked for with `set_snapshot_scanline`, once
+    pub fn take_snapshot(&mut self) -> Option<Box<PpuSnapshot>> {
+        self.snapshot.take()
+    }
+
+    /// `get_frame_buffer()` as RGBA bytes through `NES_SYSTEM_PALETTE`, for
+    /// tools that save pictures without a palette of their own
+    pub fn get_frame_rgba(&self) -> Vec<u8> {
+        self.bus
+            .ppu
+            .frame_buffer
+            .iter()
+            .flat_map(|&pixel| {
+                let (r, g, b) = NES_SYSTEM_PALETTE[(pixel & 0x1FF) as usize];
+                [r, g, b, 0xFF]
+            })
+            .collect()
+    }
+
+    /// NTSC color subcarrier phase, in twelfths of a cycle, where each line
+    /// of `get_frame_buffer()` starts. Composite video filters need it to
+    /// put the chroma artifacts in the right place
+    pub fn get_scanline_phases(&self) -> &[u8; 240] {
+        &self.bus.ppu.scanline_phases
+    }
 }
@@ -1,7 +1,8 @@
-use crate::nes::CPU_HZ_NTSC;
 use crate::nes::apu::filter::OnePole;
 use crate::nes::apu::output::ApuOutput;
 use crate::nes::apu::status_register::ApuStatusRegister;
+use crate::nes::region::Region;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 use crate::trace;
 use dmc_channel::DmcChannel;
 use noise_channel::NoiseChannel;
@@ -138,6 +139,9 @@ pub struct APU {
     frame_irq_rising: bool,
     frame_irq_reassert: u8,
 
+    region: Region,
+    frame_steps: [u32; 5],
+
     sample_rate: f64,
     low_pass_0: OnePole,
     high_pass_0: OnePole,
@@ -153,7 +157,16 @@ impl Default for APU {
     }
 }
 
-const BLIP_BUF_MAX_SAMPLES: usize = 4096 * 2;
+// Room for the host's run-ahead plus a whole frame at 1/4 speed on a 192 kHz
+// device (768 kHz, 12,800 samples a frame), since emulation is driven a
+// frame at a time
+const BLIP_BUF_MAX_SAMPLES: usize = 4096 * 8;
+
+// Frame sequencer step boundaries, in APU cycles. The 4-step sequence ends on
+// the 4th entry, the 5-step sequence on the 5th
+// See: https://www.nesdev.org/wiki/APU_Frame_Counter
+const FRAME_STEPS_NTSC: [u32; 5] = [3728, 7456, 11185, 14914, 18640];
+const FRAME_STEPS_PAL: [u32; 5] = [4156, 8313, 12469, 16626, 20782];
 
 impl APU {
     pub fn new() -> APU {
@@ -162,7 +175,7 @@ impl APU {
             cpu_phase: ApuPhase::new(),
             seq_phase: ApuPhase::new(),
 
-            output: ApuOutput::new(CPU_HZ_NTSC, 44_100, BLIP_BUF_MAX_SAMPLES),
+            output: ApuOutput::new(Region::Ntsc.cpu_hz(), 44_100, BLIP_BUF_MAX_SAMPLES),
             last_dac: 0,
             // current_sample_raw: 0.0,
 
@@ -193,6 +206,9 @@ impl APU {
             frame_irq_rising: false,
             frame_irq_reassert: 0,
 
+            region: Region::Ntsc,
+            frame_steps: FRAME_STEPS_NTSC,
+
             sample_rate: 44100.0, // A safe default
 
             low_pass_0: OnePole::default(),
@@ -209,6 +225,23 @@ impl APU {
         self.output.set_sample_rate(self.sample_rate as u32);
     }
 
+    /// Select the frame sequencer, period tables and output clock rate for `region`.
+    /// Preserved through resets
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.output.set_cpu_hz(region.cpu_hz());
+        self.frame_steps = match region.uses_pal_apu() {
+            true => FRAME_STEPS_PAL,
+            false => FRAME_STEPS_NTSC,
+        };
+        self.noise.set_region(region);
+        self.dmc.set_region(region);
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
     pub fn reset(&mut self) {
         self.cpu_phase = ApuPhase::new();
         self.seq_phase = ApuPhase::new();
@@ -221,6 +254,8 @@ impl APU {
         self.triangle = TriangleChannel::new();
         self.noise = NoiseChannel::new();
         self.dmc = DmcChannel::new();
+        self.noise.set_region(self.region);
+        self.dmc.set_region(self.region);
 
         self.status_register.update(0);
 
@@ -251,6 +286,25 @@ impl APU {
         self.error = None;
     }
 
+    /// The console's reset button: every channel is silenced as by writing 0
+    /// to $4015, and the frame counter restarts in the mode it was in, with
+    /// its IRQ cleared. Triangle phase and the DMC output level are kept
+    ///
+    /// See: https://www.nesdev.org/wiki/CPU_power_up_state
+    pub fn soft_reset(&mut self) {
+        self.write(0x4015, 0);
+        let mode = match self.master_sequence_mode {
+            SequenceMode::Mode0 => 0,
+            SequenceMode::Mode1 => 0b1000_0000,
+        };
+        let irq_disable = (self.frame_irq_disable as u8) << 6;
+        self.write(0x4017, mode | irq_disable);
+        self.status_register
+            .remove(ApuStatusRegister::FRAME_INTERRUPT);
+        self.frame_irq_rising = false;
+        self.frame_irq_reassert = 0;
+    }
+
     pub fn read(&mut self, addr: u16) -> u8 {
         match addr {
             0x4015 => {
@@ -432,21 +486,22 @@ impl APU {
                 self.clock_counter = 0;
             } else {
                 self.clock_counter += 1;
+                let steps = self.frame_steps;
                 match self.master_sequence_mode {
                     SequenceMode::Mode0 => {
                         // 4-step
                         match self.clock_counter {
-                            3728 => {
+                            n if n == steps[0] => {
                                 self.pending_quarter_clock = true;
                             }
-                            7456 => {
+                            n if n == steps[1] => {
                                 self.pending_quarter_clock = true;
                                 self.pending_half_clock = true;
                             }
-                            11185 => {
+                            n if n == steps[2] => {
                                 self.pending_quarter_clock = true;
                             }
-                            14914 => {
+                            n if n == steps[3] => {
                                 self.pending_quarter_clock = true;
                                 self.pending_half_clock = true;
 
@@ -469,18 +524,18 @@ impl APU {
                     SequenceMode::Mode1 => {
                         // 5-step
                         match self.clock_counter {
-                            3728 => {
+                            n if n == steps[0] => {
                                 self.pending_quarter_clock = true;
                             }
-                            7456 => {
+                            n if n == steps[1] => {
                                 self.pending_quarter_clock = true;
                                 self.pending_half_clock = true;
                             }
-                            11185 => {
+                            n if n == steps[2] => {
                                 self.pending_quarter_clock = true;
                             }
-                            14914 => { /* no clocks */ }
-                            18640 => {
+                            n if n == steps[3] => { /* no clocks */ }
+                            n if n == steps[4] => {
                                 self.pending_quarter_clock = true;
                                 self.pending_half_clock = true;
 
@@ -645,15 +700,117 @@ impl APU {
         self.output.read_samples_f32(out)
     }
 
+    /// Put samples taken with `read_samples_f32` back in front of any newer
+    /// ones, for callers that reload a state after the audio was made but
+    /// before it was played
+    pub fn restore_samples(&mut self, samples: &[f32]) {
+        self.output.restore_samples(samples);
+    }
+
+    /// Drop any generated samples that haven't been read yet
+    pub fn discard_samples(&mut self) {
+        self.output.reset();
+    }
+
     /// CPU cycles needed to generate `N` more samples at current rates
     pub fn clocks_needed(&self, sample_count: u32) -> u32 {
         self.output.clocks_needed(sample_count)
     }
 }
 
+fn write_phase(state: &mut StateWriter, phase: ApuPhase) {
+    state.write_bool(phase.is_odd());
+}
+
+fn read_phase(state: &mut StateReader) -> Result<ApuPhase, SaveStateError> {
+    Ok(match state.read_bool()? {
+        true => ApuPhase::Odd,
+        false => ApuPhase::Even,
+    })
+}
+
+/// Channel mutes, filters and the resampler are host-side output state and aren't saved
+impl SaveState for APU {
+    fn save_state(&self, state: &mut StateWriter) {
+        write_phase(state, self.cpu_phase);
+        write_phase(state, self.seq_phase);
+        state.write_i32(self.last_dac);
+
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        state.write_u8(self.status_register.bits());
+
+        state.write_bool(self.master_sequence_mode == SequenceMode::Mode1);
+        state.write_u8(self.frame_clock_counter);
+        state.write_u32(self.clock_counter);
+        state.write_bool(self.pending_quarter_clock);
+        state.write_bool(self.pending_half_clock);
+        state.write_bool(self.pending_clock_reset);
+        state.write_u8(self.pending_frame_reset_delay);
+
+        state.write_bool(self.frame_irq_disable);
+        state.write_bool(self.frame_irq_rising);
+        state.write_u8(self.frame_irq_reassert);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.cpu_phase = read_phase(state)?;
+        self.seq_phase = read_phase(state)?;
+        self.last_dac = state.read_i32()?;
+
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.status_register = ApuStatusRegister::from_bits_retain(state.read_u8()?);
+
+        self.master_sequence_mode = match state.read_bool()? {
+            true => SequenceMode::Mode1,
+            false => SequenceMode::Mode0,
+        };
+        self.frame_clock_counter = state.read_u8()?;
+        self.clock_counter = state.read_u32()?;
+        self.pending_quarter_clock = state.read_bool()?;
+        self.pending_half_clock = state.read_bool()?;
+        self.pending_clock_reset = state.read_bool()?;
+        self.pending_frame_reset_delay = state.read_u8()?;
+
+        self.frame_irq_disable = state.read_bool()?;
+        self.frame_irq_rising = state.read_bool()?;
+        self.frame_irq_reassert = state.read_u8()?;
+
+        // Samples buffered before the load belong to a different timeline
+        self.output.reset();
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::nes::test_utils::nes_with_program;
+
+    #[test]
+    fn test_frame_at_high_sample_rate() {
+        // JMP $8000
+        let mut nes = nes_with_program(&[0x4C, 0x00, 0x80]);
+        nes.bus.apu.set_sample_rate(384_000.0);
+        // The first frame ends at the first vblank, just after power-on
+        nes.run_frame([0; 2]);
+        nes.bus.apu.discard_samples();
+        let result = nes.run_frame([0; 2]);
+
+        let expected = result.cpu_cycles as f64 * 384_000.0 / Region::Ntsc.cpu_hz();
+        let available = nes.bus.apu.samples_available() as f64;
+        assert!(
+            (available - expected).abs() < 2.0,
+            "{available} vs {expected}"
+        );
+    }
 
     #[test]
     fn apu_length_table_pulse1() {
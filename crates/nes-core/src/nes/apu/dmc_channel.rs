@@ -1,13 +1,20 @@
 use super::units::sequence_timer::SequenceTimer;
 use crate::nes::apu::units::dmc_output::DmcOutput;
+use crate::nes::region::Region;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
-const RATE_TABLE: [u16; 16] = [
+// Rates in CPU cycles
+const RATE_TABLE_NTSC: [u16; 16] = [
     428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
 ];
+const RATE_TABLE_PAL: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
 
 pub struct DmcChannel {
     seq_timer: SequenceTimer,
     output: DmcOutput,
+    rate_table: &'static [u16; 16],
 
     enabled: bool,
 
@@ -29,6 +36,7 @@ impl DmcChannel {
         DmcChannel {
             seq_timer: SequenceTimer::new(),
             output: DmcOutput::new(),
+            rate_table: &RATE_TABLE_NTSC,
 
             enabled: false,
 
@@ -46,6 +54,13 @@ impl DmcChannel {
         }
     }
 
+    pub fn set_region(&mut self, region: Region) {
+        self.rate_table = match region.uses_pal_apu() {
+            true => &RATE_TABLE_PAL,
+            false => &RATE_TABLE_NTSC,
+        };
+    }
+
     pub fn write_4010(&mut self, value: u8) {
         /* $4010:       IL--.RRRR (write)
               bit 7    I---.---- IRQ enabled flag
@@ -60,7 +75,7 @@ impl DmcChannel {
 
         self.loop_flag = value & 0x40 != 0;
         let rate_index = (value & 0x0F) as usize;
-        let period = RATE_TABLE[rate_index];
+        let period = self.rate_table[rate_index];
 
         self.seq_timer.set_reload(period - 1);
     }
@@ -186,3 +201,34 @@ impl DmcChannel {
         self.output.level()
     }
 }
+
+impl SaveState for DmcChannel {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.seq_timer.save_state(state);
+        self.output.save_state(state);
+        state.write_bool(self.enabled);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq_pending);
+        state.write_bool(self.loop_flag);
+        state.write_u16(self.sample_address);
+        state.write_u16(self.current_address);
+        state.write_u16(self.sample_length);
+        state.write_u16(self.bytes_remaining);
+        state.write_option_u8(self.sample_buffer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.seq_timer.load_state(state)?;
+        self.output.load_state(state)?;
+        self.enabled = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_pending = state.read_bool()?;
+        self.loop_flag = state.read_bool()?;
+        self.sample_address = state.read_u16()?;
+        self.current_address = state.read_u16()?;
+        self.sample_length = state.read_u16()?;
+        self.bytes_remaining = state.read_u16()?;
+        self.sample_buffer = state.read_option_u8()?;
+        Ok(())
+    }
+}
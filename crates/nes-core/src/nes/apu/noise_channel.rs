@@ -2,6 +2,16 @@ use super::units::envelope::Envelope;
 use super::units::length_counter::LengthCounter;
 use super::units::sequence_timer::SequenceTimer;
 use crate::nes::apu::FrameClock;
+use crate::nes::region::Region;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
+// Timer periods in APU cycles
+const NOISE_TABLE_NTSC: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const NOISE_TABLE_PAL: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
 
 pub enum NoiseMode {
     Long,
@@ -13,6 +23,7 @@ pub struct NoiseChannel {
     pub length_counter: LengthCounter,
     pub envelope: Envelope,
     mode: NoiseMode,
+    period_table: &'static [u16; 16],
 
     shifter: u16,
 }
@@ -24,11 +35,19 @@ impl NoiseChannel {
             length_counter: LengthCounter::new(),
             envelope: Envelope::new(),
             mode: NoiseMode::Long,
+            period_table: &NOISE_TABLE_NTSC,
 
             shifter: 0x7FFF,
         }
     }
 
+    pub fn set_region(&mut self, region: Region) {
+        self.period_table = match region.uses_pal_apu() {
+            true => &NOISE_TABLE_PAL,
+            false => &NOISE_TABLE_NTSC,
+        };
+    }
+
     pub fn write_400c(&mut self, value: u8) {
         let length_counter_halt = value & 0b0010_0000 != 0;
         self.envelope.set(value);
@@ -42,13 +61,10 @@ impl NoiseChannel {
         };
 
         let timer_period = value & 0b0000_1111;
-        const NOISE_TABLE: [u16; 16] = [
-            4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
-        ];
 
         // since sequence timer's period is reload + 1,
         // set reload to (table_value - 1).
-        let period_apu_cycles = NOISE_TABLE[timer_period as usize];
+        let period_apu_cycles = self.period_table[timer_period as usize];
         let reload = period_apu_cycles.saturating_sub(1);
         self.seq_timer.set_reload(reload);
     }
@@ -113,3 +129,25 @@ impl NoiseChannel {
         }
     }
 }
+
+impl SaveState for NoiseChannel {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.seq_timer.save_state(state);
+        self.length_counter.save_state(state);
+        self.envelope.save_state(state);
+        state.write_bool(matches!(self.mode, NoiseMode::Short));
+        state.write_u16(self.shifter);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.seq_timer.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.mode = match state.read_bool()? {
+            true => NoiseMode::Short,
+            false => NoiseMode::Long,
+        };
+        self.shifter = state.read_u16()?;
+        Ok(())
+    }
+}
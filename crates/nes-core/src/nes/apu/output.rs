@@ -1,10 +1,18 @@
 use crate::nes::apu::blip_buf;
 
+/// Longest blip time frame, in CPU cycles. `BlipBuf::end_frame` multiplies
+/// the frame's length by a fixed-point factor that grows with the sample
+/// rate, so a whole video frame overflows it past ~240 kHz. Frames this
+/// short stay in range for any sample rate up to the CPU clock
+const MAX_FRAME_CLOCKS: u32 = 1024;
+
 pub struct ApuOutput {
     blip: blip_buf::BlipBuf,
     cpu_hz: f64,
     sample_rate: u32,
     t_cpu: u32,
+    /// Samples put back with `restore_samples`, read before the blip buffer's
+    restored: Vec<f32>,
 
     scratch_i16: Vec<i16>,
 }
@@ -19,6 +27,7 @@ impl ApuOutput {
             cpu_hz,
             sample_rate,
             t_cpu: 0,
+            restored: Vec::new(),
             scratch_i16: vec![0; max_samples],
         }
     }
@@ -26,6 +35,19 @@ impl ApuOutput {
     pub fn reset(&mut self) {
         self.blip.clear();
         self.t_cpu = 0;
+        self.restored.clear();
+    }
+
+    /// Change the emulated clock rate samples are resampled from (region dependent)
+    pub fn set_cpu_hz(&mut self, cpu_hz: f64) {
+        if self.cpu_hz == cpu_hz {
+            return;
+        }
+        self.cpu_hz = cpu_hz;
+        self.blip.set_rates(cpu_hz, self.sample_rate as f64);
+
+        self.blip.clear();
+        self.t_cpu = 0;
     }
 
     pub fn set_sample_rate(&mut self, sample_rate: u32) {
@@ -50,6 +72,9 @@ impl ApuOutput {
     #[inline]
     pub fn step_cpu_cycle(&mut self) {
         self.t_cpu += 1;
+        if self.t_cpu == MAX_FRAME_CLOCKS {
+            self.end_frame();
+        }
     }
 
     pub fn end_frame(&mut self) {
@@ -59,7 +84,12 @@ impl ApuOutput {
     }
 
     pub fn samples_available(&self) -> usize {
-        self.blip.samples_avail() as usize
+        self.restored.len() + self.blip.samples_avail() as usize
+    }
+
+    /// Put `samples` back in front of any not read yet
+    pub fn restore_samples(&mut self, samples: &[f32]) {
+        self.restored.splice(0..0, samples.iter().copied());
     }
 
     pub fn clocks_needed(&self, sample_count: u32) -> u32 {
@@ -68,6 +98,10 @@ impl ApuOutput {
 
     /// Returns how many samples were actually written
     pub fn read_samples_f32(&mut self, out: &mut [f32]) -> usize {
+        let restored = self.restored.len().min(out.len());
+        out[..restored].copy_from_slice(&self.restored[..restored]);
+        self.restored.drain(..restored);
+        let out = &mut out[restored..];
         let want = out.len();
 
         // ensure scratch big enough
@@ -82,6 +116,6 @@ impl ApuOutput {
             out[i] = (self.scratch_i16[i] as f32) / 32768.0;
         }
 
-        got
+        restored + got
     }
 }
@@ -3,6 +3,7 @@ use super::units::length_counter::LengthCounter;
 use super::units::sequence_timer::SequenceTimer;
 use super::units::sweep::{PulseType, Sweep};
 use crate::nes::apu::FrameClock;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 // See: https://www.nesdev.org/wiki/APU_Pulse#Pulse_channel_output_to_mixer
 const DUTY_TABLE: [[u8; 8]; 4] = [
@@ -148,6 +149,27 @@ impl PulseChannel {
     }
 }
 
+impl SaveState for PulseChannel {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.seq_timer.save_state(state);
+        self.length_counter.save_state(state);
+        self.envelope.save_state(state);
+        self.sweep.save_state(state);
+        state.write_u8(self.duty_cycle);
+        state.write_u8(self.duty_step);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.seq_timer.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.sweep.load_state(state)?;
+        self.duty_cycle = state.read_u8()?;
+        self.duty_step = state.read_u8()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::units::envelope::VolumeMode;
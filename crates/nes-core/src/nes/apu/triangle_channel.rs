@@ -1,6 +1,7 @@
 use super::units::length_counter::LengthCounter;
 use super::units::sequence_timer::SequenceTimer;
 use crate::nes::apu::FrameClock;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 const TRIANGLE_TABLE: [u8; 32] = [
     15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
@@ -121,3 +122,28 @@ impl TriangleChannel {
         }
     }
 }
+
+impl SaveState for TriangleChannel {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.sequence_timer.save_state(state);
+        self.length_counter.save_state(state);
+        state.write_bool(self.linear_counter_reload_flag);
+        state.write_u8(self.sequence_index);
+        state.write_u8(self.last_sample);
+        state.write_bool(self.linear_counter_control_flag);
+        state.write_u8(self.linear_counter_reload_value);
+        state.write_u8(self.linear_counter_value);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.sequence_timer.load_state(state)?;
+        self.length_counter.load_state(state)?;
+        self.linear_counter_reload_flag = state.read_bool()?;
+        self.sequence_index = state.read_u8()?;
+        self.last_sample = state.read_u8()?;
+        self.linear_counter_control_flag = state.read_bool()?;
+        self.linear_counter_reload_value = state.read_u8()?;
+        self.linear_counter_value = state.read_u8()?;
+        Ok(())
+    }
+}
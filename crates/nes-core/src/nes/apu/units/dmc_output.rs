@@ -1,3 +1,5 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
 pub struct DmcOutput {
     shift_register: u8,
     level: u8,
@@ -52,3 +54,18 @@ impl DmcOutput {
         self.level
     }
 }
+
+impl SaveState for DmcOutput {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.shift_register);
+        state.write_u8(self.level);
+        state.write_u8(self.bits_remaining);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.shift_register = state.read_u8()?;
+        self.level = state.read_u8()?;
+        self.bits_remaining = state.read_u8()?;
+        Ok(())
+    }
+}
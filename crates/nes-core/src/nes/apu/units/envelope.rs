@@ -1,3 +1,5 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
 const ENV_LOOP: u8 = 0b0010_0000;
 const ENV_CONST: u8 = 0b0001_0000;
 const ENV_VOLUME: u8 = 0b0000_1111;
@@ -121,6 +123,32 @@ impl Envelope {
     }
 }
 
+impl SaveState for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.start);
+        state.write_u8(self.divider);
+        state.write_u8(self.decay);
+        state.write_u8(self.constant_volume);
+        state.write_bool(self.loop_flag);
+        state.write_bool(matches!(self.volume_mode, VolumeMode::Constant));
+        state.write_u8(self.period);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.start = state.read_bool()?;
+        self.divider = state.read_u8()?;
+        self.decay = state.read_u8()?;
+        self.constant_volume = state.read_u8()?;
+        self.loop_flag = state.read_bool()?;
+        self.volume_mode = match state.read_bool()? {
+            true => VolumeMode::Constant,
+            false => VolumeMode::Envelope,
+        };
+        self.period = state.read_u8()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
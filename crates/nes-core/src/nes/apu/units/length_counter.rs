@@ -1,3 +1,5 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
 pub struct LengthCounter {
     enabled: bool,
     halted: bool,
@@ -61,6 +63,21 @@ impl LengthCounter {
     }
 }
 
+impl SaveState for LengthCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_bool(self.halted);
+        state.write_u8(self.value);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = state.read_bool()?;
+        self.halted = state.read_bool()?;
+        self.value = state.read_u8()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
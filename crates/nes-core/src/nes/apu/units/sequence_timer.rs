@@ -1,3 +1,5 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
 pub struct SequenceTimer {
     timer_low: u8,
     timer_high: u8,
@@ -63,6 +65,23 @@ impl SequenceTimer {
     }
 }
 
+impl SaveState for SequenceTimer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.timer_low);
+        state.write_u8(self.timer_high);
+        state.write_u16(self.reload_value);
+        state.write_u16(self.value);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.timer_low = state.read_u8()?;
+        self.timer_high = state.read_u8()?;
+        self.reload_value = state.read_u16()?;
+        self.value = state.read_u16()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
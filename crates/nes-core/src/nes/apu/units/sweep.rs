@@ -1,3 +1,5 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
 pub enum PulseType {
     Pulse1,
     Pulse2,
@@ -94,6 +96,27 @@ impl Sweep {
     }
 }
 
+impl SaveState for Sweep {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_bool(self.reload);
+        state.write_bool(self.negate);
+        state.write_u8(self.period);
+        state.write_u8(self.shift);
+        state.write_u8(self.divider);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = state.read_bool()?;
+        self.reload = state.read_bool()?;
+        self.negate = state.read_bool()?;
+        self.period = state.read_u8()?;
+        self.shift = state.read_u8()?;
+        self.divider = state.read_u8()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
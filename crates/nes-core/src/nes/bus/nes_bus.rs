@@ -2,10 +2,11 @@ use crate::nes::apu::{APU, ApuBusInterface};
 use crate::nes::bus::consts::*;
 use crate::nes::cartridge::rom::Mirroring;
 use crate::nes::cartridge::{Cartridge, MapperTiming};
-use crate::nes::controller::NesController;
-use crate::nes::controller::joypad::Joypad;
+use crate::nes::controller::expansion::{ExpansionController, ExpansionDevice};
+use crate::nes::controller::{ConsoleType, NesController, PortDevice};
 use crate::nes::cpu::{CPU, CpuBusInterface};
 use crate::nes::ppu::{PPU, PpuBusInterface};
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 pub struct NesBus {
     cart: Option<Box<dyn Cartridge>>,
@@ -23,7 +24,13 @@ pub struct NesBus {
     pub last_cpu_read: u8,
     pub last_ppu_read: u8,
 
-    pub joypads: [Joypad; 2],
+    /// Devices in the two controller ports. They stay plugged in across resets
+    pub ports: [Box<dyn NesController>; 2],
+
+    /// Famicom-only inputs: the expansion port and controller 2's microphone
+    pub console: ConsoleType,
+    pub expansion: Option<Box<dyn ExpansionController>>,
+    pub microphone: bool,
 }
 
 impl NesBus {
@@ -43,7 +50,11 @@ impl NesBus {
 
             last_cpu_read: 0,
             last_ppu_read: 0,
-            joypads: [Joypad::new(), Joypad::new()],
+            ports: [PortDevice::Joypad.create(0), PortDevice::Joypad.create(1)],
+
+            console: ConsoleType::Nes,
+            expansion: None,
+            microphone: false,
         });
 
         // Safety: This raw pointer should remain stable
@@ -61,13 +72,43 @@ impl NesBus {
         self.nmi_scheduled = None;
         self.oam_dma_request = None;
         self.last_cpu_read = 0;
-        self.joypads = [Joypad::new(), Joypad::new()];
+        for (port, device) in self.ports.iter_mut().enumerate() {
+            *device = device.kind().create(port);
+        }
+        if let Some(device) = &mut self.expansion {
+            *device = device.kind().create();
+        }
 
         self.cpu.reset();
         self.ppu.reset();
         self.apu.reset();
     }
 
+    /// The console's reset button. Unlike `reset_components`, RAM and the
+    /// plugged-in devices are left alone
+    pub fn soft_reset_components(&mut self) {
+        self.nmi_scheduled = None;
+        self.oam_dma_request = None;
+
+        self.cpu.soft_reset();
+        self.ppu.soft_reset();
+        self.apu.soft_reset();
+    }
+
+    /// Lines the Famicom adds on $4016 (`port` 0) and $4017 (`port` 1): the
+    /// expansion port, plus the microphone on $4016 D2
+    fn famicom_read(&mut self, port: usize) -> u8 {
+        if self.console != ConsoleType::Famicom {
+            return 0;
+        }
+        let expansion = match &mut self.expansion {
+            Some(device) => device.read(port),
+            None => 0,
+        };
+        let microphone = (port == 0 && self.microphone) as u8;
+        expansion | microphone << 2
+    }
+
     #[allow(dead_code)]
     pub fn new_with_cartridge(cart: Box<dyn Cartridge>) -> &'static mut NesBus {
         let bus = NesBus::new();
@@ -97,8 +138,8 @@ impl CpuBusInterface for NesBus {
                 // Open bus
                 self.last_cpu_read
             }
-            0x4016 => self.joypads[0].read(),
-            0x4017 => self.joypads[1].read(),
+            0x4016 => self.ports[0].read(&self.ppu) | self.famicom_read(0),
+            0x4017 => self.ports[1].read(&self.ppu) | self.famicom_read(1),
             0x4018..=0x401F => {
                 // Open bus
                 self.last_cpu_read
@@ -144,9 +185,13 @@ impl CpuBusInterface for NesBus {
                 self.oam_dma_request = Some(value);
             }
             0x4016 => {
-                // Used to reset strobing via bit 0
-                self.joypads[0].write(value);
-                self.joypads[1].write(value);
+                // OUT0-OUT2, bit 0 being the controller strobe
+                for device in &mut self.ports {
+                    device.write(value & 0b111);
+                }
+                if let Some(device) = &mut self.expansion {
+                    device.write(value & 0b111);
+                }
             }
             0x4000..=0x4013 | 0x4015 | 0x4017 => {
                 self.apu.write(addr, value);
@@ -214,6 +259,16 @@ impl PpuBusInterface for NesBus {
             cart.ppu_clock(addr);
         }
     }
+
+    fn ppu_bus_peek(&mut self, addr: u16) -> u8 {
+        match &self.cart {
+            Some(cart) => match cart.ppu_peek(addr) {
+                (data, false) => data,
+                (_, true) => self.last_ppu_read,
+            },
+            _ => 0,
+        }
+    }
 }
 
 impl ApuBusInterface for NesBus {
@@ -246,3 +301,85 @@ impl Traceable for NesBus {
         Some(format!("{} | {} ", cpu_trace, ppu_trace))
     }
 }
+
+impl SaveState for NesBus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.cpu_ram);
+        state.write_option_u8(self.nmi_scheduled);
+        state.write_option_u8(self.oam_dma_request);
+        state.write_bool(self.last_mapper_write_cycle.is_some());
+        state.write_usize(self.last_mapper_write_cycle.unwrap_or(0));
+        state.write_u8(self.last_cpu_read);
+        state.write_u8(self.last_ppu_read);
+        for device in &self.ports {
+            state.write_u8(device.kind() as u8);
+            device.save_state(state);
+        }
+        state.write_u8(self.console as u8);
+        // 0 for nothing plugged in, otherwise the device's index plus one
+        match &self.expansion {
+            Some(device) => {
+                state.write_u8(device.kind() as u8 + 1);
+                device.save_state(state);
+            }
+            None => state.write_u8(0),
+        }
+        state.write_bool(self.microphone);
+
+        self.cpu.save_state(state);
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        if let Some(cart) = &self.cart {
+            cart.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes(&mut self.cpu_ram)?;
+        self.nmi_scheduled = state.read_option_u8()?;
+        self.oam_dma_request = state.read_option_u8()?;
+        let has_mapper_write = state.read_bool()?;
+        let mapper_write_cycle = state.read_usize()?;
+        self.last_mapper_write_cycle = has_mapper_write.then_some(mapper_write_cycle);
+        self.last_cpu_read = state.read_u8()?;
+        self.last_ppu_read = state.read_u8()?;
+        for (port, device) in self.ports.iter_mut().enumerate() {
+            let kind = *PortDevice::ALL
+                .get(state.read_u8()? as usize)
+                .ok_or(SaveStateError::Corrupt("unknown controller device"))?;
+            // Plug in whatever was there when the state was saved
+            if device.kind() != kind {
+                *device = kind.create(port);
+            }
+            device.load_state(state)?;
+        }
+        self.console = match state.read_u8()? {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::Famicom,
+            _ => return Err(SaveStateError::Corrupt("unknown console type")),
+        };
+        self.expansion = match state.read_u8()? {
+            0 => None,
+            tag => {
+                let kind = *ExpansionDevice::ALL
+                    .get(tag as usize - 1)
+                    .ok_or(SaveStateError::Corrupt("unknown expansion device"))?;
+                let mut device = match self.expansion.take() {
+                    Some(device) if device.kind() == kind => device,
+                    _ => kind.create(),
+                };
+                device.load_state(state)?;
+                Some(device)
+            }
+        };
+        self.microphone = state.read_bool()?;
+
+        self.cpu.load_state(state)?;
+        self.ppu.load_state(state)?;
+        self.apu.load_state(state)?;
+        match &mut self.cart {
+            Some(cart) => cart.load_state(state),
+            None => Err(SaveStateError::NoCartridge),
+        }
+    }
+}
@@ -2,6 +2,11 @@
 mod test {
     use crate::nes::bus::nes_bus::NesBus;
     use crate::nes::cartridge::rom::{Mirroring, Rom};
+    use crate::nes::controller::expansion::ExpansionDevice;
+    use crate::nes::controller::family_keyboard::{FamilyKey, FamilyKeyboard};
+    use crate::nes::controller::vaus::Vaus;
+    use crate::nes::controller::{ConsoleType, PortDevice};
+    use crate::nes::cpu::CpuBusInterface;
 
     #[test]
     fn test_cpu_write_to_nametables_via_2006_2007() {
@@ -276,4 +281,51 @@ mod test {
 
         assert_eq!(bus.ppu.scroll_register.w, false); // Verify ScrollRegister's latch is reset
     }
+
+    #[test]
+    fn test_controller_ports() {
+        let rom = Rom::new_custom(vec![0; 0x4000], vec![0; 0x2000], 0, Mirroring::Vertical);
+        let bus = NesBus::new_with_cartridge(rom.into_cartridge().unwrap());
+        bus.ports[0].set_buttons(0b0000_0001);
+        bus.ports[1] = PortDevice::Vaus.create(1);
+        bus.ports[1]
+            .as_any_mut()
+            .downcast_mut::<Vaus>()
+            .unwrap()
+            .set_button(true);
+
+        // One strobe write reaches both ports
+        bus.cpu_bus_write(0x4016, 1);
+        bus.cpu_bus_write(0x4016, 0);
+        assert_eq!(bus.cpu_bus_read(0x4016), 1);
+        assert_eq!(bus.cpu_bus_read(0x4016), 0);
+        assert_eq!(bus.cpu_bus_read(0x4017) & 0b0000_1000, 0b0000_1000);
+
+        // Devices survive a reset, with their state cleared
+        bus.reset_components();
+        assert_eq!(bus.ports[1].kind(), PortDevice::Vaus);
+        assert_eq!(bus.cpu_bus_read(0x4017) & 0b0000_1000, 0);
+    }
+
+    #[test]
+    fn test_famicom_expansion_port() {
+        let rom = Rom::new_custom(vec![0; 0x4000], vec![0; 0x2000], 0, Mirroring::Vertical);
+        let bus = NesBus::new_with_cartridge(rom.into_cartridge().unwrap());
+        let mut keyboard = ExpansionDevice::FamilyBasicKeyboard.create();
+        let any = keyboard.as_any_mut();
+        let keys = any.downcast_mut::<FamilyKeyboard>().unwrap();
+        keys.set_pressed(&[FamilyKey::F8]);
+        bus.expansion = Some(keyboard);
+        bus.microphone = true;
+
+        // A NES has no expansion port or microphone
+        bus.cpu_bus_write(0x4016, 0b101);
+        bus.cpu_bus_write(0x4016, 0b100);
+        assert_eq!(bus.cpu_bus_read(0x4016) & 0b110, 0);
+        assert_eq!(bus.cpu_bus_read(0x4017) & 0b1_1110, 0);
+
+        bus.console = ConsoleType::Famicom;
+        assert_eq!(bus.cpu_bus_read(0x4016) & 0b110, 0b100);
+        assert_eq!(bus.cpu_bus_read(0x4017) & 0b1_1110, 0b1_1100);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::nes::save_state::SaveState;
 use rom::Mirroring;
 
 pub mod mapper000_nrom;
@@ -14,7 +15,7 @@ pub enum MapperTiming {
     Mmc1,
 }
 
-pub trait Cartridge: Send {
+pub trait Cartridge: Send + SaveState {
     /// CPU read ($4020–$FFFF)
     ///
     /// # Returns
@@ -32,6 +33,11 @@ pub trait Cartridge: Send {
     /// A `(u8, bool)` tuple containing the data as `u8` and open bus as `bool`
     fn ppu_read(&mut self, addr: u16) -> (u8, bool);
 
+    /// PPU read ($0000–$1FFF) for debuggers, with the result `ppu_read` would
+    /// give but none of its side effects. Mappers that switch banks on CHR
+    /// fetches, like the MMC2 latches, must leave that state alone here
+    fn ppu_peek(&self, addr: u16) -> (u8, bool);
+
     /// PPU write ($0000–$1FFF)
     fn ppu_write(&mut self, addr: u16, data: u8);
 
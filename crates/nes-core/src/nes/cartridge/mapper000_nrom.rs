@@ -1,5 +1,6 @@
 use super::Cartridge;
 use crate::nes::cartridge::rom::Mirroring;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct NromCart {
@@ -53,6 +54,10 @@ impl Cartridge for NromCart {
     }
 
     fn ppu_read(&mut self, addr: u16) -> (u8, bool) {
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_peek(&self, addr: u16) -> (u8, bool) {
         let addr = addr as usize;
         if addr < self.chr.len() {
             (self.chr[addr], false)
@@ -74,3 +79,19 @@ impl Cartridge for NromCart {
         self.mirroring
     }
 }
+
+impl SaveState for NromCart {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_is_ram {
+            state.write_vec(&self.chr);
+        }
+        state.write_vec(&self.prg_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        if self.chr_is_ram {
+            state.read_vec_into(&mut self.chr)?;
+        }
+        state.read_vec_into(&mut self.prg_ram)
+    }
+}
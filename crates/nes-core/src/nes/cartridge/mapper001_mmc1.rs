@@ -1,5 +1,6 @@
 use super::rom::Mirroring;
 use super::{Cartridge, MapperTiming};
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 // MMC1 mapper (iNES mapper #1)
 pub struct Mmc1 {
@@ -170,6 +171,10 @@ impl Cartridge for Mmc1 {
     }
 
     fn ppu_read(&mut self, addr: u16) -> (u8, bool) {
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_peek(&self, addr: u16) -> (u8, bool) {
         let bank_addr = self.ppu_bank_addr(addr) as usize;
         let data;
         if !self.chr_ram.is_empty() {
@@ -203,6 +208,31 @@ impl Cartridge for Mmc1 {
     }
 }
 
+impl SaveState for Mmc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_vec(&self.chr_ram);
+        state.write_vec(&self.prg_ram);
+        state.write_u8(self.shift_reg);
+        state.write_u8(self.shift_count);
+        state.write_u8(self.control);
+        state.write_u8(self.chr_bank0);
+        state.write_u8(self.chr_bank1);
+        state.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_vec_into(&mut self.chr_ram)?;
+        state.read_vec_into(&mut self.prg_ram)?;
+        self.shift_reg = state.read_u8()?;
+        self.shift_count = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.chr_bank0 = state.read_u8()?;
+        self.chr_bank1 = state.read_u8()?;
+        self.prg_bank = state.read_u8()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
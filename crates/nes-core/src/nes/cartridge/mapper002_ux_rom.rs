@@ -1,5 +1,6 @@
 use super::Cartridge;
 use super::rom::Mirroring;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct Mapper002UxRom {
@@ -49,6 +50,10 @@ impl Cartridge for Mapper002UxRom {
     }
 
     fn ppu_read(&mut self, addr: u16) -> (u8, bool) {
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_peek(&self, addr: u16) -> (u8, bool) {
         let addr = addr as usize;
         let bank_size = 0x4000;
         let bank_count = self.prg_bank_count();
@@ -92,3 +97,20 @@ impl Cartridge for Mapper002UxRom {
         self.mirroring.clone()
     }
 }
+
+impl SaveState for Mapper002UxRom {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_is_ram {
+            state.write_vec(&self.chr);
+        }
+        state.write_usize(self.bank_select);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        if self.chr_is_ram {
+            state.read_vec_into(&mut self.chr)?;
+        }
+        self.bank_select = state.read_usize()?;
+        Ok(())
+    }
+}
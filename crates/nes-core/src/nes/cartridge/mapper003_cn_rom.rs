@@ -1,5 +1,6 @@
 use super::Cartridge;
 use super::rom::Mirroring;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 #[derive(Debug)]
 pub struct Mapper003CnRom {
@@ -58,6 +59,10 @@ impl Cartridge for Mapper003CnRom {
     }
 
     fn ppu_read(&mut self, addr: u16) -> (u8, bool) {
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_peek(&self, addr: u16) -> (u8, bool) {
         let addr = addr as usize;
         let prg_size = self.prg_rom.len();
 
@@ -94,3 +99,14 @@ impl Cartridge for Mapper003CnRom {
         self.mirroring.clone()
     }
 }
+
+impl SaveState for Mapper003CnRom {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_usize(self.bank_select);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.bank_select = state.read_usize()?;
+        Ok(())
+    }
+}
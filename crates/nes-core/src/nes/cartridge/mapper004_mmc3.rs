@@ -1,5 +1,6 @@
 use super::Cartridge;
 use super::rom::Mirroring;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Mmc3Revision {
@@ -295,6 +296,10 @@ impl Cartridge for Mmc3 {
     }
 
     fn ppu_read(&mut self, addr: u16) -> (u8, bool) {
+        self.ppu_peek(addr)
+    }
+
+    fn ppu_peek(&self, addr: u16) -> (u8, bool) {
         let i = self.chr_addr(addr);
         (self.chr[i % self.chr.len()], false)
     }
@@ -353,6 +358,58 @@ fn detect_revision(prg_rom: &[u8]) -> Mmc3Revision {
     Mmc3Revision::B
 }
 
+impl SaveState for Mmc3 {
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_is_ram {
+            state.write_vec(&self.chr);
+        }
+        state.write_vec(&self.prg_ram);
+        state.write_u8(self.bank_select);
+        state.write_bytes(&self.bank_registers);
+        state.write_bool(self.prg_mode);
+        state.write_bool(self.chr_mode);
+        state.write_u8(self.irq_latch);
+        state.write_u8(self.irq_counter);
+        state.write_bool(self.irq_reload);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.last_ppu_a12);
+        state.write_u8(self.a12_low_cycles);
+        // Only the $A000 bit is switchable; four-screen boards are fixed
+        state.write_bool(matches!(self.mirroring, Mirroring::Horizontal));
+        state.write_bool(self.prg_ram_enabled);
+        state.write_bool(self.prg_ram_write_protect);
+        state.write_bool(self.irq_pending);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        if self.chr_is_ram {
+            state.read_vec_into(&mut self.chr)?;
+        }
+        state.read_vec_into(&mut self.prg_ram)?;
+        self.bank_select = state.read_u8()?;
+        state.read_bytes(&mut self.bank_registers)?;
+        self.prg_mode = state.read_bool()?;
+        self.chr_mode = state.read_bool()?;
+        self.irq_latch = state.read_u8()?;
+        self.irq_counter = state.read_u8()?;
+        self.irq_reload = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.last_ppu_a12 = state.read_bool()?;
+        self.a12_low_cycles = state.read_u8()?;
+        let horizontal = state.read_bool()?;
+        if !self.mirroring_fixed {
+            self.mirroring = match horizontal {
+                true => Mirroring::Horizontal,
+                false => Mirroring::Vertical,
+            };
+        }
+        self.prg_ram_enabled = state.read_bool()?;
+        self.prg_ram_write_protect = state.read_bool()?;
+        self.irq_pending = state.read_bool()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
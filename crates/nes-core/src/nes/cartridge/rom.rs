@@ -4,12 +4,18 @@ use crate::nes::cartridge::mapper001_mmc1::Mmc1;
 use crate::nes::cartridge::mapper002_ux_rom::Mapper002UxRom;
 use crate::nes::cartridge::mapper003_cn_rom::Mapper003CnRom;
 use crate::nes::cartridge::mapper004_mmc3::Mmc3;
+use crate::nes::controller::ConsoleType;
+use crate::nes::controller::expansion::ExpansionDevice;
+use crate::nes::region::Region;
 use thiserror::Error;
 
 const NES_MAGIC_BYTES: &[u8; 4] = b"NES\x1A";
 const PRG_ROM_PAGE_SIZE: usize = 0x4000;
 const CHR_ROM_PAGE_SIZE: usize = 0x2000;
 
+const INES_VERSION_1: u8 = 0;
+const INES_VERSION_2: u8 = 2;
+
 #[derive(Debug, Error)]
 pub enum RomError {
     #[error("{0}")]
@@ -36,6 +42,12 @@ pub struct Rom {
     pub chr_rom: Vec<u8>,
     pub mapper: u8,
     pub screen_mirroring: Mirroring,
+    /// Timing the header asks for, if it specifies one
+    pub region: Option<Region>,
+    /// Console the header's input device implies, if any
+    pub console: Option<ConsoleType>,
+    /// Famicom expansion port device the header asks for, if any
+    pub expansion: Option<ExpansionDevice>,
 }
 
 impl Rom {
@@ -50,9 +62,10 @@ impl Rom {
 
         // Check iNES version
         let ines_ver = (raw[7] >> 2) & 0b11;
-        if ines_ver != 0 {
-            return Err(RomError::UnsupportedVersion(2));
+        if ines_ver != INES_VERSION_1 && ines_ver != INES_VERSION_2 {
+            return Err(RomError::UnsupportedVersion(ines_ver));
         }
+        let nes2 = ines_ver == INES_VERSION_2;
 
         // Determine mirroring type
         let four_screen = raw[6] & 0b1000 != 0;
@@ -63,19 +76,70 @@ impl Rom {
             (false, false) => Mirroring::Horizontal,
         };
 
-        let prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
-        let chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
+        // NES 2.0 keeps the upper bits of the PRG/CHR page counts in byte 9
+        let (prg_pages_msb, chr_pages_msb) = match nes2 {
+            true => (raw[9] & 0x0F, raw[9] >> 4),
+            false => (0, 0),
+        };
+        if prg_pages_msb == 0x0F || chr_pages_msb == 0x0F {
+            return Err(RomError::InvalidFormat(
+                "Exponent-multiplier ROM sizes are not supported".into(),
+            ));
+        }
+        let prg_pages = ((prg_pages_msb as usize) << 8) | raw[4] as usize;
+        let chr_pages = ((chr_pages_msb as usize) << 8) | raw[5] as usize;
+
+        let prg_rom_size = prg_pages * PRG_ROM_PAGE_SIZE;
+        let chr_rom_size = chr_pages * CHR_ROM_PAGE_SIZE;
+
+        // See: https://www.nesdev.org/wiki/NES_2.0#CPU/PPU_Timing
+        let region = match nes2 {
+            true => match raw[12] & 0b11 {
+                0 => Some(Region::Ntsc),
+                1 => Some(Region::Pal),
+                3 => Some(Region::Dendy),
+                _ => None, // Multi-region
+            },
+            // iNES 1.0 flag 9 is rarely set, so a clear bit doesn't mean NTSC
+            false => (raw[9] & 0b1 != 0).then_some(Region::Pal),
+        };
+
+        // See: https://www.nesdev.org/wiki/NES_2.0#Default_Expansion_Device
+        let (console, expansion) = match nes2 {
+            true => match raw[15] & 0x3F {
+                // Four Score, Power Pad, NES Arkanoid
+                0x02 | 0x0B | 0x0C | 0x0F => (Some(ConsoleType::Nes), None),
+                0x03 => (
+                    Some(ConsoleType::Famicom),
+                    Some(ExpansionDevice::FourPlayers),
+                ),
+                // Family Trainer, Famicom Arkanoid
+                0x0D | 0x0E | 0x10 => (Some(ConsoleType::Famicom), None),
+                0x23 => (
+                    Some(ConsoleType::Famicom),
+                    Some(ExpansionDevice::FamilyBasicKeyboard),
+                ),
+                _ => (None, None),
+            },
+            false => (None, None),
+        };
 
         let skip_trainer = raw[6] & 0b100 != 0;
 
         let prg_rom_start = 16 + if skip_trainer { 512 } else { 0 };
         let chr_rom_start = prg_rom_start + prg_rom_size;
+        if raw.len() < chr_rom_start + chr_rom_size {
+            return Err(RomError::InvalidFormat("ROM file is truncated".into()));
+        }
 
         Ok(Rom {
             prg_rom: raw[prg_rom_start..(prg_rom_start + prg_rom_size)].to_vec(),
             chr_rom: raw[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec(),
             mapper,
             screen_mirroring,
+            region,
+            console,
+            expansion,
         })
     }
 
@@ -84,7 +148,7 @@ impl Rom {
         Self::parse(&vec![]).unwrap()
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "testing-utils"))]
     pub fn new_custom(
         prg_rom: Vec<u8>,
         chr_rom: Vec<u8>,
@@ -96,6 +160,9 @@ impl Rom {
             chr_rom,
             mapper,
             screen_mirroring,
+            region: None,
+            console: None,
+            expansion: None,
         }
     }
 
@@ -131,3 +198,98 @@ impl Rom {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(flags_7: u8, flags_9: u8, flags_12: u8) -> Vec<u8> {
+        let mut raw = b"NES\x1A".to_vec();
+        raw.extend_from_slice(&[1, 1, 0, flags_7, 0, flags_9, 0, 0, flags_12, 0, 0, 0]);
+        raw.extend(std::iter::repeat_n(
+            0,
+            PRG_ROM_PAGE_SIZE + CHR_ROM_PAGE_SIZE,
+        ));
+        raw
+    }
+
+    #[test]
+    fn test_ines_region() {
+        let rom = Rom::parse(&header(0, 0, 0)).unwrap();
+        assert_eq!(rom.region, None);
+        assert_eq!(rom.prg_rom.len(), PRG_ROM_PAGE_SIZE);
+        assert_eq!(rom.chr_rom.len(), CHR_ROM_PAGE_SIZE);
+
+        let rom = Rom::parse(&header(0, 1, 0)).unwrap();
+        assert_eq!(rom.region, Some(Region::Pal));
+    }
+
+    #[test]
+    fn test_nes2_region() {
+        let nes2 = 0b0000_1000;
+        let region = |timing| Rom::parse(&header(nes2, 0, timing)).unwrap().region;
+        assert_eq!(region(0), Some(Region::Ntsc));
+        assert_eq!(region(1), Some(Region::Pal));
+        assert_eq!(region(2), None);
+        assert_eq!(region(3), Some(Region::Dendy));
+    }
+
+    #[test]
+    fn test_nes2_expansion_device() {
+        let input = |device| {
+            let mut raw = header(0b0000_1000, 0, 0);
+            raw[15] = device;
+            let rom = Rom::parse(&raw).unwrap();
+            (rom.console, rom.expansion)
+        };
+        assert_eq!(input(0x01), (None, None));
+        assert_eq!(input(0x02), (Some(ConsoleType::Nes), None));
+        assert_eq!(
+            input(0x03),
+            (
+                Some(ConsoleType::Famicom),
+                Some(ExpansionDevice::FourPlayers)
+            )
+        );
+        assert_eq!(input(0x10), (Some(ConsoleType::Famicom), None));
+        assert_eq!(
+            input(0x23),
+            (
+                Some(ConsoleType::Famicom),
+                Some(ExpansionDevice::FamilyBasicKeyboard)
+            )
+        );
+
+        // Byte 15 is only meaningful in NES 2.0 headers
+        let mut raw = header(0, 0, 0);
+        raw[15] = 0x23;
+        assert_eq!(Rom::parse(&raw).unwrap().expansion, None);
+    }
+
+    #[test]
+    fn test_nes2_size_msb() {
+        let mut raw = header(0b0000_1000, 0x01, 0);
+        raw.extend(std::iter::repeat_n(0, 256 * PRG_ROM_PAGE_SIZE));
+        let rom = Rom::parse(&raw).unwrap();
+        assert_eq!(rom.prg_rom.len(), 257 * PRG_ROM_PAGE_SIZE);
+
+        let raw = header(0b0000_1000, 0x0F, 0);
+        assert!(matches!(Rom::parse(&raw), Err(RomError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_rejects_unknown_versions() {
+        let raw = header(0b0000_0100, 0, 0);
+        assert!(matches!(
+            Rom::parse(&raw),
+            Err(RomError::UnsupportedVersion(1))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_truncated_rom() {
+        let mut raw = header(0, 0, 0);
+        raw.truncate(raw.len() - 1);
+        assert!(matches!(Rom::parse(&raw), Err(RomError::InvalidFormat(_))));
+    }
+}
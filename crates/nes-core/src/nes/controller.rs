@@ -1,6 +1,117 @@
+// See: https://www.nesdev.org/wiki/Input_devices
+//      https://www.nesdev.org/wiki/Standard_controller#Hardware
+
+pub mod expansion;
+pub mod family_keyboard;
+pub mod four_score;
 pub mod joypad;
+pub mod power_pad;
+pub mod vaus;
+pub mod zapper;
+
+use crate::nes::ppu::PPU;
+use crate::nes::save_state::SaveState;
+use four_score::{FourScore, FourScoreKind};
+use joypad::Joypad;
+use power_pad::{FamilyTrainer, PowerPad};
+use std::any::Any;
+use vaus::Vaus;
+use zapper::Zapper;
+
+/// A device plugged into a controller port
+///
+/// Writes to $4016 set the OUT0-OUT2 latch, which every port sees. Reading
+/// $4016 (port 1) or $4017 (port 2) returns whatever the device drives on
+/// the D0-D4 data lines
+pub trait NesController: SaveState {
+    /// New OUT0-OUT2 latch bits. OUT0 is the strobe standard controllers use
+    fn write(&mut self, out: u8);
+
+    /// D0-D4 in the low five bits. Light guns see the screen through `ppu`
+    fn read(&mut self, ppu: &PPU) -> u8;
+
+    /// Standard controller buttons from `NES::run_frame()`, in
+    /// `JoypadButton` order. Devices without them ignore this
+    fn set_buttons(&mut self, _buttons: u8) {}
+
+    fn kind(&self) -> PortDevice;
+
+    /// For reaching device-specific input, like the Zapper's aim
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Which console is being emulated. Only the Famicom has the expansion port
+/// and the microphone on controller 2
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ConsoleType {
+    #[default]
+    Nes,
+    Famicom,
+}
+
+impl ConsoleType {
+    pub const ALL: [ConsoleType; 2] = [ConsoleType::Nes, ConsoleType::Famicom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConsoleType::Nes => "NES",
+            ConsoleType::Famicom => "Famicom",
+        }
+    }
+}
+
+/// The devices that can be plugged into a controller port
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PortDevice {
+    #[default]
+    Joypad,
+    Zapper,
+    /// NES 4-player adapter. Plug it into both ports
+    FourScore,
+    /// Famicom-style 4-player adapter, with players 3 and 4 on D1. Plug it
+    /// into both ports
+    FamicomFourPlayer,
+    /// Arkanoid paddle controller
+    Vaus,
+    PowerPad,
+    /// The Famicom version of the Power Pad mat
+    FamilyTrainer,
+}
+
+impl PortDevice {
+    /// In save state order, so new devices go at the end
+    pub const ALL: [PortDevice; 7] = [
+        PortDevice::Joypad,
+        PortDevice::Zapper,
+        PortDevice::FourScore,
+        PortDevice::FamicomFourPlayer,
+        PortDevice::Vaus,
+        PortDevice::PowerPad,
+        PortDevice::FamilyTrainer,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PortDevice::Joypad => "Controller",
+            PortDevice::Zapper => "Zapper",
+            PortDevice::FourScore => "Four Score",
+            PortDevice::FamicomFourPlayer => "Famicom 4-player adapter",
+            PortDevice::Vaus => "Arkanoid paddle",
+            PortDevice::PowerPad => "Power Pad",
+            PortDevice::FamilyTrainer => "Family Trainer",
+        }
+    }
 
-pub trait NesController {
-    fn read(&mut self) -> u8;
-    fn write(&mut self, data: u8);
+    /// A fresh device for `port` (0 or 1)
+    pub fn create(&self, port: usize) -> Box<dyn NesController> {
+        match self {
+            PortDevice::Joypad => Box::new(Joypad::new()),
+            PortDevice::Zapper => Box::new(Zapper::new()),
+            PortDevice::FourScore => Box::new(FourScore::new(port, FourScoreKind::Nes)),
+            PortDevice::FamicomFourPlayer => Box::new(FourScore::new(port, FourScoreKind::Famicom)),
+            PortDevice::Vaus => Box::new(Vaus::new()),
+            PortDevice::PowerPad => Box::new(PowerPad::new()),
+            PortDevice::FamilyTrainer => Box::new(FamilyTrainer::new()),
+        }
+    }
 }
@@ -0,0 +1,166 @@
+// See: https://www.nesdev.org/wiki/Expansion_port
+//      https://www.nesdev.org/wiki/Four_player_adapters
+
+use crate::nes::controller::family_keyboard::FamilyKeyboard;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+use std::any::Any;
+
+/// A device on the Famicom's expansion port
+///
+/// It sees the same OUT0-OUT2 latch as the controller ports, and drives D1
+/// on $4016 and D1-D4 on $4017. The Famicom's own controllers are hardwired
+/// and stay on the controller ports
+pub trait ExpansionController: SaveState {
+    fn write(&mut self, out: u8);
+
+    /// Lines this device drives on a read of $4016 (`port` 0) or $4017
+    /// (`port` 1), in place. Bit 0 is never used
+    fn read(&mut self, port: usize) -> u8;
+
+    fn kind(&self) -> ExpansionDevice;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// The devices that can be plugged into the expansion port
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExpansionDevice {
+    /// Extra controllers for players 3 and 4, read on D1. Covers the 3 and
+    /// 4-player adapters and controllers plugged straight into the port
+    FourPlayers,
+    /// Hori 4 Players Adapter in 4-player mode, which adds a signature
+    Hori4Players,
+    FamilyBasicKeyboard,
+}
+
+impl ExpansionDevice {
+    /// In save state order, so new devices go at the end
+    pub const ALL: [ExpansionDevice; 3] = [
+        ExpansionDevice::FourPlayers,
+        ExpansionDevice::Hori4Players,
+        ExpansionDevice::FamilyBasicKeyboard,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExpansionDevice::FourPlayers => "3/4-player adapter",
+            ExpansionDevice::Hori4Players => "Hori 4 Players Adapter",
+            ExpansionDevice::FamilyBasicKeyboard => "Family BASIC keyboard",
+        }
+    }
+
+    pub fn create(&self) -> Box<dyn ExpansionController> {
+        match self {
+            ExpansionDevice::FourPlayers => Box::new(FourPlayers::new(false)),
+            ExpansionDevice::Hori4Players => Box::new(FourPlayers::new(true)),
+            ExpansionDevice::FamilyBasicKeyboard => Box::new(FamilyKeyboard::new()),
+        }
+    }
+}
+
+/// Reads 17-24 of each line identify the Hori adapter, one bit set in each.
+/// Swapped compared to the Four Score
+const HORI_SIGNATURES: [u8; 2] = [0b0000_0100, 0b0000_1000];
+
+/// Controllers for players 3 and 4 on the expansion port, shifted out on D1
+/// of $4016 and $4017 alongside players 1 and 2 on D0
+pub struct FourPlayers {
+    hori: bool,
+    /// Players 3 and 4, in `JoypadButton` order
+    buttons: [u8; 2],
+    read_index: [u8; 2],
+    strobe: bool,
+}
+
+impl FourPlayers {
+    pub fn new(hori: bool) -> Self {
+        Self {
+            hori,
+            buttons: [0; 2],
+            read_index: [0; 2],
+            strobe: false,
+        }
+    }
+
+    /// `controller` 0 is player 3, 1 is player 4
+    pub fn set_controller(&mut self, controller: usize, buttons: u8) {
+        self.buttons[controller] = buttons;
+    }
+}
+
+impl ExpansionController for FourPlayers {
+    fn write(&mut self, out: u8) {
+        self.strobe = out & 1 == 1;
+        if self.strobe {
+            self.read_index = [0; 2];
+        }
+    }
+
+    fn read(&mut self, port: usize) -> u8 {
+        let index = self.read_index[port];
+        let bit = match (index, self.hori) {
+            (0..8, _) => (self.buttons[port] >> index) & 1,
+            (8..16, true) => 0,
+            (16..24, true) => (HORI_SIGNATURES[port] >> (index - 16)) & 1,
+            _ => 1,
+        };
+        if !self.strobe && index < 24 {
+            self.read_index[port] += 1;
+        }
+        bit << 1
+    }
+
+    fn kind(&self) -> ExpansionDevice {
+        match self.hori {
+            true => ExpansionDevice::Hori4Players,
+            false => ExpansionDevice::FourPlayers,
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SaveState for FourPlayers {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.buttons);
+        state.write_bytes(&self.read_index);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes(&mut self.buttons)?;
+        state.read_bytes(&mut self.read_index)?;
+        self.strobe = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_players_3_and_4_on_d1() {
+        for hori in [false, true] {
+            let mut adapter = FourPlayers::new(hori);
+            adapter.set_controller(0, 0b0000_0011);
+            adapter.set_controller(1, 0b1000_0000);
+            adapter.write(1);
+            adapter.write(0);
+
+            let p3: Vec<u8> = (0..24).map(|_| adapter.read(0) >> 1).collect();
+            let p4: Vec<u8> = (0..24).map(|_| adapter.read(1) >> 1).collect();
+            assert_eq!(p3[..8], [1, 1, 0, 0, 0, 0, 0, 0]);
+            assert_eq!(p4[..8], [0, 0, 0, 0, 0, 0, 0, 1]);
+            match hori {
+                true => {
+                    assert_eq!(p3[8..], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0]);
+                    assert_eq!(p4[16..], [0, 0, 0, 1, 0, 0, 0, 0]);
+                }
+                false => assert!(p3[8..].iter().all(|&bit| bit == 1)),
+            }
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// See: https://www.nesdev.org/wiki/Family_BASIC_Keyboard
+
+use crate::nes::controller::expansion::{ExpansionController, ExpansionDevice};
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+use std::any::Any;
+
+/// Keys on the Family BASIC keyboard
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FamilyKey {
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Minus,
+    Caret,
+    Yen,
+    Stop,
+    Escape,
+    At,
+    LeftBracket,
+    RightBracket,
+    Return,
+    Ctrl,
+    Semicolon,
+    Colon,
+    Kana,
+    LeftShift,
+    Comma,
+    Period,
+    Slash,
+    Underscore,
+    RightShift,
+    Graph,
+    Space,
+    ClrHome,
+    Insert,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl FamilyKey {
+    /// Every key, in declaration order, so `ALL[key as usize] == key`
+    pub const ALL: [FamilyKey; 72] = [
+        FamilyKey::F1,
+        FamilyKey::F2,
+        FamilyKey::F3,
+        FamilyKey::F4,
+        FamilyKey::F5,
+        FamilyKey::F6,
+        FamilyKey::F7,
+        FamilyKey::F8,
+        FamilyKey::Num0,
+        FamilyKey::Num1,
+        FamilyKey::Num2,
+        FamilyKey::Num3,
+        FamilyKey::Num4,
+        FamilyKey::Num5,
+        FamilyKey::Num6,
+        FamilyKey::Num7,
+        FamilyKey::Num8,
+        FamilyKey::Num9,
+        FamilyKey::A,
+        FamilyKey::B,
+        FamilyKey::C,
+        FamilyKey::D,
+        FamilyKey::E,
+        FamilyKey::F,
+        FamilyKey::G,
+        FamilyKey::H,
+        FamilyKey::I,
+        FamilyKey::J,
+        FamilyKey::K,
+        FamilyKey::L,
+        FamilyKey::M,
+        FamilyKey::N,
+        FamilyKey::O,
+        FamilyKey::P,
+        FamilyKey::Q,
+        FamilyKey::R,
+        FamilyKey::S,
+        FamilyKey::T,
+        FamilyKey::U,
+        FamilyKey::V,
+        FamilyKey::W,
+        FamilyKey::X,
+        FamilyKey::Y,
+        FamilyKey::Z,
+        FamilyKey::Minus,
+        FamilyKey::Caret,
+        FamilyKey::Yen,
+        FamilyKey::Stop,
+        FamilyKey::Escape,
+        FamilyKey::At,
+        FamilyKey::LeftBracket,
+        FamilyKey::RightBracket,
+        FamilyKey::Return,
+        FamilyKey::Ctrl,
+        FamilyKey::Semicolon,
+        FamilyKey::Colon,
+        FamilyKey::Kana,
+        FamilyKey::LeftShift,
+        FamilyKey::Comma,
+        FamilyKey::Period,
+        FamilyKey::Slash,
+        FamilyKey::Underscore,
+        FamilyKey::RightShift,
+        FamilyKey::Graph,
+        FamilyKey::Space,
+        FamilyKey::ClrHome,
+        FamilyKey::Insert,
+        FamilyKey::Delete,
+        FamilyKey::Up,
+        FamilyKey::Down,
+        FamilyKey::Left,
+        FamilyKey::Right,
+    ];
+}
+
+/// Keys by row, then by the column select bit, then by data line from D1 up
+const MATRIX: [[[FamilyKey; 4]; 2]; 9] = {
+    use FamilyKey::*;
+    [
+        [
+            [F8, Return, LeftBracket, RightBracket],
+            [Kana, RightShift, Yen, Stop],
+        ],
+        [
+            [F7, At, Colon, Semicolon],
+            [Underscore, Slash, Minus, Caret],
+        ],
+        [[F6, O, L, K], [Period, Comma, P, Num0]],
+        [[F5, I, U, J], [M, N, Num9, Num8]],
+        [[F4, Y, G, H], [B, V, Num7, Num6]],
+        [[F3, T, R, D], [F, C, Num5, Num4]],
+        [[F2, W, S, A], [X, Z, E, Num3]],
+        [[F1, Escape, Q, Ctrl], [LeftShift, Graph, Num1, Num2]],
+        [[ClrHome, Up, Right, Left], [Down, Space, Delete, Insert]],
+    ]
+};
+
+/// Family BASIC keyboard, scanned through the expansion port
+///
+/// With OUT2 set the keyboard is enabled: OUT0 returns to the first row,
+/// OUT1 picks which half of the row to read, and clearing OUT1 moves on to
+/// the next row. $4017 D1-D4 then report four keys, low meaning pressed
+pub struct FamilyKeyboard {
+    pressed: Vec<FamilyKey>,
+    row: u8,
+    column: u8,
+    enabled: bool,
+}
+
+impl Default for FamilyKeyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FamilyKeyboard {
+    pub fn new() -> Self {
+        Self {
+            pressed: Vec::new(),
+            row: 0,
+            column: 0,
+            enabled: false,
+        }
+    }
+
+    /// Keys held down, replacing the previous set
+    pub fn set_pressed(&mut self, keys: &[FamilyKey]) {
+        self.pressed = keys.to_vec();
+    }
+}
+
+impl ExpansionController for FamilyKeyboard {
+    fn write(&mut self, out: u8) {
+        let previous_column = self.column;
+        self.column = (out >> 1) & 1;
+        self.enabled = out & 0b100 != 0;
+        if self.enabled {
+            if self.column == 0 && previous_column == 1 {
+                self.row = (self.row + 1) % 10;
+            }
+            if out & 1 != 0 {
+                self.row = 0;
+            }
+        }
+    }
+
+    fn read(&mut self, port: usize) -> u8 {
+        if port != 1 || !self.enabled {
+            return 0;
+        }
+        // Row 9 is past the last key, so nothing is pressed
+        let Some(row) = MATRIX.get(self.row as usize) else {
+            return 0b0001_1110;
+        };
+        row[self.column as usize]
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| !self.pressed.contains(key))
+            .fold(0, |lines, (i, _)| lines | 1 << (i + 1))
+    }
+
+    fn kind(&self) -> ExpansionDevice {
+        ExpansionDevice::FamilyBasicKeyboard
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Held keys are input, set every frame, so they aren't saved
+impl SaveState for FamilyKeyboard {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.row);
+        state.write_u8(self.column);
+        state.write_bool(self.enabled);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.row = state.read_u8()?;
+        self.column = state.read_u8()?;
+        self.enabled = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scan the whole matrix the way Family BASIC does, returning the
+    /// inverted D1-D4 nibble for each half row
+    fn scan(keyboard: &mut FamilyKeyboard) -> Vec<u8> {
+        keyboard.write(0b101);
+        let mut halves = Vec::new();
+        for _ in 0..9 {
+            keyboard.write(0b100);
+            halves.push(!keyboard.read(1) >> 1 & 0xF);
+            keyboard.write(0b110);
+            halves.push(!keyboard.read(1) >> 1 & 0xF);
+        }
+        halves
+    }
+
+    #[test]
+    fn test_matrix_scan() {
+        let mut keyboard = FamilyKeyboard::new();
+        keyboard.set_pressed(&[FamilyKey::Return, FamilyKey::Q, FamilyKey::Space]);
+
+        let halves = scan(&mut keyboard);
+        let mut expected = [0u8; 18];
+        expected[0] = 0b0010; // Return
+        expected[14] = 0b0100; // Q
+        expected[17] = 0b0010; // Space
+        assert_eq!(halves, expected);
+
+        // Disabled, or read through $4016, it's silent
+        keyboard.write(0b000);
+        assert_eq!(keyboard.read(1), 0);
+        keyboard.write(0b101);
+        assert_eq!(keyboard.read(0), 0);
+    }
+}
@@ -0,0 +1,149 @@
+// See: https://www.nesdev.org/wiki/Four_player_adapters
+
+use super::{NesController, PortDevice};
+use crate::nes::ppu::PPU;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+use std::any::Any;
+
+/// Reads 17-24 on each port identify the Four Score, one bit set in each
+const NES_SIGNATURES: [u8; 2] = [0b0000_1000, 0b0000_0100];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FourScoreKind {
+    /// Both controllers on D0, one after the other, then a signature
+    Nes,
+    /// First controller on D0, second on D1, read side by side
+    Famicom,
+}
+
+/// Four player adapter. Each port carries two controllers: players 1 and 3
+/// on port 1, players 2 and 4 on port 2
+pub struct FourScore {
+    kind: FourScoreKind,
+    /// 0 or 1, which picks the signature
+    port: usize,
+    /// Buttons for each controller, in `JoypadButton` order
+    buttons: [u8; 2],
+    read_index: u8,
+    strobe: bool,
+}
+
+impl FourScore {
+    pub fn new(port: usize, kind: FourScoreKind) -> Self {
+        Self {
+            kind,
+            port,
+            buttons: [0; 2],
+            read_index: 0,
+            strobe: false,
+        }
+    }
+
+    /// `controller` 0 is player 1 or 2, `controller` 1 is player 3 or 4
+    pub fn set_controller(&mut self, controller: usize, buttons: u8) {
+        self.buttons[controller] = buttons;
+    }
+}
+
+/// Bit `index` of an 8-button report, then 1s once it runs out
+fn report_bit(buttons: u8, index: u8) -> u8 {
+    match index {
+        0..8 => (buttons >> index) & 1,
+        _ => 1,
+    }
+}
+
+impl NesController for FourScore {
+    fn write(&mut self, out: u8) {
+        self.strobe = out & 1 == 1;
+        if self.strobe {
+            self.read_index = 0;
+        }
+    }
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        let index = self.read_index;
+        let value = match self.kind {
+            FourScoreKind::Nes => match index {
+                0..8 => report_bit(self.buttons[0], index),
+                8..16 => report_bit(self.buttons[1], index - 8),
+                16..24 => (NES_SIGNATURES[self.port] >> (index - 16)) & 1,
+                _ => 1,
+            },
+            FourScoreKind::Famicom => {
+                report_bit(self.buttons[0], index) | report_bit(self.buttons[1], index) << 1
+            }
+        };
+        if !self.strobe && index < 24 {
+            self.read_index += 1;
+        }
+        value
+    }
+
+    fn set_buttons(&mut self, buttons: u8) {
+        self.buttons[0] = buttons;
+    }
+
+    fn kind(&self) -> PortDevice {
+        match self.kind {
+            FourScoreKind::Nes => PortDevice::FourScore,
+            FourScoreKind::Famicom => PortDevice::FamicomFourPlayer,
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SaveState for FourScore {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.buttons);
+        state.write_u8(self.read_index);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes(&mut self.buttons)?;
+        self.read_index = state.read_u8()?;
+        self.strobe = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(adapter: &mut FourScore, count: usize) -> Vec<u8> {
+        let ppu = PPU::new();
+        adapter.write(1);
+        adapter.write(0);
+        (0..count).map(|_| adapter.read(&ppu)).collect()
+    }
+
+    #[test]
+    fn test_nes_report_and_signature() {
+        let mut port1 = FourScore::new(0, FourScoreKind::Nes);
+        port1.set_buttons(0b0000_0001);
+        port1.set_controller(1, 0b1000_0000);
+        let bits = read_all(&mut port1, 26);
+        assert_eq!(bits[0..8], [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bits[8..16], [0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(bits[16..24], [0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(bits[24..], [1, 1]);
+
+        let mut port2 = FourScore::new(1, FourScoreKind::Nes);
+        let bits = read_all(&mut port2, 24);
+        assert_eq!(bits[16..24], [0, 0, 1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_famicom_reads_side_by_side() {
+        let mut adapter = FourScore::new(0, FourScoreKind::Famicom);
+        adapter.set_buttons(0b0000_0101);
+        adapter.set_controller(1, 0b0000_0110);
+        let bits = read_all(&mut adapter, 9);
+        assert_eq!(bits, [0b01, 0b10, 0b11, 0, 0, 0, 0, 0, 0b11]);
+    }
+}
@@ -1,7 +1,10 @@
 // See: https://www.nesdev.org/wiki/Controller_reading
 
-use super::NesController;
+use super::{NesController, PortDevice};
+use crate::nes::ppu::PPU;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 use bitflags::bitflags;
+use std::any::Any;
 
 bitflags! {
        #[derive(Copy, Clone, Debug)]
@@ -24,25 +27,29 @@ pub struct Joypad {
 }
 
 impl NesController for Joypad {
-    fn read(&mut self) -> u8 {
-        if self.button_index > 7 {
-            return 1;
-        }
-        let status = (self.buttons.bits() >> self.button_index) & 0b1;
-        if !self.strobe && self.button_index <= 7 {
-            self.button_index += 1;
-        }
-
-        status
-    }
-
-    fn write(&mut self, data: u8) {
-        self.strobe = data & 0b1 == 1;
+    fn write(&mut self, out: u8) {
+        self.strobe = out & 0b1 == 1;
         match self.strobe {
             true => self.button_index = 0,
             _ => {}
         }
     }
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        self.read_bit()
+    }
+
+    fn set_buttons(&mut self, buttons: u8) {
+        self.buttons = JoypadButton::from_bits_truncate(buttons);
+    }
+
+    fn kind(&self) -> PortDevice {
+        PortDevice::Joypad
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 impl Joypad {
@@ -58,8 +65,33 @@ impl Joypad {
         self.buttons.set(button.clone(), state);
     }
 
-    pub fn set_buttons(&mut self, buttons: u8) {
-        self.buttons = JoypadButton::from_bits_truncate(buttons);
+    /// Next button in the report on D0, or 1 once all eight have been read.
+    /// Also used by adapters that carry several controllers
+    pub(crate) fn read_bit(&mut self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+        let status = (self.buttons.bits() >> self.button_index) & 0b1;
+        if !self.strobe && self.button_index <= 7 {
+            self.button_index += 1;
+        }
+
+        status
+    }
+}
+
+impl SaveState for Joypad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.buttons.bits());
+        state.write_u8(self.button_index);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.buttons = JoypadButton::from_bits_retain(state.read_u8()?);
+        self.button_index = state.read_u8()?;
+        self.strobe = state.read_bool()?;
+        Ok(())
     }
 }
 
@@ -67,6 +99,12 @@ impl Joypad {
 mod tests {
     use super::*;
 
+    impl Joypad {
+        fn read(&mut self) -> u8 {
+            self.read_bit()
+        }
+    }
+
     #[test]
     fn test_joypad_default_state() {
         let joypad = Joypad::new();
@@ -0,0 +1,221 @@
+// See: https://www.nesdev.org/wiki/Power_Pad
+//      https://www.nesdev.org/wiki/Family_Trainer_Mat
+
+use super::{NesController, PortDevice};
+use crate::nes::ppu::PPU;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+use std::any::Any;
+
+/// Whether mat button `number` (1-12, as printed on side B) is in `pressed`,
+/// where bit 0 is button 1
+fn pressed(pressed: u16, number: usize) -> u8 {
+    (pressed >> (number - 1)) as u8 & 1
+}
+
+/// Power Pad exercise mat. The strobe latches all 12 buttons, which shift
+/// out on D3 and D4 at the same time, 1 meaning pressed
+pub struct PowerPad {
+    /// Bit 0 is button 1
+    pressed: u16,
+    /// Buttons 2, 1, 5, 9, 6, 10, 11, 7 from bit 0 up
+    d3_shift: u8,
+    /// Buttons 4, 3, 12, 8, then 1s
+    d4_shift: u8,
+    strobe: bool,
+}
+
+impl Default for PowerPad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PowerPad {
+    pub fn new() -> Self {
+        Self {
+            pressed: 0,
+            d3_shift: 0,
+            d4_shift: 0,
+            strobe: false,
+        }
+    }
+
+    /// Bit 0 is button 1, bit 11 is button 12
+    pub fn set_pressed(&mut self, pressed: u16) {
+        self.pressed = pressed;
+    }
+
+    fn latch(&mut self) {
+        let order_d3 = [2, 1, 5, 9, 6, 10, 11, 7];
+        let order_d4 = [4, 3, 12, 8];
+        self.d3_shift = order_d3
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &n)| bits | pressed(self.pressed, n) << i);
+        self.d4_shift = order_d4
+            .iter()
+            .enumerate()
+            .fold(0xF0, |bits, (i, &n)| bits | pressed(self.pressed, n) << i);
+    }
+}
+
+impl NesController for PowerPad {
+    fn write(&mut self, out: u8) {
+        self.strobe = out & 1 == 1;
+        if self.strobe {
+            self.latch();
+        }
+    }
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        if self.strobe {
+            self.latch();
+        }
+        let value = (self.d3_shift & 1) << 3 | (self.d4_shift & 1) << 4;
+        if !self.strobe {
+            self.d3_shift = self.d3_shift >> 1 | 0x80;
+            self.d4_shift = self.d4_shift >> 1 | 0x80;
+        }
+        value
+    }
+
+    fn kind(&self) -> PortDevice {
+        PortDevice::PowerPad
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SaveState for PowerPad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.pressed);
+        state.write_u8(self.d3_shift);
+        state.write_u8(self.d4_shift);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.pressed = state.read_u16()?;
+        self.d3_shift = state.read_u8()?;
+        self.d4_shift = state.read_u8()?;
+        self.strobe = state.read_bool()?;
+        Ok(())
+    }
+}
+
+/// The same 12-button mat on the Famicom. Instead of shifting out, it's
+/// scanned a row at a time: OUT0-OUT2 pick rows by going low (OUT2 for
+/// buttons 1-4, OUT1 for 5-8, OUT0 for 9-12), and D1-D4 report the row's
+/// four columns, low meaning pressed
+pub struct FamilyTrainer {
+    /// Bit 0 is button 1
+    pressed: u16,
+    out: u8,
+}
+
+impl Default for FamilyTrainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FamilyTrainer {
+    pub fn new() -> Self {
+        Self {
+            pressed: 0,
+            out: 0b111,
+        }
+    }
+
+    /// Bit 0 is button 1, bit 11 is button 12
+    pub fn set_pressed(&mut self, pressed: u16) {
+        self.pressed = pressed;
+    }
+}
+
+impl NesController for FamilyTrainer {
+    fn write(&mut self, out: u8) {
+        self.out = out & 0b111;
+    }
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        let mut columns = 0u8;
+        for row in 0..3 {
+            let selected = (self.out >> (2 - row)) & 1 == 0;
+            if selected {
+                for column in 0..4 {
+                    columns |= pressed(self.pressed, row * 4 + column + 1) << (4 - column);
+                }
+            }
+        }
+        columns ^ 0b0001_1110
+    }
+
+    fn kind(&self) -> PortDevice {
+        PortDevice::FamilyTrainer
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SaveState for FamilyTrainer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.pressed);
+        state.write_u8(self.out);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.pressed = state.read_u16()?;
+        self.out = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bit mask for mat buttons, numbered from 1
+    fn buttons(numbers: &[usize]) -> u16 {
+        numbers.iter().fold(0, |mask, n| mask | 1 << (n - 1))
+    }
+
+    #[test]
+    fn test_power_pad_serial_order() {
+        let ppu = PPU::new();
+        let mut pad = PowerPad::new();
+        pad.set_pressed(buttons(&[1, 4, 7, 12]));
+        pad.write(1);
+        pad.write(0);
+
+        let reads: Vec<u8> = (0..9).map(|_| pad.read(&ppu)).collect();
+        let d3: Vec<u8> = reads.iter().map(|r| r >> 3 & 1).collect();
+        let d4: Vec<u8> = reads.iter().map(|r| r >> 4 & 1).collect();
+        assert_eq!(d3, [0, 1, 0, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(d4, [1, 0, 1, 0, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_family_trainer_row_scan() {
+        let ppu = PPU::new();
+        let mut mat = FamilyTrainer::new();
+        mat.set_pressed(buttons(&[1, 6, 12]));
+
+        // Nothing selected
+        assert_eq!(mat.read(&ppu), 0b0001_1110);
+
+        // Buttons 1-4
+        mat.write(0b011);
+        assert_eq!(mat.read(&ppu), 0b0000_1110);
+        // Buttons 5-8
+        mat.write(0b101);
+        assert_eq!(mat.read(&ppu), 0b0001_0110);
+        // Buttons 9-12
+        mat.write(0b110);
+        assert_eq!(mat.read(&ppu), 0b0001_1100);
+    }
+}
@@ -0,0 +1,114 @@
+// See: https://www.nesdev.org/wiki/Arkanoid_controller
+
+use super::{NesController, PortDevice};
+use crate::nes::ppu::PPU;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+use std::any::Any;
+
+/// Knob readings with the paddle turned fully left and right. The
+/// potentiometer doesn't cover the whole 8-bit range
+pub const KNOB_MIN: u8 = 0x62;
+pub const KNOB_MAX: u8 = 0xF2;
+
+/// Arkanoid's "Vaus" paddle controller, for port 2
+///
+/// The strobe latches the knob position, which is then shifted out on D4
+/// most significant bit first and inverted. The fire button is on D3
+pub struct Vaus {
+    position: u8,
+    button: bool,
+    shift: u8,
+    strobe: bool,
+}
+
+impl Default for Vaus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vaus {
+    pub fn new() -> Self {
+        Self {
+            position: KNOB_MIN,
+            button: false,
+            shift: 0,
+            strobe: false,
+        }
+    }
+
+    /// Knob position, clamped to `KNOB_MIN..=KNOB_MAX`
+    pub fn set_position(&mut self, position: u8) {
+        self.position = position.clamp(KNOB_MIN, KNOB_MAX);
+    }
+
+    pub fn set_button(&mut self, pressed: bool) {
+        self.button = pressed;
+    }
+}
+
+impl NesController for Vaus {
+    fn write(&mut self, out: u8) {
+        self.strobe = out & 1 == 1;
+        if self.strobe {
+            self.shift = self.position;
+        }
+    }
+
+    fn read(&mut self, _ppu: &PPU) -> u8 {
+        let value = (!self.shift >> 7 & 1) << 4 | (self.button as u8) << 3;
+        match self.strobe {
+            true => self.shift = self.position,
+            false => self.shift <<= 1,
+        }
+        value
+    }
+
+    fn kind(&self) -> PortDevice {
+        PortDevice::Vaus
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SaveState for Vaus {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.position);
+        state.write_bool(self.button);
+        state.write_u8(self.shift);
+        state.write_bool(self.strobe);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.position = state.read_u8()?;
+        self.button = state.read_bool()?;
+        self.shift = state.read_u8()?;
+        self.strobe = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knob_shifts_out_inverted() {
+        let ppu = PPU::new();
+        let mut vaus = Vaus::new();
+        vaus.set_position(0b1010_1100);
+        vaus.set_button(true);
+        vaus.write(1);
+        vaus.write(0);
+
+        let bits: Vec<u8> = (0..9).map(|_| vaus.read(&ppu)).collect();
+        let knob: Vec<u8> = bits.iter().map(|bit| bit >> 4).collect();
+        assert_eq!(knob, [0, 1, 0, 1, 0, 0, 1, 1, 1]);
+        assert!(bits.iter().all(|bit| bit & 0b0000_1000 != 0));
+
+        vaus.set_position(0);
+        assert_eq!(vaus.position, KNOB_MIN);
+    }
+}
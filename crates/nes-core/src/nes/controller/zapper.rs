@@ -0,0 +1,180 @@
+// See: https://www.nesdev.org/wiki/Zapper
+
+use super::{NesController, PortDevice};
+use crate::nes::ppu::PPU;
+use crate::nes::ppu::consts::NES_SYSTEM_PALETTE;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+use std::any::Any;
+
+/// Scanlines the light sensor keeps reporting light after the beam passes a
+/// bright pixel. Real Zappers hold the signal for roughly 10-25 scanlines
+const LIGHT_SCANLINES: usize = 20;
+
+/// Luminance (0-255) a pixel needs for the sensor to notice it
+const LIGHT_THRESHOLD: u32 = 85;
+
+/// The sensor sees a small patch of screen, not a single pixel
+const SENSE_RADIUS: isize = 1;
+
+/// Light gun, plugged into controller port 2 and read through $4017
+///
+/// Bit 3 is low while the sensor sees light, and bit 4 is high while the
+/// trigger is pulled. The sensor is sampled from what the PPU has drawn so
+/// far this frame, so it only sees a pixel once the beam has passed it
+pub struct Zapper {
+    /// Screen pixel the gun is pointed at, or `None` when aimed off-screen
+    aim: Option<(u8, u8)>,
+    trigger: bool,
+}
+
+impl Default for Zapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Self {
+            aim: None,
+            trigger: false,
+        }
+    }
+
+    /// `y` beyond the 240 visible scanlines counts as off-screen
+    pub fn set_aim(&mut self, aim: Option<(u8, u8)>) {
+        self.aim = aim.filter(|&(_, y)| y < 240);
+    }
+
+    pub fn set_trigger(&mut self, pulled: bool) {
+        self.trigger = pulled;
+    }
+
+    fn light_detected(&self, ppu: &PPU) -> bool {
+        let Some((x, y)) = self.aim else {
+            return false;
+        };
+
+        for dy in -SENSE_RADIUS..=SENSE_RADIUS {
+            for dx in -SENSE_RADIUS..=SENSE_RADIUS {
+                let px = x as isize + dx;
+                let py = y as isize + dy;
+                if !(0..256).contains(&px) || !(0..240).contains(&py) {
+                    continue;
+                }
+                let (px, py) = (px as usize, py as usize);
+
+                // Pixel x is drawn on dot x + 1
+                let drawn = ppu.scanline > py || (ppu.scanline == py && ppu.cycles > px + 1);
+                let fresh = ppu.scanline.saturating_sub(py) <= LIGHT_SCANLINES;
+                if drawn && fresh && luminance(ppu.frame_buffer[py * 256 + px]) >= LIGHT_THRESHOLD {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl NesController for Zapper {
+    fn write(&mut self, _out: u8) {}
+
+    fn read(&mut self, ppu: &PPU) -> u8 {
+        let light = match self.light_detected(ppu) {
+            true => 0,
+            false => 1,
+        };
+        light << 3 | (self.trigger as u8) << 4
+    }
+
+    fn kind(&self) -> PortDevice {
+        PortDevice::Zapper
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl SaveState for Zapper {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.aim.is_some());
+        let (x, y) = self.aim.unwrap_or_default();
+        state.write_u8(x);
+        state.write_u8(y);
+        state.write_bool(self.trigger);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let aimed = state.read_bool()?;
+        let aim = (state.read_u8()?, state.read_u8()?);
+        self.aim = aimed.then_some(aim);
+        self.trigger = state.read_bool()?;
+        Ok(())
+    }
+}
+
+fn luminance(color: u16) -> u32 {
+    let (r, g, b) = NES_SYSTEM_PALETTE[(color & 0x1FF) as usize];
+    (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: u16 = 0x30;
+    const BLACK: u16 = 0x0F;
+
+    fn ppu_with_box(x: usize, y: usize) -> PPU {
+        let mut ppu = PPU::new();
+        ppu.frame_buffer = [BLACK; 256 * 240];
+        for row in y..y + 8 {
+            for col in x..x + 8 {
+                ppu.frame_buffer[row * 256 + col] = WHITE;
+            }
+        }
+        ppu
+    }
+
+    #[test]
+    fn test_light_follows_the_beam() {
+        let mut ppu = ppu_with_box(100, 50);
+        let mut zapper = Zapper::new();
+        zapper.set_aim(Some((104, 54)));
+
+        // Not drawn yet
+        ppu.scanline = 40;
+        ppu.cycles = 200;
+        assert_eq!(zapper.read(&ppu), 0b0000_1000);
+
+        // Beam has just passed the top of the box
+        ppu.scanline = 53;
+        ppu.cycles = 110;
+        assert_eq!(zapper.read(&ppu), 0b0000_0000);
+
+        // Light fades once the beam has moved on far enough
+        ppu.scanline = 57 + LIGHT_SCANLINES + 1;
+        assert_eq!(zapper.read(&ppu), 0b0000_1000);
+    }
+
+    #[test]
+    fn test_dark_pixels_and_trigger() {
+        let mut ppu = ppu_with_box(100, 50);
+        ppu.scanline = 120;
+        let mut zapper = Zapper::new();
+        zapper.set_aim(Some((20, 110)));
+        zapper.set_trigger(true);
+        assert_eq!(zapper.read(&ppu), 0b0001_1000);
+
+        // Dark blue is too dim, light grey is bright enough
+        ppu.frame_buffer[110 * 256 + 20] = 0x0C;
+        assert_eq!(zapper.read(&ppu), 0b0001_1000);
+        ppu.frame_buffer[110 * 256 + 20] = 0x10;
+        assert_eq!(zapper.read(&ppu), 0b0001_0000);
+
+        zapper.set_aim(None);
+        zapper.set_trigger(false);
+        assert_eq!(zapper.read(&ppu), 0b0000_1000);
+    }
+}
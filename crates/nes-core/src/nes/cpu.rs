@@ -1,5 +1,6 @@
 use super::tracer::Traceable;
-use crate::nes::cpu::interrupts::Interrupt;
+use crate::nes::cpu::interrupts::{Interrupt, InterruptType};
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 use bitflags::bitflags;
 use opcodes::Opcode;
 use thiserror::Error;
@@ -202,6 +203,29 @@ impl CPU {
         self.error = None;
     }
 
+    /// The console's reset button. Unlike `reset`, A, X and Y are kept, and
+    /// the stack pointer drops by 3 as the reset sequence's suppressed pushes
+    /// leave it. Execution restarts at the reset vector with IRQs disabled
+    pub fn soft_reset(&mut self) {
+        self.rdy_line = true;
+        let pcl = self.try_bus_read(0xFFFC).unwrap() as u16;
+        let pch = self.try_bus_read(0xFFFD).unwrap() as u16;
+        self.program_counter = (pch << 8) | pcl;
+        self.current_op = CpuCycleState::default();
+        self.stack_pointer = self.stack_pointer.wrapping_sub(3);
+        self.status.insert(Flags::INTERRUPT_DISABLE);
+        self.active_interrupt = None;
+        self.prev_nmi_line = false;
+        self.nmi_armed = true;
+        self.nmi_enable_holdoff = 0;
+        self.error = None;
+    }
+
+    /// True between instructions, i.e. no opcode or interrupt sequence is in flight
+    pub fn at_instruction_boundary(&self) -> bool {
+        self.current_op.opcode.is_none() && self.active_interrupt.is_none()
+    }
+
     /// `connect_bus` MUST be called after constructing CPU
     pub fn connect_bus(&mut self, bus: *mut dyn CpuBusInterface) {
         self.rdy_line = true;
@@ -273,3 +297,129 @@ impl Traceable for CPU {
         ))
     }
 }
+
+impl SaveState for CPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_usize(self.cycle);
+        state.write_bool(self.rdy_line);
+        state.write_bool(self.stalled_this_tick);
+
+        state.write_u8(self.register_a);
+        state.write_u8(self.register_x);
+        state.write_u8(self.register_y);
+        state.write_u8(self.stack_pointer);
+        state.write_u8(self.status.bits());
+        state.write_u16(self.program_counter);
+
+        // In-flight instruction
+        let op = &self.current_op;
+        state.write_option_u8(op.opcode.map(|opcode| opcode.code));
+        state.write_u8(op.micro_cycle);
+        state.write_u8(match op.access_type {
+            AccessType::None => 0,
+            AccessType::Read => 1,
+            AccessType::Write => 2,
+            AccessType::ReadModifyWrite => 3,
+            AccessType::Register => 4,
+        });
+        state.write_u8(match op.exec_phase {
+            ExecPhase::Idle => 0,
+            ExecPhase::Read => 1,
+            ExecPhase::Internal => 2,
+            ExecPhase::Write => 3,
+            ExecPhase::Done => 4,
+        });
+        let (addr_kind, addr) = match op.addr_result {
+            AddrResult::InProgress => (0, 0),
+            AddrResult::Ready(addr) => (1, addr),
+            AddrResult::ReadyImmediate => (2, 0),
+        };
+        state.write_u8(addr_kind);
+        state.write_u16(addr);
+        state.write_u16(op.base_addr);
+        state.write_u16(op.tmp_addr);
+        state.write_u8(op.tmp_data);
+        state.write_bool(op.page_crossed);
+
+        state.write_bool(self.prev_nmi_line);
+        state.write_bool(self.nmi_armed);
+        state.write_u8(self.nmi_enable_holdoff);
+        state.write_u8(match self.active_interrupt.map(|i| i.interrupt_type) {
+            None => 0,
+            Some(InterruptType::Nmi) => 1,
+            Some(InterruptType::Irq) => 2,
+        });
+        state.write_bool(self.stop);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.cycle = state.read_usize()?;
+        self.rdy_line = state.read_bool()?;
+        self.stalled_this_tick = state.read_bool()?;
+
+        self.register_a = state.read_u8()?;
+        self.register_x = state.read_u8()?;
+        self.register_y = state.read_u8()?;
+        self.stack_pointer = state.read_u8()?;
+        self.status = Flags::from_bits_retain(state.read_u8()?);
+        self.program_counter = state.read_u16()?;
+
+        let opcode = match state.read_option_u8()? {
+            Some(code) => Some(
+                *opcodes::OPCODES_MAP
+                    .get(&code)
+                    .ok_or(SaveStateError::Corrupt("unknown opcode"))?,
+            ),
+            None => None,
+        };
+        let micro_cycle = state.read_u8()?;
+        let access_type = match state.read_u8()? {
+            0 => AccessType::None,
+            1 => AccessType::Read,
+            2 => AccessType::Write,
+            3 => AccessType::ReadModifyWrite,
+            4 => AccessType::Register,
+            _ => return Err(SaveStateError::Corrupt("invalid access type")),
+        };
+        let exec_phase = match state.read_u8()? {
+            0 => ExecPhase::Idle,
+            1 => ExecPhase::Read,
+            2 => ExecPhase::Internal,
+            3 => ExecPhase::Write,
+            4 => ExecPhase::Done,
+            _ => return Err(SaveStateError::Corrupt("invalid exec phase")),
+        };
+        let addr_kind = state.read_u8()?;
+        let addr = state.read_u16()?;
+        let addr_result = match addr_kind {
+            0 => AddrResult::InProgress,
+            1 => AddrResult::Ready(addr),
+            2 => AddrResult::ReadyImmediate,
+            _ => return Err(SaveStateError::Corrupt("invalid address result")),
+        };
+        self.current_op = CpuCycleState {
+            opcode,
+            micro_cycle,
+            access_type,
+            exec_phase,
+            addr_result,
+            base_addr: state.read_u16()?,
+            tmp_addr: state.read_u16()?,
+            tmp_data: state.read_u8()?,
+            page_crossed: state.read_bool()?,
+        };
+
+        self.prev_nmi_line = state.read_bool()?;
+        self.nmi_armed = state.read_bool()?;
+        self.nmi_enable_holdoff = state.read_u8()?;
+        self.active_interrupt = match state.read_u8()? {
+            0 => None,
+            1 => Some(interrupts::NMI),
+            2 => Some(interrupts::IRQ),
+            _ => return Err(SaveStateError::Corrupt("invalid interrupt")),
+        };
+        self.stop = state.read_bool()?;
+        self.error = None;
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 
 pub struct DmcDma {
     // request address latched from the DMC channel
@@ -64,3 +65,20 @@ impl DmcDma {
         None
     }
 }
+
+impl SaveState for DmcDma {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_option_u16(self.req_addr);
+        state.write_bool(self.active);
+        state.write_u8(self.cycles_left);
+        state.write_u16(self.active_addr);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.req_addr = state.read_option_u16()?;
+        self.active = state.read_bool()?;
+        self.cycles_left = state.read_u8()?;
+        self.active_addr = state.read_u16()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+// Input movies in FCEUX's FM2 text format
+//
+// See: https://fceux.com/web/help/fm2.html
+//
+// A movie is a header of `key value` lines followed by one input line per
+// frame: `|commands|port0|port1|port2|`. `MovieFrame::run` feeds each line to
+// `NES::run_frame`, which latches input at the frame boundary, so playback is
+// deterministic.
+//
+// Movies recorded from a save state embed it as hex in the `savestate` key.
+// Those states are in this emulator's format, so FCEUX can't play them back.
+
+use crate::nes::cartridge::rom::Rom;
+use crate::nes::{FrameResult, NES};
+use std::fmt::Write;
+use thiserror::Error;
+
+const FM2_VERSION: u32 = 3;
+
+/// Button order of an FM2 input field, most significant bit first
+const BUTTON_CHARS: &[u8; 8] = b"RLDUTSBA";
+
+#[derive(Debug, Error)]
+pub enum MovieError {
+    #[error("Not an FM2 movie")]
+    InvalidFormat,
+
+    #[error("Unsupported FM2 version: {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("Line {line}: {reason}")]
+    InvalidLine { line: usize, reason: &'static str },
+
+    #[error("Movie uses {0}, which isn't supported")]
+    UnsupportedDevice(&'static str),
+}
+
+pub struct MovieCommand;
+
+impl MovieCommand {
+    pub const SOFT_RESET: u8 = 0b01;
+    pub const POWER: u8 = 0b10;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct MovieFrame {
+    /// `MovieCommand` bits
+    pub commands: u8,
+    /// Controller state per port, in `JoypadButton` bit order
+    pub joypads: [u8; 2],
+}
+
+impl MovieFrame {
+    /// Run one frame with this line's commands and input
+    pub fn run(&self, nes: &mut NES) -> FrameResult {
+        // A power cycle clears the console; the reset button keeps RAM. Cartridge
+        // RAM survives either way
+        if self.commands & MovieCommand::POWER != 0 {
+            nes.reset();
+        } else if self.commands & MovieCommand::SOFT_RESET != 0 {
+            nes.soft_reset();
+        }
+        nes.run_frame(self.joypads)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Movie {
+    pub rom_filename: String,
+    /// `romChecksum` value, e.g. `base64:...`
+    pub rom_checksum: String,
+    pub pal: bool,
+    pub rerecord_count: u32,
+    pub comments: Vec<String>,
+    /// Save state the movie starts from; power-on if `None`
+    pub start_state: Option<Vec<u8>>,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(rom_filename: impl Into<String>, rom_checksum: impl Into<String>) -> Self {
+        Self {
+            rom_filename: rom_filename.into(),
+            rom_checksum: rom_checksum.into(),
+            ..Self::default()
+        }
+    }
+
+    /// FCEUX's ROM checksum: MD5 over PRG and CHR, base64-encoded
+    pub fn rom_checksum(rom: &Rom) -> String {
+        let mut context = md5::Context::new();
+        context.consume(&rom.prg_rom);
+        context.consume(&rom.chr_rom);
+        format!("base64:{}", encode_base64(&context.finalize().0))
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, MovieError> {
+        let mut movie = Movie::default();
+        let mut version = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('|') {
+                movie.frames.push(parse_input_line(line, line_no)?);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" => {
+                    let v = parse_number(value, line_no)?;
+                    if v != FM2_VERSION {
+                        return Err(MovieError::UnsupportedVersion(v));
+                    }
+                    version = Some(v);
+                }
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "romChecksum" => movie.rom_checksum = value.to_string(),
+                "palFlag" => movie.pal = parse_number(value, line_no)? != 0,
+                "rerecordCount" => movie.rerecord_count = parse_number(value, line_no)?,
+                "comment" => movie.comments.push(value.to_string()),
+                "savestate" => {
+                    let hex = value.strip_prefix("0x").ok_or(MovieError::InvalidLine {
+                        line: line_no,
+                        reason: "save state must be hex",
+                    })?;
+                    movie.start_state = Some(decode_hex(hex).ok_or(MovieError::InvalidLine {
+                        line: line_no,
+                        reason: "invalid hex",
+                    })?);
+                }
+                "fourscore" if parse_number(value, line_no)? != 0 => {
+                    return Err(MovieError::UnsupportedDevice("Four Score"));
+                }
+                "FDS" if parse_number(value, line_no)? != 0 => {
+                    return Err(MovieError::UnsupportedDevice("Famicom Disk System"));
+                }
+                "port0" | "port1" if parse_number(value, line_no)? > 1 => {
+                    return Err(MovieError::UnsupportedDevice("a Zapper"));
+                }
+                // Other keys (emuVersion, guid, NewPPU, ...) don't affect playback
+                _ => {}
+            }
+        }
+
+        match version {
+            Some(_) => Ok(movie),
+            None => Err(MovieError::InvalidFormat),
+        }
+    }
+
+    /// Movies only hold standard controller input, so both ports are
+    /// written as gamepads. Don't record with other devices plugged in
+    pub fn to_fm2(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "version {FM2_VERSION}");
+        let _ = writeln!(out, "emuVersion 22020");
+        let _ = writeln!(out, "rerecordCount {}", self.rerecord_count);
+        let _ = writeln!(out, "palFlag {}", self.pal as u8);
+        let _ = writeln!(out, "romFilename {}", self.rom_filename);
+        let _ = writeln!(out, "romChecksum {}", self.rom_checksum);
+        let _ = writeln!(out, "guid 00000000-0000-0000-0000-000000000000");
+        let _ = writeln!(out, "fourscore 0");
+        let _ = writeln!(out, "microphone 0");
+        let _ = writeln!(out, "port0 1");
+        let _ = writeln!(out, "port1 1");
+        let _ = writeln!(out, "port2 0");
+        let _ = writeln!(out, "FDS 0");
+        let _ = writeln!(out, "NewPPU 1");
+        for comment in &self.comments {
+            let _ = writeln!(out, "comment {comment}");
+        }
+        if let Some(state) = &self.start_state {
+            let _ = writeln!(out, "savestate 0x{}", encode_hex(state));
+        }
+
+        for frame in &self.frames {
+            let _ = writeln!(
+                out,
+                "|{}|{}|{}||",
+                frame.commands,
+                encode_buttons(frame.joypads[0]),
+                encode_buttons(frame.joypads[1])
+            );
+        }
+        out
+    }
+}
+
+fn parse_number(value: &str, line: usize) -> Result<u32, MovieError> {
+    value.trim().parse().map_err(|_| MovieError::InvalidLine {
+        line,
+        reason: "expected a number",
+    })
+}
+
+fn parse_input_line(line: &str, line_no: usize) -> Result<MovieFrame, MovieError> {
+    let invalid = |reason| MovieError::InvalidLine {
+        line: line_no,
+        reason,
+    };
+
+    // Leading '|' gives an empty first field
+    let mut fields = line.split('|').skip(1);
+    let commands = fields.next().ok_or(invalid("missing commands"))?;
+    let commands = commands
+        .trim()
+        .parse::<u8>()
+        .map_err(|_| invalid("invalid commands"))?;
+
+    let mut joypads = [0u8; 2];
+    for joypad in &mut joypads {
+        *joypad = decode_buttons(fields.next().unwrap_or("")).ok_or(invalid("invalid input"))?;
+    }
+    Ok(MovieFrame { commands, joypads })
+}
+
+fn encode_buttons(buttons: u8) -> String {
+    BUTTON_CHARS
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| match buttons & (0x80 >> i) != 0 {
+            true => c as char,
+            false => '.',
+        })
+        .collect()
+}
+
+/// Empty fields mean "no controller"; anything other than space or '.' counts as pressed
+fn decode_buttons(field: &str) -> Option<u8> {
+    if field.is_empty() {
+        return Some(0);
+    }
+    if field.len() != BUTTON_CHARS.len() {
+        return None;
+    }
+    Some(
+        field
+            .bytes()
+            .enumerate()
+            .filter(|&(_, c)| c != b'.' && c != b' ')
+            .fold(0, |bits, (i, _)| bits | (0x80 >> i)),
+    )
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::test_utils::nes_with_program;
+
+    #[test]
+    fn test_buttons_round_trip() {
+        assert_eq!(encode_buttons(0b1000_0001), "R......A");
+        assert_eq!(decode_buttons("R......A"), Some(0b1000_0001));
+        assert_eq!(decode_buttons("...U.S.."), Some(0b0001_0100));
+        assert_eq!(decode_buttons(""), Some(0));
+        assert_eq!(decode_buttons("RL"), None);
+    }
+
+    #[test]
+    fn test_fm2_round_trip() {
+        let mut movie = Movie::new("game.nes", "base64:AAAA");
+        movie.comments.push("author test".to_string());
+        movie.start_state = Some(vec![0x4E, 0x53, 0x00, 0xFF]);
+        movie.frames = vec![
+            MovieFrame {
+                commands: MovieCommand::POWER,
+                joypads: [0, 0],
+            },
+            MovieFrame {
+                commands: 0,
+                joypads: [0b0000_1001, 0b1000_0000],
+            },
+        ];
+
+        let text = movie.to_fm2();
+        assert!(text.contains("|0|....T..A|R.......||"));
+        assert_eq!(Movie::parse(&text).unwrap(), movie);
+    }
+
+    #[test]
+    fn test_soft_reset_keeps_ram() {
+        // Counts starts in RAM: INC $00; JMP $8002
+        let mut nes = nes_with_program(&[0xE6, 0x00, 0x4C, 0x02, 0x80]);
+        for _ in 0..3 {
+            MovieFrame::default().run(&mut nes);
+        }
+        assert_eq!(nes.bus.cpu_ram[0], 1);
+        let stack_pointer = nes.bus.cpu.stack_pointer;
+
+        let soft_reset = MovieFrame {
+            commands: MovieCommand::SOFT_RESET,
+            joypads: [0, 0],
+        };
+        assert_eq!(soft_reset.run(&mut nes).frame, 4);
+        assert_eq!(nes.bus.cpu_ram[0], 2);
+        assert_eq!(nes.bus.cpu.stack_pointer, stack_pointer.wrapping_sub(3));
+
+        let power = MovieFrame {
+            commands: MovieCommand::POWER,
+            joypads: [0, 0],
+        };
+        assert_eq!(power.run(&mut nes).frame, 1);
+        assert_eq!(nes.bus.cpu_ram[0], 1);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert!(matches!(
+            Movie::parse("|0|........|||"),
+            Err(MovieError::InvalidFormat)
+        ));
+        assert!(matches!(
+            Movie::parse("version 2\n"),
+            Err(MovieError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            Movie::parse("version 3\n|x|........|||\n"),
+            Err(MovieError::InvalidLine { line: 2, .. })
+        ));
+        assert!(matches!(
+            Movie::parse("version 3\nfourscore 1\n"),
+            Err(MovieError::UnsupportedDevice(_))
+        ));
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(encode_base64(b"Man"), "TWFu");
+        assert_eq!(encode_base64(b"Ma"), "TWE=");
+        assert_eq!(encode_base64(b"M"), "TQ==");
+    }
+}
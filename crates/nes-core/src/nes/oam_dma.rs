@@ -1,3 +1,5 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
 pub enum OamDmaOp {
     Dummy,
     Read(u16),
@@ -66,3 +68,22 @@ impl OamDma {
         op
     }
 }
+
+impl SaveState for OamDma {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.active);
+        state.write_u8(self.page);
+        state.write_u16(self.cycle);
+        state.write_u8(self.latch);
+        state.write_u8(self.dummy_cycles);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.active = state.read_bool()?;
+        self.page = state.read_u8()?;
+        self.cycle = state.read_u16()?;
+        self.latch = state.read_u8()?;
+        self.dummy_cycles = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
 use crate::nes::cartridge::rom::Mirroring;
 use crate::nes::ppu::consts::{NAME_TABLE_SIZE, PRIMARY_OAM_SIZE, SECONDARY_OAM_SIZE};
 use crate::nes::ppu::nmi::{Nmi, NmiEvent};
-use crate::nes::ppu::scheduler::{DOTS, DotOperations, SCAN_LINES, ppu_schedule};
+use crate::nes::ppu::scheduler::{
+    DOTS, DotOperations, MAX_SCAN_LINES, SCAN_LINES, ppu_schedule, schedule_rows,
+};
+use crate::nes::region::Region;
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 use crate::nes::tracer::traceable::Traceable;
 use crate::{trace, trace_ppu_event};
 use consts::{PALETTE_SIZE, RAM_SIZE};
@@ -11,6 +15,7 @@ use registers::mask_register::MaskRegister;
 use registers::scroll_register::ScrollRegister;
 use registers::status_register::PpuStatusRegister;
 use scheduler::PpuOperation;
+use sprites::ExtraSprites;
 
 mod background;
 mod registers;
@@ -20,12 +25,15 @@ mod sprites;
 pub mod consts;
 mod mod_tests;
 mod nmi;
+pub mod viewer;
 
 pub trait PpuBusInterface {
     fn ppu_bus_read(&mut self, addr: u16) -> u8;
     fn ppu_bus_write(&mut self, addr: u16, value: u8);
     fn mirroring(&mut self) -> Mirroring;
     fn ppu_address(&mut self, addr: u16);
+    /// Read CHR without anything a real fetch would do to the bus or mapper
+    fn ppu_bus_peek(&mut self, addr: u16) -> u8;
 }
 
 enum PaletteKind {
@@ -35,6 +43,8 @@ enum PaletteKind {
 
 pub struct PPU {
     schedule: &'static [[DotOperations; DOTS]; SCAN_LINES],
+    schedule_rows: [usize; MAX_SCAN_LINES],
+    region: Region,
     pub cycles: usize,
     pub scanline: usize,
     pub(crate) nmi: Nmi,
@@ -57,7 +67,13 @@ pub struct PPU {
     pub mask_register: MaskRegister,        // $2001 (w)
     pub status_register: PpuStatusRegister, // $2002 (R)
     pub scroll_register: ScrollRegister,    // $2005 / $2006 - (write latched)
-    pub frame_buffer: [u8; 256 * 240],
+    /// 9-bit pixels: palette index in bits 0-5, emphasis in bits 6-8
+    pub frame_buffer: [u16; 256 * 240],
+    /// Color subcarrier phase, in twelfths of a cycle, at the first visible
+    /// dot of each line of `frame_buffer`
+    pub scanline_phases: [u8; 240],
+    /// Subcarrier phase at the current dot. Each dot is 8/12 of a cycle
+    color_phase: u8,
 
     pub oam_addr: u8,                            // $2003 (W)
     pub oam_data: [u8; PRIMARY_OAM_SIZE],        // $2004 (R/W) Object Attribute Memory
@@ -72,6 +88,9 @@ pub struct PPU {
     pub sprite_count: usize,
     sprite_zero_in_range: bool,
     sprite_zero_in_range_next: bool,
+    /// Draw at most 8 sprites per line, like the real PPU
+    sprite_limit: bool,
+    extra_sprites: ExtraSprites,
 
     // Background Registers & latches
     bg_pattern_shift_low: u16,
@@ -94,6 +113,8 @@ impl PPU {
     pub fn new() -> Self {
         PPU {
             schedule: ppu_schedule(),
+            schedule_rows: schedule_rows(Region::Ntsc),
+            region: Region::Ntsc,
             bus: None,
             v_ram: [0; RAM_SIZE],
             cycles: 0,
@@ -115,7 +136,9 @@ impl PPU {
             scroll_register: ScrollRegister::new(),
             status_register: PpuStatusRegister::new(),
 
-            frame_buffer: [0u8; 256 * 240],
+            frame_buffer: [0u16; 256 * 240],
+            scanline_phases: [0; 240],
+            color_phase: 0,
 
             // Blarrg's startup palette
             palette_table: [
@@ -138,6 +161,8 @@ impl PPU {
             sprite_count: 0,
             sprite_zero_in_range: false,
             sprite_zero_in_range_next: false,
+            sprite_limit: true,
+            extra_sprites: ExtraSprites::default(),
 
             bg_pattern_shift_low: 0,
             bg_pattern_shift_high: 0,
@@ -159,7 +184,7 @@ impl PPU {
     pub fn reset(&mut self) {
         self.v_ram = [0; RAM_SIZE];
         self.cycles = 0;
-        self.scanline = 261;
+        self.scanline = self.region.prerender_scanline();
         self.nmi = Nmi::default();
         self.global_ppu_ticks = 0;
         self.vblank_ticks = 0;
@@ -176,7 +201,9 @@ impl PPU {
         self.status_register = PpuStatusRegister::new();
         self.scroll_register = ScrollRegister::new();
 
-        self.frame_buffer = [0u8; 256 * 240];
+        self.frame_buffer = [0u16; 256 * 240];
+        self.scanline_phases = [0; 240];
+        self.color_phase = 0;
 
         // Blarrg's startup palette
         self.palette_table = [
@@ -200,6 +227,7 @@ impl PPU {
         self.sprite_count = 0;
         self.sprite_zero_in_range = false;
         self.sprite_zero_in_range_next = false;
+        self.extra_sprites = ExtraSprites::default();
 
         self.bg_pattern_shift_low = 0;
         self.bg_pattern_shift_high = 0;
@@ -214,12 +242,47 @@ impl PPU {
         self.next_tile_lsb = 0;
         self.next_tile_msb = 0;
     }
+
+    /// The console's reset button. PPUCTRL, PPUMASK, the scroll, the write
+    /// latch and the read buffer are cleared. VRAM, OAM, palettes, the VRAM
+    /// address and the PPU's place in the frame are kept.
+    ///
+    /// See: https://www.nesdev.org/wiki/PPU_power_up_state
+    pub fn soft_reset(&mut self) {
+        self.nmi = Nmi::default();
+        self.internal_data = 0;
+        self.frame_is_odd = false;
+        self.ctrl_register = ControlRegister::new();
+        self.mask_register = MaskRegister::new();
+        self.scroll_register.t = 0;
+        self.scroll_register.x = 0;
+        self.scroll_register.w = false;
+    }
 }
 
 impl PPU {
+    /// With the limit off, sprites past the 8th on a line are drawn too. Only
+    /// the picture changes: evaluation, sprite overflow and fetch timing
+    /// still work on the first 8, so games run the same with less flicker
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.sprite_limit = enabled;
+    }
+
+    /// Region timing is preserved through resets
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.schedule_rows = schedule_rows(region);
+        self.scanline = self.scanline.min(region.prerender_scanline());
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
     /// Advance the PPU by 1 dot
     pub fn tick(&mut self) -> bool {
-        let dot_ops = &self.schedule[self.scanline][self.cycles];
+        let row = self.schedule_rows[self.scanline];
+        let dot_ops = &self.schedule[row][self.cycles];
 
         // Execute PPU pipeline ops
         for i in 0..dot_ops.len as usize {
@@ -242,7 +305,8 @@ impl PPU {
         }
 
         // Odd frame skip
-        if self.scanline == 261
+        if self.region.has_odd_frame_skip()
+            && self.scanline == 261
             && self.cycles == 339
             && self.frame_is_odd
             && self.mask_register.rendering_enabled()
@@ -315,6 +379,9 @@ impl PPU {
             PpuOperation::FillSpriteRegister => {
                 let sprite_num = (self.cycles - 257) / 8;
                 self.sprite_fill_register(sprite_num, self.scanline);
+                if sprite_num == 7 {
+                    self.fill_extra_sprites(self.scanline);
+                }
             }
             PpuOperation::SetVBlank => {
                 let suppress = self.suppress_next_vblank_set;
@@ -387,14 +454,18 @@ impl PPU {
         }
 
         self.global_ppu_ticks += 1;
+        self.color_phase = (self.color_phase + 8) % 12;
         self.cycles += 1;
+        if self.cycles == 1 && self.scanline < 240 {
+            self.scanline_phases[self.scanline] = self.color_phase;
+        }
         if self.cycles == 341 {
             self.cycles = 0;
             self.scanline += 1;
             self.sprite_zero_in_range = self.sprite_zero_in_range_next;
             self.sprite_zero_in_range_next = false;
 
-            if self.scanline > 261 {
+            if self.scanline > self.region.prerender_scanline() {
                 self.scanline = 0;
                 frame_complete = true;
                 self.frame_is_odd = !self.frame_is_odd;
@@ -430,12 +501,13 @@ impl PPU {
                 let status = self.status_register.bits();
                 let had_vblank = self.status_register.vblank_active();
 
-                if self.scanline == 241 && self.cycles == 1 {
+                let vblank_scanline = self.region.vblank_scanline();
+                if self.scanline == vblank_scanline && self.cycles == 1 {
                     self.suppress_next_vblank_set = true;
                 }
 
                 let in_vblank_set_window =
-                    self.scanline == 241 && (self.cycles == 1 || self.cycles == 2);
+                    self.scanline == vblank_scanline && (self.cycles == 1 || self.cycles == 2);
                 if in_vblank_set_window {
                     self.nmi.on_event(NmiEvent::StatusReadDuringVBlankSet);
                 }
@@ -523,7 +595,7 @@ impl PPU {
 
 // Private implementations
 impl PPU {
-    fn render_dot(&mut self) -> u8 {
+    fn render_dot(&mut self) -> u16 {
         // Get raw palette indices for background and sprite
         let (bg_palette_index, bg_pixel) = self.get_background_pixel();
         let (sprite_palette_index, sprite_pixel, sprite_in_front, sprite_zero_rendered) =
@@ -576,7 +648,15 @@ impl PPU {
             (bg_palette_index, bg_pixel, PaletteKind::Background)
         };
 
-        self.read_palette_color(palette, pixel, kind) & 0x3F
+        let mut color = self.read_palette_color(palette, pixel, kind) & 0x3F;
+        // Greyscale keeps only the brightness column of the palette
+        if self.mask_register.is_greyscale() {
+            color &= 0x30;
+        }
+        let emphasis = self
+            .mask_register
+            .emphasis(self.region.swaps_emphasis_red_green());
+        (emphasis as u16) << 6 | color as u16
     }
 
     fn chr_read(&mut self, addr: u16) -> u8 {
@@ -589,6 +669,13 @@ impl PPU {
         }
     }
 
+    fn chr_peek(&mut self, addr: u16) -> u8 {
+        match self.bus {
+            Some(bus_ptr) => unsafe { (*bus_ptr).ppu_bus_peek(addr) },
+            None => 0,
+        }
+    }
+
     fn chr_write(&mut self, addr: u16, value: u8) {
         match self.bus {
             Some(bus_ptr) => unsafe { (*bus_ptr).ppu_bus_write(addr, value) },
@@ -864,16 +951,248 @@ impl Traceable for PPU {
     }
 }
 
+/// The frame buffer and its line phases are output rather than state and
+/// aren't saved; they're fully redrawn by the next frame after a load
+impl SaveState for PPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_usize(self.cycles);
+        state.write_usize(self.scanline);
+        self.nmi.save_state(state);
+        state.write_usize(self.global_ppu_ticks);
+        state.write_usize(self.vblank_ticks);
+        state.write_bool(self.prerender_rendering_enabled);
+        state.write_bool(self.suppress_next_vblank_set);
+
+        state.write_bytes(&self.v_ram);
+        state.write_u8(self.internal_data);
+        state.write_bool(self.frame_is_odd);
+        state.write_u8(self.color_phase);
+        self.last_byte_read.save_state(state);
+        state.write_u16(self.old_v);
+        state.write_u16(self.ppu_addr_latch);
+
+        state.write_bytes(&self.palette_table);
+
+        state.write_u8(self.ctrl_register.bits());
+        state.write_u8(self.mask_register.bits());
+        state.write_u8(self.status_register.bits());
+        state.write_u16(self.scroll_register.v);
+        state.write_u16(self.scroll_register.t);
+        state.write_u8(self.scroll_register.x);
+        state.write_bool(self.scroll_register.w);
+
+        state.write_u8(self.oam_addr);
+        state.write_bytes(&self.oam_data);
+        state.write_bytes(&self.secondary_oam);
+
+        state.write_bytes(&self.sprite_pattern_low);
+        state.write_bytes(&self.sprite_pattern_high);
+        state.write_bytes(&self.sprite_x_counter);
+        state.write_bytes(&self.sprite_attributes);
+        state.write_bytes(&self.sprite_x_latch);
+        state.write_usize(self.sprite_count);
+        state.write_bool(self.sprite_zero_in_range);
+        state.write_bool(self.sprite_zero_in_range_next);
+
+        state.write_u16(self.bg_pattern_shift_low);
+        state.write_u16(self.bg_pattern_shift_high);
+        state.write_u16(self.bg_attr_shift_low);
+        state.write_u16(self.bg_attr_shift_high);
+        state.write_u8(self.bg_attr_latch_low);
+        state.write_u8(self.bg_attr_latch_high);
+
+        state.write_u8(self.next_tile_id);
+        state.write_u8(self.next_tile_attr);
+        state.write_u8(self.next_tile_lsb);
+        state.write_u8(self.next_tile_msb);
+
+        state.write_usize(self.temp_counter);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.cycles = state.read_usize()?;
+        self.scanline = state.read_usize()?;
+        if self.cycles >= DOTS || self.scanline >= self.region.scanlines() {
+            return Err(SaveStateError::Corrupt("PPU position out of range"));
+        }
+        self.nmi.load_state(state)?;
+        self.global_ppu_ticks = state.read_usize()?;
+        self.vblank_ticks = state.read_usize()?;
+        self.prerender_rendering_enabled = state.read_bool()?;
+        self.suppress_next_vblank_set = state.read_bool()?;
+
+        state.read_bytes(&mut self.v_ram)?;
+        self.internal_data = state.read_u8()?;
+        self.frame_is_odd = state.read_bool()?;
+        self.color_phase = state.read_u8()? % 12;
+        self.last_byte_read.load_state(state)?;
+        self.old_v = state.read_u16()?;
+        self.ppu_addr_latch = state.read_u16()?;
+
+        state.read_bytes(&mut self.palette_table)?;
+
+        self.ctrl_register = ControlRegister::from_bits_retain(state.read_u8()?);
+        self.mask_register = MaskRegister::from_bits_retain(state.read_u8()?);
+        self.status_register = PpuStatusRegister::from_bits_retain(state.read_u8()?);
+        self.scroll_register.v = state.read_u16()?;
+        self.scroll_register.t = state.read_u16()?;
+        self.scroll_register.x = state.read_u8()?;
+        self.scroll_register.w = state.read_bool()?;
+
+        self.oam_addr = state.read_u8()?;
+        state.read_bytes(&mut self.oam_data)?;
+        state.read_bytes(&mut self.secondary_oam)?;
+
+        state.read_bytes(&mut self.sprite_pattern_low)?;
+        state.read_bytes(&mut self.sprite_pattern_high)?;
+        state.read_bytes(&mut self.sprite_x_counter)?;
+        state.read_bytes(&mut self.sprite_attributes)?;
+        state.read_bytes(&mut self.sprite_x_latch)?;
+        self.sprite_count = state.read_usize()?;
+        if self.sprite_count > 8 {
+            return Err(SaveStateError::Corrupt("sprite count out of range"));
+        }
+        self.sprite_zero_in_range = state.read_bool()?;
+        self.sprite_zero_in_range_next = state.read_bool()?;
+
+        self.bg_pattern_shift_low = state.read_u16()?;
+        self.bg_pattern_shift_high = state.read_u16()?;
+        self.bg_attr_shift_low = state.read_u16()?;
+        self.bg_attr_shift_high = state.read_u16()?;
+        self.bg_attr_latch_low = state.read_u8()?;
+        self.bg_attr_latch_high = state.read_u8()?;
+
+        self.next_tile_id = state.read_u8()?;
+        self.next_tile_attr = state.read_u8()?;
+        self.next_tile_lsb = state.read_u8()?;
+        self.next_tile_msb = state.read_u8()?;
+
+        self.temp_counter = state.read_usize()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::nes::cartridge::mapper000_nrom::NromCart;
+    use crate::nes::ppu::consts::NES_SYSTEM_PALETTE;
 
     fn create_empty_ppu() -> PPU {
         let cart = NromCart::new(vec![0; 0x4000], vec![0; 0x4000], Mirroring::Vertical);
         PPU::new()
     }
 
+    #[test]
+    fn test_greyscale_and_emphasis_pixels() {
+        let mut ppu = create_empty_ppu();
+        ppu.palette_table[0] = 0x16;
+        assert_eq!(ppu.render_dot(), 0x16);
+
+        // Greyscale, red and blue emphasis
+        ppu.mask_register.update(0b1010_0001);
+        assert_eq!(ppu.render_dot(), 0b101 << 6 | 0x10);
+
+        // The same bits emphasise green and blue on PAL
+        ppu.set_region(Region::Pal);
+        assert_eq!(ppu.render_dot(), 0b110 << 6 | 0x10);
+    }
+
+    #[test]
+    fn test_scanline_phases() {
+        let mut ppu = create_empty_ppu();
+        let frame_dots = 341 * 262;
+        for _ in 0..frame_dots {
+            ppu.advance_dot();
+        }
+        // 341 dots of 8/12 cycle each put every line 4/12 further along
+        let first = ppu.scanline_phases;
+        for line in 1..240 {
+            assert_eq!(first[line], (first[line - 1] + 4) % 12);
+        }
+
+        // And a whole frame moves the pattern by the same, which is the dot crawl
+        for _ in 0..frame_dots {
+            ppu.advance_dot();
+        }
+        assert_eq!(ppu.scanline_phases[0], (first[0] + 4) % 12);
+    }
+
+    /// Run two frames with 10 sprites on the same lines, stopping mid-frame
+    fn run_crowded_line(sprite_limit: bool) -> crate::nes::NES {
+        use crate::nes::stepping::StepKind;
+        use crate::nes::test_utils::nes_with_program_and_chr;
+
+        // Tile 1 is solid color 1
+        let mut chr_rom = vec![0; 0x2000];
+        chr_rom[16..24].fill(0xFF);
+        // JMP $8000
+        let mut nes = nes_with_program_and_chr(&[0x4C, 0x00, 0x80], chr_rom, Mirroring::Vertical);
+        nes.set_sprite_limit(sprite_limit);
+
+        let ppu = &mut nes.bus.ppu;
+        ppu.palette_table[0] = 0x0F;
+        ppu.palette_table[0x11] = 0x16;
+        for sprite in 0..10 {
+            let entry = [50, 1, 0, sprite as u8 * 20];
+            ppu.oam_data[sprite * 4..sprite * 4 + 4].copy_from_slice(&entry);
+        }
+        ppu.oam_data[40..].fill(0xFF);
+        ppu.mask_register.update(0b0001_0100);
+
+        nes.step(StepKind::Frame);
+        nes.step(StepKind::Frame);
+        while nes.position().scanline != 56 {
+            nes.step(StepKind::Scanline);
+        }
+        nes
+    }
+
+    #[test]
+    fn test_no_sprite_limit_only_changes_the_picture() {
+        let limited = run_crowded_line(true);
+        let unlimited = run_crowded_line(false);
+        let row = 54 * 256;
+        let limited_pixels = limited.get_frame_buffer();
+        let unlimited_pixels = unlimited.get_frame_buffer();
+
+        // The first 8 sprites are drawn either way
+        assert_eq!(limited_pixels[row + 7 * 20 + 2], 0x16);
+        assert_eq!(unlimited_pixels[row + 7 * 20 + 2], 0x16);
+        // The 9th and 10th only without the limit
+        assert_eq!(limited_pixels[row + 8 * 20 + 2], 0x0F);
+        assert_eq!(unlimited_pixels[row + 8 * 20 + 2], 0x16);
+        assert_eq!(unlimited_pixels[row + 9 * 20 + 7], 0x16);
+
+        // Games see the same overflow flag and timing
+        for nes in [&limited, &unlimited] {
+            assert!(
+                nes.bus
+                    .ppu
+                    .status_register
+                    .contains(PpuStatusRegister::SPRITE_OVERFLOW)
+            );
+        }
+        assert_eq!(limited.cpu_cycles(), unlimited.cpu_cycles());
+    }
+
+    #[test]
+    fn test_emphasis_dims_other_channels() {
+        let white = NES_SYSTEM_PALETTE[0x30];
+        let red_emphasis = NES_SYSTEM_PALETTE[0b001 << 6 | 0x30];
+        assert_eq!(red_emphasis.0, white.0);
+        assert!(red_emphasis.1 < white.1 && red_emphasis.2 < white.2);
+
+        let all = NES_SYSTEM_PALETTE[0b111 << 6 | 0x30];
+        assert!(all.0 < red_emphasis.0 && all.1 < red_emphasis.1);
+
+        // Black stays black
+        assert_eq!(
+            NES_SYSTEM_PALETTE[0b111 << 6 | 0x0F],
+            NES_SYSTEM_PALETTE[0x0F]
+        );
+    }
+
     #[test]
     fn test_palette_addr_mirroring() {
         let ppu = create_empty_ppu(); // adjust constructor if needed
@@ -4,7 +4,8 @@ pub const PALETTE_SIZE: usize = 32;
 pub const PRIMARY_OAM_SIZE: usize = 256;
 pub const SECONDARY_OAM_SIZE: usize = 32;
 
-pub static NES_SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
+/// Colors for the 64 palette indices without emphasis
+const BASE_PALETTE: [(u8, u8, u8); 64] = [
     (0x80, 0x80, 0x80),
     (0x00, 0x3D, 0xA6),
     (0x00, 0x12, 0xB0),
@@ -70,3 +71,40 @@ pub static NES_SYSTEM_PALETTE: [(u8, u8, u8); 64] = [
     (0x11, 0x11, 0x11),
     (0x11, 0x11, 0x11),
 ];
+
+/// How much each emphasis bit dims the other two channels, in thousandths
+const EMPHASIS_ATTENUATION: u32 = 816;
+
+/// Colors for 9-bit PPU pixels: the palette index in bits 0-5, then red,
+/// green and blue emphasis in bits 6-8. Emphasising a channel dims the other
+/// two, so with all three set everything dims. The black columns ($xE and
+/// $xF) carry no signal to dim
+pub static NES_SYSTEM_PALETTE: [(u8, u8, u8); 512] = emphasised_palette(&BASE_PALETTE);
+
+/// Extend a 64-color palette with the emphasised versions of each color,
+/// dimmed the way `NES_SYSTEM_PALETTE` is
+pub const fn emphasised_palette(base: &[(u8, u8, u8); 64]) -> [(u8, u8, u8); 512] {
+    let mut palette = [(0, 0, 0); 512];
+    let mut i = 0;
+    while i < 512 {
+        let (r, g, b) = base[i & 0x3F];
+        let emphasis = (i >> 6) as u32;
+        let mut rgb = [r as u32, g as u32, b as u32];
+        if i & 0x0E != 0x0E {
+            let mut channel = 0;
+            while channel < 3 {
+                let mut other = 0;
+                while other < 3 {
+                    if other != channel && emphasis & (1 << other) != 0 {
+                        rgb[channel] = rgb[channel] * EMPHASIS_ATTENUATION / 1000;
+                    }
+                    other += 1;
+                }
+                channel += 1;
+            }
+        }
+        palette[i] = (rgb[0] as u8, rgb[1] as u8, rgb[2] as u8);
+        i += 1;
+    }
+    palette
+}
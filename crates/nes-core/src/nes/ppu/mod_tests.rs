@@ -33,6 +33,9 @@ mod test {
         fn ppu_address(&mut self, addr: u16) {
             todo!()
         }
+        fn ppu_bus_peek(&mut self, addr: u16) -> u8 {
+            self.chr[addr as usize % 0x2000]
+        }
         // fn nmi(&mut self, _defer_one_instruction: bool) {
         //     self.triggered_nmi = true;
         // }
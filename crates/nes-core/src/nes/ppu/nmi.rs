@@ -1,3 +1,4 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
 use crate::trace_ppu_event;
 
 pub enum NmiEvent {
@@ -97,6 +98,23 @@ impl Nmi {
     }
 }
 
+impl SaveState for Nmi {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_bool(self.vblank);
+        state.write_bool(self.suppress_next_vblank_edge);
+        state.write_bool(self.line);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = state.read_bool()?;
+        self.vblank = state.read_bool()?;
+        self.suppress_next_vblank_edge = state.read_bool()?;
+        self.line = state.read_bool()?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,3 +1,5 @@
+use crate::nes::save_state::{SaveState, SaveStateError, StateReader, StateWriter};
+
 pub struct DecayRegister {
     value: u8,
     addr: u16,
@@ -34,3 +36,19 @@ impl DecayRegister {
         self.value
     }
 }
+
+/// The decay period is fixed at construction and isn't saved
+impl SaveState for DecayRegister {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.value);
+        state.write_u16(self.addr);
+        state.write_usize(self.cycle);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.value = state.read_u8()?;
+        self.addr = state.read_u16()?;
+        self.cycle = state.read_usize()?;
+        Ok(())
+    }
+}
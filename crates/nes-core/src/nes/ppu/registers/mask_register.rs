@@ -27,12 +27,6 @@ bitflags! {
     }
 }
 
-// pub enum Color {
-//     Red,
-//     Green,
-//     Blue,
-// }
-
 impl MaskRegister {
     pub fn new() -> Self {
         MaskRegister::from_bits_truncate(0)
@@ -42,9 +36,9 @@ impl MaskRegister {
         *self = MaskRegister::from_bits_truncate(data);
     }
 
-    // pub fn is_grayscale(&self) -> bool {
-    //     self.contains(MaskRegister::GREYSCALE)
-    // }
+    pub fn is_greyscale(&self) -> bool {
+        self.contains(MaskRegister::GREYSCALE)
+    }
 
     pub fn leftmost_8pxl_background(&self) -> bool {
         self.contains(MaskRegister::LEFTMOST_8PXL_BACKGROUND)
@@ -66,18 +60,16 @@ impl MaskRegister {
         self.show_background() || self.show_sprites()
     }
 
-    // TODO: Maybe?
-    // pub fn emphasise(&self) -> Vec<Color> {
-    //     let mut result = Vec::<Color>::new();
-    //     if self.contains(MaskRegister::EMPHASISE_RED) {
-    //         result.push(Color::Red);
-    //     }
-    //     if self.contains(MaskRegister::EMPHASISE_BLUE) {
-    //         result.push(Color::Blue);
-    //     }
-    //     if self.contains(MaskRegister::EMPHASISE_GREEN) {
-    //         result.push(Color::Green);
-    //     }
-    //     result
-    // }
+    /// Emphasis bits as red, green and blue from bit 0 up, the order
+    /// `NES_SYSTEM_PALETTE` uses. PAL and Dendy PPUs have red and green
+    /// swapped, which `swap_red_green` undoes
+    pub fn emphasis(&self, swap_red_green: bool) -> u8 {
+        let red = self.contains(MaskRegister::EMPHASISE_RED) as u8;
+        let green = self.contains(MaskRegister::EMPHASISE_GREEN) as u8;
+        let blue = self.contains(MaskRegister::EMPHASISE_BLUE) as u8;
+        match swap_red_green {
+            true => blue << 2 | red << 1 | green,
+            false => blue << 2 | green << 1 | red,
+        }
+    }
 }
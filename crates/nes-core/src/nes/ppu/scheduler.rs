@@ -1,5 +1,9 @@
+use crate::nes::region::Region;
+
 pub const DOTS: usize = 341;
 pub const SCAN_LINES: usize = 262;
+/// Longest frame of any region (PAL/Dendy)
+pub const MAX_SCAN_LINES: usize = 312;
 
 #[cfg(not(feature = "runtime-ppu-schedule"))]
 static PPU_SCHEDULE: [[DotOperations; DOTS]; SCAN_LINES] = build_schedule();
@@ -98,6 +102,27 @@ pub fn ppu_schedule() -> &'static [[DotOperations; DOTS]; SCAN_LINES] {
     get_ppu_schedule()
 }
 
+/// Map each scanline of a region's frame onto a row of the (NTSC) schedule table.
+///
+/// PAL and Dendy frames only differ from NTSC in how many idle lines they spend
+/// around vblank, so their extra lines reuse NTSC's idle post-render (240) and
+/// vblank (260) rows, and their last line reuses the pre-render row (261)
+pub fn schedule_rows(region: Region) -> [usize; MAX_SCAN_LINES] {
+    let vblank = region.vblank_scanline();
+    let prerender = region.prerender_scanline();
+
+    let mut rows = [0; MAX_SCAN_LINES];
+    for (scanline, row) in rows.iter_mut().enumerate() {
+        *row = match scanline {
+            0..=240 => scanline,
+            s if s < vblank => 240,
+            s if s < prerender => (241 + s - vblank).min(SCAN_LINES - 2),
+            _ => SCAN_LINES - 1,
+        };
+    }
+    rows
+}
+
 const fn build_schedule() -> [[DotOperations; DOTS]; SCAN_LINES] {
     let mut table = [[DotOperations::new(); DOTS]; SCAN_LINES];
     let mut scanline = 0;
@@ -396,6 +421,36 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_schedule_rows_ntsc_is_identity() {
+        let rows = schedule_rows(Region::Ntsc);
+        for (scanline, &row) in rows.iter().enumerate().take(SCAN_LINES) {
+            assert_eq!(row, scanline);
+        }
+    }
+
+    #[test]
+    fn test_schedule_rows_pal() {
+        let rows = schedule_rows(Region::Pal);
+        assert_eq!(rows[239], 239);
+        assert_eq!(rows[240], 240);
+        assert_eq!(rows[241], 241); // vblank set
+        assert_eq!(rows[260], 260);
+        assert!(rows[261..311].iter().all(|&row| row == 260));
+        assert_eq!(rows[311], 261); // pre-render
+    }
+
+    #[test]
+    fn test_schedule_rows_dendy() {
+        let rows = schedule_rows(Region::Dendy);
+        assert!(rows[240..291].iter().all(|&row| row == 240));
+        assert_eq!(rows[291], 241); // vblank set
+        for (scanline, &row) in rows.iter().enumerate().take(311).skip(292) {
+            assert_eq!(row, scanline - 50);
+        }
+        assert_eq!(rows[311], 261); // pre-render
+    }
+
     #[test]
     fn test_sprite_fetch_window() {
         let scanline = 0;
@@ -1,4 +1,39 @@
 use super::PPU;
+
+/// Most sprites that can share a line beyond the 8 the PPU draws
+const MAX_EXTRA_SPRITES: usize = 56;
+
+/// Sprites past the 8th on a line, drawn only with the sprite limit off.
+/// Games never see them: evaluation, the overflow flag and pattern fetch
+/// timing all still stop at 8
+#[derive(Clone)]
+pub(super) struct ExtraSprites {
+    /// OAM entries found in range while evaluating the next line
+    oam: [[u8; 4]; MAX_EXTRA_SPRITES],
+    found: usize,
+
+    // Shifters for the line being drawn, like the 8 real ones
+    count: usize,
+    pattern_low: [u8; MAX_EXTRA_SPRITES],
+    pattern_high: [u8; MAX_EXTRA_SPRITES],
+    x_counter: [u8; MAX_EXTRA_SPRITES],
+    attributes: [u8; MAX_EXTRA_SPRITES],
+}
+
+impl Default for ExtraSprites {
+    fn default() -> Self {
+        Self {
+            oam: [[0xFF; 4]; MAX_EXTRA_SPRITES],
+            found: 0,
+            count: 0,
+            pattern_low: [0; MAX_EXTRA_SPRITES],
+            pattern_high: [0; MAX_EXTRA_SPRITES],
+            x_counter: [0; MAX_EXTRA_SPRITES],
+            attributes: [0; MAX_EXTRA_SPRITES],
+        }
+    }
+}
+
 impl PPU {
     /// get_sprite_pixel determines the sprite pixel.
     /// Returns (sprite_palette, sprite_pixel, sprite_behind_bg, sprite_zero_rendered)
@@ -35,6 +70,26 @@ impl PPU {
             }
         }
 
+        // Extra sprites come after all 8 in OAM, so they only show through
+        // where none of those is drawn
+        let extra = &self.extra_sprites;
+        if sprite_pixel == 0 && !(left_clip_enabled && self.cycles <= 8) {
+            for i in 0..extra.count {
+                if extra.x_counter[i] != 0 {
+                    continue;
+                }
+                let low_bit = (extra.pattern_low[i] >> 7) & 1;
+                let high_bit = (extra.pattern_high[i] >> 7) & 1;
+                let pixel = (high_bit << 1) | low_bit;
+                if pixel != 0 {
+                    sprite_palette = extra.attributes[i] & 0b11;
+                    sprite_pixel = pixel;
+                    sprite_in_front = (extra.attributes[i] & 0b0010_0000) == 0;
+                    break;
+                }
+            }
+        }
+
         (
             sprite_palette,
             sprite_pixel,
@@ -76,18 +131,20 @@ impl PPU {
             } else {
                 // Too many sprites!
                 self.status_register.set_sprite_overflow(true);
+
+                let extra = &mut self.extra_sprites;
+                if !self.sprite_limit && extra.found < MAX_EXTRA_SPRITES {
+                    let entry = &self.oam_data[oam_base..oam_base + 4];
+                    extra.oam[extra.found].copy_from_slice(entry);
+                    extra.found += 1;
+                }
             }
         }
     }
 
-    pub(super) fn sprite_fill_register(&mut self, sprite_num: usize, scanline: usize) {
-        let base = 4 * sprite_num;
-
-        let y = self.secondary_oam[base];
-        let tile_index = self.secondary_oam[base + 1];
-        let attributes = self.secondary_oam[base + 2];
-        let x = self.secondary_oam[base + 3];
-
+    /// Address of the low pattern byte for the row of a sprite at `y` that's
+    /// on the line after `scanline`
+    fn sprite_pattern_addr(&self, y: u8, tile_index: u8, attributes: u8, scanline: usize) -> u16 {
         let sprite_height = self.ctrl_register.sprite_size() as i16;
         let render_scanline = scanline + 1;
         let mut row = (render_scanline as i16) - (y as i16 + 1);
@@ -97,7 +154,7 @@ impl PPU {
             row = (sprite_height - 1) - row;
         }
 
-        let pattern_addr = if sprite_height == 16 {
+        if sprite_height == 16 {
             // 8x16
             let table = (tile_index & 0x01) as u16;
             let tile_num = (tile_index & 0xFE) as u16;
@@ -110,7 +167,18 @@ impl PPU {
             let table_addr = self.ctrl_register.sprite_pattern_addr();
             let fine_y = (row as u16) & 0x07;
             table_addr + (tile_index as u16) * 16 + fine_y
-        };
+        }
+    }
+
+    pub(super) fn sprite_fill_register(&mut self, sprite_num: usize, scanline: usize) {
+        let base = 4 * sprite_num;
+
+        let y = self.secondary_oam[base];
+        let tile_index = self.secondary_oam[base + 1];
+        let attributes = self.secondary_oam[base + 2];
+        let x = self.secondary_oam[base + 3];
+
+        let pattern_addr = self.sprite_pattern_addr(y, tile_index, attributes, scanline);
 
         // Always run sprite pattern fetches to keep mapper A12 timing accurate
         let mut pattern_low = self.read_bus(pattern_addr);
@@ -139,6 +207,29 @@ impl PPU {
         }
     }
 
+    /// Load the shifters of the sprites past the 8th, after the real ones'
+    /// fetches. Patterns are peeked so mappers can't tell
+    pub(super) fn fill_extra_sprites(&mut self, scanline: usize) {
+        let extra = &mut self.extra_sprites;
+        extra.count = extra.found;
+        for i in 0..extra.count {
+            let [y, tile_index, attributes, x] = self.extra_sprites.oam[i];
+            let pattern_addr = self.sprite_pattern_addr(y, tile_index, attributes, scanline);
+            let mut pattern_low = self.chr_peek(pattern_addr);
+            let mut pattern_high = self.chr_peek(pattern_addr + 8);
+            if attributes & 0x40 != 0 {
+                pattern_low = pattern_low.reverse_bits();
+                pattern_high = pattern_high.reverse_bits();
+            }
+
+            let extra = &mut self.extra_sprites;
+            extra.x_counter[i] = x;
+            extra.attributes[i] = attributes;
+            extra.pattern_low[i] = pattern_low;
+            extra.pattern_high[i] = pattern_high;
+        }
+    }
+
     pub(super) fn shift_sprite_registers(&mut self) {
         // Shift sprite pattern registers for next pixel
         for i in 0..8 {
@@ -149,11 +240,22 @@ impl PPU {
                 self.sprite_pattern_high[i] <<= 1;
             }
         }
+
+        let extra = &mut self.extra_sprites;
+        for i in 0..extra.count {
+            if extra.x_counter[i] > 0 {
+                extra.x_counter[i] -= 1;
+            } else {
+                extra.pattern_low[i] <<= 1;
+                extra.pattern_high[i] <<= 1;
+            }
+        }
     }
 
     pub(super) fn reset_sprite_evaluation(&mut self) {
         self.sprite_count = 0;
         self.sprite_zero_in_range_next = false;
+        self.extra_sprites.found = 0;
 
         // Reset sprite overflow flag at the start of each scanline's sprite evaluation
         self.status_register.set_sprite_overflow(false);
@@ -0,0 +1,318 @@
+// Copies of PPU memory for debugger views
+//
+// See: https://www.nesdev.org/wiki/PPU_nametables
+//      https://www.nesdev.org/wiki/PPU_attribute_tables
+//      https://www.nesdev.org/wiki/PPU_scrolling
+//
+//      https://www.nesdev.org/wiki/PPU_pattern_tables
+//
+// A `PpuSnapshot` is taken at the start of a chosen scanline, so games that
+// change banks, mirroring or scroll partway down the screen can be looked at
+// from either side of the split.
+
+use crate::nes::cartridge::rom::Mirroring;
+use crate::nes::ppu::PPU;
+use crate::nes::ppu::consts::NAME_TABLE_SIZE;
+
+/// Width of the four nametables laid out 2x2
+pub const NAMETABLES_WIDTH: usize = 512;
+/// Height of the four nametables laid out 2x2
+pub const NAMETABLES_HEIGHT: usize = 480;
+
+/// Width of both pattern tables side by side, $0000 on the left
+pub const PATTERNS_WIDTH: usize = 256;
+/// Height of a pattern table, 16 rows of 16 tiles
+pub const PATTERNS_HEIGHT: usize = 128;
+
+/// Attribute bytes are the last 64 of each nametable
+const ATTRIBUTE_OFFSET: usize = 0x3C0;
+
+pub struct PpuSnapshot {
+    /// Scanline it was taken at the start of
+    pub scanline: usize,
+    /// $2000, $2400, $2800 and $2C00, through the cartridge's mirroring
+    pub nametables: [[u8; NAME_TABLE_SIZE as usize]; 4],
+    /// $0000-$1FFF through the CHR banks mapped at the time
+    pub patterns: [u8; 0x2000],
+    pub palette: [u8; 32],
+    pub mirroring: Mirroring,
+    /// Pattern table the background is drawn from, $0000 or $1000
+    pub background_patterns: u16,
+    /// Current and temporary VRAM addresses (`v` and `t`) and fine X scroll
+    pub v: u16,
+    pub t: u16,
+    pub fine_x: u8,
+    pub rendering: bool,
+}
+
+/// What's at one spot of the nametables
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TileInfo {
+    /// Nametable 0-3, from $2000 in steps of $400
+    pub nametable: usize,
+    /// Tile column 0-31
+    pub column: usize,
+    /// Tile row 0-29
+    pub row: usize,
+    /// Address of the tile byte
+    pub address: u16,
+    pub tile: u8,
+    /// Address of the attribute byte covering the tile
+    pub attribute_address: u16,
+    pub attribute: u8,
+    /// Background palette 0-3 the attribute byte picks for this tile
+    pub palette: u8,
+    /// Address of the tile's pattern
+    pub pattern_address: u16,
+}
+
+impl PpuSnapshot {
+    /// Where the top-left of the screen falls in the 512x480 layout, as of
+    /// the snapshot's scanline
+    ///
+    /// Horizontal scroll comes from `t`, which the PPU copies into `v` at the
+    /// end of every line. While drawing, `v` holds the vertical position of
+    /// the line about to be drawn, so the screen's top is that many lines up.
+    /// Outside of that, the next frame starts from `t`
+    pub fn scroll_origin(&self) -> (usize, usize) {
+        let x = (self.t & 0x1F) as usize * 8
+            + self.fine_x as usize
+            + ((self.t >> 10) & 1) as usize * 256;
+        let y_of = |addr: u16| {
+            ((addr >> 5) & 0x1F) as usize * 8
+                + ((addr >> 12) & 0x07) as usize
+                + ((addr >> 11) & 1) as usize * 240
+        };
+        let y = if self.rendering && self.scanline < 240 {
+            (y_of(self.v) + NAMETABLES_HEIGHT - self.scanline) % NAMETABLES_HEIGHT
+        } else {
+            y_of(self.t)
+        };
+        (x, y)
+    }
+
+    /// Tile, attribute and addresses at a pixel of the 512x480 layout
+    pub fn tile_at(&self, x: usize, y: usize) -> Option<TileInfo> {
+        if x >= NAMETABLES_WIDTH || y >= NAMETABLES_HEIGHT {
+            return None;
+        }
+        let nametable = (y / 240) * 2 + x / 256;
+        let (column, row) = (x % 256 / 8, y % 240 / 8);
+        let offset = row * 32 + column;
+        let attribute_offset = ATTRIBUTE_OFFSET + (row / 4) * 8 + column / 4;
+        let tile = self.nametables[nametable][offset];
+        let attribute = self.nametables[nametable][attribute_offset];
+        // Each attribute byte covers 4x4 tiles, two bits per 2x2 quadrant
+        let shift = ((row & 2) << 1) | (column & 2);
+        let base = 0x2000 + nametable as u16 * NAME_TABLE_SIZE;
+        Some(TileInfo {
+            nametable,
+            column,
+            row,
+            address: base + offset as u16,
+            tile,
+            attribute_address: base + attribute_offset as u16,
+            attribute,
+            palette: (attribute >> shift) & 0b11,
+            pattern_address: self.background_patterns + tile as u16 * 16,
+        })
+    }
+
+    /// The four nametables laid out 2x2 as 512x480 palette RAM values, drawn
+    /// with the background pattern table and palettes
+    pub fn render_nametables(&self) -> Vec<u16> {
+        let mut pixels = vec![0; NAMETABLES_WIDTH * NAMETABLES_HEIGHT];
+        for y in (0..NAMETABLES_HEIGHT).step_by(8) {
+            for x in (0..NAMETABLES_WIDTH).step_by(8) {
+                let Some(info) = self.tile_at(x, y) else {
+                    continue;
+                };
+                for line in 0..8 {
+                    let row = &mut pixels[(y + line) * NAMETABLES_WIDTH + x..][..8];
+                    self.draw_tile_line(row, info.pattern_address, line, info.palette);
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Both pattern tables side by side as 256x128 palette RAM values, drawn
+    /// with palette `palette` 0-7. Palettes 4-7 are the sprite palettes
+    pub fn render_patterns(&self, palette: u8) -> Vec<u16> {
+        let mut pixels = vec![0; PATTERNS_WIDTH * PATTERNS_HEIGHT];
+        for y in (0..PATTERNS_HEIGHT).step_by(8) {
+            for x in (0..PATTERNS_WIDTH).step_by(8) {
+                let Some(pattern_address) = self.pattern_at(x, y) else {
+                    continue;
+                };
+                for line in 0..8 {
+                    let row = &mut pixels[(y + line) * PATTERNS_WIDTH + x..][..8];
+                    self.draw_tile_line(row, pattern_address, line, palette);
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Address of the pattern at a pixel of the 256x128 pattern tables
+    pub fn pattern_at(&self, x: usize, y: usize) -> Option<u16> {
+        if x >= PATTERNS_WIDTH || y >= PATTERNS_HEIGHT {
+            return None;
+        }
+        let table = x / 128;
+        let tile = (y / 8) * 16 + x % 128 / 8;
+        Some((table * 0x1000 + tile * 16) as u16)
+    }
+
+    /// Draw line `line` of the pattern at `pattern_address` into `out` with
+    /// palette `palette`
+    fn draw_tile_line(&self, out: &mut [u16], pattern_address: u16, line: usize, palette: u8) {
+        let address = pattern_address as usize + line;
+        let low = self.patterns[address];
+        let high = self.patterns[address + 8];
+        for (bit, pixel) in out.iter_mut().enumerate() {
+            let color = ((low >> (7 - bit)) & 1) | (((high >> (7 - bit)) & 1) << 1);
+            *pixel = self.color(palette, color) as u16;
+        }
+    }
+
+    /// Palette RAM value for `color` 0-3 of palette `palette` 0-7. Color 0 is
+    /// the shared backdrop
+    fn color(&self, palette: u8, color: u8) -> u8 {
+        let index = match color {
+            0 => 0,
+            _ => (palette * 4 + color) as usize,
+        };
+        self.palette[index] & 0x3F
+    }
+}
+
+impl PPU {
+    /// Copy out what the debugger views draw from. CHR is read through
+    /// `chr_peek`, so mappers don't see the reads
+    pub(crate) fn snapshot(&mut self) -> PpuSnapshot {
+        let mut nametables = [[0; NAME_TABLE_SIZE as usize]; 4];
+        for (index, nametable) in nametables.iter_mut().enumerate() {
+            let base = 0x2000 + index as u16 * NAME_TABLE_SIZE;
+            for (offset, byte) in nametable.iter_mut().enumerate() {
+                *byte = self.v_ram[self.mirror_ram_addr(base + offset as u16) as usize];
+            }
+        }
+        let mut patterns = [0; 0x2000];
+        for (addr, byte) in patterns.iter_mut().enumerate() {
+            *byte = self.chr_peek(addr as u16);
+        }
+        PpuSnapshot {
+            scanline: self.scanline,
+            nametables,
+            patterns,
+            palette: self.palette_table,
+            mirroring: self.mirroring(),
+            background_patterns: self.ctrl_register.background_pattern_addr(),
+            v: self.scroll_register.v,
+            t: self.scroll_register.t,
+            fine_x: self.scroll_register.x,
+            rendering: self.mask_register.rendering_enabled(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::NES;
+    use crate::nes::cartridge::rom::Rom;
+    use crate::nes::test_utils::nes_with_program_and_chr;
+
+    #[test]
+    fn test_snapshot_follows_mirroring_and_attributes() {
+        // Tile 1 is solid color 3
+        let mut chr_rom = vec![0; 0x2000];
+        chr_rom[16..32].fill(0xFF);
+        // JMP $8000
+        let mut nes = nes_with_program_and_chr(&[0x4C, 0x00, 0x80], chr_rom, Mirroring::Horizontal);
+
+        let ppu = &mut nes.bus.ppu;
+        // Tile 1 at row 2, column 3 of $2000, in the bottom-right quadrant
+        // of its attribute byte, which picks palette 2
+        ppu.v_ram[2 * 32 + 3] = 1;
+        ppu.v_ram[0x3C0] = 0b1000_0000;
+        ppu.palette_table[0] = 0x0F;
+        ppu.palette_table[2 * 4 + 3] = 0x16;
+        // Coarse X 4, fine X 3, bottom nametables
+        ppu.scroll_register.t = 0x0800 | 4;
+        ppu.scroll_register.x = 3;
+
+        nes.set_snapshot_scanline(Some(100));
+        nes.run_frame([0; 2]);
+        nes.run_frame([0; 2]);
+        let snapshot = nes.take_snapshot().unwrap();
+        assert!(nes.take_snapshot().is_none());
+        assert_eq!(snapshot.scanline, 100);
+
+        // $2400 mirrors $2000, $2800 is the other table
+        assert_eq!(snapshot.nametables[1], snapshot.nametables[0]);
+        assert_eq!(snapshot.nametables[2][2 * 32 + 3], 0);
+
+        assert_eq!(
+            snapshot.tile_at(3 * 8 + 1, 2 * 8 + 5),
+            Some(TileInfo {
+                nametable: 0,
+                column: 3,
+                row: 2,
+                address: 0x2043,
+                tile: 1,
+                attribute_address: 0x23C0,
+                attribute: 0x80,
+                palette: 2,
+                pattern_address: 0x0010,
+            })
+        );
+        assert_eq!(snapshot.tile_at(512, 0), None);
+
+        let pixels = snapshot.render_nametables();
+        assert_eq!(pixels[0], 0x0F);
+        let line = (2 * 8 + 5) * NAMETABLES_WIDTH;
+        assert_eq!(pixels[line + 3 * 8 + 1], 0x16);
+        assert_eq!(pixels[line + 256 + 3 * 8 + 1], 0x16);
+
+        assert_eq!(snapshot.scroll_origin(), (35, 240));
+    }
+
+    #[test]
+    fn test_pattern_tables_follow_chr_banks() {
+        // Map CHR bank 5 at $1000 and spin
+        let mut prg_rom = vec![0xEA; 0x8000];
+        let program = [
+            0xA9, 0x02, 0x8D, 0x00, 0x80, // LDA #$02, STA $8000
+            0xA9, 0x05, 0x8D, 0x01, 0x80, // LDA #$05, STA $8001
+            0x4C, 0x0A, 0xE0, // JMP $E00A
+        ];
+        prg_rom[0x6000..0x6000 + program.len()].copy_from_slice(&program);
+        prg_rom[0x7FFC] = 0x00;
+        prg_rom[0x7FFD] = 0xE0;
+        // Line 0 of the first tile in 1 KB bank 5 is color 1
+        let mut chr_rom = vec![0; 0x4000];
+        chr_rom[5 * 0x400] = 0xFF;
+        let rom = Rom::new_custom(prg_rom, chr_rom, 4, Mirroring::Vertical);
+        let mut nes = NES::new_with_cartridge(rom.into_cartridge().unwrap());
+        nes.bus.ppu.palette_table[0] = 0x0F;
+        nes.bus.ppu.palette_table[5 * 4 + 1] = 0x2A;
+
+        nes.set_snapshot_scanline(Some(0));
+        nes.run_frame([0; 2]);
+        nes.run_frame([0; 2]);
+        let snapshot = nes.take_snapshot().unwrap();
+        assert_eq!(snapshot.patterns[0x1000], 0xFF);
+        assert_eq!(snapshot.patterns[0x0000], 0x00);
+
+        // Tile $21 of the right table
+        assert_eq!(snapshot.pattern_at(128 + 8 + 1, 16), Some(0x1210));
+        assert_eq!(snapshot.pattern_at(256, 0), None);
+
+        let pixels = snapshot.render_patterns(5);
+        assert_eq!(pixels[128..136], [0x2A; 8]);
+        assert_eq!(pixels[PATTERNS_WIDTH + 128], 0x0F);
+        assert_eq!(pixels[0], 0x0F);
+    }
+}
@@ -0,0 +1,251 @@
+// Audio and video recording straight to disk, with no external encoder
+//
+// `AvRecorder` takes every emulated frame as RGBA along with the samples read
+// from `APU::read_samples_f32`, and writes either an uncompressed AVI or a
+// numbered PNG per frame plus a WAV. Timing comes from the region's exact
+// frame rate and the count of samples, never from the wall clock, so a
+// recording made faster or slower than real time (fast-forward, a headless
+// runner) still plays back in sync.
+
+mod avi;
+mod wav;
+
+use crate::nes::region::Region;
+use avi::{AviWriter, HEIGHT, WIDTH};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use wav::WavWriter;
+
+#[derive(Debug, Error)]
+pub enum RecorderError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("PNG encoding failed: {0}")]
+    Png(#[from] png::EncodingError),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// One uncompressed AVI file
+    Avi,
+    /// A directory of `frame_NNNNNN.png` files and `audio.wav`
+    PngWav,
+}
+
+impl RecordFormat {
+    /// AVI for paths ending in `.avi`, otherwise a PNG sequence in a directory
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("avi") => RecordFormat::Avi,
+            _ => RecordFormat::PngWav,
+        }
+    }
+}
+
+enum Sink {
+    Avi(AviWriter<BufWriter<File>>),
+    PngWav {
+        dir: PathBuf,
+        wav: WavWriter<BufWriter<File>>,
+    },
+}
+
+pub struct AvRecorder {
+    sink: Sink,
+    frame_rate: (u32, u32),
+    sample_rate: u32,
+    frames: u64,
+    samples: u64,
+    /// Samples waiting to be written after the next frame
+    pending: Vec<i16>,
+    resampler: Resampler,
+}
+
+impl AvRecorder {
+    /// Start recording to `path` at the region's frame rate, with audio at
+    /// `sample_rate`
+    pub fn create(
+        path: impl AsRef<Path>,
+        format: RecordFormat,
+        region: Region,
+        sample_rate: u32,
+    ) -> Result<Self, RecorderError> {
+        let path = path.as_ref();
+        let frame_rate = region.frame_rate_ratio();
+        let sink = match format {
+            RecordFormat::Avi => {
+                let file = BufWriter::new(File::create(path)?);
+                Sink::Avi(AviWriter::new(file, frame_rate, sample_rate)?)
+            }
+            RecordFormat::PngWav => {
+                fs::create_dir_all(path)?;
+                let file = BufWriter::new(File::create(path.join("audio.wav"))?);
+                Sink::PngWav {
+                    dir: path.to_path_buf(),
+                    wav: WavWriter::new(file, sample_rate)?,
+                }
+            }
+        };
+        Ok(Self {
+            sink,
+            frame_rate,
+            sample_rate,
+            frames: 0,
+            samples: 0,
+            pending: Vec::new(),
+            resampler: Resampler::default(),
+        })
+    }
+
+    /// Frames recorded so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Add a 256x240 RGBA frame, such as `NES::get_frame_rgba`
+    pub fn add_frame(&mut self, rgba: &[u8]) -> Result<(), RecorderError> {
+        assert_eq!(rgba.len(), WIDTH * HEIGHT * 4, "frames are 256x240 RGBA");
+        match &mut self.sink {
+            Sink::Avi(avi) => avi.write_frame(rgba)?,
+            Sink::PngWav { dir, .. } => {
+                let path = dir.join(format!("frame_{:06}.png", self.frames));
+                let mut encoder = png::Encoder::new(
+                    BufWriter::new(File::create(path)?),
+                    WIDTH as u32,
+                    HEIGHT as u32,
+                );
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_compression(png::Compression::Fast);
+                let mut writer = encoder.write_header()?;
+                writer.write_image_data(rgba)?;
+                writer.finish()?;
+            }
+        }
+        self.frames += 1;
+        self.flush_samples()
+    }
+
+    /// Add samples the APU produced at `sample_rate`. They're resampled when
+    /// that isn't the recording's rate, as when the emulator runs at another
+    /// speed, so they keep their emulated duration
+    pub fn add_samples(&mut self, samples: &[f32], sample_rate: u32) {
+        let to_pcm = |sample: f32| (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        if sample_rate == self.sample_rate || sample_rate == 0 {
+            self.pending.extend(samples.iter().copied().map(to_pcm));
+            self.resampler.skip(samples);
+        } else {
+            let step = sample_rate as f64 / self.sample_rate as f64;
+            let mut resampled = Vec::new();
+            self.resampler.run(samples, step, &mut resampled);
+            self.pending.extend(resampled.into_iter().map(to_pcm));
+        }
+    }
+
+    /// Pad the audio with silence to the video's length and finish the files
+    pub fn finish(mut self) -> Result<(), RecorderError> {
+        self.flush_samples()?;
+        let (num, den) = self.frame_rate;
+        let video_samples =
+            (self.frames * den as u64 * self.sample_rate as u64).div_ceil(num as u64);
+        if let Some(missing) = video_samples.checked_sub(self.samples) {
+            self.pending = vec![0; missing as usize];
+            self.flush_samples()?;
+        }
+        match self.sink {
+            Sink::Avi(avi) => {
+                avi.finish()?;
+            }
+            Sink::PngWav { wav, .. } => {
+                wav.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_samples(&mut self) -> Result<(), RecorderError> {
+        match &mut self.sink {
+            Sink::Avi(avi) => avi.write_samples(&self.pending)?,
+            Sink::PngWav { wav, .. } => wav.write_samples(&self.pending)?,
+        }
+        self.samples += self.pending.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// Linear interpolation to the recording's sample rate, carried across calls
+#[derive(Default)]
+struct Resampler {
+    /// The input sample before the current batch
+    last: f32,
+    /// Where the next output falls, in input samples after `last`
+    pos: f64,
+}
+
+impl Resampler {
+    /// Resample `input`, advancing `step` input samples per output sample
+    fn run(&mut self, input: &[f32], step: f64, out: &mut Vec<f32>) {
+        let last = self.last;
+        let at = |i: usize| if i == 0 { last } else { input[i - 1] };
+        while self.pos < input.len() as f64 {
+            let i = self.pos as usize;
+            let frac = (self.pos - i as f64) as f32;
+            out.push(at(i) + (at(i + 1) - at(i)) * frac);
+            self.pos += step;
+        }
+        self.pos -= input.len() as f64;
+        self.skip(input);
+    }
+
+    /// Carry on from the end of `input` without resampling it
+    fn skip(&mut self, input: &[f32]) {
+        if let Some(&last) = input.last() {
+            self.last = last;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resampler_keeps_duration() {
+        let input: Vec<f32> = (0..1000).map(|i| i as f32).collect();
+        let mut resampler = Resampler::default();
+        let mut out = Vec::new();
+        // Half speed in uneven batches
+        for batch in input.chunks(37) {
+            resampler.run(batch, 0.5, &mut out);
+        }
+        assert_eq!(out.len(), 2000);
+        assert_eq!(out[1001], 499.5);
+    }
+
+    #[test]
+    fn test_png_wav_audio_matches_video_length() {
+        let dir = std::env::temp_dir().join(format!("nes-recorder-{}", std::process::id()));
+        let mut recorder =
+            AvRecorder::create(&dir, RecordFormat::PngWav, Region::Ntsc, 48_000).unwrap();
+        let frame = vec![0x80; WIDTH * HEIGHT * 4];
+        for _ in 0..3 {
+            recorder.add_frame(&frame).unwrap();
+            // Short of a frame's worth, at double speed
+            recorder.add_samples(&[0.5; 300], 24_000);
+        }
+        recorder.finish().unwrap();
+
+        assert!(dir.join("frame_000002.png").exists());
+        assert!(!dir.join("frame_000003.png").exists());
+        let wav = fs::read(dir.join("audio.wav")).unwrap();
+        // 3 frames at 60.0988 fps, rounded up
+        assert_eq!(wav.len(), 44 + 2 * 2397);
+        assert_eq!(i16::from_le_bytes([wav[48], wav[49]]), 16_384);
+        assert_eq!(&wav[wav.len() - 2..], &[0, 0]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
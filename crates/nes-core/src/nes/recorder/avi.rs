@@ -0,0 +1,538 @@
+// Uncompressed AVI writer: 24-bit RGB video and 16-bit mono PCM audio
+//
+// See: https://learn.microsoft.com/en-us/windows/win32/directshow/avi-riff-file-reference
+//      OpenDML AVI File Format Extensions, version 1.02
+//
+// Raw frames fill the 1 GiB that AVI 1.0 readers cope with in about a minute
+// and a half, so the file uses the OpenDML extensions. The first `RIFF AVI `
+// is an ordinary AVI with an `idx1` index; everything after it goes in
+// `RIFF AVIX` chunks. Each RIFF's `movi` list ends with a standard index per
+// stream (`ix00`, `ix01`), and the `indx` super index in each stream header
+// points at those.
+//
+// The header is written with zeros up front and rewritten by `finish`, once
+// the totals are known. Its size doesn't depend on them.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 240;
+const FRAME_BYTES: usize = WIDTH * HEIGHT * 3;
+
+/// Start a new RIFF once the current one would pass this
+const RIFF_LIMIT: u64 = 1 << 30;
+/// Room for one standard index per stream per RIFF, so up to 256 GiB
+const SUPER_INDEX_ENTRIES: usize = 256;
+
+const CHUNK_IDS: [&[u8; 4]; 2] = [b"00db", b"01wb"];
+const INDEX_IDS: [&[u8; 4]; 2] = [b"ix00", b"ix01"];
+
+const AVIF_HASINDEX: u32 = 0x10;
+const AVIF_ISINTERLEAVED: u32 = 0x100;
+const AVIIF_KEYFRAME: u32 = 0x10;
+const AVI_INDEX_OF_INDEXES: u8 = 0x00;
+const AVI_INDEX_OF_CHUNKS: u8 = 0x01;
+
+#[derive(Copy, Clone)]
+struct SuperIndexEntry {
+    /// File offset of the standard index chunk
+    offset: u64,
+    /// Its size, header included
+    size: u32,
+    /// Frames or samples it covers
+    duration: u32,
+}
+
+/// The RIFF currently being filled
+struct Riff {
+    /// File offset of its `RIFF` header
+    start: u64,
+    /// File offset of the `movi` list type, which `idx1` offsets count from
+    movi: u64,
+    /// Data offset and length of each chunk, per stream
+    chunks: [Vec<(u64, u32)>; 2],
+}
+
+pub struct AviWriter<W: Write + Seek> {
+    out: W,
+    /// Bytes written so far
+    pos: u64,
+    frame_rate: (u32, u32),
+    sample_rate: u32,
+    riff_limit: u64,
+    frames: u64,
+    samples: u64,
+    max_audio_chunk: u32,
+    riff: Riff,
+    /// Index entries of the first RIFF, for `idx1`
+    legacy_index: Vec<[u32; 4]>,
+    first_riff: Option<FirstRiff>,
+    super_index: [Vec<SuperIndexEntry>; 2],
+}
+
+/// What the header needs to know about the first RIFF once it's closed
+#[derive(Copy, Clone)]
+struct FirstRiff {
+    len: u32,
+    movi_len: u32,
+    frames: u32,
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+    /// `frame_rate` is `(numerator, denominator)` frames per second
+    pub fn new(out: W, frame_rate: (u32, u32), sample_rate: u32) -> io::Result<Self> {
+        Self::with_riff_limit(out, frame_rate, sample_rate, RIFF_LIMIT)
+    }
+
+    fn with_riff_limit(
+        out: W,
+        frame_rate: (u32, u32),
+        sample_rate: u32,
+        riff_limit: u64,
+    ) -> io::Result<Self> {
+        let mut writer = Self {
+            out,
+            pos: 0,
+            frame_rate,
+            sample_rate,
+            riff_limit,
+            frames: 0,
+            samples: 0,
+            max_audio_chunk: 0,
+            riff: Riff {
+                start: 0,
+                movi: 0,
+                chunks: Default::default(),
+            },
+            legacy_index: Vec::new(),
+            first_riff: None,
+            super_index: Default::default(),
+        };
+        let header = writer.header();
+        writer.write(&header)?;
+        // The header ends with the `movi` list type
+        writer.riff.movi = writer.pos - 4;
+        Ok(writer)
+    }
+
+    /// Write a 256x240 RGBA frame
+    pub fn write_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        assert_eq!(rgba.len(), WIDTH * HEIGHT * 4);
+        // Bottom-up BGR rows
+        let mut data = Vec::with_capacity(FRAME_BYTES);
+        for row in rgba.chunks_exact(WIDTH * 4).rev() {
+            for pixel in row.chunks_exact(4) {
+                data.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            }
+        }
+        self.write_chunk(0, &data)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.write_chunk(1, &data)?;
+        self.samples += samples.len() as u64;
+        self.max_audio_chunk = self.max_audio_chunk.max(data.len() as u32);
+        Ok(())
+    }
+
+    /// Close the last RIFF, fill in the header and hand back the output
+    pub fn finish(mut self) -> io::Result<W> {
+        self.close_riff()?;
+        let header = self.header();
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header)?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.pos += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, stream: usize, data: &[u8]) -> io::Result<()> {
+        // Frames and 16-bit samples never need a pad byte
+        debug_assert!(data.len().is_multiple_of(2));
+        let has_chunks = self.riff.chunks.iter().any(|chunks| !chunks.is_empty());
+        if has_chunks && self.pos + 8 + data.len() as u64 - self.riff.start > self.riff_limit {
+            self.close_riff()?;
+            self.open_riff()?;
+        }
+
+        let header_pos = self.pos;
+        self.write(CHUNK_IDS[stream])?;
+        self.write(&(data.len() as u32).to_le_bytes())?;
+        self.write(data)?;
+        self.riff.chunks[stream].push((header_pos + 8, data.len() as u32));
+        if self.first_riff.is_none() {
+            self.legacy_index.push([
+                u32::from_le_bytes(*CHUNK_IDS[stream]),
+                AVIIF_KEYFRAME,
+                (header_pos - self.riff.movi) as u32,
+                data.len() as u32,
+            ]);
+        }
+        Ok(())
+    }
+
+    fn open_riff(&mut self) -> io::Result<()> {
+        self.riff.start = self.pos;
+        self.write(b"RIFF\0\0\0\0AVIXLIST\0\0\0\0movi")?;
+        self.riff.movi = self.pos - 4;
+        Ok(())
+    }
+
+    /// Write the standard indexes, end the `movi` list and fix up the sizes
+    fn close_riff(&mut self) -> io::Result<()> {
+        for stream in 0..2 {
+            let chunks = std::mem::take(&mut self.riff.chunks[stream]);
+            if chunks.is_empty() {
+                continue;
+            }
+            if self.super_index[stream].len() == SUPER_INDEX_ENTRIES {
+                return Err(io::Error::other("Recording is too long for one AVI file"));
+            }
+
+            let mut index = Vec::with_capacity(32 + chunks.len() * 8);
+            index.extend_from_slice(INDEX_IDS[stream]);
+            index.extend_from_slice(&(24 + chunks.len() as u32 * 8).to_le_bytes());
+            index.extend_from_slice(&2u16.to_le_bytes()); // Longs per entry
+            index.push(0); // Subtype
+            index.push(AVI_INDEX_OF_CHUNKS);
+            index.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+            index.extend_from_slice(CHUNK_IDS[stream]);
+            index.extend_from_slice(&self.riff.start.to_le_bytes()); // Base offset
+            index.extend_from_slice(&0u32.to_le_bytes());
+            for &(offset, len) in &chunks {
+                index.extend_from_slice(&((offset - self.riff.start) as u32).to_le_bytes());
+                index.extend_from_slice(&len.to_le_bytes());
+            }
+
+            let duration = match stream {
+                0 => chunks.len() as u32,
+                _ => chunks.iter().map(|&(_, len)| len / 2).sum(),
+            };
+            self.super_index[stream].push(SuperIndexEntry {
+                offset: self.pos,
+                size: index.len() as u32,
+                duration,
+            });
+            self.write(&index)?;
+        }
+        let movi_len = (self.pos - self.riff.movi) as u32;
+
+        if self.first_riff.is_none() {
+            let mut index = Vec::with_capacity(8 + self.legacy_index.len() * 16);
+            index.extend_from_slice(b"idx1");
+            index.extend_from_slice(&(self.legacy_index.len() as u32 * 16).to_le_bytes());
+            for entry in std::mem::take(&mut self.legacy_index) {
+                for field in entry {
+                    index.extend_from_slice(&field.to_le_bytes());
+                }
+            }
+            self.write(&index)?;
+            // The header is rewritten at the end with these
+            self.first_riff = Some(FirstRiff {
+                len: (self.pos - self.riff.start - 8) as u32,
+                movi_len,
+                frames: self.frames as u32,
+            });
+        } else {
+            let riff_len = (self.pos - self.riff.start - 8) as u32;
+            self.out.seek(SeekFrom::Start(self.riff.start + 4))?;
+            self.out.write_all(&riff_len.to_le_bytes())?;
+            self.out.seek(SeekFrom::Start(self.riff.movi - 4))?;
+            self.out.write_all(&movi_len.to_le_bytes())?;
+            self.out.seek(SeekFrom::Start(self.pos))?;
+        }
+        Ok(())
+    }
+
+    /// Everything up to and including the first `movi` list type
+    fn header(&self) -> Vec<u8> {
+        let (rate, scale) = self.frame_rate;
+        let first = self.first_riff.unwrap_or(FirstRiff {
+            len: 0,
+            movi_len: 0,
+            frames: 0,
+        });
+        let frames = self.frames.min(u32::MAX as u64) as u32;
+        let samples = self.samples.min(u32::MAX as u64) as u32;
+
+        let mut avih = Vec::with_capacity(56);
+        for field in [
+            (1_000_000 * scale as u64 / rate as u64) as u32, // Microseconds per frame
+            (FRAME_BYTES as u64 * rate as u64 / scale as u64) as u32 + self.sample_rate * 2,
+            0, // Padding granularity
+            AVIF_HASINDEX | AVIF_ISINTERLEAVED,
+            first.frames,
+            0, // Initial frames
+            2, // Streams
+            FRAME_BYTES as u32 + 8,
+            WIDTH as u32,
+            HEIGHT as u32,
+            0,
+            0,
+            0,
+            0,
+        ] {
+            avih.extend_from_slice(&field.to_le_bytes());
+        }
+
+        let mut bitmap_info = Vec::with_capacity(40);
+        bitmap_info.extend_from_slice(&40u32.to_le_bytes());
+        bitmap_info.extend_from_slice(&(WIDTH as i32).to_le_bytes());
+        // Positive height means bottom-up rows
+        bitmap_info.extend_from_slice(&(HEIGHT as i32).to_le_bytes());
+        bitmap_info.extend_from_slice(&1u16.to_le_bytes()); // Planes
+        bitmap_info.extend_from_slice(&24u16.to_le_bytes()); // Bits per pixel
+        bitmap_info.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+        bitmap_info.extend_from_slice(&(FRAME_BYTES as u32).to_le_bytes());
+        bitmap_info.extend_from_slice(&[0; 16]);
+
+        let mut wave_format = Vec::with_capacity(18);
+        wave_format.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wave_format.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        wave_format.extend_from_slice(&self.sample_rate.to_le_bytes());
+        wave_format.extend_from_slice(&(self.sample_rate * 2).to_le_bytes());
+        wave_format.extend_from_slice(&2u16.to_le_bytes()); // Block align
+        wave_format.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+        wave_format.extend_from_slice(&0u16.to_le_bytes()); // Extra size
+
+        let video = stream_list(
+            b"vids",
+            [scale, rate, frames, FRAME_BYTES as u32, 0],
+            &bitmap_info,
+            &self.super_index[0],
+            CHUNK_IDS[0],
+        );
+        let audio = stream_list(
+            b"auds",
+            [1, self.sample_rate, samples, self.max_audio_chunk, 2],
+            &wave_format,
+            &self.super_index[1],
+            CHUNK_IDS[1],
+        );
+
+        let mut dmlh = Vec::with_capacity(248);
+        dmlh.extend_from_slice(&frames.to_le_bytes());
+        dmlh.resize(248, 0);
+        let mut odml = Vec::new();
+        chunk(&mut odml, b"dmlh", &dmlh);
+
+        let mut hdrl = Vec::new();
+        chunk(&mut hdrl, b"avih", &avih);
+        list(&mut hdrl, b"strl", &video);
+        list(&mut hdrl, b"strl", &audio);
+        list(&mut hdrl, b"odml", &odml);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&first.len.to_le_bytes());
+        header.extend_from_slice(b"AVI ");
+        list(&mut header, b"hdrl", &hdrl);
+        header.extend_from_slice(b"LIST");
+        header.extend_from_slice(&first.movi_len.to_le_bytes());
+        header.extend_from_slice(b"movi");
+        header
+    }
+}
+
+/// A stream's `strh`, `strf` and `indx` chunks. `timing` is the scale, rate,
+/// length, suggested buffer size and sample size
+fn stream_list(
+    kind: &[u8; 4],
+    timing: [u32; 5],
+    format: &[u8],
+    super_index: &[SuperIndexEntry],
+    chunk_id: &[u8; 4],
+) -> Vec<u8> {
+    let [scale, rate, length, buffer_size, sample_size] = timing;
+    let mut strh = Vec::with_capacity(56);
+    strh.extend_from_slice(kind);
+    strh.extend_from_slice(&[0; 4]); // Handler
+    strh.extend_from_slice(&0u32.to_le_bytes()); // Flags
+    strh.extend_from_slice(&0u16.to_le_bytes()); // Priority
+    strh.extend_from_slice(&0u16.to_le_bytes()); // Language
+    for field in [
+        0, // Initial frames
+        scale,
+        rate,
+        0, // Start
+        length,
+        buffer_size,
+        u32::MAX, // Default quality
+        sample_size,
+    ] {
+        strh.extend_from_slice(&field.to_le_bytes());
+    }
+    // Frame rectangle
+    for field in [0, 0, WIDTH as i16, HEIGHT as i16] {
+        strh.extend_from_slice(&field.to_le_bytes());
+    }
+
+    let mut indx = Vec::with_capacity(24 + SUPER_INDEX_ENTRIES * 16);
+    indx.extend_from_slice(&4u16.to_le_bytes()); // Longs per entry
+    indx.push(0); // Subtype
+    indx.push(AVI_INDEX_OF_INDEXES);
+    indx.extend_from_slice(&(super_index.len() as u32).to_le_bytes());
+    indx.extend_from_slice(chunk_id);
+    indx.extend_from_slice(&[0; 12]);
+    for entry in super_index {
+        indx.extend_from_slice(&entry.offset.to_le_bytes());
+        indx.extend_from_slice(&entry.size.to_le_bytes());
+        indx.extend_from_slice(&entry.duration.to_le_bytes());
+    }
+    // Unused entries stay reserved so the header keeps its size
+    indx.resize(24 + SUPER_INDEX_ENTRIES * 16, 0);
+
+    let mut strl = Vec::new();
+    chunk(&mut strl, b"strh", &strh);
+    chunk(&mut strl, b"strf", format);
+    chunk(&mut strl, b"indx", &indx);
+    strl
+}
+
+fn chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if !data.len().is_multiple_of(2) {
+        out.push(0);
+    }
+}
+
+fn list(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(b"LIST");
+    out.extend_from_slice(&(data.len() as u32 + 4).to_le_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u16_at(bytes: &[u8], pos: usize) -> u16 {
+        u16::from_le_bytes(bytes[pos..pos + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], pos: usize) -> u64 {
+        u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap())
+    }
+
+    /// Offset of the first chunk with `id`, searching a list's contents
+    /// depth-first
+    fn find(bytes: &[u8], start: usize, end: usize, id: &[u8; 4]) -> Option<usize> {
+        let mut pos = start;
+        while pos + 8 <= end {
+            let size = u32_at(bytes, pos + 4) as usize;
+            if &bytes[pos..pos + 4] == id {
+                return Some(pos);
+            }
+            if &bytes[pos..pos + 4] == b"LIST"
+                && let Some(found) = find(bytes, pos + 12, pos + 8 + size, id)
+            {
+                return Some(found);
+            }
+            pos += 8 + size + size % 2;
+        }
+        None
+    }
+
+    fn frame(seed: u8) -> Vec<u8> {
+        (0..WIDTH * HEIGHT * 4).map(|i| (i as u8) ^ seed).collect()
+    }
+
+    #[test]
+    fn test_avi_layout_across_riffs() {
+        // Room for two frames and their audio per RIFF, so five frames need three
+        let limit = 2 * (FRAME_BYTES as u64 + 8) + 2 * (1600 + 8) + 16 * 1024;
+        let mut avi =
+            AviWriter::with_riff_limit(Cursor::new(Vec::new()), (60, 1), 48_000, limit).unwrap();
+        for i in 0..5u8 {
+            avi.write_frame(&frame(i)).unwrap();
+            avi.write_samples(&[i as i16; 800]).unwrap();
+        }
+        let bytes = avi.finish().unwrap().into_inner();
+
+        // The RIFFs cover the file exactly
+        let mut riffs = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            assert_eq!(&bytes[pos..pos + 4], b"RIFF");
+            riffs.push(pos);
+            pos += 8 + u32_at(&bytes, pos + 4) as usize;
+        }
+        assert_eq!(pos, bytes.len());
+        assert_eq!(riffs.len(), 3);
+        assert_eq!(&bytes[8..12], b"AVI ");
+        assert_eq!(&bytes[riffs[1] + 8..riffs[1] + 12], b"AVIX");
+
+        let avih = find(&bytes, 12, bytes.len(), b"avih").unwrap();
+        assert_eq!(u32_at(&bytes, avih + 8), 16_666);
+        assert_eq!(u32_at(&bytes, avih + 8 + 16), 2, "frames in the first RIFF");
+        let dmlh = find(&bytes, 12, riffs[1], b"dmlh").unwrap();
+        assert_eq!(u32_at(&bytes, dmlh + 8), 5);
+        let idx1 = find(&bytes, 12, riffs[1], b"idx1").unwrap();
+        assert_eq!(u32_at(&bytes, idx1 + 4), 4 * 16);
+
+        // Walk both streams through the super index
+        let mut indx = find(&bytes, 12, riffs[1], b"indx").unwrap();
+        for (stream, expected) in [(0, 5), (1, 5 * 800)] {
+            let chunk_id = CHUNK_IDS[stream];
+            assert_eq!(u16_at(&bytes, indx + 8), 4);
+            assert_eq!(bytes[indx + 11], AVI_INDEX_OF_INDEXES);
+            assert_eq!(u32_at(&bytes, indx + 12), 3);
+            assert_eq!(&bytes[indx + 16..indx + 20], chunk_id);
+
+            let mut duration = 0;
+            let mut chunks = 0;
+            for entry in 0..3 {
+                let entry = indx + 32 + entry * 16;
+                let ix = u64_at(&bytes, entry) as usize;
+                assert_eq!(&bytes[ix..ix + 4], INDEX_IDS[stream]);
+                assert_eq!(
+                    u32_at(&bytes, entry + 8) as usize,
+                    8 + u32_at(&bytes, ix + 4) as usize
+                );
+                duration += u32_at(&bytes, entry + 12);
+
+                let base = u64_at(&bytes, ix + 20) as usize;
+                for n in 0..u32_at(&bytes, ix + 12) as usize {
+                    let data = base + u32_at(&bytes, ix + 32 + n * 8) as usize;
+                    let len = u32_at(&bytes, ix + 36 + n * 8);
+                    assert_eq!(&bytes[data - 8..data - 4], chunk_id);
+                    assert_eq!(u32_at(&bytes, data - 4), len);
+                    if stream == 0 {
+                        // The first pixel of the last row, as BGR
+                        let rgba = frame(chunks as u8);
+                        let last_row = (HEIGHT - 1) * WIDTH * 4;
+                        assert_eq!(bytes[data..data + 3], [2, 1, 0].map(|c| rgba[last_row + c]));
+                    } else {
+                        assert_eq!(u16_at(&bytes, data), chunks as u16);
+                    }
+                    chunks += 1;
+                }
+            }
+            assert_eq!(duration, expected);
+            assert_eq!(chunks, 5);
+
+            let next = indx + 8 + u32_at(&bytes, indx + 4) as usize;
+            indx = find(&bytes, next, riffs[1], b"indx").unwrap_or(0);
+        }
+    }
+}
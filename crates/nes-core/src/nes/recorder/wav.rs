@@ -0,0 +1,85 @@
+// WAVE writer for 16-bit mono PCM
+//
+// See: http://soundfile.sapp.org/doc/WaveFormat/
+//
+// The header goes out with zero sizes and is rewritten by `finish`.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    sample_rate: u32,
+    samples: u64,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, sample_rate: u32) -> io::Result<Self> {
+        out.write_all(&header(sample_rate, 0))?;
+        Ok(Self {
+            out,
+            sample_rate,
+            samples: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.out.write_all(&bytes)?;
+        self.samples += samples.len() as u64;
+        Ok(())
+    }
+
+    /// Fill in the header's sizes and hand back the output
+    pub fn finish(mut self) -> io::Result<W> {
+        // Sizes are 32-bit; a longer recording still plays, just with a short header
+        let data_len = (self.samples * 2).min(u32::MAX as u64 - 36) as u32;
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&header(self.sample_rate, data_len))?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn header(sample_rate: u32, data_len: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // Bytes per second
+    header.extend_from_slice(&2u16.to_le_bytes()); // Bytes per sample frame
+    header.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_wav_layout() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 48_000).unwrap();
+        wav.write_samples(&[0, 1, -1]).unwrap();
+        wav.write_samples(&[i16::MAX]).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 44);
+        assert_eq!(
+            u32::from_le_bytes(bytes[24..28].try_into().unwrap()),
+            48_000
+        );
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 8);
+        assert_eq!(&bytes[44..], &[0, 0, 1, 0, 0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+}
@@ -0,0 +1,160 @@
+// See: https://www.nesdev.org/wiki/Cycle_reference_chart
+//      https://www.nesdev.org/wiki/Clock_rate
+
+/// Console timing region
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    /// Famiclone timing: PAL master clock and frame length, NTSC-like CPU divider and APU
+    Dendy,
+}
+
+const NTSC_MASTER_CLOCK_HZ: f64 = 21_477_272.0;
+const PAL_MASTER_CLOCK_HZ: f64 = 26_601_712.0;
+
+impl Region {
+    pub const ALL: [Region; 3] = [Region::Ntsc, Region::Pal, Region::Dendy];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        }
+    }
+
+    pub fn master_clock_hz(&self) -> f64 {
+        match self {
+            Region::Ntsc => NTSC_MASTER_CLOCK_HZ,
+            Region::Pal | Region::Dendy => PAL_MASTER_CLOCK_HZ,
+        }
+    }
+
+    /// Master clocks per CPU cycle
+    pub fn cpu_divider(&self) -> u32 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        }
+    }
+
+    /// Master clocks per PPU dot
+    pub fn ppu_divider(&self) -> u32 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal | Region::Dendy => 5,
+        }
+    }
+
+    pub fn cpu_hz(&self) -> f64 {
+        self.master_clock_hz() / self.cpu_divider() as f64
+    }
+
+    pub fn ppu_hz(&self) -> f64 {
+        self.master_clock_hz() / self.ppu_divider() as f64
+    }
+
+    /// Scanlines per frame, including vblank and the pre-render line
+    pub fn scanlines(&self) -> usize {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /// Scanline on which the vblank flag is raised
+    pub fn vblank_scanline(&self) -> usize {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            // Dendy idles for 51 post-render lines before vblank
+            Region::Dendy => 291,
+        }
+    }
+
+    /// Last scanline of the frame
+    pub fn prerender_scanline(&self) -> usize {
+        self.scanlines() - 1
+    }
+
+    /// Only NTSC drops a dot on odd frames when rendering is enabled
+    pub fn has_odd_frame_skip(&self) -> bool {
+        matches!(self, Region::Ntsc)
+    }
+
+    /// PAL and Dendy PPUs swap the red and green emphasis bits of PPUMASK
+    pub fn swaps_emphasis_red_green(&self) -> bool {
+        matches!(self, Region::Pal | Region::Dendy)
+    }
+
+    /// PAL uses its own APU noise/DMC period tables and frame sequencer steps
+    pub fn uses_pal_apu(&self) -> bool {
+        matches!(self, Region::Pal)
+    }
+
+    /// Nominal frames per second
+    pub fn frame_rate(&self) -> f64 {
+        let dots_per_frame = (self.scanlines() * 341) as f64;
+        let dots_per_frame = if self.has_odd_frame_skip() {
+            dots_per_frame - 0.5
+        } else {
+            dots_per_frame
+        };
+        self.ppu_hz() / dots_per_frame
+    }
+
+    /// `frame_rate` as an exact `(numerator, denominator)` pair, for file
+    /// formats that store the rate as a fraction
+    pub fn frame_rate_ratio(&self) -> (u32, u32) {
+        let master_clock = self.master_clock_hz() as u64;
+        let dots_per_frame = (self.scanlines() * 341) as u64;
+        // The skipped dot lands on every other frame, so count in frame pairs
+        let (num, den) = if self.has_odd_frame_skip() {
+            (
+                2 * master_clock,
+                (2 * dots_per_frame - 1) * self.ppu_divider() as u64,
+            )
+        } else {
+            (master_clock, dots_per_frame * self.ppu_divider() as u64)
+        };
+        let gcd = gcd(num, den);
+        ((num / gcd) as u32, (den / gcd) as u32)
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_rates() {
+        assert_eq!(Region::Ntsc.cpu_hz().round(), 1_789_773.0);
+        assert_eq!(Region::Pal.cpu_hz().round(), 1_662_607.0);
+        assert_eq!(Region::Dendy.cpu_hz().round(), 1_773_447.0);
+
+        assert_eq!(Region::Ntsc.ppu_hz().round(), 5_369_318.0);
+        assert_eq!(Region::Pal.ppu_hz().round(), 5_320_342.0);
+    }
+
+    #[test]
+    fn test_frame_rates() {
+        assert!((Region::Ntsc.frame_rate() - 60.0988).abs() < 0.001);
+        assert!((Region::Pal.frame_rate() - 50.0070).abs() < 0.001);
+        assert!((Region::Dendy.frame_rate() - 50.0070).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_frame_rate_ratios() {
+        for region in Region::ALL {
+            let (num, den) = region.frame_rate_ratio();
+            assert!((num as f64 / den as f64 - region.frame_rate()).abs() < 1e-9);
+        }
+        assert_eq!(Region::Ntsc.frame_rate_ratio(), (10_738_636, 178_683));
+    }
+}
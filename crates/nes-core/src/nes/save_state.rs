@@ -0,0 +1,338 @@
+// Binary machine snapshots
+//
+// Every stateful component implements `SaveState` and writes its fields in a
+// fixed order. There's no per-field tagging, so any change to what a component
+// writes must bump `SAVE_STATE_VERSION`. ROM contents aren't stored, so states
+// can only be loaded back into the same game.
+
+use thiserror::Error;
+
+const SAVE_STATE_MAGIC: &[u8; 4] = b"NSS\x1A";
+pub const SAVE_STATE_VERSION: u16 = 5;
+
+#[derive(Debug, Error)]
+pub enum SaveStateError {
+    #[error("Not a save state")]
+    InvalidFormat,
+
+    #[error("Unsupported save state version: v{0}")]
+    UnsupportedVersion(u16),
+
+    #[error("Save state is truncated")]
+    Truncated,
+
+    #[error("Save state is corrupt: {0}")]
+    Corrupt(&'static str),
+
+    #[error("No cartridge inserted")]
+    NoCartridge,
+}
+
+pub trait SaveState {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut writer = Self { buf: Vec::new() };
+        writer.write_bytes(SAVE_STATE_MAGIC);
+        writer.write_u16(SAVE_STATE_VERSION);
+        writer
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.buf.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_option_u8(&mut self, value: Option<u8>) {
+        self.write_bool(value.is_some());
+        self.write_u8(value.unwrap_or(0));
+    }
+
+    pub fn write_option_u16(&mut self, value: Option<u16>) {
+        self.write_bool(value.is_some());
+        self.write_u16(value.unwrap_or(0));
+    }
+
+    /// Fixed-size data (arrays); the reader must know the length
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Variable-size data, prefixed with its length
+    pub fn write_vec(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.buf.extend_from_slice(bytes);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, SaveStateError> {
+        let mut reader = Self { data, pos: 0 };
+        let mut magic = [0u8; 4];
+        reader
+            .read_bytes(&mut magic)
+            .map_err(|_| SaveStateError::InvalidFormat)?;
+        if &magic != SAVE_STATE_MAGIC {
+            return Err(SaveStateError::InvalidFormat);
+        }
+        let version = reader.read_u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        Ok(reader)
+    }
+
+    /// Errors unless every byte has been consumed
+    pub fn finish(&self) -> Result<(), SaveStateError> {
+        match self.pos == self.data.len() {
+            true => Ok(()),
+            false => Err(SaveStateError::Corrupt("trailing data")),
+        }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + N)
+            .ok_or(SaveStateError::Truncated)?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Corrupt("invalid bool")),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_usize(&mut self) -> Result<usize, SaveStateError> {
+        usize::try_from(self.read_u64()?).map_err(|_| SaveStateError::Corrupt("invalid size"))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, SaveStateError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, SaveStateError> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_option_u8(&mut self) -> Result<Option<u8>, SaveStateError> {
+        let is_some = self.read_bool()?;
+        let value = self.read_u8()?;
+        Ok(is_some.then_some(value))
+    }
+
+    pub fn read_option_u16(&mut self) -> Result<Option<u16>, SaveStateError> {
+        let is_some = self.read_bool()?;
+        let value = self.read_u16()?;
+        Ok(is_some.then_some(value))
+    }
+
+    pub fn read_bytes(&mut self, out: &mut [u8]) -> Result<(), SaveStateError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + out.len())
+            .ok_or(SaveStateError::Truncated)?;
+        out.copy_from_slice(bytes);
+        self.pos += out.len();
+        Ok(())
+    }
+
+    /// Read length-prefixed data into `out`, which must already have the saved length
+    /// (RAM sizes are fixed by the cartridge, so a mismatch means a different game)
+    pub fn read_vec_into(&mut self, out: &mut [u8]) -> Result<(), SaveStateError> {
+        let len = self.read_u32()? as usize;
+        if len != out.len() {
+            return Err(SaveStateError::Corrupt("memory size mismatch"));
+        }
+        self.read_bytes(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::NES;
+    use crate::nes::controller::PortDevice;
+    use crate::nes::controller::vaus::Vaus;
+    use crate::nes::test_utils::nes_with_program;
+
+    fn test_nes() -> NES {
+        // INX; STX $00; INC $01; STX $2006; JMP $8000
+        let program = [
+            0xE8, 0x86, 0x00, 0xE6, 0x01, 0x8E, 0x06, 0x20, 0x4C, 0x00, 0x80,
+        ];
+        nes_with_program(&program)
+    }
+
+    fn run_frames(nes: &mut NES, frames: usize) {
+        for _ in 0..frames {
+            while !nes.tick().1 {}
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0xAB);
+        writer.write_bool(true);
+        writer.write_u16(0x1234);
+        writer.write_u64(u64::MAX - 1);
+        writer.write_i32(-5);
+        writer.write_f32(1.5);
+        writer.write_option_u16(Some(0x4000));
+        writer.write_option_u8(None);
+        writer.write_vec(&[1, 2, 3]);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes).unwrap();
+        assert_eq!(reader.read_u8().unwrap(), 0xAB);
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_u16().unwrap(), 0x1234);
+        assert_eq!(reader.read_u64().unwrap(), u64::MAX - 1);
+        assert_eq!(reader.read_i32().unwrap(), -5);
+        assert_eq!(reader.read_f32().unwrap(), 1.5);
+        assert_eq!(reader.read_option_u16().unwrap(), Some(0x4000));
+        assert_eq!(reader.read_option_u8().unwrap(), None);
+        let mut ram = [0u8; 3];
+        reader.read_vec_into(&mut ram).unwrap();
+        assert_eq!(ram, [1, 2, 3]);
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn test_rejects_bad_header_and_truncation() {
+        assert!(matches!(
+            StateReader::new(b"NES\x1A\x01\x00"),
+            Err(SaveStateError::InvalidFormat)
+        ));
+        assert!(matches!(
+            StateReader::new(b"NSS\x1A\x63\x00"),
+            Err(SaveStateError::UnsupportedVersion(0x63))
+        ));
+
+        let bytes = StateWriter::new().into_bytes();
+        let mut reader = StateReader::new(&bytes).unwrap();
+        assert!(matches!(reader.read_u32(), Err(SaveStateError::Truncated)));
+    }
+
+    #[test]
+    fn test_ram_size_mismatch() {
+        let mut writer = StateWriter::new();
+        writer.write_vec(&[0; 8]);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes).unwrap();
+        let mut ram = [0u8; 4];
+        assert!(reader.read_vec_into(&mut ram).is_err());
+    }
+
+    #[test]
+    fn test_nes_state_replays_deterministically() {
+        let mut nes = test_nes();
+        run_frames(&mut nes, 3);
+        let snapshot = nes.save_state();
+
+        run_frames(&mut nes, 2);
+        let expected_ram = nes.bus.cpu_ram;
+        let expected_state = nes.save_state();
+
+        nes.load_state(&snapshot).unwrap();
+        run_frames(&mut nes, 2);
+        assert_eq!(nes.bus.cpu_ram, expected_ram);
+        assert_eq!(nes.save_state(), expected_state);
+    }
+
+    #[test]
+    fn test_nes_state_restores_port_devices() {
+        let mut nes = test_nes();
+        nes.set_port_device(1, PortDevice::Vaus);
+        nes.port_mut::<Vaus>(1).unwrap().set_position(0x80);
+        let snapshot = nes.save_state();
+
+        nes.set_port_device(1, PortDevice::Zapper);
+        nes.load_state(&snapshot).unwrap();
+        assert_eq!(nes.port_device(0), PortDevice::Joypad);
+        assert_eq!(nes.port_device(1), PortDevice::Vaus);
+        assert_eq!(nes.save_state(), snapshot);
+    }
+
+    #[test]
+    fn test_nes_state_rejects_trailing_data() {
+        let mut nes = test_nes();
+        let mut snapshot = nes.save_state();
+        snapshot.push(0);
+        assert!(matches!(
+            nes.load_state(&snapshot),
+            Err(SaveStateError::Corrupt(_))
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+// Single-stepping for debugging and TAS work
+//
+// Each step runs whole master-clock ticks until the requested boundary is
+// crossed, so the machine is always left in a state that `tick()` can resume
+// from normally.
+
+use crate::nes::NES;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StepKind {
+    /// Run until the PPU finishes the current frame
+    Frame,
+    /// Run until the PPU moves to the next scanline
+    Scanline,
+    /// Run until the CPU completes the current instruction (or interrupt sequence)
+    Instruction,
+    /// Run a single PPU dot
+    Dot,
+}
+
+/// Where the machine is, as shown by the debugger status line
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct MachinePosition {
+    pub frame: u64,
+    pub scanline: usize,
+    pub dot: usize,
+    pub cpu_cycles: u64,
+    pub pc: u16,
+}
+
+impl NES {
+    /// Advance by one `kind` step
+    ///
+    /// # Returns
+    ///
+    /// `true` if a frame was completed along the way
+    pub fn step(&mut self, kind: StepKind) -> bool {
+        let mut frame_ready = false;
+        match kind {
+            StepKind::Frame => {
+                while !frame_ready {
+                    frame_ready = self.tick().1;
+                }
+            }
+            StepKind::Scanline => {
+                let scanline = self.bus.ppu.scanline;
+                while self.bus.ppu.scanline == scanline {
+                    frame_ready |= self.tick().1;
+                }
+            }
+            StepKind::Instruction => {
+                // Stalled (DMA) cycles don't count, and a JAM never completes,
+                // so cap the search at a couple of frames
+                let limit = self.cpu_cycles() + 2 * 30_000;
+                let mut cpu_ran = false;
+                while !(cpu_ran && self.bus.cpu.at_instruction_boundary()) {
+                    let (cpu_ticked, frame) = self.tick();
+                    cpu_ran |= cpu_ticked;
+                    frame_ready |= frame;
+                    if self.cpu_cycles() > limit {
+                        break;
+                    }
+                }
+            }
+            StepKind::Dot => {
+                frame_ready = self.tick().1;
+            }
+        }
+        frame_ready
+    }
+
+    pub fn position(&self) -> MachinePosition {
+        MachinePosition {
+            frame: self.frame_count(),
+            scanline: self.bus.ppu.scanline,
+            dot: self.bus.ppu.cycles,
+            cpu_cycles: self.cpu_cycles(),
+            pc: self.bus.cpu.program_counter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::test_utils::nes_with_program;
+
+    fn test_nes() -> NES {
+        // NOP; LDA #$01; JMP $8000
+        let program = [0xEA, 0xA9, 0x01, 0x4C, 0x00, 0x80];
+        nes_with_program(&program)
+    }
+
+    #[test]
+    fn test_step_instruction_walks_program() {
+        let mut nes = test_nes();
+        let start = nes.position().pc;
+        assert_eq!(start, 0x8000);
+
+        nes.step(StepKind::Instruction);
+        assert_eq!(nes.position().pc, 0x8001);
+        nes.step(StepKind::Instruction);
+        assert_eq!(nes.position().pc, 0x8003);
+        assert_eq!(nes.bus.cpu.register_a, 0x01);
+        nes.step(StepKind::Instruction);
+        assert_eq!(nes.position().pc, 0x8000);
+    }
+
+    #[test]
+    fn test_step_dot_scanline_and_frame() {
+        let mut nes = test_nes();
+        let before = nes.position();
+        nes.step(StepKind::Dot);
+        assert_ne!(nes.position().dot, before.dot);
+
+        let scanline = nes.position().scanline;
+        nes.step(StepKind::Scanline);
+        assert_ne!(nes.position().scanline, scanline);
+        assert_eq!(nes.position().dot, 0);
+
+        let frame = nes.position().frame;
+        assert!(nes.step(StepKind::Frame));
+        assert_eq!(nes.position().frame, frame + 1);
+        assert!(nes.step(StepKind::Frame));
+        assert_eq!(nes.position().frame, frame + 2);
+    }
+
+    #[test]
+    fn test_run_frame_latches_input() {
+        let mut nes = test_nes();
+        nes.step(StepKind::Frame);
+
+        let result = nes.run_frame([0b1000_0001, 0b0000_0010]);
+        assert_eq!(result.frame, nes.frame_count());
+        assert_eq!(nes.position().scanline, 0);
+        assert!((29_000..30_000).contains(&result.cpu_cycles));
+        assert_eq!(nes.run_frame([0, 0]).frame, result.frame + 1);
+
+        // Strobe, shift both controllers' reports into $00 and $01, then
+        // copy the finished reports to $02 and $03
+        let program = [
+            0xA9, 0x01, // LDA #$01
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x16, 0x40, // STA $4016
+            0xA2, 0x08, // LDX #$08
+            0xAD, 0x16, 0x40, // LDA $4016
+            0x4A, // LSR A
+            0x66, 0x00, // ROR $00
+            0xAD, 0x17, 0x40, // LDA $4017
+            0x4A, // LSR A
+            0x66, 0x01, // ROR $01
+            0xCA, // DEX
+            0xD0, 0xF1, // BNE $800C
+            0xA5, 0x00, // LDA $00
+            0x85, 0x02, // STA $02
+            0xA5, 0x01, // LDA $01
+            0x85, 0x03, // STA $03
+            0x4C, 0x00, 0x80, // JMP $8000
+        ];
+        let mut nes = nes_with_program(&program);
+        nes.step(StepKind::Frame);
+        nes.run_frame([0b1000_0001, 0b0000_0010]);
+        assert_eq!(nes.bus.cpu_ram[2..4], [0b1000_0001, 0b0000_0010]);
+        nes.run_frame([0, 0b0100_0000]);
+        assert_eq!(nes.bus.cpu_ram[2..4], [0, 0b0100_0000]);
+    }
+}
@@ -1,2 +1,21 @@
 pub use crate::nes::bus::simple_bus::SimpleBus;
 pub use crate::nes::cpu::{CpuBusInterface, Flags};
+
+use crate::nes::NES;
+use crate::nes::cartridge::rom::{Mirroring, Rom};
+
+/// An NES with `program` at $8000, where the reset vector points, on a
+/// 16 KB NROM cartridge with CHR RAM. The rest of PRG is NOPs
+pub fn nes_with_program(program: &[u8]) -> NES {
+    nes_with_program_and_chr(program, vec![], Mirroring::Vertical)
+}
+
+/// Like `nes_with_program`, with `chr_rom` (CHR RAM if empty) and mirroring
+pub fn nes_with_program_and_chr(program: &[u8], chr_rom: Vec<u8>, mirroring: Mirroring) -> NES {
+    let mut prg_rom = vec![0xEA; 0x4000];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFC] = 0x00;
+    prg_rom[0x3FFD] = 0x80;
+    let rom = Rom::new_custom(prg_rom, chr_rom, 0, mirroring);
+    NES::new_with_cartridge(rom.into_cartridge().unwrap())
+}
@@ -6,9 +6,18 @@
 //! ```
 
 // Main NES emulator API
-pub use crate::nes::NES;
 pub use crate::nes::cartridge::rom::{Rom, RomError};
+pub use crate::nes::controller::expansion::ExpansionDevice;
+pub use crate::nes::controller::family_keyboard::FamilyKey;
 pub use crate::nes::controller::joypad::JoypadButton;
+pub use crate::nes::controller::{ConsoleType, PortDevice};
+pub use crate::nes::movie::{Movie, MovieError, MovieFrame};
+pub use crate::nes::ppu::viewer::{PpuSnapshot, TileInfo};
+pub use crate::nes::recorder::{AvRecorder, RecordFormat, RecorderError};
+pub use crate::nes::region::Region;
+pub use crate::nes::save_state::SaveStateError;
+pub use crate::nes::stepping::{MachinePosition, StepKind};
+pub use crate::nes::{FrameResult, NES};
 
 // Traits that users might need
 pub use crate::nes::cartridge::Cartridge;
@@ -17,7 +26,7 @@ pub use crate::nes::cartridge::Cartridge;
 pub use crate::trace_dump;
 
 // Constants
-pub use crate::nes::ppu::consts::NES_SYSTEM_PALETTE;
+pub use crate::nes::ppu::consts::{NES_SYSTEM_PALETTE, emphasised_palette};
 
 // Conditional testing utilities
 #[cfg(feature = "testing-utils")]
@@ -3,6 +3,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 use nes_app::app::app::App;
 use nes_app::app::event::{AppEvent, AppEventSource};
+use nes_app::app::ui::video_options::saved_window_size;
+use nes_app::emu::netplay::NetplayConfig;
+use std::net::{SocketAddr, ToSocketAddrs};
 
 pub struct NativeEventSource {
     // rx: Receiver<AppEvent>,
@@ -23,15 +26,54 @@ impl AppEventSource for NativeEventSource {
 fn main() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([256.0 * 3.0, 240.0 * 3.0])
+            .with_inner_size(saved_window_size())
             .with_title("NES Emulator"),
         ..Default::default()
     };
 
+    // Usage: nes-native [rom] [--host <port> | --join <address:port>] [--delay <frames>]
     let mut initial_events = vec![AppEvent::Start];
-    if let Some(rom_path) = std::env::args_os().nth(1) {
+    let mut rom_path = None;
+    let mut netplay = None;
+    let mut input_delay = None;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().and_then(|v| v.into_string().ok());
+        match arg.to_str() {
+            Some("--host") => match value().and_then(|port| port.parse::<u16>().ok()) {
+                Some(port) => {
+                    let bind = SocketAddr::from(([0, 0, 0, 0], port));
+                    netplay = Some(NetplayConfig::new(bind, None, 0));
+                }
+                None => eprintln!("--host expects a port"),
+            },
+            Some("--join") => match value().and_then(|peer| peer.to_socket_addrs().ok()?.next()) {
+                Some(peer) => {
+                    let bind = SocketAddr::from(([0, 0, 0, 0], 0));
+                    netplay = Some(NetplayConfig::new(bind, Some(peer), 1));
+                }
+                None => eprintln!("--join expects an address:port"),
+            },
+            Some("--delay") => match value().and_then(|frames| frames.parse().ok()) {
+                Some(frames) => input_delay = Some(frames),
+                None => eprintln!("--delay expects a number of frames"),
+            },
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    if let Some(mut config) = netplay {
+        if let Some(frames) = input_delay {
+            config.input_delay = frames;
+        }
+        initial_events.push(AppEvent::Netplay(config));
+    }
+    if let Some(rom_path) = rom_path {
         match std::fs::read(&rom_path) {
-            Ok(rom_data) => initial_events.push(AppEvent::LoadRom(rom_data)),
+            Ok(bytes) => {
+                let name = rom_path.to_string_lossy().into_owned();
+                initial_events.push(AppEvent::LoadRom { name, bytes });
+            }
             Err(e) => eprintln!("Failed to load ROM '{}': {e}", rom_path.to_string_lossy()),
         }
     }
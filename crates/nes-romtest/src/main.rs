@@ -1,21 +1,40 @@
 use std::env;
 use std::fs;
+use std::path::Path;
 use std::process;
 
 use nes_core::prelude::*;
 
 enum RunMode {
-    Frames { frames: usize, buffer: usize },
-    Ticks { ticks: usize },
+    Frames {
+        frames: usize,
+        buffer: usize,
+    },
+    Ticks {
+        ticks: usize,
+    },
+    /// Play an FM2 movie, then report a hash of the final frame
+    Movie {
+        path: String,
+        buffer: usize,
+        expect_hash: Option<String>,
+    },
 }
 
 struct Options {
     rom_path: String,
     run_mode: RunMode,
     result_addr: usize,
+    /// Overrides the region from the ROM header
+    region: Option<Region>,
+    /// Record video and audio to an `.avi` file, or PNGs and a WAV in a directory
+    record: Option<String>,
     verbose: bool,
 }
 
+/// Audio rate of recordings
+const RECORD_SAMPLE_RATE: u32 = 48_000;
+
 fn parse_args() -> Options {
     let mut args = env::args().skip(1);
     let mut rom_path: Option<String> = None;
@@ -23,6 +42,10 @@ fn parse_args() -> Options {
     let mut ticks: Option<usize> = None;
     let mut buffer: usize = 0;
     let mut result_addr: usize = 0x00F8;
+    let mut region: Option<Region> = None;
+    let mut movie: Option<String> = None;
+    let mut expect_hash: Option<String> = None;
+    let mut record: Option<String> = None;
     let mut verbose = false;
 
     while let Some(arg) = args.next() {
@@ -43,6 +66,19 @@ fn parse_args() -> Options {
                 let val = args.next().unwrap_or_default();
                 result_addr = parse_usize(&val, "result-addr");
             }
+            "-m" | "--movie" => {
+                movie = Some(args.next().unwrap_or_default());
+            }
+            "--expect-hash" => {
+                expect_hash = Some(args.next().unwrap_or_default().to_ascii_lowercase());
+            }
+            "--region" => {
+                let val = args.next().unwrap_or_default();
+                region = Some(parse_region(&val));
+            }
+            "--record" => {
+                record = Some(args.next().unwrap_or_default());
+            }
             "-v" | "--verbose" => {
                 verbose = true;
             }
@@ -61,11 +97,30 @@ fn parse_args() -> Options {
         eprintln!("Missing ROM path.");
         print_usage_and_exit();
     });
-    if frames.is_some() && ticks.is_some() {
-        eprintln!("Provide either --frames or --ticks, not both.");
+    if [frames.is_some(), ticks.is_some(), movie.is_some()]
+        .iter()
+        .filter(|&&set| set)
+        .count()
+        > 1
+    {
+        eprintln!("Provide only one of --frames, --ticks or --movie.");
+        print_usage_and_exit();
+    }
+    if expect_hash.is_some() && movie.is_none() {
+        eprintln!("--expect-hash requires --movie.");
         print_usage_and_exit();
     }
-    let run_mode = if let Some(ticks) = ticks {
+    if record.is_some() && ticks.is_some() {
+        eprintln!("--record requires --frames or --movie.");
+        print_usage_and_exit();
+    }
+    let run_mode = if let Some(path) = movie {
+        RunMode::Movie {
+            path,
+            buffer,
+            expect_hash,
+        }
+    } else if let Some(ticks) = ticks {
         RunMode::Ticks { ticks }
     } else {
         let frames = frames.unwrap_or_else(|| {
@@ -79,10 +134,22 @@ fn parse_args() -> Options {
         rom_path,
         run_mode,
         result_addr,
+        region,
+        record,
         verbose,
     }
 }
 
+fn parse_region(value: &str) -> Region {
+    Region::ALL
+        .into_iter()
+        .find(|region| region.name().eq_ignore_ascii_case(value))
+        .unwrap_or_else(|| {
+            eprintln!("Invalid region: {value}");
+            print_usage_and_exit();
+        })
+}
+
 fn parse_usize(value: &str, name: &str) -> usize {
     if value.starts_with("0x") || value.starts_with("0X") {
         usize::from_str_radix(&value[2..], 16).unwrap_or_else(|_| {
@@ -100,11 +167,21 @@ fn parse_usize(value: &str, name: &str) -> usize {
 fn print_usage_and_exit() -> ! {
     eprintln!("Usage: rom-test-runner <rom_path> --frames <count> [options]");
     eprintln!("   or: rom-test-runner <rom_path> --ticks <count> [options]");
+    eprintln!("   or: rom-test-runner <rom_path> --movie <file.fm2> [options]");
     eprintln!("Options:");
     eprintln!("  -f, --frames <count>        Number of frames to run (required)");
     eprintln!("  -t, --ticks <count>         Number of PPU ticks to run");
+    eprintln!("  -m, --movie <file.fm2>      Play an FM2 movie and print the final frame hash");
     eprintln!("  -b, --buffer <count>        Extra frames to add (default: 0)");
     eprintln!("  -r, --result-addr <addr>    Result RAM address (default: 0x00F8)");
+    eprintln!(
+        "      --region <name>         ntsc, pal or dendy (default: from ROM header, else ntsc)"
+    );
+    eprintln!("      --expect-hash <md5>     Fail unless the movie's frame hash matches");
+    eprintln!("                              (frames with color emphasis hash 9-bit pixels)");
+    eprintln!(
+        "      --record <path>         Record to an .avi file, or PNGs and a WAV in a directory"
+    );
     eprintln!("  -v, --verbose               Print extra diagnostics");
     process::exit(2);
 }
@@ -116,16 +193,53 @@ fn main() {
         process::exit(2);
     });
 
-    let cart = match NES::parse_rom_bytes(&rom_data) {
-        Ok(cart) => cart,
-        Err(err) => {
-            eprintln!("ROM parse error: {err}");
-            process::exit(2);
-        }
+    let rom = Rom::parse(&rom_data).unwrap_or_else(|err| {
+        eprintln!("ROM parse error: {err}");
+        process::exit(2);
+    });
+    let movie = match &opts.run_mode {
+        RunMode::Movie { path, .. } => Some(load_movie(path)),
+        _ => None,
     };
+    let movie_region = movie
+        .as_ref()
+        .and_then(|movie| movie.pal.then_some(Region::Pal));
+    let region = opts
+        .region
+        .or(movie_region)
+        .or(rom.region)
+        .unwrap_or_default();
+    let cart = rom.into_cartridge().unwrap_or_else(|err| {
+        eprintln!("ROM parse error: {err}");
+        process::exit(2);
+    });
 
     let mut nes = NES::new();
+    nes.set_region(region);
     nes.insert_cartridge(cart);
+    let mut recorder = opts
+        .record
+        .as_deref()
+        .map(|path| start_recording(&mut nes, path));
+
+    if let (
+        Some(movie),
+        RunMode::Movie {
+            buffer,
+            expect_hash,
+            ..
+        },
+    ) = (&movie, &opts.run_mode)
+    {
+        run_movie(
+            &mut nes,
+            movie,
+            *buffer,
+            expect_hash.as_deref(),
+            recorder,
+            &opts,
+        );
+    }
 
     let mut frames = 0usize;
     let mut ticks = 0usize;
@@ -137,8 +251,13 @@ fn main() {
         } => {
             let target_frames = target + buffer;
             while frames < target_frames {
-                if nes.tick() {
+                let (_, frame_ready) = nes.tick();
+                if frame_ready {
                     frames += 1;
+                    if let Some(recorder) = &mut recorder {
+                        nes.bus.apu.end_frame();
+                        record_frame(&mut nes, recorder);
+                    }
                 }
                 ticks += 1;
             }
@@ -149,10 +268,15 @@ fn main() {
                 ticks += 1;
             }
         }
+        RunMode::Movie { .. } => unreachable!("handled by run_movie"),
+    }
+    if let Some(recorder) = recorder {
+        finish_recording(recorder, opts.verbose);
     }
 
     let result = nes.bus.cpu_ram[opts.result_addr];
     if opts.verbose {
+        println!("Region: {}", region.name());
         println!("Ticks: {ticks}");
         println!("Frames: {frames}");
         println!("Result addr: 0x{:04X}", opts.result_addr);
@@ -172,3 +296,114 @@ fn main() {
     println!("\\**************************************************************/");
     process::exit(exit_code);
 }
+
+fn load_movie(path: &str) -> Movie {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Failed to read movie '{path}': {err}");
+        process::exit(2);
+    });
+    Movie::parse(&text).unwrap_or_else(|err| {
+        eprintln!("Movie parse error: {err}");
+        process::exit(2);
+    })
+}
+
+fn run_movie(
+    nes: &mut NES,
+    movie: &Movie,
+    buffer: usize,
+    expect_hash: Option<&str>,
+    mut recorder: Option<AvRecorder>,
+    opts: &Options,
+) -> ! {
+    if let Some(state) = &movie.start_state
+        && let Err(err) = nes.load_state(state)
+    {
+        eprintln!("Failed to load the movie's save state: {err}");
+        process::exit(2);
+    }
+
+    let idle = MovieFrame::default();
+    let inputs = movie
+        .frames
+        .iter()
+        .chain(std::iter::repeat_n(&idle, buffer));
+    let mut frames = 0usize;
+    for input in inputs {
+        input.run(nes);
+        frames += 1;
+        if let Some(recorder) = &mut recorder {
+            record_frame(nes, recorder);
+        }
+    }
+    if let Some(recorder) = recorder {
+        finish_recording(recorder, opts.verbose);
+    }
+
+    let hash = format!("{:x}", md5::compute(frame_bytes(nes.get_frame_buffer())));
+    if opts.verbose {
+        println!("Region: {}", nes.region().name());
+        println!("Movie frames: {}", movie.frames.len());
+        println!("Frames: {frames}");
+    }
+    println!("Frame hash: {hash}");
+
+    match expect_hash {
+        Some(expected) if expected != hash => {
+            println!("DESYNC: expected frame hash {expected}");
+            if uses_emphasis(nes.get_frame_buffer()) {
+                println!(
+                    "The frame uses color emphasis, which older versions didn't hash, so their hashes won't match"
+                );
+            }
+            process::exit(1);
+        }
+        _ => process::exit(0),
+    }
+}
+
+fn start_recording(nes: &mut NES, path: &str) -> AvRecorder {
+    nes.bus.apu.set_sample_rate(RECORD_SAMPLE_RATE as f64);
+    let format = RecordFormat::from_path(Path::new(path));
+    AvRecorder::create(path, format, nes.region(), RECORD_SAMPLE_RATE).unwrap_or_else(|err| {
+        eprintln!("Failed to start recording '{path}': {err}");
+        process::exit(2);
+    })
+}
+
+/// Record the frame that just finished and the audio it produced
+fn record_frame(nes: &mut NES, recorder: &mut AvRecorder) {
+    let mut samples = vec![0.0; nes.bus.apu.samples_available()];
+    nes.bus.apu.read_samples_f32(&mut samples);
+    recorder.add_samples(&samples, RECORD_SAMPLE_RATE);
+    if let Err(err) = recorder.add_frame(&nes.get_frame_rgba()) {
+        eprintln!("Recording failed: {err}");
+        process::exit(2);
+    }
+}
+
+fn finish_recording(recorder: AvRecorder, verbose: bool) {
+    let frames = recorder.frames();
+    if let Err(err) = recorder.finish() {
+        eprintln!("Recording failed: {err}");
+        process::exit(2);
+    }
+    if verbose {
+        println!("Recorded frames: {frames}");
+    }
+}
+
+/// The frame's pixels as bytes, for hashing. Without color emphasis that's
+/// one palette index per pixel, so hashes from before emphasis was rendered
+/// still match. Frames using emphasis hash all 9 bits, little-endian
+fn frame_bytes(frame: &[u16]) -> Vec<u8> {
+    match uses_emphasis(frame) {
+        true => frame.iter().flat_map(|pixel| pixel.to_le_bytes()).collect(),
+        false => frame.iter().map(|&pixel| pixel as u8).collect(),
+    }
+}
+
+/// Whether any pixel has emphasis bits set
+fn uses_emphasis(frame: &[u16]) -> bool {
+    frame.iter().any(|&pixel| pixel >= 0x40)
+}
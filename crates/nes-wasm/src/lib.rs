@@ -37,13 +37,24 @@ impl WasmEventSource {
 impl AppEventSource for WasmEventSource {
     fn poll_event(&mut self) -> Option<AppEvent> {
         self.messenger.receive().map(|cmd| match cmd {
-            ClientMessage::LoadRom(rom) => AppEvent::LoadRom(rom),
+            // The page only sends the file's contents
+            ClientMessage::LoadRom(rom) => AppEvent::LoadRom {
+                name: String::new(),
+                bytes: rom,
+            },
             ClientMessage::Reset => AppEvent::Reset,
             ClientMessage::Pause => AppEvent::Pause,
         })
     }
 }
 
+#[wasm_bindgen]
+extern "C" {
+    /// Defined by the page (see `index.html`) to save a file from the app
+    #[wasm_bindgen(js_name = downloadFile)]
+    fn download_file(name: &str, bytes: &[u8]);
+}
+
 thread_local! {
     static APP: RefCell<OnceCell<Rc<RefCell<App<WasmEventSource>>>>> =
         RefCell::new(OnceCell::new());
@@ -99,9 +110,13 @@ fn spawn_eframe() {
                 Box::new(|_cc| {
                     let event_source = WasmEventSource::new();
 
-                    let app = Rc::new(RefCell::new(App::new(event_source).with_logger(|msg| {
-                        web_sys::console::log_1(&msg.into());
-                    })));
+                    let app = Rc::new(RefCell::new(
+                        App::new(event_source)
+                            .with_logger(|msg| {
+                                web_sys::console::log_1(&msg.into());
+                            })
+                            .with_download_callback(download_file),
+                    ));
 
                     // Expose to JS calls (gesture-sensitive start_emulator)
                     set_app(app.clone());